@@ -1,6 +1,10 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+};
 
 use bevy::{
+    asset::{FileAssetIo, HandleId, LoadState},
     prelude::{
         shape::{Cube, Plane},
         *,
@@ -10,8 +14,12 @@ use bevy::{
 
 use bevy_polyline::prelude::*;
 
+use crate::{physics::aabb::AABB, states::UpdateStageState, timings::StartupTimings};
+
 //fonts
 pub const FONT_SCHLUBER: &str = "Schluber.otf";
+///Copy of font built into binary. Used when assets folder can't be found.
+const FALLBACK_FONT: &[u8] = include_bytes!("../assets/fonts/Schluber.otf");
 
 //images
 pub const IMAGE_UI: usize = 0;
@@ -30,15 +38,122 @@ pub const GUN_TOWER_0_GUN: &str = "gun_tower0.glb#Mesh2/Primitive0";
 pub const S_MAT_BUILT_IN: usize = 0;
 pub const WHITE: &str = "white";
 pub const WHITE_TRANS: &str = "white_trans";
+pub const WHITE_FAINT: &str = "white_faint";
+pub const RED_TRANS: &str = "red_trans";
 pub const SEA_GREEN: &str = "sea_green";
+///Small fixed set of opaque colors that imported content is mapped to.
+pub const S_MAT_PALETTE: usize = 1;
+pub const PALETTE: [(&str, Color); 12] = [
+    ("black", Color::rgb(0.05, 0.05, 0.05)),
+    ("gray", Color::rgb(0.5, 0.5, 0.5)),
+    ("white", Color::rgb(0.95, 0.95, 0.95)),
+    ("red", Color::rgb(0.8, 0.1, 0.1)),
+    ("orange", Color::rgb(0.9, 0.5, 0.1)),
+    ("yellow", Color::rgb(0.9, 0.85, 0.2)),
+    ("green", Color::rgb(0.2, 0.7, 0.2)),
+    ("cyan", Color::rgb(0.2, 0.75, 0.8)),
+    ("blue", Color::rgb(0.15, 0.25, 0.8)),
+    ("purple", Color::rgb(0.5, 0.2, 0.7)),
+    ("brown", Color::rgb(0.45, 0.3, 0.15)),
+    ("pink", Color::rgb(0.95, 0.55, 0.7)),
+];
 
 //polylines
 pub const UNIT_X: &str = "unit_x";
+///Wireframe of box centered at origin with size 1.
+pub const UNIT_BOX: &str = "unit_box";
+///Circle on xz plane centered at origin with radius 1.
+pub const UNIT_CIRCLE: &str = "unit_circle";
+///Grid of 5x5 unit cells on xz plane centered at origin.
+pub const GRID_PATCH: &str = "grid_patch";
+///Outline of square on xz plane centered at origin with size 1.
+pub const UNIT_SQUARE: &str = "unit_square";
 
 //polyline materials
 pub const RED: &str = "red";
 pub const GREEN: &str = "green";
 pub const BLUE: &str = "blue";
+pub const GRAY: &str = "gray";
+pub const YELLOW: &str = "yellow";
+
+///Where assets are read from.
+#[derive(Resource, Clone, Debug)]
+pub struct Paths {
+    ///Folder as given to asset server.
+    pub asset_folder: String,
+    ///Folder resolved same as asset server does.
+    pub assets: PathBuf,
+}
+
+impl Paths {
+    pub const DEFAULT_ASSET_FOLDER: &'static str = "assets";
+
+    pub fn new(asset_folder: impl Into<String>) -> Self {
+        let asset_folder = asset_folder.into();
+        let assets = FileAssetIo::get_base_path().join(&asset_folder);
+        Self {
+            asset_folder,
+            assets,
+        }
+    }
+
+    ///Reads `--assets <dir>` override from command line arguments.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        let mut asset_folder = Self::DEFAULT_ASSET_FOLDER.to_owned();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--assets" {
+                match args.next() {
+                    Some(dir) => asset_folder = dir,
+                    //Logger isn't set up yet.
+                    None => eprintln!("--assets needs a directory, using default"),
+                }
+            }
+        }
+        Self::new(asset_folder)
+    }
+}
+
+///Result of checking files that app can't start without.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct AssetCheck {
+    ///Paths relative to assets folder. Empty string is the folder itself.
+    pub missing: Vec<PathBuf>,
+}
+
+impl AssetCheck {
+    ///Relative to assets folder.
+    const CRITICAL: [&'static str; 2] = ["fonts/Schluber.otf", "textures/ui"];
+
+    pub fn run(paths: &Paths) -> Self {
+        let mut missing = Vec::new();
+        if !paths.assets.is_dir() {
+            missing.push(PathBuf::new());
+        } else {
+            for critical in Self::CRITICAL {
+                let path = paths.assets.join(critical);
+                let readable = if path.is_dir() {
+                    path.read_dir().is_ok()
+                } else {
+                    std::fs::File::open(&path).is_ok()
+                };
+                if !readable {
+                    missing.push(PathBuf::from(critical));
+                }
+            }
+        }
+        Self { missing }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    ///Whether file, or folder containing it, is missing.
+    pub fn is_missing(&self, path: &Path) -> bool {
+        self.missing.iter().any(|missing| path.starts_with(missing))
+    }
+}
 
 pub struct AssetManagingPlugin;
 
@@ -50,7 +165,13 @@ impl Plugin for AssetManagingPlugin {
             .init_resource::<StandardMaterials>()
             .init_resource::<Polylines>()
             .init_resource::<PolylineMaterials>()
-            .add_startup_system(assets_set_up);
+            .init_resource::<AssetCheck>()
+            .init_resource::<DeferredAssets>()
+            .add_startup_system(assets_set_up)
+            .add_system_set_to_stage(
+                CoreStage::Update,
+                SystemSet::on_update(UpdateStageState::MainMenu).with_system(start_deferred_assets),
+            );
     }
 }
 
@@ -119,7 +240,7 @@ impl_handle_container!(
     ///StandardMaterial handle access by str. Should index name be sank to whether type or path?
     StandardMaterials,
     StandardMaterial,
-    1
+    2
 );
 
 impl_handle_container!(
@@ -134,10 +255,105 @@ impl_handle_container!(
     PolylineMaterial
 );
 
+///Load state of assets main menu can't be shown without.
+pub fn critical_load_state(
+    asset_server: &AssetServer,
+    fonts: &Fonts,
+    images: &Images,
+) -> LoadState {
+    let handles = fonts.values().map(|handle| handle.id()).chain(
+        images
+            .iter()
+            .flat_map(|map| map.values().map(|handle| handle.id())),
+    );
+    asset_server.get_group_load_state(handles)
+}
+
+///Assets not needed until game is entered.
+///Loaded once main menu is interactive, so they don't compete with critical assets.
+#[derive(Resource, Default)]
+pub struct DeferredAssets {
+    ///Path and handle of every deferred asset. Empty until started.
+    loads: Vec<(&'static str, HandleId)>,
+    started: bool,
+}
+
+impl DeferredAssets {
+    ///Starts loading and maps handles to str. Does nothing once started.
+    pub fn start(&mut self, asset_server: &AssetServer, meshes: &mut Meshes) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        //weapon
+        for (name, path) in [
+            (
+                GUN_TOWER_0_BASE,
+                "models/weapon/gun_tower0.glb#Mesh0/Primitive0",
+            ),
+            (
+                GUN_TOWER_0_TOWER,
+                "models/weapon/gun_tower0.glb#Mesh1/Primitive0",
+            ),
+            (
+                GUN_TOWER_0_GUN,
+                "models/weapon/gun_tower0.glb#Mesh2/Primitive0",
+            ),
+        ] {
+            let handle: Handle<Mesh> = asset_server.load(path);
+            self.loads.push((path, handle.id()));
+            meshes[MESH_WEAPON].insert(name, handle);
+        }
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    ///Whether deferred assets are ready. Failed is told apart from still loading.
+    pub fn load_state(&self, asset_server: &AssetServer) -> LoadState {
+        if !self.started {
+            return LoadState::NotLoaded;
+        }
+        asset_server.get_group_load_state(self.loads.iter().map(|(_, id)| *id))
+    }
+
+    ///Paths that failed to load, which are most likely missing.
+    pub fn failed<'a>(
+        &'a self,
+        asset_server: &'a AssetServer,
+    ) -> impl Iterator<Item = &'static str> + 'a {
+        self.loads
+            .iter()
+            .filter(|(_, id)| asset_server.get_load_state(*id) == LoadState::Failed)
+            .map(|(path, _)| *path)
+    }
+}
+
+///Starts deferred loads once critical assets are loaded.
+fn start_deferred_assets(
+    asset_server: Res<AssetServer>,
+    fonts: Res<Fonts>,
+    images: Res<Images>,
+    mut deferred: ResMut<DeferredAssets>,
+    mut meshes: ResMut<Meshes>,
+    mut timings: ResMut<StartupTimings>,
+) {
+    if deferred.is_started()
+        || critical_load_state(&asset_server, &fonts, &images) != LoadState::Loaded
+    {
+        return;
+    }
+    deferred.start(&asset_server, &mut meshes);
+    timings.mark("deferred assets");
+}
+
 ///Load assets and map them to str.
 #[allow(const_item_mutation)]
 pub fn assets_set_up(
     asset_server: Res<AssetServer>,
+    check: Res<AssetCheck>,
+    mut font_assets: ResMut<Assets<Font>>,
     mut fonts: ResMut<Fonts>,
     mut textures: ResMut<Images>,
     mut mesh_assets: ResMut<Assets<Mesh>>,
@@ -148,14 +364,19 @@ pub fn assets_set_up(
     mut polylines: ResMut<Polylines>,
     mut polyline_material_assets: ResMut<Assets<PolylineMaterial>>,
     mut polyline_materials: ResMut<PolylineMaterials>,
+    mut timings: ResMut<StartupTimings>,
 ) {
-    use std::path::Path;
+    let _span = timings.span("assets_set_up");
     //fonts
     let fonts_dir = Path::new("fonts");
-    fonts.insert(
-        FONT_SCHLUBER,
-        asset_server.load(fonts_dir.join(FONT_SCHLUBER)),
-    );
+    let font_path = fonts_dir.join(FONT_SCHLUBER);
+    let font = if check.is_missing(&font_path) {
+        let font = Font::try_from_bytes(FALLBACK_FONT.to_vec()).expect("Built in font is valid");
+        font_assets.add(font)
+    } else {
+        asset_server.load(font_path)
+    };
+    fonts.insert(FONT_SCHLUBER, font);
     //textures
     let textures_dir = Path::new("textures");
     {
@@ -163,26 +384,12 @@ pub fn assets_set_up(
         let ui_dir = textures_dir.join("ui");
         textures[IMAGE_UI].insert(CROSSHAIR, asset_server.load(ui_dir.join(CROSSHAIR)));
     }
-    let models_dir = Path::new("models");
     //meshes
     {
         //builtin
         meshes[MESH_BUILT_IN].insert(CUBE, mesh_assets.add(Cube::new(1.).into()));
         meshes[MESH_BUILT_IN].insert(PLANE, mesh_assets.add(Plane { size: 1. }.into()));
-        //weapon
-        let _weapon_dir = models_dir.join("weapon");
-        meshes[MESH_WEAPON].insert(
-            GUN_TOWER_0_BASE,
-            asset_server.load("models/weapon/gun_tower0.glb#Mesh0/Primitive0"),
-        );
-        meshes[MESH_WEAPON].insert(
-            GUN_TOWER_0_TOWER,
-            asset_server.load("models/weapon/gun_tower0.glb#Mesh1/Primitive0"),
-        );
-        meshes[MESH_WEAPON].insert(
-            GUN_TOWER_0_GUN,
-            asset_server.load("models/weapon/gun_tower0.glb#Mesh2/Primitive0"),
-        );
+        //weapon meshes are deferred
     }
     //materials
     {
@@ -193,10 +400,23 @@ pub fn assets_set_up(
             WHITE_TRANS,
             standard_material_assets.add((*Color::WHITE.set_a(0.4)).into()),
         );
+        standard_materials[S_MAT_BUILT_IN].insert(
+            WHITE_FAINT,
+            standard_material_assets.add((*Color::WHITE.set_a(0.15)).into()),
+        );
+        standard_materials[S_MAT_BUILT_IN].insert(
+            RED_TRANS,
+            standard_material_assets.add((*Color::RED.set_a(0.4)).into()),
+        );
         standard_materials[S_MAT_BUILT_IN].insert(
             SEA_GREEN,
             standard_material_assets.add(Color::SEA_GREEN.into()),
         );
+        //palette
+        for (name, color) in PALETTE {
+            standard_materials[S_MAT_PALETTE]
+                .insert(name, standard_material_assets.add(color.into()));
+        }
     }
     //polylines
     polylines.insert(
@@ -205,6 +425,36 @@ pub fn assets_set_up(
             vertices: vec![Vec3::ZERO, Vec3::X],
         }),
     );
+    polylines.insert(
+        UNIT_BOX,
+        polyline_assets.add(Polyline {
+            vertices: box_edges(&AABB::from_size(1.)),
+        }),
+    );
+    polylines.insert(
+        UNIT_CIRCLE,
+        polyline_assets.add(Polyline {
+            vertices: circle_points(32),
+        }),
+    );
+    polylines.insert(
+        GRID_PATCH,
+        polyline_assets.add(Polyline {
+            vertices: grid_points(5),
+        }),
+    );
+    polylines.insert(
+        UNIT_SQUARE,
+        polyline_assets.add(Polyline {
+            vertices: vec![
+                Vec3::new(-0.5, 0., -0.5),
+                Vec3::new(0.5, 0., -0.5),
+                Vec3::new(0.5, 0., 0.5),
+                Vec3::new(-0.5, 0., 0.5),
+                Vec3::new(-0.5, 0., -0.5),
+            ],
+        }),
+    );
     //polyline materials
     polyline_materials.insert(
         RED,
@@ -230,4 +480,203 @@ pub fn assets_set_up(
             ..default()
         }),
     );
+    polyline_materials.insert(
+        GRAY,
+        polyline_material_assets.add(PolylineMaterial {
+            color: Color::GRAY,
+            perspective: true,
+            ..default()
+        }),
+    );
+    polyline_materials.insert(
+        YELLOW,
+        polyline_material_assets.add(PolylineMaterial {
+            color: Color::YELLOW,
+            perspective: true,
+            ..default()
+        }),
+    );
+}
+
+///Closed loop of unit circle on xz plane.
+pub fn circle_points(segments: usize) -> Vec<Vec3> {
+    (0..=segments)
+        .map(|i| {
+            let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+            Vec3::new(angle.cos(), 0., angle.sin())
+        })
+        .collect()
+}
+
+///Grid of unit cells on xz plane as one line strip.
+///Strip snakes along rows then columns, so every connecting segment lies on grid line.
+pub fn grid_points(cells: usize) -> Vec<Vec3> {
+    let half = cells as f32 * 0.5;
+    let mut ret = Vec::with_capacity((cells + 1) * 4);
+    for i in 0..=cells {
+        let z = -half + i as f32;
+        let x = if i % 2 == 0 { half } else { -half };
+        ret.push(Vec3::new(-x, 0., z));
+        ret.push(Vec3::new(x, 0., z));
+    }
+    //Columns start from where rows ended.
+    let start = ret.last().map_or(half, |last| last.x);
+    for j in 0..=cells {
+        let x = start - start.signum() * j as f32;
+        let z = if j % 2 == 0 { half } else { -half };
+        ret.push(Vec3::new(x, 0., z));
+        ret.push(Vec3::new(x, 0., -z));
+    }
+    ret
+}
+
+///Line strip that passes every edge of box. Three edges are passed twice.
+pub fn box_edges(aabb: &AABB) -> Vec<Vec3> {
+    let corners = aabb.corners();
+    [0, 1, 3, 2, 0, 4, 5, 7, 6, 4, 5, 1, 3, 7, 6, 2]
+        .into_iter()
+        .map(|i| corners[i])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, Instant};
+
+    use bevy::{
+        asset::{AssetIo, AssetIoError, AssetLoader, BoxedFuture, LoadContext, Metadata},
+        tasks::{IoTaskPool, TaskPool},
+    };
+
+    #[test]
+    fn box_edges_cover_twelve_edges() {
+        let aabb = AABB::new(Vec3::new(-1., 0., 2.), Vec3::new(3., 5., 4.));
+        let strip = box_edges(&aabb);
+        let corners = aabb.corners();
+        let mut edges = Vec::new();
+        for pair in strip.windows(2) {
+            let [a, b] = [pair[0], pair[1]].map(|point| {
+                corners
+                    .iter()
+                    .position(|&corner| corner == point)
+                    .expect("Strip should only pass corners")
+            });
+            //Box edge joins corners that differ on exactly one axis.
+            let delta = (corners[a] - corners[b]).abs();
+            assert!(delta.cmpgt(Vec3::ZERO).bitmask().count_ones() == 1);
+            edges.push((a.min(b), a.max(b)));
+        }
+        edges.sort_unstable();
+        edges.dedup();
+        assert_eq!(edges.len(), 12);
+        assert_eq!(strip.len(), 16);
+    }
+
+    ///Asset source whose files are missing, or never finish loading when slow.
+    struct FakeSource {
+        slow: bool,
+    }
+
+    impl AssetIo for FakeSource {
+        fn load_path<'a>(
+            &'a self,
+            path: &'a Path,
+        ) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+            Box::pin(async move {
+                if self.slow {
+                    std::future::pending::<()>().await;
+                }
+                Err(AssetIoError::NotFound(path.to_owned()))
+            })
+        }
+
+        fn read_directory(
+            &self,
+            path: &Path,
+        ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+            Err(AssetIoError::NotFound(path.to_owned()))
+        }
+
+        fn get_metadata(&self, path: &Path) -> Result<Metadata, AssetIoError> {
+            Err(AssetIoError::NotFound(path.to_owned()))
+        }
+
+        fn watch_path_for_changes(&self, _path: &Path) -> Result<(), AssetIoError> {
+            Ok(())
+        }
+
+        fn watch_for_changes(&self) -> Result<(), AssetIoError> {
+            Ok(())
+        }
+    }
+
+    ///Takes glb, so failure is from source and not from missing loader. Never reached.
+    struct GlbLoader;
+
+    impl AssetLoader for GlbLoader {
+        fn load<'a>(
+            &'a self,
+            _bytes: &'a [u8],
+            _load_context: &'a mut LoadContext,
+        ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &["glb"]
+        }
+    }
+
+    ///Deferred loads started over fake source, and their state once it settles or after a while.
+    fn deferred_over(slow: bool) -> (AssetServer, DeferredAssets, Meshes) {
+        IoTaskPool::init(TaskPool::new);
+        let asset_server = AssetServer::new(FakeSource { slow });
+        asset_server.add_loader(GlbLoader);
+        let mut deferred = DeferredAssets::default();
+        let mut meshes = Meshes::default();
+        assert_eq!(deferred.load_state(&asset_server), LoadState::NotLoaded);
+        deferred.start(&asset_server, &mut meshes);
+        deferred.start(&asset_server, &mut meshes);
+        //Source isn't known to server until its load task runs, so it reads as not loaded until then.
+        let start = Instant::now();
+        while matches!(
+            deferred.load_state(&asset_server),
+            LoadState::NotLoaded | LoadState::Loading
+        ) && start.elapsed() < Duration::from_millis(if slow { 50 } else { 5000 })
+        {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        (asset_server, deferred, meshes)
+    }
+
+    #[test]
+    fn deferred_assets_tell_missing_from_loading() {
+        let (asset_server, deferred, meshes) = deferred_over(false);
+        //Handles exist right after start, so game entered early has something to spawn with.
+        assert_eq!(meshes[MESH_WEAPON].len(), 3);
+        assert_eq!(deferred.loads.len(), 3);
+        assert_eq!(deferred.load_state(&asset_server), LoadState::Failed);
+        assert_eq!(deferred.failed(&asset_server).count(), 3);
+
+        let (asset_server, deferred, meshes) = deferred_over(true);
+        assert_eq!(meshes[MESH_WEAPON].len(), 3);
+        assert_eq!(deferred.load_state(&asset_server), LoadState::Loading);
+        assert_eq!(deferred.failed(&asset_server).count(), 0);
+    }
+
+    #[test]
+    fn assets_folder_is_overridden_from_args() {
+        let paths = |args: &[&str]| Paths::from_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(paths(&[]).asset_folder, Paths::DEFAULT_ASSET_FOLDER);
+        let shared = paths(&["--seed", "3", "--assets", "shared/assets"]);
+        assert_eq!(shared.asset_folder, "shared/assets");
+        assert!(shared.assets.ends_with("shared/assets"));
+        //Flag without folder keeps default.
+        assert_eq!(
+            paths(&["--assets"]).asset_folder,
+            Paths::DEFAULT_ASSET_FOLDER
+        );
+    }
 }
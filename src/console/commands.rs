@@ -0,0 +1,1174 @@
+use crate::{
+    asset::{Meshes, StandardMaterials, CUBE, MESH_BUILT_IN, S_MAT_PALETTE},
+    console::{param::Param, *},
+    consts::POINT_EPS,
+    demo::*,
+    physics::{
+        aabb::AABB,
+        collider::{Collider, Shape},
+        octree::*,
+        ray::Ray,
+    },
+    rng::{parse_seed, RngSeeds},
+    settings::{KeyBindings, Lighting, Settings},
+    specs::Specs,
+    states::in_game::{compass::Waypoint, LookAt, OctreeCounterLog, PlacementTrail, ShiftBuild},
+    structure::{BoundsDebug, Fixture, Structure, StructureDesc, WorldMutator},
+    tag::*,
+    timings::StartupTimings,
+    toggles::SystemToggles,
+    trace::QueryTrace,
+    vox::VoxModel,
+};
+
+use std::time::Instant;
+
+use bevy::{
+    ecs::{component::ComponentTicks, system::SystemState},
+    utils::HashSet,
+};
+
+const OVERSIZE_FRACTION: Param = Param::scalar("oversize fraction").soft_range(0.001, 1.);
+const MOUSE_BUTTON: Param = Param::count("mouse button").range(0., u16::MAX as f64);
+const ILLUMINANCE: Param = Param::scalar("illuminance").range(0., 150_000.);
+const COLOR_CHANNEL: Param = Param::scalar("color").soft_range(0., 1.);
+const YAW: Param = Param::angle("yaw").soft_range(-360., 360.);
+const PITCH: Param = Param::angle("pitch").range(-90., 90.);
+const TRAIL_LEN: Param =
+    Param::count("trail length").soft_range(0., PlacementTrail::MAX_LEN as f64);
+const LOCK_RELEASE: Param = Param::count("placements").range(1., u32::MAX as f64);
+const FRAME_CAP: Param = Param::scalar("frame cap").soft_range(1., 1000.);
+const SHIFT: Param = Param::length("offset");
+
+///Registers built-in commands.
+pub fn register(commands: &mut ConsoleCommands) {
+    commands.register("help", CommandCost::Instant, |_| Ok(Box::new(Help)));
+    commands.register("debug bounds", CommandCost::Instant, |_| {
+        Ok(Box::new(ToggleBoundsDebug))
+    });
+    commands.register("dedupe", CommandCost::Heavy, |_| {
+        Ok(Box::<Dedupe>::default())
+    });
+    commands.register("generate demo", CommandCost::Heavy, |args| {
+        Ok(Box::new(GenerateDemo::new(DemoPreset::parse(args)?)))
+    });
+    commands.register("octree rebuild", CommandCost::Heavy, |args| match args {
+        [] => Ok(Box::<OctreeRebuild>::default()),
+        [fraction] => {
+            let fraction = OVERSIZE_FRACTION
+                .parse(fraction)
+                .map_err(|e| format!("octree rebuild: {e}"))?;
+            Ok(Box::new(WithNotes {
+                command: OctreeRebuild {
+                    oversize_fraction: Some(fraction.value as f32),
+                    ..default()
+                },
+                notes: fraction.note.into_iter().collect(),
+            }))
+        }
+        _ => Err("usage: octree rebuild [oversize fraction]".to_owned()),
+    });
+    commands.register("octree validate", CommandCost::Instant, |args| match args {
+        [] => Ok(Box::new(OctreeValidate(None))),
+        ["on"] => Ok(Box::new(OctreeValidate(Some(true)))),
+        ["off"] => Ok(Box::new(OctreeValidate(Some(false)))),
+        _ => Err("usage: octree validate [on|off]".to_owned()),
+    });
+    commands.register("octree counters", CommandCost::Instant, |args| match args {
+        [] => Ok(Box::new(OctreeCounterCommand::Show)),
+        ["on"] => Ok(Box::new(OctreeCounterCommand::Log(true))),
+        ["off"] => Ok(Box::new(OctreeCounterCommand::Log(false))),
+        ["reset"] => Ok(Box::new(OctreeCounterCommand::Reset)),
+        _ => Err("usage: octree counters [on|off|reset]".to_owned()),
+    });
+    commands.register("import vox", CommandCost::Heavy, |args| match args {
+        [path] => Ok(Box::new(ImportVox::new(path, false))),
+        [path, "hollow"] => Ok(Box::new(ImportVox::new(path, true))),
+        _ => Err("usage: import vox <path> [hollow]".to_owned()),
+    });
+    commands.register("bind", CommandCost::Instant, |args| {
+        let (place, button) = match args {
+            ["place", button] => (true, button),
+            ["remove", button] => (false, button),
+            _ => return Err("usage: bind place|remove left|right|middle|<number>".to_owned()),
+        };
+        let button = match KeyBindings::parse_mouse_button(button) {
+            Some(button) => button,
+            None => MouseButton::Other(
+                MOUSE_BUTTON
+                    .parse_value(button)
+                    .map_err(|e| format!("bind: {e}"))? as u16,
+            ),
+        };
+        Ok(Box::new(Bind { place, button }))
+    });
+    commands.register("seed show", CommandCost::Instant, |_| {
+        Ok(Box::new(Seed(None)))
+    });
+    commands.register("seed set", CommandCost::Instant, |args| match args {
+        [seed] => parse_seed(seed)
+            .map(|seed| Box::new(Seed(Some(seed))) as Box<dyn ResumableCommand>)
+            .ok_or_else(|| format!("seed set: not a hex seed: {seed}")),
+        _ => Err("usage: seed set <hex>".to_owned()),
+    });
+    commands.register("light", CommandCost::Instant, |args| {
+        let mut notes = Vec::new();
+        let mut number = |param: Param, arg: &str| {
+            let parsed = param.parse(arg).map_err(|e| format!("light: {e}"))?;
+            notes.extend(parsed.note);
+            Ok::<_, String>(parsed.value as f32)
+        };
+        let change = match args {
+            ["intensity", lux] => LightChange::Illuminance(number(ILLUMINANCE, *lux)?),
+            ["color", r, g, b] => LightChange::Color(Color::rgb(
+                number(COLOR_CHANNEL, *r)?,
+                number(COLOR_CHANNEL, *g)?,
+                number(COLOR_CHANNEL, *b)?,
+            )),
+            ["angle", yaw, pitch] => LightChange::Angle(
+                number(YAW, *yaw)?.to_radians(),
+                number(PITCH, *pitch)?.to_radians(),
+            ),
+            _ => {
+                return Err(
+                    "usage: light intensity <lux> | color <r> <g> <b> | angle <yaw> <pitch>"
+                        .to_owned(),
+                )
+            }
+        };
+        Ok(Box::new(WithNotes {
+            command: change,
+            notes,
+        }))
+    });
+    commands.register("trail", CommandCost::Instant, |args| match args {
+        [len] => {
+            let len = TRAIL_LEN.parse(len).map_err(|e| format!("trail: {e}"))?;
+            Ok(Box::new(WithNotes {
+                command: SettingChange::Trail(len.value as usize),
+                notes: len.note.into_iter().collect(),
+            }))
+        }
+        _ => Err("usage: trail <count>".to_owned()),
+    });
+    commands.register("lock release", CommandCost::Instant, |args| match args {
+        ["off"] => Ok(Box::new(SettingChange::LockRelease(None))),
+        [placements] => {
+            let placements = LOCK_RELEASE
+                .parse_value(placements)
+                .map_err(|e| format!("lock release: {e}"))?;
+            Ok(Box::new(SettingChange::LockRelease(Some(
+                placements as usize,
+            ))))
+        }
+        _ => Err("usage: lock release <placements>|off".to_owned()),
+    });
+    commands.register("vsync", CommandCost::Instant, |args| match args {
+        ["on"] => Ok(Box::new(SettingChange::Vsync(true))),
+        ["off"] => Ok(Box::new(SettingChange::Vsync(false))),
+        _ => Err("usage: vsync on|off".to_owned()),
+    });
+    commands.register("frame cap", CommandCost::Instant, |args| match args {
+        ["off"] => Ok(Box::new(SettingChange::FrameCap(None))),
+        [fps] => {
+            let fps = FRAME_CAP
+                .parse(fps)
+                .map_err(|e| format!("frame cap: {e}"))?;
+            Ok(Box::new(WithNotes {
+                command: SettingChange::FrameCap(Some(fps.value as f32)),
+                notes: fps.note.into_iter().collect(),
+            }))
+        }
+        _ => Err("usage: frame cap <fps>|off".to_owned()),
+    });
+    commands.register("docs physics", CommandCost::Instant, |_| {
+        Ok(Box::new(DocsPhysics))
+    });
+    commands.register("tags", CommandCost::Instant, |args| match args {
+        [] => Ok(Box::new(Tags(None))),
+        [name] => Ok(Box::new(Tags(Some(name.to_string())))),
+        _ => Err("usage: tags [name]".to_owned()),
+    });
+    commands.register("trace raycast", CommandCost::Instant, |args| match args {
+        ["on"] => Ok(Box::new(TraceRaycast(true))),
+        ["off"] => Ok(Box::new(TraceRaycast(false))),
+        _ => Err("usage: trace raycast on|off".to_owned()),
+    });
+    commands.register("waypoint", CommandCost::Instant, |args| match args {
+        [] => Ok(Box::new(SetWaypoint::Show)),
+        ["set", "here"] => Ok(Box::new(SetWaypoint::Here)),
+        ["clear"] => Ok(Box::new(SetWaypoint::Clear)),
+        _ => Err("usage: waypoint [set here | clear]".to_owned()),
+    });
+    commands.register("compass", CommandCost::Instant, |args| match args {
+        ["on"] => Ok(Box::new(ShowCompass(true))),
+        ["off"] => Ok(Box::new(ShowCompass(false))),
+        _ => Err("usage: compass on|off".to_owned()),
+    });
+    commands.register("sys list", CommandCost::Instant, |_| {
+        Ok(Box::new(ListSystems))
+    });
+    commands.register("sys off", CommandCost::Instant, |args| match args {
+        [name] => Ok(Box::new(ToggleSystem(name.to_string(), false))),
+        _ => Err("usage: sys off <name>".to_owned()),
+    });
+    commands.register("sys on", CommandCost::Instant, |args| match args {
+        [name] => Ok(Box::new(ToggleSystem(name.to_string(), true))),
+        _ => Err("usage: sys on <name>".to_owned()),
+    });
+    commands.register("shift build", CommandCost::Instant, |args| match args {
+        ["center"] => Ok(Box::new(Shift(None))),
+        [x, y, z] => {
+            let mut offset = Vec3::ZERO;
+            for (i, arg) in [x, y, z].into_iter().enumerate() {
+                let value = SHIFT
+                    .parse_value(arg)
+                    .map_err(|e| format!("shift build: {e}"))?;
+                //Structures stay on cells.
+                if value.fract() != 0. {
+                    return Err(format!("shift build: offset should be whole cells: {arg}"));
+                }
+                offset[i] = value as f32;
+            }
+            Ok(Box::new(Shift(Some(offset))))
+        }
+        _ => Err("usage: shift build <dx> <dy> <dz> | center".to_owned()),
+    });
+    commands.register("startup report", CommandCost::Instant, |_| {
+        Ok(Box::new(StartupReport))
+    });
+    #[cfg(feature = "inspect")]
+    commands.register("inspect", CommandCost::Instant, |_| Ok(Box::new(Inspect)));
+}
+
+///Lists every command.
+struct Help;
+
+impl ResumableCommand for Help {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        let mut names: Vec<_> = world.resource::<ConsoleCommands>().names().collect();
+        names.sort_unstable();
+        Progress::Done(names.join(", "))
+    }
+}
+
+///Toggles drawing physics and render bounds of structures.
+struct ToggleBoundsDebug;
+
+impl ResumableCommand for ToggleBoundsDebug {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        match world.get_resource_mut::<BoundsDebug>() {
+            Some(mut debug) => {
+                debug.0 = !debug.0;
+                Progress::Done(format!("debug bounds: {}", debug.0))
+            }
+            None => Progress::Done("debug bounds: unavailable".to_owned()),
+        }
+    }
+}
+
+///Freezes gameplay and dumps octree, or unfreezes.
+#[cfg(feature = "inspect")]
+struct Inspect;
+
+#[cfg(feature = "inspect")]
+impl ResumableCommand for Inspect {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        use crate::{inspect::InspectFreeze, states::*};
+
+        if world.resource::<GlobalState>().should_change() {
+            return Progress::Done("inspect: state is changing".to_owned());
+        }
+        match world.resource::<State<UpdateStageState>>().current() {
+            UpdateStageState::Inspecting => {
+                world.resource_mut::<GlobalState>().pop();
+                let restored = world.resource::<InspectFreeze>().bounds_debug;
+                world.resource_mut::<BoundsDebug>().0 = restored;
+                return Progress::Done("inspect: resumed".to_owned());
+            }
+            UpdateStageState::InGame => {}
+            _ => return Progress::Done("inspect: only in game".to_owned()),
+        }
+        let report = match world.query::<&Octree>().iter(world).next() {
+            Some(octree) => format!("{}, memory {} bytes", octree.stats(), octree.memory_usage()),
+            None => return Progress::Done("inspect: no octree".to_owned()),
+        };
+        let bounds_debug = world.resource::<BoundsDebug>().0;
+        world.resource_mut::<InspectFreeze>().bounds_debug = bounds_debug;
+        world.resource_mut::<BoundsDebug>().0 = true;
+        //Pauses in game, so none of its update systems run until resumed.
+        world
+            .resource_mut::<GlobalState>()
+            .push(InGameState::Inspecting);
+        info!("inspect: {report}");
+        Progress::Done(format!("inspect: frozen, {report}"))
+    }
+}
+
+///Binds mouse button to place or remove.
+struct Bind {
+    place: bool,
+    button: MouseButton,
+}
+
+impl ResumableCommand for Bind {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        let mut bindings = world.resource_mut::<KeyBindings>();
+        let (action, bound) = if self.place {
+            ("place", &mut bindings.place)
+        } else {
+            ("remove", &mut bindings.remove)
+        };
+        *bound = self.button;
+        Progress::Done(format!("bind: {action} is {:?}", self.button))
+    }
+}
+
+///Shows session seed, or sets it when given.
+struct Seed(Option<u64>);
+
+impl ResumableCommand for Seed {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        let mut seeds = match world.get_resource_mut::<RngSeeds>() {
+            Some(seeds) => seeds,
+            None => return Progress::Done("seed: no session in progress".to_owned()),
+        };
+        if let Some(seed) = self.0 {
+            if let Err(e) = seeds.set_root(seed) {
+                return Progress::Done(format!("seed set refused: {e}"));
+            }
+        }
+        Progress::Done(format!("seed: {:016x}", seeds.root()))
+    }
+}
+
+///Changes one part of lighting settings. Angles are in radians.
+enum LightChange {
+    Illuminance(f32),
+    Color(Color),
+    Angle(f32, f32),
+}
+
+impl ResumableCommand for LightChange {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        let mut lighting = world.resource_mut::<Lighting>();
+        match *self {
+            Self::Illuminance(lux) => lighting.illuminance = lux,
+            Self::Color(color) => lighting.color = color,
+            Self::Angle(yaw, pitch) => {
+                lighting.yaw = yaw;
+                lighting.pitch = pitch;
+            }
+        }
+        Progress::Done(format!("light: {:?}", *lighting))
+    }
+}
+
+///Changes one of user settings that has no menu.
+enum SettingChange {
+    Trail(usize),
+    LockRelease(Option<usize>),
+    Vsync(bool),
+    FrameCap(Option<f32>),
+}
+
+impl ResumableCommand for SettingChange {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        let mut settings = world.resource_mut::<Settings>();
+        Progress::Done(match *self {
+            Self::Trail(len) => {
+                settings.placement_trail = len;
+                format!("trail: {len}")
+            }
+            Self::LockRelease(release) => {
+                settings.plane_lock_release = release;
+                match release {
+                    Some(placements) => format!("lock release: after {placements} placements"),
+                    None => "lock release: off".to_owned(),
+                }
+            }
+            Self::Vsync(vsync) => {
+                settings.vsync = vsync;
+                format!("vsync: {}", if vsync { "on" } else { "off" })
+            }
+            Self::FrameCap(cap) => {
+                settings.frame_cap = cap;
+                match cap {
+                    Some(fps) => format!("frame cap: {fps} fps"),
+                    None => "frame cap: off".to_owned(),
+                }
+            }
+        })
+    }
+}
+
+///Reports physics contracts from live values and exports them next to saves.
+struct DocsPhysics;
+
+impl ResumableCommand for DocsPhysics {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        let specs = Specs::physics(world.query::<&Octree>().iter(world).next());
+        let path = Specs::path();
+        let exported = match specs.write(&path) {
+            Ok(()) => format!("written to {}", path.display()),
+            Err(e) => format!("failed to write {}: {e}", path.display()),
+        };
+        Progress::Done(format!("{specs}{exported}"))
+    }
+}
+
+///Counts structures per tag, or structures of one tag.
+struct Tags(Option<String>);
+
+impl ResumableCommand for Tags {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        let mut system_state =
+            SystemState::<(Res<TagRegistry>, Res<StructureIndex>, Query<&StructureTags>)>::new(
+                world,
+            );
+        let (registry, index, tags) = system_state.get(world);
+        let name = match &self.0 {
+            Some(name) => name,
+            None => {
+                let counts: Vec<_> = registry
+                    .iter()
+                    .map(|(tag, name)| format!("{name} {}", index.count_with_tag(tag)))
+                    .collect();
+                return Progress::Done(format!("tags: {}", counts.join(", ")));
+            }
+        };
+        let tag = match registry.get(name) {
+            Some(tag) => tag,
+            None => return Progress::Done(format!("tags: unknown tag {name}")),
+        };
+        //Index is cross checked against components, so stale entry shows up here.
+        let (tagged, stale) =
+            index
+                .entities_with_tag(tag)
+                .fold((0, 0), |(tagged, stale), entity| {
+                    if has_tag(entity, name, &registry, &tags) {
+                        (tagged + 1, stale)
+                    } else {
+                        (tagged, stale + 1)
+                    }
+                });
+        Progress::Done(format!(
+            "tags: {tagged} structures tagged {}, {stale} stale in index",
+            registry.name(tag)
+        ))
+    }
+}
+
+///Arms tracing of next crosshair raycast, or clears drawn trace.
+struct TraceRaycast(bool);
+
+impl ResumableCommand for TraceRaycast {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        match world.get_resource_mut::<QueryTrace>() {
+            Some(mut trace) => {
+                trace.armed = self.0;
+                if !self.0 {
+                    trace.last = None;
+                }
+                Progress::Done(format!(
+                    "trace raycast: {}",
+                    if self.0 { "on" } else { "off" }
+                ))
+            }
+            None => Progress::Done("trace raycast: unavailable".to_owned()),
+        }
+    }
+}
+
+///Shows or changes waypoint of compass.
+enum SetWaypoint {
+    Show,
+    ///Cell of camera.
+    Here,
+    Clear,
+}
+
+impl ResumableCommand for SetWaypoint {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        if let Self::Here = self {
+            let here = world
+                .query_filtered::<&Transform, (With<Camera>, With<LookAt>)>()
+                .iter(world)
+                .next()
+                .map(|transform| transform.translation.round());
+            match here {
+                Some(here) => world.resource_mut::<Waypoint>().0 = Some(here),
+                None => return Progress::Done("waypoint: no camera in game".to_owned()),
+            }
+        } else if let Self::Clear = self {
+            world.resource_mut::<Waypoint>().0 = None;
+        }
+        match world.resource::<Waypoint>().0 {
+            Some(point) => Progress::Done(format!("waypoint: {} {} {}", point.x, point.y, point.z)),
+            None => Progress::Done("waypoint: none, compass leads to origin".to_owned()),
+        }
+    }
+}
+
+struct ShowCompass(bool);
+
+impl ResumableCommand for ShowCompass {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        world.resource_mut::<Settings>().show_compass = self.0;
+        Progress::Done(format!("compass: {}", if self.0 { "on" } else { "off" }))
+    }
+}
+
+///Shifts whole build by offset, or centers it when none.
+///Shift is done by game, which warns with structures that would leave blueprint.
+struct Shift(Option<Vec3>);
+
+impl ResumableCommand for Shift {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        if world.query::<&Octree>().iter(world).next().is_none() {
+            return Progress::Done("shift build: no build to shift".to_owned());
+        }
+        world.send_event(ShiftBuild(self.0));
+        Progress::Done(match self.0 {
+            Some(offset) => format!("shift build: by {} {} {}", offset.x, offset.y, offset.z),
+            None => "shift build: centering".to_owned(),
+        })
+    }
+}
+
+///Shows where startup time went.
+struct StartupReport;
+
+impl ResumableCommand for StartupReport {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        match world.get_resource::<StartupTimings>() {
+            Some(timings) => Progress::Done(timings.report()),
+            None => Progress::Done("startup report: unavailable".to_owned()),
+        }
+    }
+}
+
+///Lists systems that could be switched off, with whether each runs.
+struct ListSystems;
+
+impl ResumableCommand for ListSystems {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        let toggles = world.get_resource_or_insert_with(SystemToggles::default);
+        if toggles.names().next().is_none() {
+            return Progress::Done("sys: no systems to switch".to_owned());
+        }
+        let states: Vec<_> = toggles
+            .iter()
+            .map(|(name, enabled)| format!("{name} {}", if enabled { "on" } else { "off" }))
+            .collect();
+        Progress::Done(format!("sys: {}", states.join(", ")))
+    }
+}
+
+///Switches system on or off by registered name.
+struct ToggleSystem(String, bool);
+
+impl ResumableCommand for ToggleSystem {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        let mut toggles = world.get_resource_or_insert_with(SystemToggles::default);
+        Progress::Done(match toggles.set(&self.0, self.1) {
+            Ok(()) => format!("sys: {} {}", self.0, if self.1 { "on" } else { "off" }),
+            Err(e) => format!("sys: {e}"),
+        })
+    }
+}
+
+///Removes structures that are placed at same position, keeping one of them.
+#[derive(Default)]
+struct Dedupe {
+    ///Structures at the moment command started. None until first resume.
+    structures: Option<Vec<(Entity, Vec3)>>,
+    next: usize,
+    seen: HashSet<IVec3>,
+    removed: usize,
+}
+
+impl Dedupe {
+    ///Positions closer than this are considered same.
+    const PRECISION: f32 = POINT_EPS;
+}
+
+impl ResumableCommand for Dedupe {
+    fn resume(&mut self, world: &mut World, budget: &TimeBudget) -> Progress {
+        if world.query::<&Octree>().iter(world).next().is_none() {
+            return Progress::Done("dedupe: no world to dedupe".to_owned());
+        }
+        let structures = self.structures.get_or_insert_with(|| {
+            let mut structures: Vec<_> = world
+                .query::<(Entity, &Transform, &Structure)>()
+                .iter(world)
+                .map(|(entity, transform, structure)| {
+                    (structure.placement(), entity, transform.translation)
+                })
+                .collect();
+            //Older one is kept, which placement order tells unlike recycled entity id.
+            structures.sort_unstable_by_key(|(placement, ..)| *placement);
+            structures
+                .into_iter()
+                .map(|(_, entity, translation)| (entity, translation))
+                .collect()
+        });
+        let mut duplicates = Vec::new();
+        while self.next < structures.len() && budget.start_unit() {
+            let (entity, translation) = structures[self.next];
+            self.next += 1;
+            let key = (translation / Self::PRECISION).round().as_ivec3();
+            if !self.seen.insert(key) {
+                duplicates.push(entity);
+            }
+        }
+        let finished = self.next >= structures.len();
+        //Removal goes through mutator, so world stays consistent even if cancelled.
+        let mut system_state = SystemState::<WorldMutator>::new(world);
+        let mut mutator = system_state.get_mut(world);
+        for entity in duplicates {
+            if mutator.despawn_structure(entity).is_some() {
+                self.removed += 1;
+            }
+        }
+        system_state.apply(world);
+        if finished {
+            Progress::Done(format!("dedupe: removed {} structures", self.removed))
+        } else {
+            Progress::Continue
+        }
+    }
+}
+
+///Rebuilds octree from structures. Tree is swapped only when done, so cancel leaves old tree.
+#[derive(Default)]
+struct OctreeRebuild {
+    ///Replaces oversize fraction of tree when given.
+    oversize_fraction: Option<f32>,
+    ///New tree being built. None until first resume.
+    rebuilt: Option<Octree>,
+    structures: Vec<Entity>,
+    next: usize,
+    ///What is inserted so far, to drop structures removed while rebuilding.
+    inserted: Vec<(Entity, AABB)>,
+    ///Change tick when rebuild started. Structures added or moved after it are inserted again.
+    since: u32,
+}
+
+impl ResumableCommand for OctreeRebuild {
+    fn resume(&mut self, world: &mut World, budget: &TimeBudget) -> Progress {
+        let tree = match world
+            .query_filtered::<Entity, With<Octree>>()
+            .iter(world)
+            .next()
+        {
+            Some(tree) => tree,
+            None => return Progress::Done("octree rebuild: no octree".to_owned()),
+        };
+        if self.rebuilt.is_none() {
+            self.since = world.increment_change_tick();
+            let mut structures: Vec<_> = world
+                .query::<(Entity, &Structure)>()
+                .iter(world)
+                .map(|(entity, structure)| (structure.placement(), entity))
+                .collect();
+            //Placement order, so same build makes same tree.
+            structures.sort_unstable();
+            self.structures = structures.into_iter().map(|(_, entity)| entity).collect();
+            self.rebuilt = world.get::<Octree>(tree).map(|octree| {
+                let mut rebuilt = octree.empty_like();
+                if let Some(fraction) = self.oversize_fraction {
+                    rebuilt.set_oversize_fraction(fraction);
+                }
+                //Fixtures aren't structures, so they are carried over as they are.
+                for entity in octree.iter() {
+                    if world.get::<Fixture>(entity.entity()).is_some() {
+                        rebuilt.insert_result(entity.clone());
+                    }
+                }
+                rebuilt.reserve(rebuilt.nodes_for(self.structures.len()));
+                rebuilt
+            });
+        }
+        let rebuilt = self
+            .rebuilt
+            .as_mut()
+            .expect("Rebuilt tree is created above");
+        while self.next < self.structures.len() {
+            if !budget.start_unit() {
+                return Progress::Continue;
+            }
+            let entity = self.structures[self.next];
+            self.next += 1;
+            if let (Some(transform), Some(collider), Some(structure)) = (
+                world.get::<Transform>(entity),
+                world.get::<Collider>(entity),
+                world.get::<Structure>(entity),
+            ) {
+                rebuilt.insert_result(OctreeEntity::new(
+                    entity,
+                    structure.placement(),
+                    collider,
+                    transform,
+                ));
+                self.inserted.push((entity, collider.aabb(transform)));
+            }
+        }
+        //Structures could be changed while rebuilding across frames.
+        for (entity, aabb) in self.inserted.iter() {
+            if world.get_entity(*entity).is_none() {
+                rebuilt.remove(*entity, *aabb);
+            }
+        }
+        //Only ticks are checked for the rest, so last frame stays as light as others.
+        let now = world.read_change_tick();
+        let changed = |ticks: Option<&ComponentTicks>| {
+            ticks.map_or(false, |ticks| ticks.is_changed(self.since, now))
+        };
+        for (entity, transform, collider, structure) in world
+            .query::<(Entity, &Transform, &Collider, &Structure)>()
+            .iter(world)
+        {
+            let entity_ref = world.entity(entity);
+            if changed(entity_ref.get_change_ticks::<Transform>())
+                || changed(entity_ref.get_change_ticks::<Collider>())
+                || changed(entity_ref.get_change_ticks::<Structure>())
+            {
+                rebuilt.insert_result(OctreeEntity::new(
+                    entity,
+                    structure.placement(),
+                    collider,
+                    transform,
+                ));
+            }
+        }
+        let rebuilt = self.rebuilt.take().expect("Rebuilt tree is created above");
+        let (len, nodes, oversize) = (rebuilt.len(), rebuilt.node_count(), rebuilt.oversize_len());
+        match world.get_mut::<Octree>(tree) {
+            Some(mut octree) => *octree = rebuilt,
+            None => return Progress::Done("octree rebuild: octree is gone".to_owned()),
+        }
+        Progress::Done(format!(
+            "octree rebuild: {len} entities ({oversize} oversize), {nodes} nodes"
+        ))
+    }
+}
+
+///Checks octree structure once, or turns checking after every mutation on or off.
+///Checking after mutation panics on broken tree, and only in debug build.
+struct OctreeValidate(Option<bool>);
+
+impl ResumableCommand for OctreeValidate {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        let crosshair = world
+            .query_filtered::<&Transform, With<LookAt>>()
+            .iter(world)
+            .next()
+            .map(|transform| Ray::new(transform.translation, transform.forward()));
+        let mut octree = match world.query::<&mut Octree>().iter_mut(world).next() {
+            Some(octree) => octree,
+            None => return Progress::Done("octree validate: no octree".to_owned()),
+        };
+        if let Some(validate) = self.0 {
+            octree.set_validate(validate);
+        }
+        let mut result = match octree.debug_validate() {
+            Ok(()) => "ok".to_owned(),
+            Err(e) => format!("broken, {e}"),
+        };
+        //Traversal is checked by crosshair ray against every bound. Distance is compared, as ties
+        //could pick other entity.
+        if let Some(ray) = crosshair {
+            let boxes: Vec<_> = octree
+                .iter()
+                .map(|entity| (entity.entity(), entity.aabb()))
+                .collect();
+            let tree = octree.raycast_aabb(&ray).map(|hit| hit.t);
+            let brute = raycast_brute(&boxes, &ray).map(|hit| hit.t);
+            if tree != brute {
+                result.push_str(&format!(
+                    ", crosshair ray hits bound at {tree:?} in tree but {brute:?} by brute force"
+                ));
+            }
+        }
+        Progress::Done(match self.0 {
+            Some(validate) => format!(
+                "octree validate: {}, {result}",
+                if validate { "on" } else { "off" }
+            ),
+            None => format!("octree validate: {result}"),
+        })
+    }
+}
+
+///Shows octree counters, switches their log every second, or resets them.
+enum OctreeCounterCommand {
+    Show,
+    Log(bool),
+    Reset,
+}
+
+impl ResumableCommand for OctreeCounterCommand {
+    fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+        if let Self::Log(log) = self {
+            world.resource_mut::<OctreeCounterLog>().0 = *log;
+        }
+        let mut octree = match world.query::<&mut Octree>().iter_mut(world).next() {
+            Some(octree) => octree,
+            None => return Progress::Done("octree counters: no octree".to_owned()),
+        };
+        if let Self::Reset = self {
+            octree.reset_counters();
+        }
+        let counters = format!("{}, nodes {}", octree.counters(), octree.node_count());
+        Progress::Done(match self {
+            Self::Log(log) => format!(
+                "octree counters: log {}, {counters}",
+                if *log { "on" } else { "off" }
+            ),
+            _ => format!("octree counters: {counters}"),
+        })
+    }
+}
+
+///Builds demo preset through world mutator, chunked across frames.
+struct GenerateDemo {
+    preset: DemoPreset,
+    ///None until first resume, since bound comes from octree.
+    transforms: Option<Vec<Transform>>,
+    next: usize,
+    placed: usize,
+    start: Instant,
+}
+
+impl GenerateDemo {
+    fn new(preset: DemoPreset) -> Self {
+        Self {
+            preset,
+            transforms: None,
+            next: 0,
+            placed: 0,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl ResumableCommand for GenerateDemo {
+    fn resume(&mut self, world: &mut World, budget: &TimeBudget) -> Progress {
+        let bound = match world.query::<&Octree>().iter(world).next() {
+            Some(octree) => *octree.base_aabb(),
+            None => return Progress::Done("generate demo: no world to build in".to_owned()),
+        };
+        if self.transforms.is_none() {
+            if let Some(mut seeds) = world.get_resource_mut::<RngSeeds>() {
+                self.preset = self.preset.seeded(&mut seeds);
+            }
+        }
+        let mut system_state =
+            SystemState::<(WorldMutator, Res<Meshes>, Res<StandardMaterials>)>::new(world);
+        let (mut mutator, meshes, standard_materials) = system_state.get_mut(world);
+        let transforms = self.transforms.get_or_insert_with(|| {
+            let transforms = self.preset.transforms(&bound);
+            mutator.reserve(transforms.len());
+            transforms
+        });
+        while self.next < transforms.len() && budget.start_unit() {
+            let desc = piece_desc(
+                self.preset.piece(),
+                transforms[self.next],
+                &meshes,
+                &standard_materials,
+            );
+            self.next += 1;
+            if mutator.spawn_structure(desc).is_some() {
+                self.placed += 1;
+            }
+        }
+        let finished = self.next >= transforms.len();
+        system_state.apply(world);
+        if finished {
+            let mut message = format!(
+                "generate demo: placed {} of {} in {:.1}ms",
+                self.placed,
+                self.next,
+                self.start.elapsed().as_secs_f64() * 1000.
+            );
+            //So scatter could be reproduced with explicit seed.
+            if let DemoPreset::Random {
+                seed: Some(seed), ..
+            } = self.preset
+            {
+                message.push_str(&format!(", seed {seed}"));
+            }
+            Progress::Done(message)
+        } else {
+            Progress::Continue
+        }
+    }
+}
+
+///Places first model of MagicaVoxel file as cubes, centered on floor of world.
+struct ImportVox {
+    path: String,
+    hollow: bool,
+    ///Offset and material. None until first resume.
+    placements: Option<Vec<(Vec3, Handle<StandardMaterial>)>>,
+    next: usize,
+    placed: usize,
+    ///What is told along result, such as dropped voxels.
+    notes: Vec<String>,
+}
+
+impl ImportVox {
+    fn new(path: &str, hollow: bool) -> Self {
+        Self {
+            path: path.to_owned(),
+            hollow,
+            placements: None,
+            next: 0,
+            placed: 0,
+            notes: Vec::new(),
+        }
+    }
+}
+
+impl ResumableCommand for ImportVox {
+    fn resume(&mut self, world: &mut World, budget: &TimeBudget) -> Progress {
+        let bound = match world.query::<&Octree>().iter(world).next() {
+            Some(octree) => *octree.base_aabb(),
+            None => return Progress::Done("import vox: no world to build in".to_owned()),
+        };
+        let mut system_state =
+            SystemState::<(WorldMutator, Res<Meshes>, Res<StandardMaterials>)>::new(world);
+        let (mut mutator, meshes, standard_materials) = system_state.get_mut(world);
+        if self.placements.is_none() {
+            let mut model = match VoxModel::read(self.path.as_ref()) {
+                Ok(model) => model,
+                Err(e) => return Progress::Done(format!("import vox: {e}")),
+            };
+            if self.hollow {
+                let dropped = model.hollow();
+                self.notes.push(format!("hollowed {dropped}"));
+            }
+            let truncated = model.truncate(VoxModel::MAX_VOXELS);
+            if truncated > 0 {
+                warn!("import vox: {} voxels over cap are dropped", truncated);
+                self.notes.push(format!("truncated {truncated}"));
+            }
+            let floor = Vec3::new(bound.center().x, bound.min().y + 1., bound.center().z).round();
+            let placements: Vec<_> = model
+                .placements()
+                .into_iter()
+                .map(|(offset, material)| {
+                    (
+                        floor + offset,
+                        standard_materials[S_MAT_PALETTE][material].clone(),
+                    )
+                })
+                .collect();
+            mutator.reserve(placements.len());
+            self.placements = Some(placements);
+        }
+        let placements = self.placements.as_ref().expect("Placements are read above");
+        while self.next < placements.len() && budget.start_unit() {
+            let (translation, material) = &placements[self.next];
+            self.next += 1;
+            let desc = StructureDesc {
+                transform: Transform::from_translation(*translation),
+                collider: Collider::from_shape(Shape::Cuboid {
+                    half_extents: Vec3::splat(0.5),
+                }),
+                meshes: vec![meshes[MESH_BUILT_IN][CUBE].clone()],
+                material: material.clone(),
+                tags: vec![TagId::DECORATIVE],
+            };
+            if mutator.spawn_structure(desc).is_some() {
+                self.placed += 1;
+            }
+        }
+        let finished = self.next >= placements.len();
+        system_state.apply(world);
+        if finished {
+            let mut message = format!("import vox: placed {} of {}", self.placed, self.next);
+            for note in self.notes.iter() {
+                message.push_str(", ");
+                message.push_str(note);
+            }
+            Progress::Done(message)
+        } else {
+            Progress::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asset::*, structure::tests::mutation_world};
+
+    use std::time::Duration;
+
+    ///World demo could be built in, with handles that pieces take.
+    fn demo_world() -> World {
+        let mut world = mutation_world(EntityOctree::new(
+            64,
+            Vec3::splat(0.9),
+            AABB::from_size(64.),
+        ));
+        let mut meshes = Meshes::default();
+        meshes[MESH_BUILT_IN].insert(CUBE, default());
+        for name in [GUN_TOWER_0_BASE, GUN_TOWER_0_TOWER, GUN_TOWER_0_GUN] {
+            meshes[MESH_WEAPON].insert(name, default());
+        }
+        world.insert_resource(meshes);
+        let mut standard_materials = StandardMaterials::default();
+        standard_materials[S_MAT_BUILT_IN].insert(WHITE, default());
+        world.insert_resource(standard_materials);
+        world
+    }
+
+    ///Placed structures in placement order.
+    fn scene(world: &mut World) -> Vec<(u64, Transform)> {
+        let mut ret = world
+            .query::<(&Structure, &Transform)>()
+            .iter(world)
+            .map(|(structure, &transform)| (structure.placement(), transform))
+            .collect::<Vec<_>>();
+        ret.sort_by_key(|&(placement, _)| placement);
+        ret
+    }
+
+    #[test]
+    fn chunked_demo_builds_same_scene() {
+        for preset in [
+            DemoPreset::Grid { x: 4, y: 3, z: 5 },
+            DemoPreset::Towers { count: 6 },
+            DemoPreset::Random {
+                count: 40,
+                seed: Some(11),
+            },
+            DemoPreset::Pathological,
+        ] {
+            let mut whole = demo_world();
+            let progress =
+                GenerateDemo::new(preset).resume(&mut whole, &TimeBudget::new(Duration::MAX));
+            assert!(matches!(progress, Progress::Done(_)), "{preset:?}");
+
+            //Budget that is always spent still places one piece a frame.
+            let mut chunked = demo_world();
+            let mut command = GenerateDemo::new(preset);
+            let mut frames = 1;
+            while let Progress::Continue =
+                command.resume(&mut chunked, &TimeBudget::new(Duration::ZERO))
+            {
+                frames += 1;
+            }
+            assert_eq!(
+                frames,
+                preset.transforms(&AABB::from_size(64.)).len(),
+                "{preset:?}"
+            );
+            let scene = scene(&mut whole);
+            assert!(!scene.is_empty(), "{preset:?}");
+            assert_eq!(scene, self::scene(&mut chunked), "{preset:?}");
+        }
+    }
+
+    #[test]
+    fn shift_build_sends_whole_cell_offset() {
+        let mut commands = ConsoleCommands::default();
+        register(&mut commands);
+        let start = |line: &str| {
+            let (command, args) = commands.find(line).unwrap();
+            (command.start)(&args)
+        };
+        let error = start("shift build 1.5 0 0").err().unwrap();
+        assert!(error.contains("whole cells"), "{error}");
+        assert!(start("shift build 1 2").err().unwrap().starts_with("usage"));
+        let mut world = demo_world();
+        world.init_resource::<Events<ShiftBuild>>();
+        for line in ["shift build 2c -1 0u", "shift build center"] {
+            let progress = start(line)
+                .unwrap()
+                .resume(&mut world, &TimeBudget::new(Duration::MAX));
+            assert!(matches!(progress, Progress::Done(_)), "{line}");
+        }
+        let mut reader = world.resource::<Events<ShiftBuild>>().get_reader();
+        let sent: Vec<_> = reader
+            .iter(world.resource::<Events<ShiftBuild>>())
+            .map(|shift| shift.0)
+            .collect();
+        assert_eq!(sent, [Some(Vec3::new(2., -1., 0.)), None]);
+    }
+
+    #[cfg(feature = "inspect")]
+    #[test]
+    fn inspect_reports_populated_tree_and_pauses_game() {
+        use crate::{inspect::InspectFreeze, states::*};
+
+        #[derive(Resource, Default)]
+        struct Ran {
+            gameplay: u32,
+            paused: u32,
+        }
+
+        let mut app = App::new();
+        app.add_plugin(StatesPlugin)
+            .init_resource::<BoundsDebug>()
+            .init_resource::<InspectFreeze>()
+            .init_resource::<Ran>()
+            .add_system_set_to_stage(
+                CoreStage::Update,
+                SystemSet::on_update(UpdateStageState::InGame)
+                    .with_system(|mut ran: ResMut<Ran>| ran.gameplay += 1),
+            )
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_pause(PreUpdateStageState::InGame)
+                    .with_system(|mut ran: ResMut<Ran>| ran.paused += 1),
+            );
+        let mut octree = EntityOctree::new(64, Vec3::splat(0.9), AABB::from_size(64.));
+        let collider = Collider::from_shape(Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        });
+        for i in 0..10 {
+            let transform = Transform::from_xyz(i as f32 * 3., 0., 0.);
+            let entity = app.world.spawn_empty().id();
+            octree.insert_result(OctreeEntity::new(entity, i, &collider, &transform));
+        }
+        app.world.spawn(octree);
+        let inspect =
+            |world: &mut World| match Inspect.resume(world, &TimeBudget::new(Duration::MAX)) {
+                Progress::Done(message) => message,
+                Progress::Continue => panic!("Inspect should finish at once"),
+            };
+        app.update();
+        assert_eq!(inspect(&mut app.world), "inspect: only in game");
+        app.world
+            .resource_mut::<GlobalState>()
+            .replace(AppState::InGame);
+        app.update();
+        assert_eq!(app.world.resource::<Ran>().gameplay, 1);
+
+        let report = inspect(&mut app.world);
+        assert!(
+            report.starts_with("inspect: frozen, entities 10 "),
+            "{report}"
+        );
+        assert!(
+            report.contains("occupancy ") && report.contains("memory"),
+            "{report}"
+        );
+        assert!(app.world.resource::<BoundsDebug>().0);
+        for _ in 0..3 {
+            app.update();
+        }
+        let ran = app.world.resource::<Ran>();
+        assert_eq!((ran.gameplay, ran.paused), (1, 1));
+
+        assert_eq!(inspect(&mut app.world), "inspect: resumed");
+        assert!(!app.world.resource::<BoundsDebug>().0);
+        app.update();
+        assert_eq!(app.world.resource::<Ran>().gameplay, 2);
+    }
+}
@@ -0,0 +1,511 @@
+pub mod commands;
+pub mod param;
+
+use crate::{
+    asset::{Fonts, FONT_SCHLUBER},
+    func::TimeBudget,
+};
+
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::{input::InputSystem, prelude::*, utils::HashMap};
+
+const SCROLLBACK_LEN: usize = 16;
+///Time that heavy command could spend per frame.
+const FRAME_BUDGET: Duration = Duration::from_millis(4);
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+///Batch setup for developer console.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let mut commands = ConsoleCommands::default();
+        commands::register(&mut commands);
+        app.init_resource::<Console>()
+            .insert_resource(commands)
+            .add_startup_system_to_stage(StartupStage::PostStartup, setup_console)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                console_input.label(ConsoleInput).after(InputSystem),
+            )
+            .add_system_to_stage(CoreStage::Update, run_console)
+            .add_system_to_stage(CoreStage::PostUpdate, update_console_text);
+    }
+}
+
+///Console reading keys, which resets them while it is open.
+///Systems reading keys in PreUpdate should run after it, so typing doesn't reach them.
+#[derive(SystemLabel, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ConsoleInput;
+
+///Whether command finishes in one go or could span frames.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum CommandCost {
+    ///Runs right away with other instant commands.
+    Instant,
+    ///Only one heavy command runs per frame.
+    Heavy,
+}
+
+///What command did in a step.
+pub enum Progress {
+    ///Yielded for next frame.
+    Continue,
+    ///Finished with message for scrollback.
+    Done(String),
+}
+
+///Command that could yield mid-way and resume next frame.
+pub trait ResumableCommand: Send + Sync + 'static {
+    ///Does work until done or budget is exceeded.
+    fn resume(&mut self, world: &mut World, budget: &TimeBudget) -> Progress;
+
+    ///Called instead of further resume when cancelled. World should be left consistent.
+    fn cancel(&mut self, _world: &mut World) {}
+}
+
+///Command with notes, such as clamped args, that lead its message.
+pub struct WithNotes<C> {
+    pub command: C,
+    pub notes: Vec<String>,
+}
+
+impl<C: ResumableCommand> ResumableCommand for WithNotes<C> {
+    fn resume(&mut self, world: &mut World, budget: &TimeBudget) -> Progress {
+        match self.command.resume(world, budget) {
+            Progress::Done(message) if !self.notes.is_empty() => {
+                Progress::Done(format!("{}; {message}", self.notes.join("; ")))
+            }
+            progress => progress,
+        }
+    }
+
+    fn cancel(&mut self, world: &mut World) {
+        self.command.cancel(world);
+    }
+}
+
+///Parses args and creates command.
+pub type CommandStarter = fn(&[&str]) -> Result<Box<dyn ResumableCommand>, String>;
+
+struct RegisteredCommand {
+    cost: CommandCost,
+    start: CommandStarter,
+}
+
+///Every command console knows. Name could contain spaces, such as "octree rebuild".
+#[derive(Resource, Default)]
+pub struct ConsoleCommands(HashMap<&'static str, RegisteredCommand>);
+
+impl ConsoleCommands {
+    pub fn register(&mut self, name: &'static str, cost: CommandCost, start: CommandStarter) {
+        self.0.insert(name, RegisteredCommand { cost, start });
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.0.keys().copied()
+    }
+
+    ///Longest registered name that line starts with, and rest words as args.
+    fn find<'a>(&self, line: &'a str) -> Option<(&RegisteredCommand, Vec<&'a str>)> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        (1..=words.len()).rev().find_map(|n| {
+            self.0
+                .get(words[..n].join(" ").as_str())
+                .map(|command| (command, words[n..].to_vec()))
+        })
+    }
+}
+
+///Heavy command that spans frames.
+struct RunningCommand {
+    line: String,
+    command: Box<dyn ResumableCommand>,
+    frames: usize,
+}
+
+///State of developer console.
+#[derive(Resource, Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    scrollback: VecDeque<String>,
+    pending: VecDeque<String>,
+    running: Option<RunningCommand>,
+    cancel_requested: bool,
+}
+
+impl Console {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    ///Queues line. Identical consecutive commands are coalesced into one.
+    pub fn submit(&mut self, line: impl Into<String>) {
+        let line = line.into().trim().to_owned();
+        if line.is_empty() {
+            return;
+        }
+        let last = match self.pending.back() {
+            Some(last) => Some(last),
+            None => self.running.as_ref().map(|running| &running.line),
+        };
+        if last == Some(&line) {
+            self.print(format!("> {line} (coalesced)"));
+        } else {
+            self.print(format!("> {line}"));
+            self.pending.push_back(line);
+        }
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.scrollback.push_back(line.into());
+        while self.scrollback.len() > SCROLLBACK_LEN {
+            self.scrollback.pop_front();
+        }
+    }
+}
+
+#[derive(Component)]
+struct ConsoleText;
+
+fn setup_console(mut commands: Commands, fonts: Res<Fonts>) {
+    let style = TextStyle {
+        font: fonts[FONT_SCHLUBER].clone(),
+        font_size: 16.,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(40.)),
+                position_type: PositionType::Absolute,
+                position: UiRect::new(Val::Px(0.), Val::Undefined, Val::Px(0.), Val::Undefined),
+                padding: UiRect::all(Val::Px(4.)),
+                ..default()
+            },
+            background_color: BackgroundColor(Color::rgba(0., 0., 0., 0.7)),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(Name::new("Console"))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_sections([
+                    TextSection::from_style(style.clone()),
+                    TextSection::from_style(style),
+                ]),
+                ConsoleText,
+            ));
+        });
+}
+
+///Toggles console with grave key and types into it. Keyboard is swallowed while open.
+fn console_input(
+    mut console: ResMut<Console>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut chars: EventReader<ReceivedCharacter>,
+) {
+    if keys.just_pressed(KeyCode::Grave) {
+        console.open = !console.open;
+        keys.reset_all();
+        chars.clear();
+        return;
+    }
+    if !console.open {
+        chars.clear();
+        return;
+    }
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if ctrl && keys.just_pressed(KeyCode::C) {
+        console.cancel_requested = true;
+    } else if keys.just_pressed(KeyCode::Escape) {
+        console.open = false;
+    } else if keys.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+        console.submit(line);
+    } else if keys.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+    if !ctrl {
+        for c in chars.iter() {
+            if !c.char.is_control() && c.char != '`' {
+                console.input.push(c.char);
+            }
+        }
+    }
+    chars.clear();
+    keys.reset_all();
+}
+
+///Runs queued commands. Instant ones run together, but at most one heavy command runs per frame.
+fn run_console(world: &mut World) {
+    world.resource_scope(|world, mut console: Mut<Console>| {
+        //Avoid marking console changed when there is nothing to do.
+        if !console.cancel_requested && console.running.is_none() && console.pending.is_empty() {
+            return;
+        }
+        if console.cancel_requested {
+            console.cancel_requested = false;
+            if let Some(mut running) = console.running.take() {
+                running.command.cancel(world);
+                console.print(format!("^C {} cancelled", running.line));
+            }
+        }
+        let mut heavy_ran = false;
+        loop {
+            if let Some(mut running) = console.running.take() {
+                //Commands after heavy one wait for it, so order of script is kept.
+                if heavy_ran {
+                    console.running = Some(running);
+                    break;
+                }
+                heavy_ran = true;
+                match running
+                    .command
+                    .resume(world, &TimeBudget::new(FRAME_BUDGET))
+                {
+                    Progress::Continue => {
+                        running.frames += 1;
+                        console.running = Some(running);
+                        break;
+                    }
+                    Progress::Done(message) => console.print(message),
+                }
+            }
+            let line = match console.pending.pop_front() {
+                Some(line) => line,
+                None => break,
+            };
+            let commands = world.resource::<ConsoleCommands>();
+            let (cost, started) = match commands.find(&line) {
+                Some((command, args)) => (command.cost, (command.start)(&args)),
+                None => {
+                    console.print(format!("unknown command: {line}"));
+                    continue;
+                }
+            };
+            let mut command = match started {
+                Ok(command) => command,
+                Err(e) => {
+                    console.print(e);
+                    continue;
+                }
+            };
+            match cost {
+                CommandCost::Instant => loop {
+                    if let Progress::Done(message) =
+                        command.resume(world, &TimeBudget::new(Duration::MAX))
+                    {
+                        console.print(message);
+                        break;
+                    }
+                },
+                CommandCost::Heavy => {
+                    console.running = Some(RunningCommand {
+                        line,
+                        command,
+                        frames: 0,
+                    })
+                }
+            }
+        }
+    });
+}
+
+///Shows scrollback, spinner of running command and input line.
+fn update_console_text(
+    console: Res<Console>,
+    mut text: Query<(&mut Text, &Parent), With<ConsoleText>>,
+    mut visibility: Query<&mut Visibility>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    for (mut text, parent) in text.iter_mut() {
+        if let Ok(mut visibility) = visibility.get_mut(parent.get()) {
+            if visibility.is_visible != console.open {
+                visibility.is_visible = console.open;
+            }
+        }
+        let mut scrollback = console
+            .scrollback
+            .iter()
+            .fold(String::new(), |acc, line| acc + line + "\n");
+        if let Some(running) = &console.running {
+            scrollback.push(SPINNER[running.frames % SPINNER.len()]);
+            scrollback.push(' ');
+            scrollback.push_str(&running.line);
+            scrollback.push('\n');
+        }
+        text.sections[0].value = scrollback;
+        text.sections[1].value = format!("> {}_", console.input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///What test commands did.
+    #[derive(Resource, Default)]
+    struct Runs {
+        instant: Vec<&'static str>,
+        heavy_steps: usize,
+        cancelled: bool,
+    }
+
+    ///Instant command that records its name.
+    struct Record(&'static str);
+
+    impl ResumableCommand for Record {
+        fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+            world.resource_mut::<Runs>().instant.push(self.0);
+            Progress::Done(self.0.to_owned())
+        }
+    }
+
+    ///Heavy command that takes three frames. Cancel takes back its steps.
+    #[derive(Default)]
+    struct Slow(usize);
+
+    impl Slow {
+        const STEPS: usize = 3;
+    }
+
+    impl ResumableCommand for Slow {
+        fn resume(&mut self, world: &mut World, _budget: &TimeBudget) -> Progress {
+            self.0 += 1;
+            world.resource_mut::<Runs>().heavy_steps += 1;
+            if self.0 < Self::STEPS {
+                Progress::Continue
+            } else {
+                Progress::Done("slow done".to_owned())
+            }
+        }
+
+        fn cancel(&mut self, world: &mut World) {
+            let mut runs = world.resource_mut::<Runs>();
+            runs.heavy_steps -= self.0;
+            runs.cancelled = true;
+        }
+    }
+
+    fn test_commands() -> ConsoleCommands {
+        let mut commands = ConsoleCommands::default();
+        commands.register("a", CommandCost::Instant, |_| Ok(Box::new(Record("a"))));
+        commands.register("b", CommandCost::Instant, |_| Ok(Box::new(Record("b"))));
+        commands.register("slow", CommandCost::Heavy, |_| Ok(Box::<Slow>::default()));
+        commands.register("slow b", CommandCost::Instant, |_| {
+            Ok(Box::new(Record("slow b")))
+        });
+        commands
+    }
+
+    fn test_world(script: &[&str]) -> World {
+        let mut world = World::new();
+        world.insert_resource(test_commands());
+        world.init_resource::<Runs>();
+        let mut console = Console::default();
+        for line in script {
+            console.submit(*line);
+        }
+        world.insert_resource(console);
+        world
+    }
+
+    fn runs(world: &World) -> (&[&'static str], usize) {
+        let runs = world.resource::<Runs>();
+        (&runs.instant, runs.heavy_steps)
+    }
+
+    #[test]
+    fn find_takes_longest_name() {
+        let commands = test_commands();
+        let (command, args) = commands.find("slow b  1 2").unwrap();
+        assert_eq!(command.cost, CommandCost::Instant);
+        assert_eq!(args, ["1", "2"]);
+        let (command, args) = commands.find(" slow x").unwrap();
+        assert_eq!(command.cost, CommandCost::Heavy);
+        assert_eq!(args, ["x"]);
+        assert!(commands.find("c slow").is_none());
+        assert!(commands.find("").is_none());
+    }
+
+    #[test]
+    fn heavy_runs_once_per_frame_in_order() {
+        let mut world = test_world(&["a", "slow", "b", "slow", "a"]);
+        run_console(&mut world);
+        assert_eq!(runs(&world), (&["a"][..], 1));
+        run_console(&mut world);
+        assert_eq!(runs(&world), (&["a"][..], 2));
+        //Finished heavy one lets instant ones after it run, but next heavy waits for next frame.
+        run_console(&mut world);
+        assert_eq!(runs(&world), (&["a", "b"][..], 3));
+        for steps in 4..=6 {
+            run_console(&mut world);
+            assert_eq!(runs(&world).1, steps);
+        }
+        assert_eq!(runs(&world).0, ["a", "b", "a"]);
+        let console = world.resource::<Console>();
+        assert!(console.running.is_none() && console.pending.is_empty());
+    }
+
+    #[test]
+    fn consecutive_commands_coalesce() {
+        let mut world = test_world(&["a", "a", "slow", "slow", "a", "  a "]);
+        assert_eq!(world.resource::<Console>().pending, ["a", "slow", "a"]);
+        for _ in 0..Slow::STEPS {
+            run_console(&mut world);
+        }
+        assert_eq!(runs(&world), (&["a", "a"][..], Slow::STEPS));
+        let coalesced = |world: &World| {
+            world
+                .resource::<Console>()
+                .scrollback
+                .iter()
+                .filter(|line| line.ends_with("(coalesced)"))
+                .count()
+        };
+        assert_eq!(coalesced(&world), 3);
+
+        //Same as running one is coalesced too, when nothing waits after it.
+        let mut world = test_world(&["slow"]);
+        run_console(&mut world);
+        world.resource_mut::<Console>().submit("slow");
+        assert!(world.resource::<Console>().pending.is_empty());
+        assert_eq!(coalesced(&world), 1);
+    }
+
+    #[test]
+    fn cancel_stops_running_command() {
+        let mut world = test_world(&["slow", "b"]);
+        run_console(&mut world);
+        world.resource_mut::<Console>().cancel_requested = true;
+        run_console(&mut world);
+        let runs = world.resource::<Runs>();
+        assert!(runs.cancelled);
+        assert_eq!(runs.heavy_steps, 0);
+        //Queue goes on after cancelled one.
+        assert_eq!(runs.instant, ["b"]);
+        let console = world.resource::<Console>();
+        assert!(console.running.is_none());
+        assert!(console.scrollback.contains(&"^C slow cancelled".to_owned()));
+        //Nothing to cancel is fine.
+        world.resource_mut::<Console>().cancel_requested = true;
+        run_console(&mut world);
+        assert!(!world.resource::<Console>().cancel_requested);
+    }
+
+    #[test]
+    fn unknown_and_refused_commands_are_reported() {
+        let mut world = test_world(&["nope", "a"]);
+        run_console(&mut world);
+        assert_eq!(runs(&world).0, ["a"]);
+        assert!(world
+            .resource::<Console>()
+            .scrollback
+            .contains(&"unknown command: nope".to_owned()));
+    }
+}
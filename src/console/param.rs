@@ -0,0 +1,279 @@
+use std::fmt::{self, Display};
+
+///What a number measures. Each kind has its own units.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ParamKind {
+    Length,
+    Angle,
+    ///Whole number of things.
+    Count,
+    ///Number without unit, such as ratio or lux.
+    Scalar,
+}
+
+///Unit of number, written as suffix right after it.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Unit {
+    Cells,
+    Units,
+    Degrees,
+    Radians,
+    ///Unit of count and scalar, which take no suffix.
+    None,
+}
+
+impl Unit {
+    ///Every unit with its suffixes, first one is shown. Suffix is case insensitive.
+    const SUFFIXES: [(Unit, &'static [&'static str]); 4] = [
+        (Unit::Cells, &["c", "cell", "cells"]),
+        (Unit::Units, &["u", "unit", "units"]),
+        (Unit::Degrees, &["deg"]),
+        (Unit::Radians, &["rad"]),
+    ];
+
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        let suffix = suffix.to_ascii_lowercase();
+        Self::SUFFIXES
+            .iter()
+            .find(|(_, suffixes)| suffixes.contains(&suffix.as_str()))
+            .map(|(unit, _)| *unit)
+    }
+
+    fn kind(self) -> Option<ParamKind> {
+        match self {
+            Self::Cells | Self::Units => Some(ParamKind::Length),
+            Self::Degrees | Self::Radians => Some(ParamKind::Angle),
+            Self::None => None,
+        }
+    }
+
+    ///How many of base unit of its kind one of this is.
+    ///Base units are cells and radians. A cell is a world unit wide.
+    fn scale(self) -> f64 {
+        match self {
+            Self::Cells | Self::Units | Self::Radians => 1.,
+            Self::Degrees => std::f64::consts::PI / 180.,
+            Self::None => 1.,
+        }
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match Self::SUFFIXES.iter().find(|(unit, _)| unit == self) {
+            Some((_, suffixes)) => write!(f, "{}", suffixes[0]),
+            None => Ok(()),
+        }
+    }
+}
+
+///Declared numeric argument of command. Value is in its default unit, whatever unit is written.
+#[derive(Clone, Copy, Debug)]
+pub struct Param {
+    name: &'static str,
+    kind: ParamKind,
+    unit: Unit,
+    min: f64,
+    max: f64,
+    ///Out of range value is clamped with note, instead of refused.
+    soft: bool,
+}
+
+///Parsed value and note when it had to be clamped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parsed {
+    pub value: f64,
+    pub note: Option<String>,
+}
+
+impl Param {
+    const fn new(name: &'static str, kind: ParamKind, unit: Unit) -> Self {
+        Self {
+            name,
+            kind,
+            unit,
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY,
+            soft: false,
+        }
+    }
+
+    ///In cells by default.
+    pub const fn length(name: &'static str) -> Self {
+        Self::new(name, ParamKind::Length, Unit::Cells)
+    }
+
+    ///In degrees by default.
+    pub const fn angle(name: &'static str) -> Self {
+        Self::new(name, ParamKind::Angle, Unit::Degrees)
+    }
+
+    ///Whole number, zero or more.
+    pub const fn count(name: &'static str) -> Self {
+        Self::new(name, ParamKind::Count, Unit::None).range(0., u32::MAX as f64)
+    }
+
+    pub const fn scalar(name: &'static str) -> Self {
+        Self::new(name, ParamKind::Scalar, Unit::None)
+    }
+
+    ///Value out of range is refused. Bounds are inclusive and in default unit.
+    pub const fn range(mut self, min: f64, max: f64) -> Self {
+        self.min = min;
+        self.max = max;
+        self.soft = false;
+        self
+    }
+
+    ///Value out of range is clamped into it with note.
+    pub const fn soft_range(mut self, min: f64, max: f64) -> Self {
+        self.min = min;
+        self.max = max;
+        self.soft = true;
+        self
+    }
+
+    fn range_text(&self) -> String {
+        format!("{}..{}{}", self.min, self.max, self.unit)
+    }
+
+    pub fn parse(&self, text: &str) -> Result<Parsed, String> {
+        //Suffix is trailing letters. Exponent is followed by digit, so it stays in number.
+        let number_len = text
+            .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+            .len();
+        let (number, suffix) = text.split_at(number_len);
+        let value: f64 = number
+            .parse()
+            .map_err(|_| format!("{}: not a number: {text}", self.name))?;
+        if !value.is_finite() {
+            return Err(format!("{}: not a finite number: {text}", self.name));
+        }
+        let value = if suffix.is_empty() {
+            value
+        } else {
+            let unit = Unit::from_suffix(suffix)
+                .filter(|unit| unit.kind() == Some(self.kind))
+                .ok_or_else(|| format!("{}: unknown unit {suffix} in {text}", self.name))?;
+            value * unit.scale() / self.unit.scale()
+        };
+        //Large value could overflow into infinity by unit.
+        if !value.is_finite() {
+            return Err(format!("{}: too large: {text}", self.name));
+        }
+        if self.kind == ParamKind::Count && value.fract() != 0. {
+            return Err(format!("{}: should be whole number: {text}", self.name));
+        }
+        if (self.min..=self.max).contains(&value) {
+            return Ok(Parsed { value, note: None });
+        }
+        if self.soft {
+            let clamped = value.clamp(self.min, self.max);
+            Ok(Parsed {
+                value: clamped,
+                note: Some(format!(
+                    "{} {text} is clamped to {clamped}{} within {}",
+                    self.name,
+                    self.unit,
+                    self.range_text()
+                )),
+            })
+        } else {
+            Err(format!(
+                "{}: {text} is out of {}",
+                self.name,
+                self.range_text()
+            ))
+        }
+    }
+
+    ///Parses value that can't be clamped, such as count of hard range.
+    pub fn parse_value(&self, text: &str) -> Result<f64, String> {
+        self.parse(text).map(|parsed| parsed.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f64::consts::PI;
+
+    fn close(parsed: Result<f64, String>, expected: f64) -> bool {
+        parsed.map_or(false, |value| {
+            (value - expected).abs() <= expected.abs() * 1e-12
+        })
+    }
+
+    #[test]
+    fn every_suffix_converts_to_default_unit() {
+        let length = Param::length("length");
+        for text in [
+            "3c", "3cell", "3cells", "3u", "3unit", "3units", "3C", "3Cells", "3",
+        ] {
+            assert!(close(length.parse_value(text), 3.), "{text}");
+        }
+        let angle = Param::angle("angle");
+        assert!(close(angle.parse_value("90deg"), 90.));
+        assert!(close(angle.parse_value("1.5rad"), 1.5 * 180. / PI));
+        assert!(close(angle.parse_value("90"), 90.));
+        assert!(close(angle.parse_value("2RAD"), 2. * 180. / PI));
+    }
+
+    #[test]
+    fn suffix_of_other_kind_is_refused() {
+        assert!(Param::length("length").parse("3deg").is_err());
+        assert!(Param::angle("angle").parse("3c").is_err());
+        assert!(Param::count("count").parse("3c").is_err());
+        assert!(Param::scalar("scalar").parse("3x").is_err());
+    }
+
+    #[test]
+    fn negatives_follow_range() {
+        let angle = Param::angle("angle");
+        assert!(close(angle.parse_value("-45deg"), -45.));
+        assert!(close(angle.parse_value("-0.5rad"), -0.5 * 180. / PI));
+        assert!(Param::count("count").parse("-1").is_err());
+        let pitch = Param::angle("pitch").range(-90., 90.);
+        assert!(close(pitch.parse_value("-90"), -90.));
+        let error = pitch.parse("-91").unwrap_err();
+        assert!(error.contains("-90..90deg"), "{error}");
+    }
+
+    #[test]
+    fn soft_range_clamps_with_note() {
+        let channel = Param::scalar("color").soft_range(0., 1.);
+        assert_eq!(
+            channel.parse("0.5"),
+            Ok(Parsed {
+                value: 0.5,
+                note: None
+            })
+        );
+        let parsed = channel.parse("-2").unwrap();
+        assert_eq!(parsed.value, 0.);
+        assert!(parsed.note.unwrap().contains("0..1"));
+        assert_eq!(channel.parse("7").unwrap().value, 1.);
+    }
+
+    #[test]
+    fn not_numbers_are_refused() {
+        let scalar = Param::scalar("scalar");
+        for text in [
+            "", "NaN", "nan", "inf", "-inf", "infinity", "abc", "1.2.3", "--1", "1e400",
+        ] {
+            assert!(scalar.parse(text).is_err(), "{text}");
+        }
+        assert!(Param::count("count").parse("1.5").is_err());
+        assert!(close(Param::count("count").parse_value("1e3"), 1000.));
+    }
+
+    #[test]
+    fn overflow_by_unit_is_refused() {
+        let angle = Param::angle("angle");
+        assert!(angle.parse("1e308").is_ok());
+        assert!(angle.parse("1e308deg").is_ok());
+        let error = angle.parse("1e308rad").unwrap_err();
+        assert!(error.contains("too large"), "{error}");
+    }
+}
@@ -3,3 +3,18 @@ pub use std::f32::consts::*;
 //math
 pub const RADIANS: f32 = PI / 180.;
 pub const GIMBAL_LOCK: f32 = 89.0 * RADIANS;
+
+//physics
+///Points closer than this are treated as same, such as snapping and nudging past a surface.
+pub const POINT_EPS: f32 = 1e-3;
+///Ray direction component smaller than this is treated as parallel to that axis.
+pub const RAY_EPS: f32 = 1e-6;
+///Point closer than this to split plane of bound is on the plane, not in an octant.
+pub const OCTANT_EPS: f32 = f32::EPSILON;
+///Shapes going into each other less than this are touching, not overlapping.
+///So structures exactly adjacent on grid don't block each other.
+pub const OVERLAP_EPS: f32 = 1e-3;
+
+//render
+///Transform or color components closer than this look same, so new value isn't written.
+pub const VISUAL_EPS: f32 = 1e-5;
@@ -0,0 +1,261 @@
+use crate::{
+    asset::*,
+    console::param::Param,
+    consts::*,
+    physics::{
+        aabb::AABB,
+        collider::{Collider, Shape},
+    },
+    rng::{RngSeeds, SplitMix64},
+    structure::StructureDesc,
+    tag::TagId,
+};
+
+use bevy::prelude::*;
+
+const GRID_SIZE: Param = Param::count("grid size").range(1., 10_000.);
+const PIECE_COUNT: Param = Param::count("count");
+
+///Procedural scene for reproducible performance work and bug reports.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum DemoPreset {
+    ///Dense cube lattice.
+    Grid { x: u32, y: u32, z: u32 },
+    ///Gun towers in a spiral.
+    Towers { count: u32 },
+    ///Seeded cube scatter. Seed is drawn from session when not given.
+    Random { count: u32, seed: Option<u64> },
+    ///Cubes straddling octant boundaries at several depths. Worst case of octree.
+    Pathological,
+}
+
+///What preset is built with.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum DemoPiece {
+    Cube,
+    Tower,
+}
+
+impl DemoPreset {
+    ///How deep boundaries pathological preset straddles.
+    const PATHOLOGICAL_DEPTH: u32 = 4;
+    ///Guards against typo allocating whole memory.
+    pub const MAX_PIECES: u64 = 1_000_000;
+
+    ///Parses `grid NxNxN`, `grid N`, `towers N`, `random N [seed S]` or `pathological`.
+    pub fn parse(args: &[&str]) -> Result<Self, String> {
+        let preset = Self::parse_unchecked(args)?;
+        let pieces = match preset {
+            Self::Grid { x, y, z } => x as u64 * y as u64 * z as u64,
+            Self::Towers { count } | Self::Random { count, .. } => count as u64,
+            Self::Pathological => 0,
+        };
+        if pieces > Self::MAX_PIECES {
+            return Err(format!("too many pieces: {pieces}"));
+        }
+        Ok(preset)
+    }
+
+    fn parse_unchecked(args: &[&str]) -> Result<Self, String> {
+        let number = |param: Param, arg: &str| param.parse_value(arg).map(|n| n as u32);
+        match args {
+            ["grid", dims] => {
+                let dims = dims
+                    .split('x')
+                    .map(|dim| number(GRID_SIZE, dim))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match dims[..] {
+                    [n] => Ok(Self::Grid { x: n, y: n, z: n }),
+                    [x, y, z] => Ok(Self::Grid { x, y, z }),
+                    _ => Err("grid expects N or NxNxN".to_owned()),
+                }
+            }
+            ["towers", count] => Ok(Self::Towers {
+                count: number(PIECE_COUNT, count)?,
+            }),
+            ["random", count] => Ok(Self::Random {
+                count: number(PIECE_COUNT, count)?,
+                seed: None,
+            }),
+            ["random", count, "seed", seed] => Ok(Self::Random {
+                count: number(PIECE_COUNT, count)?,
+                seed: Some(seed.parse().map_err(|_| format!("not a seed: {seed}"))?),
+            }),
+            ["pathological"] => Ok(Self::Pathological),
+            _ => Err("presets: grid NxNxN, towers N, random N [seed S], pathological".to_owned()),
+        }
+    }
+
+    ///Draws missing seed from demo stream of session.
+    pub fn seeded(self, seeds: &mut RngSeeds) -> Self {
+        match self {
+            Self::Random { count, seed: None } => Self::Random {
+                count,
+                seed: Some(seeds.stream("demo").next_u64()),
+            },
+            _ => self,
+        }
+    }
+
+    pub fn piece(&self) -> DemoPiece {
+        match self {
+            Self::Towers { .. } => DemoPiece::Tower,
+            _ => DemoPiece::Cube,
+        }
+    }
+
+    ///Transforms of every piece. Same preset and bound always give same result.
+    ///Unseeded scatter uses zero, so it should be `seeded` first.
+    ///Bound is where scene is built, such as blueprint bound.
+    pub fn transforms(&self, bound: &AABB) -> Vec<Transform> {
+        //Cubes are unit sized and snapped to integer like placement does.
+        let floor = Vec3::new(bound.center().x, bound.min().y + 1., bound.center().z).round();
+        match *self {
+            Self::Grid { x, y, z } => {
+                let start = floor - Vec3::new((x / 2) as f32, 0., (z / 2) as f32);
+                let mut ret = Vec::with_capacity((x * y * z) as usize);
+                for i in 0..x {
+                    for j in 0..y {
+                        for k in 0..z {
+                            ret.push(Transform::from_translation(
+                                start + Vec3::new(i as f32, j as f32, k as f32),
+                            ));
+                        }
+                    }
+                }
+                ret
+            }
+            Self::Towers { count } => {
+                //Golden angle spreads towers evenly.
+                let golden_angle = PI * (3. - 5f32.sqrt());
+                (0..count)
+                    .map(|i| {
+                        let radius = 6. * (i as f32).sqrt();
+                        let angle = i as f32 * golden_angle;
+                        Transform::from_translation(
+                            (floor + Vec3::new(angle.cos(), 0., angle.sin()) * radius).round(),
+                        )
+                    })
+                    .collect()
+            }
+            Self::Random { count, seed } => {
+                let mut rng = SplitMix64::new(seed.unwrap_or_default());
+                let (min, max) = (bound.min().ceil(), bound.max().floor() - 1.);
+                (0..count)
+                    .map(|_| {
+                        let t = Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32());
+                        Transform::from_translation((min + (max - min) * t).round())
+                    })
+                    .collect()
+            }
+            Self::Pathological => {
+                //Center of every node is on boundary of its octants.
+                let mut ret = Vec::new();
+                let mut nodes = vec![*bound];
+                for _ in 0..Self::PATHOLOGICAL_DEPTH {
+                    ret.extend(
+                        nodes
+                            .iter()
+                            .map(|node| Transform::from_translation(node.center())),
+                    );
+                    nodes = nodes
+                        .iter()
+                        .flat_map(|node| {
+                            (0..8).map(|i| {
+                                node.get_octant(BVec3::new(i & 4 != 0, i & 2 != 0, i & 1 != 0))
+                            })
+                        })
+                        .collect();
+                }
+                ret
+            }
+        }
+    }
+}
+
+///Description of piece at transform.
+pub fn piece_desc(
+    piece: DemoPiece,
+    transform: Transform,
+    meshes: &Meshes,
+    standard_materials: &StandardMaterials,
+) -> StructureDesc {
+    match piece {
+        DemoPiece::Cube => StructureDesc {
+            transform,
+            collider: Collider::from_shape(Shape::Cuboid {
+                half_extents: Vec3::splat(0.5),
+            }),
+            meshes: vec![meshes[MESH_BUILT_IN][CUBE].clone()],
+            material: standard_materials[S_MAT_BUILT_IN][WHITE].clone(),
+            tags: vec![TagId::FOUNDATION],
+        },
+        DemoPiece::Tower => StructureDesc {
+            transform,
+            collider: Collider::from_shape(Shape::CutSphere {
+                radius: 2.5,
+                cut: 0.5,
+            }),
+            meshes: vec![
+                meshes[MESH_WEAPON][GUN_TOWER_0_BASE].clone(),
+                meshes[MESH_WEAPON][GUN_TOWER_0_TOWER].clone(),
+                meshes[MESH_WEAPON][GUN_TOWER_0_GUN].clone(),
+            ],
+            material: standard_materials[S_MAT_BUILT_IN][WHITE].clone(),
+            tags: vec![TagId::DEFENSIVE],
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_build_same_scene_every_time() {
+        let bound = AABB::from_size(64.);
+        let presets = [
+            (DemoPreset::Grid { x: 3, y: 4, z: 5 }, 60),
+            (DemoPreset::Towers { count: 10 }, 10),
+            (
+                DemoPreset::Random {
+                    count: 50,
+                    seed: Some(7),
+                },
+                50,
+            ),
+            //Centers of nodes down to depth 3.
+            (DemoPreset::Pathological, 1 + 8 + 64 + 512),
+        ];
+        for (preset, count) in presets {
+            let transforms = preset.transforms(&bound);
+            assert_eq!(transforms.len(), count, "{preset:?}");
+            assert_eq!(transforms, preset.transforms(&bound), "{preset:?}");
+        }
+        let scatter = |seed| {
+            DemoPreset::Random {
+                count: 50,
+                seed: Some(seed),
+            }
+            .transforms(&bound)
+        };
+        assert_ne!(scatter(7), scatter(8));
+    }
+
+    #[test]
+    fn session_seed_decides_unseeded_scatter() {
+        let unseeded = DemoPreset::Random {
+            count: 20,
+            seed: None,
+        };
+        let seeded = |root| unseeded.seeded(&mut RngSeeds::new(root));
+        assert_eq!(seeded(3), seeded(3));
+        assert_ne!(seeded(3), seeded(4));
+        //Given seed is kept.
+        let given = DemoPreset::Random {
+            count: 20,
+            seed: Some(5),
+        };
+        assert_eq!(given.seeded(&mut RngSeeds::new(3)), given);
+    }
+}
@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+
+///Look of a scene. Templates and saves carry this so that scene keeps its mood.
+#[derive(Resource, Clone, Copy, PartialEq, Debug)]
+pub struct SceneEnvironment {
+    pub clear_color: Color,
+    pub ambient: Color,
+    pub ambient_brightness: f32,
+    ///Whether sun is spawned.
+    pub sun_enabled: bool,
+}
+
+impl Default for SceneEnvironment {
+    fn default() -> Self {
+        let clear_color = ClearColor::default();
+        let ambient = AmbientLight::default();
+        Self {
+            clear_color: clear_color.0,
+            ambient: ambient.color,
+            ambient_brightness: ambient.brightness,
+            sun_enabled: true,
+        }
+    }
+}
+
+impl SceneEnvironment {
+    ///Writes environment to render resources.
+    pub fn apply(&self, clear_color: &mut ClearColor, ambient: &mut AmbientLight) {
+        clear_color.0 = self.clear_color;
+        ambient.color = self.ambient;
+        ambient.brightness = self.ambient_brightness;
+    }
+}
+
+///Applies scene environment when entering scene.
+pub fn apply_scene_environment(
+    environment: Res<SceneEnvironment>,
+    mut clear_color: ResMut<ClearColor>,
+    mut ambient: ResMut<AmbientLight>,
+) {
+    environment.apply(&mut clear_color, &mut ambient);
+}
+
+///Restores menu look when leaving scene.
+pub fn restore_default_environment(
+    mut clear_color: ResMut<ClearColor>,
+    mut ambient: ResMut<AmbientLight>,
+) {
+    SceneEnvironment::default().apply(&mut clear_color, &mut ambient);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene() -> SceneEnvironment {
+        SceneEnvironment {
+            clear_color: Color::rgb(0.5, 0.25, 1.),
+            ambient: Color::ORANGE,
+            ambient_brightness: 0.6,
+            sun_enabled: true,
+        }
+    }
+
+    #[test]
+    fn entering_applies_and_leaving_restores() {
+        let mut world = World::new();
+        world.init_resource::<ClearColor>();
+        world.init_resource::<AmbientLight>();
+        world.insert_resource(scene());
+        SystemStage::single(apply_scene_environment).run(&mut world);
+        assert_eq!(world.resource::<ClearColor>().0, scene().clear_color);
+        let ambient = world.resource::<AmbientLight>();
+        assert_eq!((ambient.color, ambient.brightness), (Color::ORANGE, 0.6));
+
+        SystemStage::single(restore_default_environment).run(&mut world);
+        assert_eq!(world.resource::<ClearColor>().0, ClearColor::default().0);
+        let (ambient, default) = (world.resource::<AmbientLight>(), AmbientLight::default());
+        assert_eq!(ambient.color, default.color);
+        assert_eq!(ambient.brightness, default.brightness);
+    }
+}
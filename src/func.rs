@@ -1,6 +1,13 @@
+use crate::consts::VISUAL_EPS;
+
 use macros::impl_with_tuples;
 
-use bevy::prelude::Component;
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::{Color, Component, Mut, Transform};
 
 ///Container component for function.
 #[derive(Component)]
@@ -26,3 +33,171 @@ macro_rules! impl_action {
 }
 
 impl_with_tuples!(impl_action, 0, 16, P);
+
+///Value that could tell whether it looks same as other.
+pub trait VisuallyEq {
+    fn visually_eq(&self, other: &Self) -> bool;
+}
+
+impl VisuallyEq for Transform {
+    fn visually_eq(&self, other: &Self) -> bool {
+        self.translation.abs_diff_eq(other.translation, VISUAL_EPS)
+            //Negated quaternion is same rotation.
+            && (self.rotation.abs_diff_eq(other.rotation, VISUAL_EPS)
+                || self.rotation.abs_diff_eq(-other.rotation, VISUAL_EPS))
+            && self.scale.abs_diff_eq(other.scale, VISUAL_EPS)
+    }
+}
+
+impl VisuallyEq for Color {
+    fn visually_eq(&self, other: &Self) -> bool {
+        self.as_rgba_f32()
+            .iter()
+            .zip(other.as_rgba_f32())
+            .all(|(a, b)| (a - b).abs() <= VISUAL_EPS)
+    }
+}
+
+///Writes value only when it looks different, so per frame systems don't mark unchanged one as changed.
+///Return is whether it is written.
+pub fn set_if_changed<T: VisuallyEq>(target: &mut Mut<T>, value: T) -> bool {
+    //Reading through Deref doesn't mark change.
+    if target.visually_eq(&value) {
+        return false;
+    }
+    **target = value;
+    true
+}
+
+///Limits how long work could run in a frame.
+pub struct TimeBudget {
+    start: Instant,
+    limit: Duration,
+    ///Units of work started so far.
+    units: Cell<u32>,
+}
+
+impl TimeBudget {
+    pub fn new(limit: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            limit,
+            units: Cell::new(0),
+        }
+    }
+
+    ///Checked before starting a unit of work, and counts it when it fits.
+    ///Unit fits if average of started ones would still end within limit,
+    ///so last unit can't go far past it. First unit always fits, so work always progresses.
+    pub fn start_unit(&self) -> bool {
+        let units = self.units.get();
+        let elapsed = self.start.elapsed();
+        if units > 0 && elapsed + elapsed / units > self.limit {
+            return false;
+        }
+        self.units.set(units + 1);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bevy::prelude::*;
+
+    #[test]
+    fn budget_is_checked_before_unit() {
+        //First unit runs even without any time, and nothing after it.
+        let budget = TimeBudget::new(Duration::ZERO);
+        assert!(budget.start_unit());
+        assert!(!budget.start_unit());
+
+        let budget = TimeBudget::new(Duration::from_secs(3600));
+        assert!((0..100).all(|_| budget.start_unit()));
+
+        //Units take at least 10ms. Third would end past 25ms, though only 20ms passed before it.
+        let budget = TimeBudget::new(Duration::from_millis(25));
+        let mut units = 0;
+        while budget.start_unit() {
+            units += 1;
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!((1..=2).contains(&units), "{units}");
+    }
+
+    #[derive(Resource, Default)]
+    struct ChangedCount(usize);
+
+    #[derive(Resource)]
+    struct Frame(u32);
+
+    fn count_changed(mut count: ResMut<ChangedCount>, changed: Query<(), Changed<Transform>>) {
+        count.0 = changed.iter().count();
+    }
+
+    ///Idle sway too small to see, as animation settles into.
+    fn sway(time: f32) -> Transform {
+        Transform::from_xyz(0., (time * 7.).sin() * VISUAL_EPS * 0.5, 0.)
+            .with_rotation(Quat::from_rotation_y(time.cos() * VISUAL_EPS * 0.1))
+    }
+
+    ///Changed transforms per frame over frames of sway, when written by given way.
+    fn changed_per_frame<Params>(write: impl IntoSystem<(), (), Params>) -> Vec<usize> {
+        let mut world = World::new();
+        world.init_resource::<ChangedCount>();
+        world.insert_resource(Frame(0));
+        for _ in 0..5000 {
+            world.spawn(Transform::default());
+        }
+        let mut stage = SystemStage::single_threaded()
+            .with_system(write.before(count_changed))
+            .with_system(count_changed);
+        (0..10)
+            .map(|_| {
+                stage.run(&mut world);
+                world.resource_mut::<Frame>().0 += 1;
+                world.resource::<ChangedCount>().0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unseen_changes_dont_mark_changed() {
+        let always = changed_per_frame(|frame: Res<Frame>, mut query: Query<&mut Transform>| {
+            for mut transform in query.iter_mut() {
+                *transform = sway(frame.0 as f32);
+            }
+        });
+        assert!(always.iter().all(|count| *count == 5000));
+        let gated = changed_per_frame(|frame: Res<Frame>, mut query: Query<&mut Transform>| {
+            for mut transform in query.iter_mut() {
+                set_if_changed(&mut transform, sway(frame.0 as f32));
+            }
+        });
+        //Only spawn is seen.
+        assert_eq!(gated[0], 5000);
+        assert!(gated[1..].iter().all(|count| *count == 0));
+
+        let mut world = World::new();
+        let entity = world.spawn(Transform::default()).id();
+        world.clear_trackers();
+        let mut query = world.query::<&mut Transform>();
+        let mut transform = query.get_mut(&mut world, entity).unwrap();
+        //Negated quaternion is same rotation.
+        let same = Transform::from_rotation(Quat::from_xyzw(0., 0., 0., -1.));
+        assert!(!set_if_changed(&mut transform, same));
+        assert!(!transform.is_changed());
+        assert!(set_if_changed(
+            &mut transform,
+            Transform::from_xyz(0., 0.01, 0.)
+        ));
+        assert!(transform.is_changed());
+        assert!(Color::rgb(0.5, 0.5, 0.5).visually_eq(&Color::rgb(
+            0.5,
+            0.5,
+            0.5 + VISUAL_EPS * 0.5
+        )));
+        assert!(!Color::RED.visually_eq(&Color::rgba(1., 0., 0., 0.5)));
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    console::Console, states::*, structure::draw_bounds_debug, ui::primary_close_requested,
+};
+
+use bevy::{prelude::*, window::WindowCloseRequested};
+
+///Key that toggles inspect.
+pub const INSPECT_KEY: KeyCode = KeyCode::F9;
+
+///Batch setup for debug freeze-frame.
+pub struct InspectPlugin;
+
+impl Plugin for InspectPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InspectFreeze>()
+            .add_system_to_stage(CoreStage::Update, inspect_key)
+            .add_system_set_to_stage(
+                CoreStage::Update,
+                SystemSet::on_update(UpdateStageState::Inspecting)
+                    .with_system(inspect_close_requested),
+            )
+            //Bounds are what inspect is for, so they're drawn even though in game is paused.
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::on_update(PostUpdateStageState::Inspecting)
+                    .with_system(draw_bounds_debug),
+            );
+    }
+}
+
+///What inspect changed, to be restored when gameplay resumes.
+///Gameplay itself is frozen by inspecting state, which pauses in game like pause menu does.
+#[derive(Resource, Default)]
+pub struct InspectFreeze {
+    ///Bounds debug before freezing.
+    pub bounds_debug: bool,
+}
+
+///Submits inspect command, so key and console do same thing.
+fn inspect_key(input: Res<Input<KeyCode>>, mut console: ResMut<Console>) {
+    if input.just_pressed(INSPECT_KEY) {
+        console.submit("inspect");
+    }
+}
+
+///Window close goes to exit popup same as in game.
+fn inspect_close_requested(
+    mut closed: EventReader<WindowCloseRequested>,
+    mut state: ResMut<GlobalState>,
+) {
+    if primary_close_requested(&mut closed) && !state.should_change() {
+        state.push_exit();
+    }
+}
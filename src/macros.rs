@@ -9,3 +9,28 @@ macro_rules! unreachable_release {
         }
     };
 }
+
+///Gets only item of query, or warns and returns when there isn't exactly one.
+///Entities come and go during state transitions, which is not worth a crash.
+///Prefix `mut` for mutable item. Optional second argument is what is returned.
+#[macro_export]
+macro_rules! single_or_return {
+    (mut $query:expr $(, $ret:expr)?) => {
+        match $query.get_single_mut() {
+            Ok(item) => item,
+            Err(e) => {
+                bevy::log::warn!("{}: {e}", stringify!($query));
+                return $($ret)?;
+            }
+        }
+    };
+    ($query:expr $(, $ret:expr)?) => {
+        match $query.get_single() {
+            Ok(item) => item,
+            Err(e) => {
+                bevy::log::warn!("{}: {e}", stringify!($query));
+                return $($ret)?;
+            }
+        }
+    };
+}
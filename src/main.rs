@@ -1,39 +1,116 @@
 pub(crate) mod asset;
+pub(crate) mod console;
 pub(crate) mod consts;
+pub(crate) mod demo;
+pub(crate) mod environment;
 pub(crate) mod func;
+#[cfg(feature = "inspect")]
+pub(crate) mod inspect;
 pub(crate) mod macros;
 pub(crate) mod physics;
+pub(crate) mod recovery;
+pub(crate) mod rng;
+pub(crate) mod save;
+pub(crate) mod settings;
+pub(crate) mod simulation;
+pub(crate) mod specs;
 pub(crate) mod states;
+pub(crate) mod structure;
+pub(crate) mod tag;
+pub(crate) mod task;
+pub(crate) mod telemetry;
+pub(crate) mod timings;
+pub(crate) mod toggles;
+pub(crate) mod trace;
 pub(crate) mod ui;
+pub(crate) mod vox;
 
 use crate::{
-    asset::AssetManagingPlugin,
-    states::{in_game::*, main_menu::*, *},
+    asset::{AssetCheck, AssetManagingPlugin, Paths},
+    console::ConsolePlugin,
+    settings::SettingsPlugin,
+    simulation::SimulationPlugin,
+    states::{
+        in_game::compass::CompassPlugin, in_game::framing::FramingPlugin,
+        in_game::pause_menu::PauseMenuPlugin, in_game::*, main_menu::*, missing_assets::*, *,
+    },
+    task::TaskPlugin,
+    telemetry::TelemetryPlugin,
+    timings::{StartupTimings, StartupTimingsPlugin},
+    ui::UiManagingPlugin,
 };
 
-use bevy::prelude::*;
+use bevy::{asset::AssetPlugin, prelude::*};
 
 use bevy_polyline::PolylinePlugin;
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
+    let mut timings = StartupTimings::new();
+    let span = timings.span("plugin build");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let paths = Paths::from_args(args.iter().cloned());
+    let check = AssetCheck::run(&paths);
+    let default_plugins = DefaultPlugins
+        .set(WindowPlugin {
             window: WindowDescriptor {
                 title: "Game made with Rust".to_owned(),
                 ..default()
             },
             close_when_requested: false,
             ..default()
-        }))
+        })
+        .set(AssetPlugin {
+            asset_folder: paths.asset_folder.clone(),
+            ..default()
+        });
+    let mut app = App::new();
+    app.insert_resource(paths)
+        .insert_resource(check)
+        .add_plugins(default_plugins)
         //Asset manage helpers
         .add_plugin(AssetManagingPlugin)
+        //Ui helpers
+        .add_plugin(UiManagingPlugin)
+        //Background task reporting
+        .add_plugin(TaskPlugin)
+        //Developer console
+        .add_plugin(ConsolePlugin)
+        //User settings
+        .add_plugin(SettingsPlugin)
         //Polyline lib
         .add_plugin(PolylinePlugin)
         //Global states manager
         .add_plugin(StatesPlugin)
+        //Fixed step stage for physics
+        .add_plugin(SimulationPlugin)
         //Main Menu
         .add_plugin(MainMenuPlugin)
         //In Game
         .add_plugin(InGamePlugin)
-        .run();
+        //Compass and position readout
+        .add_plugin(CompassPlugin)
+        //Camera flight that frames whole build
+        .add_plugin(FramingPlugin)
+        //Pause menu over game
+        .add_plugin(PauseMenuPlugin)
+        //Missing assets screen
+        .add_plugin(MissingAssetsPlugin)
+        //Startup instrumentation
+        .add_plugin(StartupTimingsPlugin)
+        //Opt-in build event stream
+        .add_plugin(TelemetryPlugin::from_args(&args));
+    //Debug freeze-frame
+    #[cfg(feature = "inspect")]
+    app.add_plugin(inspect::InspectPlugin);
+    //Aim cell and octree leaf overlay
+    #[cfg(feature = "coords")]
+    app.add_plugin(states::in_game::coords::CoordOverlayPlugin);
+    //Top-down view in second window
+    #[cfg(feature = "top_view")]
+    app.add_plugin(states::in_game::top_view::TopViewPlugin);
+    //Octree node and entity wireframes
+    #[cfg(feature = "octree_debug")]
+    app.add_plugin(states::in_game::octree_debug::OctreeDebugPlugin);
+    drop(span);
+    app.insert_resource(timings).run();
 }
@@ -1,4 +1,7 @@
-use crate::physics::ray::Ray;
+use crate::{
+    consts::{OCTANT_EPS, POINT_EPS},
+    physics::ray::Ray,
+};
 
 use std::{
     cmp::Ordering,
@@ -6,7 +9,7 @@ use std::{
 };
 
 use bevy::{
-    math::{BVec3, Vec3},
+    math::{BVec3, Vec3, Vec4},
     prelude::Component,
 };
 
@@ -18,6 +21,9 @@ pub struct AABB {
 }
 
 impl AABB {
+    ///Minimum thickness of aabb that comes from shape.
+    pub const MIN_EXTENT: f32 = POINT_EPS;
+
     pub fn new(min: Vec3, max: Vec3) -> Self {
         if min.cmpge(max).any() || min.is_nan() || max.is_nan() {
             panic!(
@@ -27,12 +33,33 @@ impl AABB {
         Self { min, max }
     }
 
+    ///Farthest coordinate of clamped box. Infinite shape is bounded by it.
+    pub const MAX_COORD: f32 = 1e30;
+
+    ///Same as `new`, but widens axis thinner than `MIN_EXTENT` around its center.
+    ///So flat or zero sized shape still makes valid aabb.
+    ///Infinite coordinate is clamped to `MAX_COORD`, and NaN one is taken as widest on its side.
+    pub fn new_clamped(min: Vec3, max: Vec3) -> Self {
+        let limit = Vec3::splat(Self::MAX_COORD);
+        let min = Vec3::select(min.is_nan_mask(), -limit, min).clamp(-limit, limit);
+        let max = Vec3::select(max.is_nan_mask(), limit, max).clamp(-limit, limit);
+        let center = (min + max) * 0.5;
+        //Far from origin, epsilon could be lost by float precision.
+        let min_half = Vec3::splat(Self::MIN_EXTENT * 0.5).max(center.abs() * f32::EPSILON * 2.);
+        //Thick axis is kept as it is, so bound still encloses what it is given.
+        let thin = (max - min).cmplt(min_half * 2.);
+        Self::new(
+            Vec3::select(thin, center - min_half, min),
+            Vec3::select(thin, center + min_half, max),
+        )
+    }
+
     pub const unsafe fn new_unchecked(min: Vec3, max: Vec3) -> Self {
         Self { min, max }
     }
 
     ///Determine min and max from size and zero offset.
-    pub fn _from_size(mut size: f32) -> Self {
+    pub fn from_size(mut size: f32) -> Self {
         size = size.abs() * 0.5;
         Self::new(Vec3::splat(-size), Vec3::splat(size))
     }
@@ -44,18 +71,28 @@ impl AABB {
     }
 
     //Extract aabb from shape vertices and objects' pos and rot.
-    pub fn from_points(points: &[Vec3]) -> Self {
+    pub fn _from_points(points: &[Vec3]) -> Self {
+        let (min, max) = Self::bound_of(points);
+        Self::new(min, max)
+    }
+
+    ///Same as `from_points`, but flat set of points is widened by `new_clamped`.
+    pub fn from_points_clamped(points: &[Vec3]) -> Self {
+        let (min, max) = Self::bound_of(points);
+        Self::new_clamped(min, max)
+    }
+
+    fn bound_of(points: &[Vec3]) -> (Vec3, Vec3) {
         if points.len() < 3 {
             panic!("Number of points should be at least 3 to be polygon.");
-        } else {
-            let mut min = Vec3::splat(f32::INFINITY);
-            let mut max = Vec3::splat(f32::NEG_INFINITY);
-            for point in points {
-                min = min.min(*point);
-                max = max.max(*point);
-            }
-            Self::new(min, max)
         }
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+        for point in points {
+            min = min.min(*point);
+            max = max.max(*point);
+        }
+        (min, max)
     }
 
     pub fn min(&self) -> Vec3 {
@@ -70,10 +107,58 @@ impl AABB {
         self.max - self.min
     }
 
+    pub const fn x_length(&self) -> f32 {
+        self.max.x - self.min.x
+    }
+
+    pub const fn y_length(&self) -> f32 {
+        self.max.y - self.min.y
+    }
+
+    pub const fn z_length(&self) -> f32 {
+        self.max.z - self.min.z
+    }
+
+    ///Zero for box without thickness, which only unchecked box could be.
+    pub const fn volume(&self) -> f32 {
+        let [x, y, z] = self.thickness();
+        x * y * z
+    }
+
+    ///Zero for box without thickness in two axes.
+    pub const fn surface_area(&self) -> f32 {
+        let [x, y, z] = self.thickness();
+        2. * (x * y + y * z + x * z)
+    }
+
+    ///Lengths where inverted axis of unchecked box counts as zero, not negative.
+    const fn thickness(&self) -> [f32; 3] {
+        [
+            self.x_length().max(0.),
+            self.y_length().max(0.),
+            self.z_length().max(0.),
+        ]
+    }
+
     pub fn center(&self) -> Vec3 {
         (self.min + self.max) * 0.5
     }
 
+    ///Corners indexed same as octant, x is the most significant bit. True bit is max.
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+
     pub fn center_x(&self) -> f32 {
         (self.min.x + self.max.x) * 0.5
     }
@@ -97,6 +182,22 @@ impl AABB {
         self
     }
 
+    ///Smallest box that encloses both.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    ///Smallest box that encloses this and point.
+    pub fn union_point(&self, point: Vec3) -> Self {
+        Self {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
     ///Same as extend, but get function as parameter.
     pub fn extend_for(mut self, other: &Self, mut f: impl FnMut(AABB)) {
         while self.min.x > other.min.x || self.min.y > other.min.y || self.min.z > other.min.z {
@@ -110,16 +211,17 @@ impl AABB {
     }
 
     ///Determines which octant from origin this box is placed. True is positive, false is negative.
-    pub fn octant(&self) -> Option<BVec3> {
-        let x_p = self.min.x >= 0. && self.max.x > 0.;
-        let x_n = self.min.x < 0. && self.max.x <= 0.;
-        let y_p = self.min.y >= 0. && self.max.y > 0.;
-        let y_n = self.min.y < 0. && self.max.y <= 0.;
-        let z_p = self.min.z >= 0. && self.max.z > 0.;
-        let z_n = self.min.z < 0. && self.max.z <= 0.;
-
-        if x_p ^ x_n && y_p ^ y_n && z_p ^ z_n {
-            Some(BVec3::new(x_p, y_p, z_p))
+    pub fn _octant(&self) -> Option<BVec3> {
+        self.octant_about(Vec3::ZERO)
+    }
+
+    ///Same as `(self - origin)._octant()`, without building shifted box.
+    pub fn octant_about(&self, origin: Vec3) -> Option<BVec3> {
+        let positive = self.min.cmpge(origin) & self.max.cmpgt(origin);
+        let negative = self.min.cmplt(origin) & self.max.cmple(origin);
+
+        if (positive ^ negative).all() {
+            Some(positive)
         } else {
             None
         }
@@ -155,21 +257,21 @@ impl AABB {
     pub fn is_on_octant(&self, point: Vec3) -> [Ordering; 3] {
         let center = self.center();
         [
-            if (point.x - center.x).abs() <= f32::EPSILON {
+            if (point.x - center.x).abs() <= OCTANT_EPS {
                 Ordering::Equal
             } else if point.x < center.x {
                 Ordering::Less
             } else {
                 Ordering::Greater
             },
-            if (point.y - center.y).abs() <= f32::EPSILON {
+            if (point.y - center.y).abs() <= OCTANT_EPS {
                 Ordering::Equal
             } else if point.y < center.y {
                 Ordering::Less
             } else {
                 Ordering::Greater
             },
-            if (point.z - center.z).abs() <= f32::EPSILON {
+            if (point.z - center.z).abs() <= OCTANT_EPS {
                 Ordering::Equal
             } else if point.z < center.z {
                 Ordering::Less
@@ -179,20 +281,59 @@ impl AABB {
         ]
     }
 
-    ///Check which bound face point is lying on.
-    ///Returns unit vector of face.
-    pub fn face(&self, point: Vec3) -> Vec3 {
-        let check = (point - self.center()) / self.length();
-        let check_abs = check.abs();
-        let x = check_abs.x >= check_abs.y && check_abs.x >= check_abs.z;
-        let y = check_abs.y >= check_abs.x && check_abs.y >= check_abs.z;
-        let z = check_abs.z >= check_abs.x && check_abs.z >= check_abs.y;
-        Vec3::new(
-            if x { check.x.signum() } else { 0. },
-            if y { check.y.signum() } else { 0. },
-            if z { check.z.signum() } else { 0. },
-        )
-        .normalize()
+    ///Check which bound face point is lying on. Returns unit vector of face.
+    ///Nearest face is chosen. Faces within epsilon of it, such as on edge or corner, are told apart by
+    ///which one vector from center to point heads to the most, and then by axis order.
+    pub fn face(&self, point: Vec3, epsilon: f32) -> Vec3 {
+        self.nearest_face(point, epsilon).0
+    }
+
+    ///Same as `face`, but None if point is farther than epsilon from every face.
+    pub fn face_checked(&self, point: Vec3, epsilon: f32) -> Option<Vec3> {
+        let (face, distance) = self.nearest_face(point, epsilon);
+        (distance <= epsilon).then_some(face)
+    }
+
+    ///Nearest face and distance to it. Distance is to face itself, not to its plane.
+    fn nearest_face(&self, point: Vec3, epsilon: f32) -> (Vec3, f32) {
+        let from_center = point - self.center();
+        //How far point is out of box on each axis.
+        let outside = (self.min - point).max(point - self.max).max(Vec3::ZERO);
+        //Face, distance and heading of every face, in axis order.
+        let mut faces = [(Vec3::ZERO, f32::INFINITY, f32::NEG_INFINITY); 6];
+        for i in 0..3 {
+            //Point out of box on other axes is off face by that much.
+            let mut off_face = outside;
+            off_face[i] = 0.;
+            for (j, (plane, sign)) in [(self.min[i], -1.), (self.max[i], 1.)]
+                .into_iter()
+                .enumerate()
+            {
+                let mut face = Vec3::ZERO;
+                face[i] = sign;
+                let distance = ((point[i] - plane).powi(2) + off_face.length_squared()).sqrt();
+                faces[i * 2 + j] = (face, distance, from_center[i] * sign);
+            }
+        }
+        let nearest = faces
+            .iter()
+            .map(|(_, distance, _)| *distance)
+            .fold(f32::INFINITY, f32::min);
+        //Window is around nearest one, so tie breaking can't drift away from it.
+        let mut face = Vec3::ZERO;
+        let mut heading = f32::NEG_INFINITY;
+        for (candidate, distance, candidate_heading) in faces {
+            if distance <= nearest + epsilon && candidate_heading > heading {
+                face = candidate;
+                heading = candidate_heading;
+            }
+        }
+        (face, nearest)
+    }
+
+    ///Checks whether other bounding box is entirely inside of this. Inclusive bound line.
+    pub fn contains(&self, other: &Self) -> bool {
+        self.min.cmple(other.min).all() && self.max.cmpge(other.max).all()
     }
 
     ///Checks whether this and other bounding box intersected. Exclusive bound line.
@@ -200,9 +341,27 @@ impl AABB {
         self.min.cmplt(other.max).all() && self.max.cmpgt(other.min).all()
     }
 
-    ///Checks whether point is in bounding box.
-    pub fn _overlaps_point(&self, point: Vec3) -> bool {
-        self.min.cmplt(point).all() && self.max.cmplt(point).all()
+    ///Checks whether point is strictly inside bounding box.
+    ///Point on boundary isn't, same as touching isn't intersecting.
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.min.cmplt(point).all() && self.max.cmpgt(point).all()
+    }
+
+    ///Checks whether box is entirely on negative side of plane.
+    ///Plane is normal and distance, as in bevy's `Plane::normal_d`. Touching is not outside.
+    pub fn outside_plane(&self, plane: Vec4) -> bool {
+        let normal = plane.truncate();
+        //Corner farthest along normal.
+        let farthest = Vec3::select(normal.cmpge(Vec3::ZERO), self.max, self.min);
+        normal.dot(farthest) + plane.w < 0.
+    }
+
+    ///Checks whether box is entirely on positive side of plane. Touching is inside.
+    pub fn inside_plane(&self, plane: Vec4) -> bool {
+        let normal = plane.truncate();
+        //Corner farthest against normal.
+        let nearest = Vec3::select(normal.cmpge(Vec3::ZERO), self.min, self.max);
+        normal.dot(nearest) + plane.w >= 0.
     }
 
     ///Checks if ray is penetrating box.
@@ -211,6 +370,35 @@ impl AABB {
             .map(|(t_min, t_max)| if t_min <= 0. { t_max } else { t_min })
     }
 
+    ///Same as `intersects_ray`, with outward normal of face ray meets.
+    ///Ray enters through slab it enters last and leaves through slab it leaves first.
+    pub fn intersects_ray_normal(&self, ray: &Ray) -> Option<(f32, Vec3)> {
+        let (t_min, t_max) = self.intersects_ray_raw(ray)?;
+        let d_min = ray.t(self.min);
+        let d_max = ray.t(self.max);
+        let (t, slabs, outward) = if t_min <= 0. {
+            (t_max, d_min.max(d_max), 1.)
+        } else {
+            (t_min, d_min.min(d_max), -1.)
+        };
+        //Parallel axis has no slab crossing. Tie on edge or corner goes to former axis.
+        let mut axis = None;
+        for i in 0..3 {
+            if ray.dir()[i] == 0. {
+                continue;
+            }
+            match axis {
+                Some(j) if (slabs[j] - t).abs() <= (slabs[i] - t).abs() => {}
+                _ => axis = Some(i),
+            }
+        }
+        let mut normal = Vec3::ZERO;
+        if let Some(i) = axis {
+            normal[i] = outward * ray.dir()[i].signum();
+        }
+        Some((t, normal))
+    }
+
     ///Checks if ray is penetrating box and returns raw data for two location on bound that ray passes.
     pub fn intersects_ray_raw(&self, ray: &Ray) -> Option<(f32, f32)> {
         let mut t_min = f32::NEG_INFINITY;
@@ -281,3 +469,236 @@ impl Sub<Vec3> for AABB {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::rng::SplitMix64;
+
+    #[test]
+    fn contains_point() {
+        let aabb = AABB::from_size_offset(2., Vec3::ONE);
+        assert!(aabb.contains_point(Vec3::ONE));
+        assert!(aabb.contains_point(Vec3::splat(1.999)));
+        //Corners and faces are on boundary.
+        for corner in aabb.corners() {
+            assert!(!aabb.contains_point(corner));
+        }
+        assert!(!aabb.contains_point(Vec3::new(1., 1., 2.)));
+        assert!(!aabb.contains_point(Vec3::new(5., 1., 1.)));
+        assert!(!aabb.contains_point(Vec3::new(1., -1., 1.)));
+    }
+
+    #[test]
+    fn union_encloses_disjoint() {
+        let a = AABB::from_size_offset(1., Vec3::ZERO);
+        let b = AABB::from_size_offset(2., Vec3::new(5., -3., 1.));
+        let union = a.union(&b);
+        assert_eq!(union, b.union(&a));
+        assert!(union.contains(&a) && union.contains(&b));
+        assert_eq!(union.min(), Vec3::new(-0.5, -4., -0.5));
+        assert_eq!(union.max(), Vec3::new(6., 0.5, 2.));
+    }
+
+    #[test]
+    fn union_with_contained_is_same() {
+        let outer = AABB::from_size_offset(4., Vec3::ONE);
+        let inner = AABB::from_size_offset(1., Vec3::ONE);
+        assert_eq!(outer.union(&inner), outer);
+        assert_eq!(inner.union(&outer), outer);
+        assert_eq!(outer.union(&outer), outer);
+    }
+
+    #[test]
+    fn union_point() {
+        let aabb = AABB::from_size_offset(2., Vec3::ZERO);
+        assert_eq!(aabb.union_point(Vec3::ZERO), aabb);
+        assert_eq!(aabb.union_point(aabb.max()), aabb);
+        let union = aabb.union_point(Vec3::new(3., 0., -2.));
+        assert_eq!(union.min(), Vec3::new(-1., -1., -2.));
+        assert_eq!(union.max(), Vec3::new(3., 1., 1.));
+    }
+
+    #[test]
+    fn new_clamped_is_valid() {
+        let nan = f32::NAN;
+        let inf = f32::INFINITY;
+        for (min, max) in [
+            (Vec3::ZERO, Vec3::ZERO),
+            (Vec3::ONE, Vec3::ZERO),
+            (Vec3::splat(1e7), Vec3::splat(1e7)),
+            (Vec3::splat(-inf), Vec3::splat(inf)),
+            (Vec3::splat(inf), Vec3::splat(inf)),
+            (Vec3::new(nan, 0., -inf), Vec3::new(1., nan, nan)),
+            (Vec3::splat(nan), Vec3::splat(nan)),
+        ] {
+            let aabb = AABB::new_clamped(min, max);
+            assert!(aabb.min().is_finite() && aabb.max().is_finite());
+            assert!(aabb.min().cmplt(aabb.max()).all(), "{min} {max}");
+        }
+        //Finite thick box is kept as it is.
+        let (min, max) = (Vec3::new(-1., 2., 3.), Vec3::new(4., 5., 6.));
+        assert_eq!(AABB::new_clamped(min, max), AABB::new(min, max));
+        //Infinite one is bounded, and NaN is widest.
+        let aabb = AABB::new_clamped(Vec3::new(-inf, nan, 0.), Vec3::new(inf, 1., 1.));
+        assert_eq!(
+            aabb.min(),
+            Vec3::new(-AABB::MAX_COORD, -AABB::MAX_COORD, 0.)
+        );
+        assert_eq!(aabb.max(), Vec3::new(AABB::MAX_COORD, 1., 1.));
+    }
+
+    #[test]
+    fn face_of_face_centers() {
+        let aabb = AABB::new(Vec3::new(-1., -0.5, -0.5), Vec3::new(1., 0.5, 0.5));
+        let e = POINT_EPS;
+        for face in [Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, Vec3::Z, -Vec3::Z] {
+            let point = face * aabb.max().abs();
+            assert_eq!(aabb.face(point, e), face);
+            assert_eq!(aabb.face_checked(point, e), Some(face));
+            //Slightly inside and outside.
+            assert_eq!(aabb.face_checked(point - face * e * 0.5, e), Some(face));
+            assert_eq!(aabb.face_checked(point + face * e * 0.5, e), Some(face));
+        }
+        //Near face of thin axis, although point is farther along long axis.
+        assert_eq!(aabb.face(Vec3::new(0.7, 0.49, 0.), e), Vec3::Y);
+        assert_eq!(aabb.face(Vec3::new(1.5, 0., 0.), e), Vec3::X);
+    }
+
+    #[test]
+    fn face_of_edges_and_corners() {
+        let aabb = AABB::new(Vec3::new(-1., -0.5, -0.5), Vec3::new(1., 0.5, 0.5));
+        let e = POINT_EPS;
+        //Face that point heads to the most wins.
+        assert_eq!(aabb.face(Vec3::new(1., 0.5, 0.), e), Vec3::X);
+        assert_eq!(aabb.face(Vec3::new(-1., -0.5, 0.5), e), -Vec3::X);
+        //Tie in heading goes to former axis.
+        assert_eq!(aabb.face(Vec3::new(0., 0.5, 0.5), e), Vec3::Y);
+        assert_eq!(aabb.face(Vec3::new(0., 0.5, -0.5), e), Vec3::Y);
+        let cube = AABB::from_size(1.);
+        assert_eq!(cube.face(Vec3::splat(0.5), e), Vec3::X);
+        assert_eq!(cube.face(Vec3::new(0.5, 0.5, -0.5), e), Vec3::X);
+        //Within epsilon of edge, heading still decides.
+        assert_eq!(aabb.face(Vec3::new(0., 0.4995, 0.5), e), Vec3::Z);
+        assert_eq!(aabb.face(Vec3::new(0., 0.5, 0.4995), e), Vec3::Y);
+        assert_eq!(cube.face(Vec3::new(0.4999, 0.5, -0.5), e), Vec3::Y);
+        //Outside past edge, distance counts part off face.
+        assert_eq!(
+            aabb.face_checked(Vec3::new(1.0005, 0.5005, 0.), e),
+            Some(Vec3::X)
+        );
+        assert_eq!(aabb.face_checked(Vec3::new(1.002, 0.502, 0.), e), None);
+    }
+
+    #[test]
+    fn face_of_inside_points() {
+        let aabb = AABB::new(Vec3::new(-0.5, -1., -2.), Vec3::new(0.5, 1., 2.));
+        let e = POINT_EPS;
+        //On x face, with y and z faces each a bit less than epsilon farther than last one.
+        //Longer axes head more, but z face is out of epsilon of x face.
+        let point = Vec3::new(0.5, 1. - e * 0.9, 2. - e * 1.8);
+        assert_eq!(aabb.face(point, e), Vec3::Y);
+        assert_eq!(aabb.face_checked(point, e), Some(Vec3::Y));
+        assert_eq!(aabb.face_checked(Vec3::ZERO, e), None);
+        assert_eq!(aabb.face_checked(Vec3::new(0., 0.3, 0.), e), None);
+        assert_eq!(aabb.face(Vec3::new(0.1, 0.2, 1.9), e), Vec3::Z);
+    }
+
+    #[test]
+    fn octant_about_matches_shifted_octant() {
+        let mut rng = SplitMix64::new(733);
+        //Halves make origin often lie on sides, where comparisons matter.
+        let mut halves = |range: f32| {
+            let v = Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32());
+            ((v - 0.5) * range * 2.).round() * 0.5
+        };
+        for _ in 0..10_000 {
+            let min = halves(4.);
+            let aabb = AABB::new(min, min + halves(2.).abs() + 0.5);
+            let origin = halves(4.);
+            let shifted = aabb - origin;
+            assert_eq!(
+                aabb.octant_about(origin),
+                shifted._octant(),
+                "{aabb:?} {origin}"
+            );
+            //Per axis rule that octant was first written with.
+            let side = |min: f32, max: f32| match (min >= 0. && max > 0., min < 0. && max <= 0.) {
+                (true, false) => Some(true),
+                (false, true) => Some(false),
+                _ => None,
+            };
+            let (min, max) = (shifted.min(), shifted.max());
+            let expected = match (side(min.x, max.x), side(min.y, max.y), side(min.z, max.z)) {
+                (Some(x), Some(y), Some(z)) => Some(BVec3::new(x, y, z)),
+                _ => None,
+            };
+            assert_eq!(shifted._octant(), expected);
+        }
+    }
+
+    #[test]
+    fn volume_and_surface_area() {
+        let unit = AABB::from_size_offset(1., Vec3::new(3., -2., 7.));
+        assert!((unit.volume() - 1.).abs() < 1e-6);
+        assert!((unit.surface_area() - 6.).abs() < 1e-6);
+        let flat = AABB::new(Vec3::ZERO, Vec3::new(4., 0.5, 2.));
+        assert_eq!(
+            [flat.x_length(), flat.y_length(), flat.z_length()],
+            flat.length().to_array()
+        );
+        assert!((flat.volume() - 4.).abs() < 1e-6);
+        assert!((flat.surface_area() - 22.).abs() < 1e-6);
+        //Only unchecked box could be thinner, and it measures zero instead of negative or NaN.
+        let sheet = unsafe { AABB::new_unchecked(Vec3::ZERO, Vec3::new(4., 0., 2.)) };
+        assert_eq!(sheet.volume(), 0.);
+        assert_eq!(sheet.surface_area(), 16.);
+        let inverted = unsafe { AABB::new_unchecked(Vec3::ONE, Vec3::new(2., 0., 0.)) };
+        assert_eq!(inverted.volume(), 0.);
+        assert_eq!(inverted.surface_area(), 0.);
+    }
+
+    #[test]
+    fn ray_normal_of_each_face() {
+        let aabb = AABB::new(Vec3::new(-1., 2., -3.), Vec3::new(1., 4., -1.));
+        let center = aabb.center();
+        for normal in [
+            Vec3::X,
+            Vec3::NEG_X,
+            Vec3::Y,
+            Vec3::NEG_Y,
+            Vec3::Z,
+            Vec3::NEG_Z,
+        ] {
+            //Slightly oblique, so other slabs are crossed too but earlier.
+            let origin = center + normal * 10. + normal.any_orthogonal_vector() * 0.3;
+            let ray = Ray::new(origin, center - origin);
+            let (t, hit) = aabb.intersects_ray_normal(&ray).unwrap();
+            assert_eq!(hit, normal);
+            let point = ray.point(t);
+            let face = center + normal;
+            assert!(
+                ((point - face) * normal).length() < 1e-5,
+                "{normal} {point}"
+            );
+        }
+    }
+
+    #[test]
+    fn ray_from_inside_gets_exit_face() {
+        let aabb = AABB::new(Vec3::ZERO, Vec3::new(2., 4., 6.));
+        let ray = Ray::new(Vec3::new(1., 1., 3.), Vec3::NEG_Y);
+        let (t, normal) = aabb.intersects_ray_normal(&ray).unwrap();
+        assert!((t - 1.).abs() < 1e-6);
+        assert_eq!(normal, Vec3::NEG_Y);
+        let ray = Ray::new(Vec3::new(1., 1., 3.), Vec3::new(1., 0., 0.5));
+        let (t, normal) = aabb.intersects_ray_normal(&ray).unwrap();
+        assert!(ray.point(t).abs_diff_eq(Vec3::new(2., 1., 3.5), 1e-5));
+        assert_eq!(normal, Vec3::X);
+        //Box behind ray isn't hit.
+        assert!(aabb
+            .intersects_ray_normal(&Ray::new(Vec3::new(1., 5., 3.), Vec3::Y))
+            .is_none());
+    }
+}
@@ -1,4 +1,7 @@
-use crate::physics::aabb::AABB;
+use crate::{
+    consts::OVERLAP_EPS,
+    physics::{aabb::AABB, ray::Ray},
+};
 use bevy::prelude::*;
 
 #[derive(Component, Clone)]
@@ -20,7 +23,7 @@ impl Collider {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Shape {
     Sphere {
         radius: f32,
@@ -30,23 +33,256 @@ pub enum Shape {
         radius: f32,
         cut: f32,
     },
+    ///Box centered at origin, such as ground.
+    Cuboid {
+        half_extents: Vec3,
+    },
 }
 
 impl Shape {
+    ///Short name of shape kind, such as for naming entity.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Shape::Sphere { .. } => "Sphere",
+            Shape::CutSphere { .. } => "CutSphere",
+            Shape::Cuboid { .. } => "Cuboid",
+        }
+    }
+
     pub fn aabb(&self, transform: &Transform) -> AABB {
         match self {
             Shape::Sphere { radius } => sphere_aabb(*radius, transform),
             Shape::CutSphere { radius, cut } => cut_sphere_aabb(*radius, *cut, transform),
+            Shape::Cuboid { half_extents } => cuboid_aabb(*half_extents, transform),
+        }
+    }
+
+    ///Whether bound of shape is finite, as huge size or scale overflows it.
+    pub fn has_finite_bound(&self, transform: &Transform) -> bool {
+        let extent = match self {
+            Shape::Sphere { radius } => Vec3::splat(*radius),
+            Shape::CutSphere { radius, cut } => Vec3::splat(radius.abs().max(cut.abs())),
+            Shape::Cuboid { half_extents } => *half_extents,
+        };
+        //Turned shape stays within length of its extent.
+        let reach = (extent * transform.scale).length();
+        (transform.translation.abs() + reach).is_finite()
+    }
+
+    ///Distance to where ray hits shape. Exit is given if ray starts inside, as in `AABB::intersects_ray`.
+    pub fn intersects_ray(&self, transform: &Transform, ray: &Ray) -> Option<f32> {
+        self.intersects_ray_normal(transform, ray).map(|(t, _)| t)
+    }
+
+    ///Same as `intersects_ray`, with outward normal of surface ray meets.
+    pub fn intersects_ray_normal(&self, transform: &Transform, ray: &Ray) -> Option<(f32, Vec3)> {
+        let (enter, leave) = self.ray_span(transform, ray)?;
+        let (t, normal) = if enter.0 <= 0. { leave } else { enter };
+        //Normal goes back to world by inverse transpose, which is rotation after division by scale.
+        Some((
+            t,
+            (transform.rotation * (normal / transform.scale)).normalize_or_zero(),
+        ))
+    }
+
+    ///Whether shapes go into each other deeper than `OVERLAP_EPS`. Touching ones don't overlap.
+    ///Non uniform scale is taken as largest axis, as for bound of sphere.
+    pub fn overlaps(
+        &self,
+        transform: &Transform,
+        other: &Shape,
+        other_transform: &Transform,
+    ) -> bool {
+        match (
+            Solid::eroded(self, transform),
+            Solid::eroded(other, other_transform),
+        ) {
+            (Some(a), Some(b)) => a.meets(&b),
+            //Shape thinner than tolerance has nothing to go into.
+            _ => false,
+        }
+    }
+
+    ///Where ray enters and leaves shape, as in `AABB::intersects_ray_raw`, each with normal in shape's own space.
+    fn ray_span(&self, transform: &Transform, ray: &Ray) -> Option<((f32, Vec3), (f32, Vec3))> {
+        //Flat shape has nothing to hit.
+        if transform.scale.cmpeq(Vec3::ZERO).any() {
+            return None;
+        }
+        //Ray is brought into shape's own space. Map is linear, so distance along ray stays same.
+        let inverse = transform.rotation.inverse();
+        let local = Ray::new(
+            inverse * (ray.origin() - transform.translation) / transform.scale,
+            inverse * ray.dir() / transform.scale,
+        );
+        let (origin, dir) = (local.origin(), local.dir());
+        //Normal of sphere at origin is point itself.
+        let sphere_normal = |t: f32| local.point(t);
+        let (enter, leave) = match self {
+            Shape::Sphere { radius } => {
+                let (t_min, t_max) = local.sphere_span(Vec3::ZERO, *radius)?;
+                ((t_min, sphere_normal(t_min)), (t_max, sphere_normal(t_max)))
+            }
+            Shape::CutSphere { radius, cut } => {
+                let (t_min, t_max) = local.sphere_span(Vec3::ZERO, *radius)?;
+                //Ray entering through cut face meets plane before sphere. Cut face looks down.
+                let (p_min, p_max) = above_plane_ray(-cut, origin.y, dir.y)?;
+                (
+                    if p_min > t_min {
+                        (p_min, Vec3::NEG_Y)
+                    } else {
+                        (t_min, sphere_normal(t_min))
+                    },
+                    if p_max < t_max {
+                        (p_max, Vec3::NEG_Y)
+                    } else {
+                        (t_max, sphere_normal(t_max))
+                    },
+                )
+            }
+            Shape::Cuboid { half_extents } => cuboid_ray(half_extents.abs(), origin, dir)?,
+        };
+        if leave.0 <= 0. || enter.0 >= leave.0 {
+            None
+        } else {
+            Some((enter, leave))
         }
     }
 }
 
+///Shape in world space. Every shape is convex.
+struct Solid {
+    center: Vec3,
+    body: Body,
+}
+
+enum Body {
+    ///Ball, cut by half space for cut sphere.
+    Ball {
+        radius: f32,
+        ///Normal and distance of plane. Points whose dot with normal is at least distance are kept.
+        plane: Option<(Vec3, f32)>,
+    },
+    ///Box turned about center.
+    Cuboid { rotation: Quat, half_extents: Vec3 },
+}
+
+impl Solid {
+    ///Most times projections go back and forth before shapes are taken as apart.
+    const MAX_PROJECTIONS: usize = 64;
+
+    ///Shrunk by half of tolerance on every side, so touching shapes end up apart by tolerance.
+    ///None if nothing is left.
+    fn eroded(shape: &Shape, transform: &Transform) -> Option<Self> {
+        let center = transform.translation;
+        let scale = transform.scale.abs().max_element();
+        let erosion = OVERLAP_EPS * 0.5;
+        let (radius, plane) = match shape {
+            Shape::Sphere { radius } => (radius.abs() * scale - erosion, None),
+            Shape::CutSphere { radius, cut } => {
+                let normal = transform.rotation * Vec3::Y;
+                let distance = normal.dot(center) - cut * scale + erosion;
+                (radius.abs() * scale - erosion, Some((normal, distance)))
+            }
+            //Scaled box is still box, so each axis takes its own scale.
+            Shape::Cuboid { half_extents } => {
+                let half_extents = half_extents.abs() * transform.scale.abs() - erosion;
+                return half_extents.cmpgt(Vec3::ZERO).all().then_some(Self {
+                    center,
+                    body: Body::Cuboid {
+                        rotation: transform.rotation,
+                        half_extents,
+                    },
+                });
+            }
+        };
+        //Plane could cut whole ball away.
+        let above_center = plane.map_or(f32::NEG_INFINITY, |(normal, distance)| {
+            distance - normal.dot(center)
+        });
+        (radius > 0. && above_center < radius).then_some(Self {
+            center,
+            body: Body::Ball { radius, plane },
+        })
+    }
+
+    ///Closest point of solid to given point.
+    fn project(&self, point: Vec3) -> Vec3 {
+        let (radius, plane) = match self.body {
+            Body::Ball { radius, plane } => (radius, plane),
+            //Clamped in box's own space.
+            Body::Cuboid {
+                rotation,
+                half_extents,
+            } => {
+                let local = rotation.inverse() * (point - self.center);
+                return self.center + rotation * local.clamp(-half_extents, half_extents);
+            }
+        };
+        let onto_ball = point - self.center;
+        let ball = if onto_ball.length() <= radius {
+            point
+        } else {
+            self.center + onto_ball * (radius / onto_ball.length())
+        };
+        let (normal, distance) = match plane {
+            Some(plane) => plane,
+            None => return ball,
+        };
+        if normal.dot(ball) >= distance {
+            return ball;
+        }
+        let onto_plane = point + normal * (distance - normal.dot(point)).max(0.);
+        if onto_plane.distance(self.center) <= radius {
+            return onto_plane;
+        }
+        //Neither alone is in solid, so closest point is on rim where plane cuts ball.
+        let rim_center = self.center + normal * (distance - normal.dot(self.center));
+        let rim_radius = (radius * radius - rim_center.distance_squared(self.center))
+            .max(0.)
+            .sqrt();
+        let along = match (onto_plane - rim_center).try_normalize() {
+            Some(along) => along,
+            None => normal.any_orthonormal_vector(),
+        };
+        rim_center + along * rim_radius
+    }
+
+    ///Whether solids share a point. Projecting onto each in turn gets closer to common point,
+    ///or stalls at gap between them.
+    fn meets(&self, other: &Self) -> bool {
+        let mut on_other = other.project(other.center);
+        let mut gap = f32::INFINITY;
+        for _ in 0..Self::MAX_PROJECTIONS {
+            let on_self = self.project(on_other);
+            on_other = other.project(on_self);
+            let next = on_self.distance(on_other);
+            //Apart solids keep gap of tolerance, as both are eroded.
+            if next < OVERLAP_EPS * 0.5 {
+                return true;
+            }
+            if gap - next < OVERLAP_EPS * 1e-3 {
+                return false;
+            }
+            gap = next;
+        }
+        false
+    }
+}
+
+//Every shape goes through clamped constructors, so extreme parameters don't panic.
+
 fn sphere_aabb(radius: f32, transform: &Transform) -> AABB {
-    AABB::from_size_offset(radius * 2., transform.translation)
+    //Non uniform scale makes ellipsoid, which is bounded by largest axis.
+    let radius = radius.abs() * transform.scale.abs().max_element();
+    AABB::new_clamped(
+        transform.translation - radius,
+        transform.translation + radius,
+    )
 }
 
 fn cut_sphere_aabb(radius: f32, cut: f32, transform: &Transform) -> AABB {
-    AABB::from_points(&[
+    AABB::from_points_clamped(&[
         transform.transform_point(Vec3::new(radius, 0., 0.)),
         transform.transform_point(Vec3::new(-radius, 0., 0.)),
         transform.transform_point(Vec3::new(0., radius, 0.)),
@@ -55,3 +291,210 @@ fn cut_sphere_aabb(radius: f32, cut: f32, transform: &Transform) -> AABB {
         transform.transform_point(Vec3::new(0., 0., -radius)),
     ])
 }
+
+///Every corner is turned, so bound stays tight however box is rotated.
+fn cuboid_aabb(half_extents: Vec3, transform: &Transform) -> AABB {
+    let mut corners = [Vec3::ZERO; 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let sign = Vec3::new(
+            if i & 4 == 0 { -1. } else { 1. },
+            if i & 2 == 0 { -1. } else { 1. },
+            if i & 1 == 0 { -1. } else { 1. },
+        );
+        *corner = transform.transform_point(half_extents * sign);
+    }
+    AABB::from_points_clamped(&corners)
+}
+
+///Where ray enters and leaves box at origin, each with normal of face. Slabs are intersected
+///as for `AABB`, but ray isn't cached, so axis ray runs along is tested by origin alone.
+fn cuboid_ray(half_extents: Vec3, origin: Vec3, dir: Vec3) -> Option<((f32, Vec3), (f32, Vec3))> {
+    //Ray that doesn't go anywhere hits nothing, as for sphere.
+    if dir == Vec3::ZERO {
+        return None;
+    }
+    let mut enter = (f32::NEG_INFINITY, Vec3::ZERO);
+    let mut leave = (f32::INFINITY, Vec3::ZERO);
+    for axis in 0..3 {
+        let (o, d, h) = (origin[axis], dir[axis], half_extents[axis]);
+        if d == 0. {
+            if o.abs() > h {
+                return None;
+            }
+            continue;
+        }
+        let mut normal = Vec3::ZERO;
+        normal[axis] = d.signum();
+        //Ray going along axis enters through negative face and leaves through positive one.
+        let (near, far) = ((-h * d.signum() - o) / d, (h * d.signum() - o) / d);
+        if near > enter.0 {
+            enter = (near, -normal);
+        }
+        if far < leave.0 {
+            leave = (far, normal);
+        }
+    }
+    Some((enter, leave))
+}
+
+///Distances where ray is at or above height y. Unbounded sides are infinite.
+fn above_plane_ray(height: f32, origin_y: f32, dir_y: f32) -> Option<(f32, f32)> {
+    if dir_y == 0. {
+        return (origin_y >= height).then_some((f32::NEG_INFINITY, f32::INFINITY));
+    }
+    let t = (height - origin_y) / dir_y;
+    if dir_y > 0. {
+        Some((t, f32::INFINITY))
+    } else {
+        Some((f32::NEG_INFINITY, t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    #[test]
+    fn degenerate_shapes_make_valid_aabb() {
+        let (nan, inf) = (f32::NAN, f32::INFINITY);
+        let shapes = [
+            Shape::Sphere { radius: 0. },
+            Shape::Sphere { radius: -1. },
+            Shape::Sphere { radius: inf },
+            Shape::Sphere { radius: nan },
+            Shape::CutSphere {
+                radius: 0.,
+                cut: 0.,
+            },
+            Shape::CutSphere {
+                radius: 1.,
+                cut: -1.,
+            },
+            Shape::CutSphere {
+                radius: inf,
+                cut: 0.,
+            },
+            Shape::CutSphere {
+                radius: 1.,
+                cut: nan,
+            },
+            Shape::Cuboid {
+                half_extents: Vec3::ZERO,
+            },
+            Shape::Cuboid {
+                half_extents: Vec3::new(1., 0., 1.),
+            },
+            Shape::Cuboid {
+                half_extents: Vec3::splat(inf),
+            },
+            Shape::Cuboid {
+                half_extents: Vec3::new(nan, 1., 1.),
+            },
+        ];
+        let transforms = [
+            Transform::IDENTITY,
+            Transform::from_xyz(1e6, 0., -1e6),
+            Transform::from_scale(Vec3::ZERO),
+            Transform::from_scale(Vec3::new(1., inf, 1.)),
+            Transform::from_rotation(Quat::from_rotation_y(0.7)),
+            Transform::from_xyz(nan, 0., 0.),
+        ];
+        for shape in shapes.iter() {
+            for transform in transforms.iter() {
+                let aabb = shape.aabb(transform);
+                assert!(aabb.min().is_finite() && aabb.max().is_finite());
+                assert!(
+                    aabb.min().cmplt(aabb.max()).all(),
+                    "{shape:?} {transform:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn adjacent_shapes_dont_overlap() {
+        let sphere = Shape::Sphere { radius: 0.5 };
+        let cube = Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        };
+        let at = |x: f32, y: f32, z: f32| Transform::from_xyz(x, y, z);
+        let origin = Transform::IDENTITY;
+        for shape in [&sphere, &cube] {
+            assert!(!shape.overlaps(&origin, shape, &at(1., 0., 0.)));
+            assert!(!shape.overlaps(&origin, shape, &at(0., -1., 0.)));
+            assert!(!shape.overlaps(&origin, shape, &at(1.5, 0., 0.)));
+            assert!(shape.overlaps(&origin, shape, &at(0.99, 0., 0.)));
+            assert!(shape.overlaps(&origin, shape, &origin));
+        }
+        //Cube corner reaches where sphere doesn't.
+        assert!(cube.overlaps(&origin, &cube, &at(0.99, 0.99, 0.99)));
+        assert!(!sphere.overlaps(&origin, &sphere, &at(0.99, 0.99, 0.99)));
+        //Cubes turned on grid still just touch.
+        let turned = at(0., 0., 1.).with_rotation(Quat::from_rotation_y(FRAC_PI_2));
+        assert!(!cube.overlaps(&origin, &cube, &turned));
+    }
+
+    #[test]
+    fn cut_sphere_overlaps() {
+        let sphere = Shape::Sphere { radius: 0.5 };
+        let dome = Shape::CutSphere {
+            radius: 2.5,
+            cut: 0.5,
+        };
+        let at = |x: f32, y: f32, z: f32| Transform::from_xyz(x, y, z);
+        let origin = Transform::IDENTITY;
+        assert!(dome.overlaps(&origin, &dome, &at(1., 0., 0.)));
+        assert!(!dome.overlaps(&origin, &dome, &at(0., 3., 0.)));
+        assert!(dome.overlaps(&origin, &dome, &at(0., 2.9, 0.)));
+        assert!(!dome.overlaps(&origin, &dome, &at(5., 0., 0.)));
+        assert!(dome.overlaps(&origin, &dome, &at(4.9, 0., 0.)));
+        //Below cut face, ball is cut away.
+        assert!(!dome.overlaps(&origin, &sphere, &at(0., -1., 0.)));
+        assert!(sphere.overlaps(&at(0., -0.9, 0.), &dome, &origin));
+        //Rim, where cut face meets ball.
+        let rim = Vec3::new((2.5f32 * 2.5 - 0.25).sqrt(), -0.5, 0.);
+        let out = Vec3::new(1., -1., 0.).normalize() * 0.5;
+        assert!(!dome.overlaps(
+            &origin,
+            &sphere,
+            &Transform::from_translation(rim + out * 1.01)
+        ));
+        assert!(dome.overlaps(
+            &origin,
+            &sphere,
+            &Transform::from_translation(rim + out * 0.95)
+        ));
+        //Upside down one below stands on cut face.
+        let flipped = |y: f32| at(0., y, 0.).with_rotation(Quat::from_rotation_x(PI));
+        assert!(!dome.overlaps(&origin, &dome, &flipped(-1.)));
+        assert!(dome.overlaps(&origin, &dome, &flipped(-0.9)));
+        //Scaled.
+        let scaled = |x: f32| at(x, 0., 0.).with_scale(Vec3::splat(2.));
+        assert!(!dome.overlaps(&origin, &dome, &scaled(7.5)));
+        assert!(dome.overlaps(&origin, &dome, &scaled(7.4)));
+    }
+
+    #[test]
+    fn rotated_cuboid_aabb_is_tight() {
+        let cube = Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        };
+        let turned = Transform::from_xyz(2., 3., 4.).with_rotation(Quat::from_rotation_y(PI / 4.));
+        let aabb = cube.aabb(&turned);
+        let half = Vec3::new(0.5 * 2f32.sqrt(), 0.5, 0.5 * 2f32.sqrt());
+        assert!(aabb.min().abs_diff_eq(turned.translation - half, 1e-5));
+        assert!(aabb.max().abs_diff_eq(turned.translation + half, 1e-5));
+        //Scaled box turned about other axis reaches as far as its turned corners, no farther.
+        let slab = Shape::Cuboid {
+            half_extents: Vec3::new(2., 0.25, 1.),
+        };
+        let turned = Transform::from_rotation(Quat::from_rotation_x(PI / 4.))
+            .with_scale(Vec3::new(1., 2., 1.));
+        let aabb = slab.aabb(&turned);
+        let reach = 0.5 * 2f32.sqrt() * (0.25 * 2. + 1.);
+        assert!(aabb.max().abs_diff_eq(Vec3::new(2., reach, reach), 1e-5));
+        assert!(aabb.min().abs_diff_eq(-aabb.max(), 1e-5));
+    }
+}
@@ -0,0 +1,42 @@
+use crate::physics::aabb::AABB;
+
+use bevy::math::{Mat4, Vec4};
+
+///Convex volume of six planes, such as camera view or box selection.
+///Plane is normal and distance with normal facing inside, as in bevy's `Plane::normal_d`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    ///Planes need not be normalized, but then only sign of distance is meaningful.
+    pub fn new(planes: [Vec4; 6]) -> Self {
+        Self { planes }
+    }
+
+    ///Planes of clip space of bevy's projection, which has reversed depth from 1 at near to 0 at far.
+    ///Infinite projection gets far plane that nothing is outside of.
+    pub fn from_view_projection(view_projection: &Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_projection.row(i));
+        let normalize = |plane: Vec4| {
+            let length = plane.truncate().length();
+            if length > 0. {
+                plane / length
+            } else {
+                plane
+            }
+        };
+        Self::new([w + x, w - x, w + y, w - y, w - z, z].map(normalize))
+    }
+
+    ///Whether box is entirely outside of any plane. Touching is not outside.
+    pub fn outside(&self, aabb: &AABB) -> bool {
+        self.planes.iter().any(|plane| aabb.outside_plane(*plane))
+    }
+
+    ///Whether box is entirely inside of every plane. Touching is inside.
+    pub fn contains(&self, aabb: &AABB) -> bool {
+        self.planes.iter().all(|plane| aabb.inside_plane(*plane))
+    }
+}
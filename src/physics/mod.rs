@@ -1,4 +1,5 @@
 pub mod aabb;
 pub mod collider;
+pub mod frustum;
 pub mod octree;
 pub mod ray;
@@ -2,56 +2,231 @@ use crate::physics::{
     aabb::AABB,
     collider::Collider,
     collider::Shape,
-    ray::{Ray, RayHitInfo},
+    frustum::Frustum,
+    ray::{Ray, RayHitInfo, RayTrace, TraceStep},
 };
 
-use std::{borrow::Borrow, cmp::Ordering, collections::BTreeSet};
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    collections::{BTreeSet, BinaryHeap},
+    fmt::{self, Debug, Display},
+    hash::Hash,
+    mem::size_of,
+};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+///What tree stores entries by, such as bevy `Entity` or index of non-ECS data.
+///Each entry should have its own one.
+pub trait OctreeId: Copy + Ord + Hash + Debug {}
 
-use bevy::prelude::*;
+impl<T: Copy + Ord + Hash + Debug> OctreeId for T {}
+
+///Tree of bevy entities, which is what game uses.
+pub type EntityOctree = Octree<Entity>;
+
+///Logical order of entities. Key comes first, so order doesn't depend on how entity ids are recycled.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug)]
+struct OctreeKey<T> {
+    key: u64,
+    entity: T,
+}
 
 ///Caching data for octree to prevent frequent recalculate.
 #[derive(Clone)]
-pub struct OctreeEntity {
-    entity: Entity,
+pub struct OctreeEntity<T = Entity> {
+    id: OctreeKey<T>,
     aabb: AABB,
-    shape: Shape,
-    rotation: Quat,
+    ///None for entity indexed by bound alone, whose bound stands for shape.
+    shape: Option<Shape>,
+    transform: Transform,
 }
 
-impl OctreeEntity {
-    pub fn new(entity: Entity, collider: &Collider, transform: &Transform) -> Self {
+impl<T: OctreeId> OctreeEntity<T> {
+    ///Key should be stable for same logical entity, such as order of placement.
+    ///It orders entities in nodes and breaks ties of raycast.
+    pub fn new(entity: T, key: u64, collider: &Collider, transform: &Transform) -> Self {
         Self {
-            entity,
+            id: OctreeKey { key, entity },
             aabb: collider.aabb(transform),
-            shape: collider.shape(),
-            rotation: transform.rotation,
+            shape: Some(collider.shape()),
+            transform: *transform,
+        }
+    }
+
+    ///Entity without collider, such as marker or light, indexed by precomputed bound.
+    ///Raycast and overlap treat bound as its shape.
+    pub fn from_aabb(entity: T, key: u64, aabb: AABB) -> Self {
+        Self {
+            id: OctreeKey { key, entity },
+            aabb,
+            shape: None,
+            transform: Transform::from_translation(aabb.center()),
         }
     }
+
+    pub fn entity(&self) -> T {
+        self.id.entity
+    }
+
+    pub fn aabb(&self) -> AABB {
+        self.aabb
+    }
 }
 
-impl Eq for OctreeEntity {}
+impl<T: OctreeId> Eq for OctreeEntity<T> {}
 
-impl PartialEq for OctreeEntity {
+impl<T: OctreeId> PartialEq for OctreeEntity<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.entity.eq(&other.entity)
+        self.id.eq(&other.id)
     }
 }
 
-impl PartialOrd for OctreeEntity {
+impl<T: OctreeId> PartialOrd for OctreeEntity<T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.entity.partial_cmp(&other.entity)
+        Some(self.cmp(other))
     }
 }
 
-impl Ord for OctreeEntity {
+impl<T: OctreeId> Ord for OctreeEntity<T> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.entity.cmp(&other.entity)
+        self.id.cmp(&other.id)
+    }
+}
+
+impl<T: OctreeId> OctreeEntity<T> {
+    fn hit(&self, ray: &Ray, t: f32, normal: Vec3) -> RayHitInfo<T> {
+        RayHitInfo::new(self.id.entity, self.id.key, self.aabb, ray, t, normal)
+    }
+
+    ///Distance to where ray hits shape and normal there. Bound is only broad phase before shape.
+    fn shape_ray(&self, ray: &Ray) -> Option<(f32, Vec3)> {
+        match &self.shape {
+            Some(shape) => {
+                self.aabb.intersects_ray_raw(ray)?;
+                shape.intersects_ray_normal(&self.transform, ray)
+            }
+            None => self.aabb.intersects_ray_normal(ray),
+        }
+    }
+
+    fn intersects_ray(&self, ray: &Ray) -> Option<RayHitInfo<T>> {
+        let (t, normal) = self.shape_ray(ray)?;
+        Some(self.hit(ray, t, normal))
+    }
+
+    ///Distance to hit, normal there and whether it is seen from behind,
+    ///which only happens when ray starts inside.
+    fn ray_test(&self, ray: &Ray, test: RayTest) -> Option<(f32, Vec3, bool)> {
+        let (t, normal) = if test.exact {
+            self.shape_ray(ray)?
+        } else {
+            self.aabb.intersects_ray_normal(ray)?
+        };
+        //Only face ray leaves through looks along it.
+        Some((t, normal, normal.dot(ray.dir()) > 0.))
+    }
+}
+
+impl<T> Borrow<OctreeKey<T>> for OctreeEntity<T> {
+    fn borrow(&self) -> &OctreeKey<T> {
+        &self.id
+    }
+}
+
+///Describes what insertion did to the tree.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum InsertOutcome {
+    ///Entity didn't exist and is newly inserted.
+    Inserted,
+    ///Entity already exists with same bound. Nothing changed.
+    AlreadyPresent,
+    ///Entity already existed with different bound. Old entry is replaced by new one.
+    Updated,
+    ///Entity collides with another one on a node that can't be split anymore. Not inserted.
+    TooDeep,
+    ///Entity isn't inside base bound of bounded tree. Not inserted.
+    OutOfBounds,
+}
+
+///Shape of tree at a moment, for debugging.
+#[derive(Clone, Debug, Default)]
+pub struct OctreeStats {
+    pub entities: usize,
+    ///Nodes reachable from root.
+    pub nodes: usize,
+    ///Nodes in pool waiting to be recycled.
+    pub idle_nodes: usize,
+    ///Slots of child blocks that hold no node. Memory paid for contiguous siblings.
+    pub reserved_nodes: usize,
+    pub leaves: usize,
+    ///Entities kept out of nodes for being too large.
+    pub oversize: usize,
+    ///Root is depth 0.
+    pub max_depth: usize,
+    ///Number of nodes indexed by how many entities they hold.
+    pub occupancy: Vec<usize>,
+}
+
+///What tree did since it is made or counters are reset. Kept by tree itself, so reading is free.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct OctreeCounters {
+    pub inserts: usize,
+    pub removes: usize,
+    ///Nodes created under parent.
+    pub splits: usize,
+    ///Nodes idled out of parent.
+    pub merges: usize,
+    ///Roots added above old one to cover entity out of base.
+    pub extends: usize,
+    ///Deepest node entity was placed in. Root is depth 0.
+    pub max_depth: usize,
+}
+
+impl Display for OctreeCounters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "inserts {}, removes {}, splits {}, merges {}, extends {}, max depth {}",
+            self.inserts, self.removes, self.splits, self.merges, self.extends, self.max_depth
+        )
     }
 }
 
-impl Borrow<Entity> for OctreeEntity {
-    fn borrow(&self) -> &Entity {
-        &self.entity
+///Node a point falls into.
+#[derive(Clone, Copy, Debug)]
+pub struct LeafInfo {
+    pub index: usize,
+    ///Root is depth 0.
+    pub depth: usize,
+    pub aabb: AABB,
+    ///Entities held by node itself.
+    pub entities: usize,
+}
+
+impl Display for OctreeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "entities {} (oversize {}), nodes {} (idle {}, reserved {}), leaves {}, max depth {}, occupancy",
+            self.entities,
+            self.oversize,
+            self.nodes,
+            self.idle_nodes,
+            self.reserved_nodes,
+            self.leaves,
+            self.max_depth
+        )?;
+        for (count, nodes) in self.occupancy.iter().enumerate() {
+            if *nodes > 0 {
+                write!(f, " {count}:{nodes}")?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -61,36 +236,69 @@ impl Borrow<Entity> for OctreeEntity {
 /// - This guarantees entity is on only one leaf.
 /// - A leaf could have entities itself while having children.
 /// - This has node pool that Empty leaf could be recycled.
+/// - Children of a node are allocated as a block of 8 adjacent slots, so siblings are walked in order.
+///   Root always lives in slot 0.
+/// - Entity larger than a fraction of base bound on any axis never fits an octant and would pile up near root.
+///   Such one is kept in flat oversize set instead, which every query tests after cheap bound reject.
+/// - Entities are stored by id of type T, which is bevy `Entity` by default. Any `OctreeId` works,
+///   such as index of navmesh node. Constructors need type named, like `EntityOctree::new`.
 #[derive(Component)]
-pub struct Octree {
+pub struct Octree<T = Entity> {
     ///Index of root node from pool.
     root: usize,
     ///Base aabb for creating root node.
     base_aabb: AABB,
+    ///Base aabb tree is constructed with. Base grows or moves, and clear restores this.
+    initial_aabb: AABB,
     ///Kinda node pool
-    nodes: Vec<OctreeNode>,
+    nodes: Vec<OctreeNode<T>>,
     ///Min leaf size to prevent too deep nodes.
     min_leaf_extent: Vec3,
-    ///Index of idle root node from pool.
+    ///First slot of idle child block from pool. Blocks are linked through parent of first slot.
     idle: usize,
+    ///Index of node that each entity is stored in, and its key. `OVERSIZE_INDEX` if it is in oversize set.
+    ///Key is needed to find entity in node, as nodes are ordered by it.
+    indices: HashMap<T, (usize, u64)>,
+    ///Entities too large for nodes. Few, so it is a plain list.
+    oversize: Vec<OctreeEntity<T>>,
+    ///Entity is oversize when any axis of its bound is longer than this fraction of base bound.
+    oversize_fraction: f32,
+    ///Entity out of base bound is refused instead of extending tree.
+    bounded: bool,
+    ///Structure is validated after every mutation in debug build.
+    validate: bool,
     len: usize,
+    ///Nodes not in idle pool.
+    node_count: usize,
+    counters: OctreeCounters,
 }
 
-impl Octree {
+impl<T: OctreeId> Octree<T> {
     const NULL_INDEX: usize = usize::MAX;
+    ///Index of entity in oversize set. Never a node, as pool can't grow that large.
+    const OVERSIZE_INDEX: usize = usize::MAX - 1;
+    pub const DEFAULT_OVERSIZE_FRACTION: f32 = 0.25;
 
     pub fn new(capacity: usize, min_leaf_extent: Vec3, aabb: AABB) -> Self {
         Self {
             root: Self::NULL_INDEX,
             base_aabb: aabb,
+            initial_aabb: aabb,
             nodes: Vec::with_capacity(capacity),
             min_leaf_extent,
             idle: Self::NULL_INDEX,
+            indices: HashMap::default(),
+            oversize: Vec::new(),
+            oversize_fraction: Self::DEFAULT_OVERSIZE_FRACTION,
+            bounded: false,
+            validate: false,
             len: 0,
+            node_count: 0,
+            counters: OctreeCounters::default(),
         }
     }
 
-    pub fn from_size_offset(
+    pub fn _from_size_offset(
         capacity: usize,
         min_leaf_extent: Vec3,
         size: f32,
@@ -103,102 +311,653 @@ impl Octree {
         )
     }
 
+    ///Tree whose base is `blueprint_base` of given bound, so both come from one place.
+    pub fn for_blueprint(capacity: usize, min_leaf_extent: Vec3, bound: &AABB) -> Self {
+        Self::new(capacity, min_leaf_extent, Self::blueprint_base(bound))
+    }
+
+    ///Cube that covers blueprint bound, with side of power of two rounded up from longest axis.
+    ///Cube is anchored at min of bound, so margin is only past max and nodes split on cell bounds.
+    pub fn blueprint_base(bound: &AABB) -> AABB {
+        let side = (bound.length().max_element().ceil() as u32).next_power_of_two() as f32;
+        AABB::new(bound.min(), bound.min() + side)
+    }
+
+    ///Whether base is what `blueprint_base` derives from bound. Err has base it should be.
+    pub fn check_blueprint_base(&self, bound: &AABB) -> Result<(), AABB> {
+        let expected = Self::blueprint_base(bound);
+        if self.base_aabb == expected {
+            Ok(())
+        } else {
+            Err(expected)
+        }
+    }
+
+    ///Bounded tree refuses entity out of base bound with `OutOfBounds`, such as for fixed build area.
+    ///Otherwise tree grows to cover it, which is default.
+    pub fn bounded(mut self, bounded: bool) -> Self {
+        self.bounded = bounded;
+        self
+    }
+
+    ///Number of entities.
     pub fn len(&self) -> usize {
         self.len
     }
 
+    ///Number of live nodes, excluding idle ones. Cheap unlike `stats`.
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    ///Nodes waiting in idle pool. Walks idle blocks only.
+    pub fn idle_nodes(&self) -> usize {
+        let mut blocks = 0;
+        let mut base = self.idle;
+        while base != Self::NULL_INDEX {
+            blocks += 1;
+            base = self.nodes[base].parent;
+        }
+        blocks * 8
+    }
+
+    pub fn counters(&self) -> &OctreeCounters {
+        &self.counters
+    }
+
+    pub fn reset_counters(&mut self) {
+        self.counters = OctreeCounters::default();
+    }
+
     ///If node and its leaves entirely empty.
     pub fn _is_empty(&self) -> bool {
         self.len == 0
     }
 
     ///Root node aabb.
-    pub fn _base_aabb(&self) -> &AABB {
+    pub fn base_aabb(&self) -> &AABB {
         &self.base_aabb
     }
 
-    ///Create a node or find and set a idle node.
-    fn get_or_create_node(&mut self, aabb: AABB, parent: usize) -> usize {
-        if self.idle == Self::NULL_INDEX {
-            //Create a node if there is no idle node.
-            self.nodes.push(OctreeNode::new(aabb, parent));
-            return self.nodes.len() - 1;
-        }
-        //Get and set idle node.
-        let index = self.idle;
-        let node = &mut self.nodes[self.idle];
-        self.idle = node.parent;
+    ///Nodes are not split below this.
+    pub fn min_leaf_extent(&self) -> Vec3 {
+        self.min_leaf_extent
+    }
+
+    ///Number of entities in oversize set.
+    pub fn oversize_len(&self) -> usize {
+        self.oversize.len()
+    }
+
+    pub fn oversize_fraction(&self) -> f32 {
+        self.oversize_fraction
+    }
+
+    ///Takes effect on entities inserted later. Rebuild to reroute existing ones.
+    pub fn set_oversize_fraction(&mut self, fraction: f32) {
+        self.oversize_fraction = fraction;
+    }
+
+    ///Turns on `debug_validate` after every mutation, which panics on first broken invariant.
+    ///Does nothing in release build.
+    pub fn set_validate(&mut self, validate: bool) {
+        self.validate = validate;
+    }
+
+    ///Panics when validation is on and structure is broken. Passes return of mutation through.
+    fn checked<R>(&self, ret: R) -> R {
+        if cfg!(debug_assertions) && self.validate {
+            if let Err(e) = self.debug_validate() {
+                panic!("Octree is broken: {e}");
+            }
+        }
+        ret
+    }
+
+    ///Walks whole tree and checks that nodes, entities and indices agree. Return is first broken one.
+    ///Slow, so it is for debugging.
+    pub fn debug_validate(&self) -> Result<(), String> {
+        //Idle chain is walked first, as a cycle in it would hang allocation.
+        let mut idle = HashSet::new();
+        let mut base = self.idle;
+        while base != Self::NULL_INDEX {
+            if base >= self.nodes.len() {
+                return Err(format!("idle block {base} is out of pool"));
+            }
+            if !idle.insert(base) {
+                return Err(format!("idle chain loops at block {base}"));
+            }
+            base = self.nodes[base].parent;
+        }
+        let mut entities = HashSet::new();
+        let mut node_count = 0;
+        if self.root != Self::NULL_INDEX {
+            if self.nodes[self.root].parent != Self::NULL_INDEX {
+                return Err(format!("root {} has parent", self.root));
+            }
+            let mut stack = vec![self.root];
+            while let Some(index) = stack.pop() {
+                node_count += 1;
+                let node = &self.nodes[index];
+                if node.entities.is_empty() && node.children_len() == 0 {
+                    return Err(format!("node {index} is empty leaf"));
+                }
+                if (node.children_base == Self::NULL_INDEX) != (node.children_mask == 0) {
+                    return Err(format!("node {index} has children mask without block"));
+                }
+                if idle.contains(&node.children_base) {
+                    return Err(format!("node {index} has idle block as children"));
+                }
+                for entity in node.entities.iter() {
+                    if !entities.insert(entity.id.entity) {
+                        return Err(format!("{:?} is stored twice", entity.id.entity));
+                    }
+                    if self.indices.get(&entity.id.entity) != Some(&(index, entity.id.key)) {
+                        return Err(format!(
+                            "{:?} is in node {index} but indexed elsewhere",
+                            entity.id.entity
+                        ));
+                    }
+                    if !node.aabb.contains(&entity.aabb) {
+                        return Err(format!("{:?} is out of node {index}", entity.id.entity));
+                    }
+                }
+                for child in node.children() {
+                    if child >= self.nodes.len() {
+                        return Err(format!("child {child} of node {index} is out of pool"));
+                    }
+                    if self.nodes[child].parent != index {
+                        return Err(format!("child {child} doesn't point back to node {index}"));
+                    }
+                    stack.push(child);
+                }
+            }
+        }
+        for entity in self.oversize.iter() {
+            if !entities.insert(entity.id.entity) {
+                return Err(format!("{:?} is stored twice", entity.id.entity));
+            }
+            if self.indices.get(&entity.id.entity) != Some(&(Self::OVERSIZE_INDEX, entity.id.key)) {
+                return Err(format!(
+                    "{:?} is oversize but indexed elsewhere",
+                    entity.id.entity
+                ));
+            }
+        }
+        if entities.len() != self.len || self.indices.len() != self.len {
+            return Err(format!(
+                "len is {} but {} entities are reachable and {} indexed",
+                self.len,
+                entities.len(),
+                self.indices.len()
+            ));
+        }
+        if node_count != self.node_count {
+            return Err(format!(
+                "node count is {} but {node_count} nodes are reachable",
+                self.node_count
+            ));
+        }
+        Ok(())
+    }
+
+    ///Whether entity of given bound goes to oversize set.
+    fn is_oversize(&self, aabb: &AABB) -> bool {
+        aabb.length()
+            .cmpgt(self.base_aabb.length() * self.oversize_fraction)
+            .any()
+    }
+
+    ///Moves whole tree rigidly by offset. Nodes keep their structure.
+    ///Cheaper than rebuild when everything moves together, such as recentering build area.
+    pub fn translate(&mut self, offset: Vec3) {
+        self.base_aabb = self.base_aabb + offset;
+        for entity in self.oversize.iter_mut() {
+            entity.aabb = entity.aabb + offset;
+            entity.transform.translation += offset;
+        }
+        for node in self.nodes.iter_mut() {
+            node.aabb = node.aabb + offset;
+            //Shift doesn't change order, so set could be rebuilt from sorted iterator.
+            node.entities = std::mem::take(&mut node.entities)
+                .into_iter()
+                .map(|mut entity| {
+                    entity.aabb = entity.aabb + offset;
+                    entity.transform.translation += offset;
+                    entity
+                })
+                .collect();
+        }
+        self.checked(())
+    }
+
+    ///Removes every entity and restores base bound tree is constructed with.
+    ///Node pool keeps its allocation, so refilling tree doesn't allocate again up to previous size.
+    ///Counters aren't reset, as clearing is part of what tree did. Use `reset_counters` for that.
+    pub fn clear(&mut self) {
+        self.root = Self::NULL_INDEX;
+        self.idle = Self::NULL_INDEX;
+        self.base_aabb = self.initial_aabb;
+        self.nodes.clear();
+        self.indices.clear();
+        self.oversize.clear();
+        self.len = 0;
+        self.node_count = 0;
+        self.checked(())
+    }
+
+    ///Empty tree that has same settings and bound.
+    pub fn empty_like(&self) -> Self {
+        let mut ret = Self::new(self.nodes.len(), self.min_leaf_extent, self.base_aabb);
+        ret.oversize_fraction = self.oversize_fraction;
+        ret.bounded = self.bounded;
+        ret.validate = self.validate;
+        ret
+    }
+
+    pub fn stats(&self) -> OctreeStats {
+        let mut stats = OctreeStats {
+            entities: self.len,
+            oversize: self.oversize.len(),
+            ..default()
+        };
+        for node in self.nodes() {
+            stats.nodes += 1;
+            stats.max_depth = stats.max_depth.max(node.depth());
+            if node.is_leaf() {
+                stats.leaves += 1;
+            } else {
+                stats.reserved_nodes += 8 - node.children().count();
+            }
+            let count = node.entity_count();
+            if stats.occupancy.len() <= count {
+                stats.occupancy.resize(count + 1, 0);
+            }
+            stats.occupancy[count] += 1;
+        }
+        stats.idle_nodes = self.nodes.len() - stats.nodes - stats.reserved_nodes;
+        stats
+    }
+
+    ///Nodes reachable from root, each before its children. Idle and reserved slots are skipped.
+    pub fn nodes(&self) -> impl Iterator<Item = OctreeNodeRef<'_, T>> {
+        let mut stack = Vec::new();
+        if self.root != Self::NULL_INDEX {
+            stack.push(OctreeNodeRef {
+                octree: self,
+                index: self.root,
+                depth: 0,
+            });
+        }
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            //Reversed, so children come out in octant order.
+            let mut children: Vec<_> = node.children().collect();
+            children.reverse();
+            stack.extend(children);
+            Some(node)
+        })
+    }
+
+    ///Estimated heap bytes owned by tree. Overhead of set and map internals is approximated.
+    pub fn memory_usage(&self) -> usize {
+        let nodes = self.nodes.capacity() * size_of::<OctreeNode<T>>();
+        let entities = self.len * size_of::<OctreeEntity<T>>();
+        //Hash map keeps a control byte per bucket.
+        let indices = self.indices.capacity() * (size_of::<(T, (usize, u64))>() + 1);
+        nodes + entities + indices
+    }
+
+    ///Grows node pool up front, so bulk insertion doesn't reallocate mid way.
+    pub fn reserve(&mut self, additional_nodes: usize) {
+        self.nodes.reserve(additional_nodes);
+    }
+
+    ///Rough upper bound of nodes that inserting entities could create.
+    ///Each entity creates at most one child block per level, but tree can't have more nodes than full one.
+    pub fn nodes_for(&self, entities: usize) -> usize {
+        let ratio = (self.base_aabb.length() / self.min_leaf_extent).max_element();
+        let depth = if ratio > 1. {
+            ratio.log2().ceil() as u32 + 1
+        } else {
+            1
+        };
+        //Nodes of full tree, which is 1 + 8 + 64 + ... per level.
+        let full = (0..depth)
+            .map(|level| 8usize.saturating_pow(level))
+            .fold(0usize, usize::saturating_add);
+        entities
+            .saturating_mul(depth as usize)
+            .saturating_mul(8)
+            .min(full)
+    }
+
+    ///Inserts entities after reserving for them.
+    ///Return is entities that are not newly inserted and why.
+    pub fn _insert_many(
+        &mut self,
+        entities: impl IntoIterator<Item = OctreeEntity<T>>,
+    ) -> Vec<(T, InsertOutcome)> {
+        let entities = entities.into_iter();
+        let (count, _) = entities.size_hint();
+        self.reserve(self.nodes_for(count));
+        self.indices.reserve(count);
+        entities
+            .filter_map(|entity| {
+                let id = entity.id.entity;
+                match self.insert_result(entity) {
+                    InsertOutcome::Inserted => None,
+                    outcome => Some((id, outcome)),
+                }
+            })
+            .collect()
+    }
+
+    ///Create a node in slot of parent's child block, or in root slot when there is no parent.
+    ///Block is taken from idle ones or created when parent has none yet.
+    fn get_or_create_node(&mut self, aabb: AABB, parent: usize, octant_index: usize) -> usize {
+        self.node_count += 1;
+        let index = if parent == Self::NULL_INDEX {
+            if self.nodes.is_empty() {
+                self.nodes.push(OctreeNode::new(aabb, parent));
+            }
+            0
+        } else {
+            if self.nodes[parent].children_base == Self::NULL_INDEX {
+                self.nodes[parent].children_base = self.get_or_create_block();
+            }
+            let parent = &mut self.nodes[parent];
+            parent.children_mask |= 1 << octant_index;
+            parent.children_base + octant_index
+        };
+        let node = &mut self.nodes[index];
         node.aabb = aabb;
         node.parent = parent;
         index
     }
 
-    ///Idles empty node.
+    ///First slot of block of 8 free slots.
+    fn get_or_create_block(&mut self) -> usize {
+        if self.idle == Self::NULL_INDEX {
+            let base = self.nodes.len();
+            self.nodes
+                .extend((0..8).map(|_| OctreeNode::new(self.base_aabb, Self::NULL_INDEX)));
+            return base;
+        }
+        let base = self.idle;
+        self.idle = self.nodes[base].parent;
+        base
+    }
+
+    ///Idles empty node. Its parent's block is idled too when no sibling is left.
     ///Note: It doesn't idle empty parent node too.
     fn idles_node(&mut self, index: usize, octant_index: usize) {
         let parent_index = self.nodes[index].parent;
+        self.nodes[index].parent = Self::NULL_INDEX;
         if parent_index != Self::NULL_INDEX {
             //Remove children from parent.
             let parent = &mut self.nodes[parent_index];
-            parent.children[octant_index] = Self::NULL_INDEX;
-            parent.children_len -= 1;
+            parent.children_mask &= !(1 << octant_index);
+            if parent.children_mask == 0 {
+                let base = parent.children_base;
+                parent.children_base = Self::NULL_INDEX;
+                self.nodes[base].parent = self.idle;
+                self.idle = base;
+            }
+            self.counters.merges += 1;
         } else {
             //No nodes left.
             self.root = Self::NULL_INDEX;
         }
-        self.nodes[index].parent = self.idle;
-        self.idle = index;
+        self.node_count -= 1;
+    }
+
+    ///Return is whether entity doesn't already exist. See also `insert_result`.
+    pub fn insert(&mut self, entity: OctreeEntity<T>) -> bool {
+        self.insert_result(entity) == InsertOutcome::Inserted
     }
 
-    ///Return is whether entity doesn't already exist.
-    pub fn insert(&mut self, entity: OctreeEntity) -> bool {
+    ///Inserts entity. If entity already exists with different bound, old entry is replaced.
+    ///Return describes what happened to give a reason on failure.
+    pub fn insert_result(&mut self, entity: OctreeEntity<T>) -> InsertOutcome {
+        let previous = match self.indices.get(&entity.id.entity) {
+            Some(&(index, key)) => {
+                let stored = if index == Self::OVERSIZE_INDEX {
+                    self.oversize
+                        .iter()
+                        .find(|stored| stored.id.entity == entity.id.entity)
+                } else {
+                    self.nodes[index].entities.get(&OctreeKey {
+                        key,
+                        entity: entity.id.entity,
+                    })
+                }
+                .expect("Index should point node that has entity")
+                .clone();
+                if stored.aabb == entity.aabb {
+                    return InsertOutcome::AlreadyPresent;
+                }
+                //Bound is changed, so old entry could be on wrong node.
+                self.remove_at(index, &stored.id);
+                Some(stored)
+            }
+            None => None,
+        };
+        let outcome = match self.insert_new(entity) {
+            Ok(()) if previous.is_some() => InsertOutcome::Updated,
+            Ok(()) => InsertOutcome::Inserted,
+            Err(outcome) => {
+                //Restore old entry on failure.
+                if let Some(previous) = previous {
+                    let _ = self.insert_new(previous);
+                }
+                outcome
+            }
+        };
+        self.checked(outcome)
+    }
+
+    ///Inserts entity which is guaranteed not to be in tree.
+    fn insert_new(&mut self, entity: OctreeEntity<T>) -> Result<(), InsertOutcome> {
+        if self.bounded && !self.base_aabb.contains(&entity.aabb) {
+            return Err(InsertOutcome::OutOfBounds);
+        }
+        //Oversize entity doesn't extend tree either, so it can't blow up root.
+        if self.is_oversize(&entity.aabb) {
+            self.indices
+                .insert(entity.id.entity, (Self::OVERSIZE_INDEX, entity.id.key));
+            self.oversize.push(entity);
+            self.len += 1;
+            self.counters.inserts += 1;
+            return Ok(());
+        }
         self.try_extend(&entity.aabb);
-        let mut index = self.root;
+        self.place_from(entity, self.root)?;
+        self.len += 1;
+        self.counters.inserts += 1;
+        Ok(())
+    }
+
+    ///Puts entity into deepest node it fits, going down from start. Root is created when start is null.
+    ///Start node should contain entity. Only node and index are touched, not len.
+    fn place_from(
+        &mut self,
+        entity: OctreeEntity<T>,
+        start: usize,
+    ) -> Result<usize, InsertOutcome> {
+        let mut index = start;
         let mut parent_index = Self::NULL_INDEX;
         let mut octant_index = Self::NULL_INDEX;
         let mut node_aabb = self.base_aabb;
-        let ret;
         loop {
             if index == Self::NULL_INDEX {
                 //Prevent tree to have too deep node.
                 if self.min_leaf_extent.cmpgt(node_aabb.length()).any() {
-                    ret = self.nodes[parent_index].entities.insert(entity);
+                    //Entity can't be separated from what parent already has.
+                    let parent = &self.nodes[parent_index];
+                    if parent
+                        .entities
+                        .iter()
+                        .any(|other| other.aabb._intersects(&entity.aabb))
+                    {
+                        self.idles_empty(parent_index);
+                        return Err(InsertOutcome::TooDeep);
+                    }
+                    index = parent_index;
                     break;
                 }
                 //When there is no next node, add new node into tree.
-                index = self.get_or_create_node(node_aabb, parent_index);
+                index = self.get_or_create_node(node_aabb, parent_index, octant_index);
                 if parent_index == Self::NULL_INDEX {
                     self.root = index;
                 } else {
-                    //If there was parent, add child to it.
-                    println!("split");
-                    let parent = &mut self.nodes[parent_index];
-                    parent.children_len += 1;
-                    parent.children[octant_index] = index;
+                    self.counters.splits += 1;
                 }
             }
             let node = &mut self.nodes[index];
             //Whether entity is fit in node's arbitrary octant.
-            match (entity.aabb - node.aabb.center()).octant() {
+            match entity.aabb.octant_about(node.aabb.center()) {
                 Some(octant) => {
                     //Determine octant of child.
                     parent_index = index;
-                    octant_index = OctreeNode::octant_to_index(octant);
+                    octant_index = OctreeNode::<T>::octant_to_index(octant);
                     node_aabb = node.aabb.get_octant(octant);
-                    index = node.children[octant_index];
-                }
-                None => {
-                    //Put directly to current node.
-                    ret = node.entities.insert(entity);
-                    break;
+                    index = node.child(octant_index);
                 }
+                //Put directly to current node.
+                None => break,
             };
         }
-        if ret {
-            self.len += 1;
+        self.indices
+            .insert(entity.id.entity, (index, entity.id.key));
+        self.nodes[index].entities.insert(entity);
+        //Start isn't always root, so depth is counted back up.
+        let mut depth = 0;
+        let mut ancestor = self.nodes[index].parent;
+        while ancestor != Self::NULL_INDEX {
+            depth += 1;
+            ancestor = self.nodes[ancestor].parent;
+        }
+        self.counters.max_depth = self.counters.max_depth.max(depth);
+        Ok(index)
+    }
+
+    ///Moves entity from old bound to new one. Return is whether entity existed and is moved.
+    ///Entity that still fits its node is swapped in place. Otherwise it is placed again
+    ///from deepest node that contains both old and new bound, instead of from root.
+    ///When new bound is refused, entity is left at old bound.
+    pub fn update(&mut self, entity: T, old_aabb: AABB, new: OctreeEntity<T>) -> bool {
+        debug_assert_eq!(entity, new.id.entity, "Updated entity should be same one");
+        let (index, key) = match self.indices.get(&entity) {
+            Some(&slot) => slot,
+            None => return false,
+        };
+        //Bound grown out of tree or crossing oversize threshold needs to go through root.
+        if index == Self::OVERSIZE_INDEX
+            || self.is_oversize(&new.aabb)
+            || !self.base_aabb.contains(&new.aabb)
+        {
+            return matches!(
+                self.insert_result(new),
+                InsertOutcome::Updated | InsertOutcome::AlreadyPresent
+            );
+        }
+        let node = &mut self.nodes[index];
+        let old = node
+            .entities
+            .take(&OctreeKey { key, entity })
+            .expect("Index should point node that has entity");
+        if node.aabb.contains(&new.aabb) && new.aabb.octant_about(node.aabb.center()).is_none() {
+            self.indices.insert(entity, (index, new.id.key));
+            node.entities.insert(new);
+            return self.checked(true);
+        }
+        //Node itself could still hold new bound, going into its child.
+        let span = old_aabb.union(&new.aabb);
+        let mut ancestor = index;
+        while ancestor != Self::NULL_INDEX && !self.nodes[ancestor].aabb.contains(&span) {
+            ancestor = self.nodes[ancestor].parent;
+        }
+        //Root always contains what base bound contains.
+        let start = if ancestor == Self::NULL_INDEX {
+            self.root
+        } else {
+            ancestor
+        };
+        let moved = match self.place_from(new, start) {
+            Ok(placed) => {
+                if placed != index {
+                    self.idles_empty(index);
+                }
+                true
+            }
+            Err(_) => {
+                //Failed placement could idle emptied nodes on its way, so old one goes from root.
+                let _ = self.place_from(old, self.root);
+                false
+            }
+        };
+        self.checked(moved)
+    }
+
+    ///Octant index of node in its parent's children.
+    fn octant_index_of(&self, index: usize) -> usize {
+        let parent_index = self.nodes[index].parent;
+        if parent_index == Self::NULL_INDEX {
+            Self::NULL_INDEX
+        } else {
+            index - self.nodes[parent_index].children_base
+        }
+    }
+
+    ///Idles node and its ancestors while they are totally empty.
+    fn idles_empty(&mut self, mut index: usize) {
+        while index != Self::NULL_INDEX {
+            let node = &self.nodes[index];
+            if !node.entities.is_empty() || node.children_len() != 0 {
+                break;
+            }
+            let parent_index = node.parent;
+            self.idles_node(index, self.octant_index_of(index));
+            index = parent_index;
+        }
+    }
+
+    ///Removes entity from node of given index and idles node if it is totally empty.
+    fn remove_at(&mut self, index: usize, id: &OctreeKey<T>) -> bool {
+        if index == Self::OVERSIZE_INDEX {
+            return self.remove_oversize(&id.entity);
+        }
+        let node = &mut self.nodes[index];
+        if !node.entities.remove(id) {
+            return false;
+        }
+        self.indices.remove(&id.entity);
+        self.len -= 1;
+        self.counters.removes += 1;
+        if node.entities.is_empty() && node.children_len() == 0 {
+            //Ancestors left empty go too, so removing by index doesn't leave dead branch.
+            self.idles_empty(index);
+        }
+        true
+    }
+
+    ///Removes entity from oversize set.
+    fn remove_oversize(&mut self, entity: &T) -> bool {
+        match self
+            .oversize
+            .iter()
+            .position(|stored| stored.id.entity == *entity)
+        {
+            Some(position) => {
+                self.oversize.swap_remove(position);
+                self.indices.remove(entity);
+                self.len -= 1;
+                self.counters.removes += 1;
+                true
+            }
+            None => false,
         }
-        println!("counts {}", self.len());
-        ret
     }
 
     ///Extend above root to cover given aabb.
@@ -207,76 +966,189 @@ impl Octree {
             self.base_aabb = self.base_aabb.extend(aabb);
         } else {
             self.base_aabb.extend_for(aabb, |aabb| {
-                println!("extend");
-                let index = self.get_or_create_node(aabb, Self::NULL_INDEX);
-                let octant = (self.nodes[self.root].aabb - self.nodes[index].aabb.center())
-                    .octant()
+                self.counters.extends += 1;
+                let octant = self.nodes[self.root]
+                    .aabb
+                    .octant_about(aabb.center())
                     .expect("Maybe float point precision problem");
-                self.nodes[self.root].parent = index;
-                let parent = &mut self.nodes[index];
-                parent.children_len += 1;
-                parent.children[OctreeNode::octant_to_index(octant)] = self.root;
+                let octant_index = OctreeNode::<T>::octant_to_index(octant);
+                //Old root moves into new root's block, and new root takes root slot.
+                let base = self.get_or_create_block();
+                let slot = base + octant_index;
+                let mut new_root = OctreeNode::new(aabb, Self::NULL_INDEX);
+                new_root.children_base = base;
+                new_root.children_mask = 1 << octant_index;
+                let mut old_root = std::mem::replace(&mut self.nodes[self.root], new_root);
+                old_root.parent = self.root;
+                for child in old_root.children() {
+                    self.nodes[child].parent = slot;
+                }
+                for entity in old_root.entities.iter() {
+                    self.indices.insert(entity.id.entity, (slot, entity.id.key));
+                }
+                self.nodes[slot] = old_root;
+                self.node_count += 1;
                 self.base_aabb = aabb;
-                self.root = index;
             });
         }
     }
 
+    ///Inserts every entity of other tree.
+    ///Return is entities that are not inserted and why. Entity that already exists is kept as is.
+    pub fn merge(&mut self, other: Self) -> Vec<(T, InsertOutcome)> {
+        let mut conflicts = Vec::new();
+        self.reserve(self.nodes_for(other.len()));
+        self.indices.reserve(other.len());
+        //Idle nodes have no entities, so whole pool could be walked.
+        let entities = other
+            .nodes
+            .into_iter()
+            .flat_map(|node| node.entities.into_iter())
+            .chain(other.oversize.into_iter());
+        for entity in entities {
+            let id = entity.id.entity;
+            if self.indices.contains_key(&id) {
+                conflicts.push((id, InsertOutcome::AlreadyPresent));
+            } else if let Err(outcome) = self.insert_new(entity) {
+                conflicts.push((id, outcome));
+            }
+        }
+        self.checked(conflicts)
+    }
+
+    ///Every entity in tree once, oversize ones included. Order follows node pool, not space.
+    pub fn iter(&self) -> impl Iterator<Item = &OctreeEntity<T>> {
+        //Idle nodes and free slots of blocks hold no entities, so whole pool could be walked.
+        self.oversize
+            .iter()
+            .chain(self.nodes.iter().flat_map(|node| node.entities.iter()))
+    }
+
     ///Return is whether existed entity is removed.
-    pub fn remove(&mut self, entity: Entity, aabb: AABB) -> bool {
+    ///Bound is only for walking down to entity. If it differs from stored one and walk misses,
+    ///entity is removed through its index as `remove_entity` does.
+    pub fn remove(&mut self, entity: T, aabb: AABB) -> bool {
+        let id = match self.indices.get(&entity) {
+            Some(&(Self::OVERSIZE_INDEX, _)) => {
+                let removed = self.remove_oversize(&entity);
+                return self.checked(removed);
+            }
+            Some(&(_, key)) => OctreeKey { key, entity },
+            None => return false,
+        };
         let mut index = self.root;
-        let mut octant_index = Self::NULL_INDEX;
         let mut ret = false;
         //Stops when tree traversal met dead end.
         while index != Self::NULL_INDEX {
             let node = &mut self.nodes[index];
-            if node.children_len == 0 {
+            if node.children_len() == 0 {
                 //When node has no child.
-                ret = node.entities.remove(&entity);
+                ret = node.entities.remove(&id);
                 if node.entities.is_empty() {
-                    //Makes node idle when it is totally empty.
-                    self.idles_node(index, octant_index);
-                    println!("unsplit");
+                    //Makes node idle when it is totally empty, and ancestors left empty too.
+                    self.idles_empty(index);
                 }
                 break;
             } else {
                 //Whether entity is fit in node's arbitrary octant.
-                match (aabb - node.aabb.center()).octant() {
-                    Some(octant) => {
-                        octant_index = OctreeNode::octant_to_index(octant);
-                        index = node.children[octant_index];
-                    }
+                match aabb.octant_about(node.aabb.center()) {
+                    Some(octant) => index = node.get_child_index(octant),
                     None => {
-                        ret = node.entities.remove(&entity);
+                        ret = node.entities.remove(&id);
                         break;
                     }
                 }
             }
         }
-        if ret {
-            self.len -= 1;
+        if !ret {
+            return self.remove_entity(entity);
+        }
+        self.indices.remove(&entity);
+        self.len -= 1;
+        self.counters.removes += 1;
+        self.checked(ret)
+    }
+
+    ///Removes entity from node it is stored in, without its bound.
+    ///Return is whether existed entity is removed.
+    pub fn remove_entity(&mut self, entity: T) -> bool {
+        match self.indices.get(&entity) {
+            Some(&(index, key)) => {
+                let removed = self.remove_at(index, &OctreeKey { key, entity });
+                self.checked(removed)
+            }
+            None => false,
+        }
+    }
+
+    ///Deepest node whose bound has point. None if point is outside of tree.
+    ///Point on boundary of octants belongs to positive one.
+    pub fn leaf_at(&self, point: Vec3) -> Option<LeafInfo> {
+        let root = self.nodes.get(self.root)?;
+        if !(root.aabb.min().cmple(point).all() && root.aabb.max().cmpgt(point).all()) {
+            return None;
+        }
+        let (mut index, mut depth) = (self.root, 0);
+        loop {
+            let node = &self.nodes[index];
+            let child = node.get_child_index(point.cmpge(node.aabb.center()));
+            if child == Self::NULL_INDEX {
+                return Some(LeafInfo {
+                    index,
+                    depth,
+                    aabb: node.aabb,
+                    entities: node.entities.len(),
+                });
+            }
+            index = child;
+            depth += 1;
         }
-        println!("counts {}", self.len());
+    }
+
+    ///Calls f with every entity that intersects with given bounding box. Touching is not intersecting.
+    ///Order follows tree shape. Use `intersect_sorted` when order matters.
+    pub fn intersect(&self, aabb: AABB, mut f: impl FnMut(T)) {
+        self.intersect_entities(aabb, |entity| f(entity.id.entity));
+    }
+
+    ///Whether shape at transform goes into any entity, beyond what `Shape::overlaps` tolerates.
+    ///Bound is tested through tree first, then shapes of entities left.
+    ///Entity without shape overlaps whatever its bound intersects.
+    pub fn overlaps(&self, shape: &Shape, transform: &Transform) -> bool {
+        let mut ret = false;
+        self.intersect_entities(shape.aabb(transform), |entity| {
+            ret = ret
+                || entity.shape.as_ref().map_or(true, |own| {
+                    own.overlaps(&entity.transform, shape, transform)
+                });
+        });
         ret
     }
 
-    ///Iterating entities that intersects with given bounding box.
-    pub fn _intersect(&self, aabb: AABB, f: impl Fn(&Entity)) {
+    ///Same as `intersect`, with whole entity.
+    fn intersect_entities(&self, aabb: AABB, mut f: impl FnMut(&OctreeEntity<T>)) {
+        for entity in self.oversize.iter() {
+            if entity.aabb._intersects(&aabb) {
+                f(entity);
+            }
+        }
         let mut index = self.root;
         while index != Self::NULL_INDEX {
             let node = &self.nodes[index];
             for entity in node.entities.iter() {
                 if entity.aabb._intersects(&aabb) {
-                    f(&entity.entity);
+                    f(entity);
                 }
             }
-            match (aabb - node.aabb.center()).octant() {
+            match aabb.octant_about(node.aabb.center()) {
                 Some(octant) => {
                     //Go deep until entity does not fit with leaf.
+                    //Box inside one octant could only meet that child, so missing child ends search.
                     index = node.get_child_index(octant);
                 }
+                //Box straddling octants could meet any child, so all of them are walked.
                 None => {
-                    self._intersect_children(&index, &aabb, &f);
+                    self.intersect_children(&index, &aabb, &mut f);
                     break;
                 }
             }
@@ -284,60 +1156,306 @@ impl Octree {
     }
 
     ///When entity has possibility to intersect with all leaves below.
-    fn _intersect_children(&self, index: &usize, aabb: &AABB, f: &impl Fn(&Entity)) {
+    fn intersect_children(&self, index: &usize, aabb: &AABB, f: &mut impl FnMut(&OctreeEntity<T>)) {
         //Iterates all possible child.
-        for child_index in self.nodes[*index].children.iter() {
-            if *child_index == Self::NULL_INDEX {
-                continue;
-            }
-            let child = &self.nodes[*child_index];
+        for child_index in self.nodes[*index].children() {
+            let child = &self.nodes[child_index];
             if child.aabb._intersects(&aabb) {
                 for entity in child.entities.iter() {
                     if entity.aabb._intersects(&aabb) {
-                        f(&entity.entity);
+                        f(entity);
                     }
                 }
-                self._intersect_children(child_index, aabb, f);
+                self.intersect_children(&child_index, aabb, f);
+            }
+        }
+    }
+
+    ///Calls f for every entity not entirely outside of frustum, so straddling ones are included.
+    ///Node outside of any plane is skipped with its subtree. Node inside of every plane
+    ///reports its subtree without more plane tests.
+    pub fn intersect_frustum(&self, frustum: &Frustum, mut f: impl FnMut(T)) {
+        self.frustum_entities(frustum, |entity| f(entity.id.entity));
+    }
+
+    fn frustum_entities<'a>(&'a self, frustum: &Frustum, mut f: impl FnMut(&'a OctreeEntity<T>)) {
+        for entity in self.oversize.iter() {
+            if !frustum.outside(&entity.aabb) {
+                f(entity);
+            }
+        }
+        if self.root == Self::NULL_INDEX {
+            return;
+        }
+        //Node holds only entities inside of its bound, so inside flag carries to whole subtree.
+        let mut stack = vec![(self.root, false)];
+        while let Some((index, mut inside)) = stack.pop() {
+            let node = &self.nodes[index];
+            if !inside {
+                if frustum.outside(&node.aabb) {
+                    continue;
+                }
+                inside = frustum.contains(&node.aabb);
+            }
+            for entity in node.entities.iter() {
+                if inside || !frustum.outside(&entity.aabb) {
+                    f(entity);
+                }
             }
+            stack.extend(node.children().map(|child| (child, inside)));
         }
     }
 
+    ///Entities that intersect with given bounding box, sorted by key.
+    ///Same set gives same order however it is inserted, so it fits tests and selection.
+    pub fn intersect_sorted(&self, aabb: AABB) -> Vec<T> {
+        let mut ret = Vec::new();
+        self.intersect(aabb, |entity| ret.push(entity));
+        ret.sort_unstable_by_key(|entity| (self.indices[entity].1, *entity));
+        ret
+    }
+
+    ///Collects entities not outside of frustum into out, sorted from far to near about camera_pos.
+    ///Back to front order is what transparent blending needs. Distance is to center of entity bound.
+    pub fn query_frustum_sorted(&self, frustum: &Frustum, camera_pos: Vec3, out: &mut Vec<T>) {
+        let mut visible = Vec::new();
+        self.frustum_entities(frustum, |entity| {
+            let distance = entity.aabb.center().distance_squared(camera_pos);
+            visible.push((distance, entity.id));
+        });
+        //Ties are broken by key, so order doesn't depend on tree shape.
+        visible.sort_unstable_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        out.extend(visible.into_iter().map(|(_, id)| id.entity));
+    }
+
     ///Return hit information about raycast.
-    pub fn raycast(&self, ray: &Ray) -> Option<RayHitInfo> {
-        let mut len = f32::INFINITY;
+    pub fn raycast(&self, ray: &Ray) -> Option<RayHitInfo<T>> {
+        self.raycast_with(ray, false)
+    }
+
+    ///Same as `raycast`, but back faces could be culled.
+    ///Culled hits are passed through, such as far wall of structure ray starts inside.
+    pub fn raycast_with(&self, ray: &Ray, cull_backfaces: bool) -> Option<RayHitInfo<T>> {
+        self.raycast_test(
+            ray,
+            RayTest {
+                cull_backfaces,
+                exact: true,
+            },
+        )
+    }
+
+    ///Same as `raycast`, but bounding boxes are hit instead of shapes. Only for debugging.
+    pub fn raycast_aabb(&self, ray: &Ray) -> Option<RayHitInfo<T>> {
+        self.raycast_test(
+            ray,
+            RayTest {
+                cull_backfaces: false,
+                exact: false,
+            },
+        )
+    }
+
+    fn raycast_test(&self, ray: &Ray, test: RayTest) -> Option<RayHitInfo<T>> {
+        let mut nearest = Nearest::default();
         let mut pivot = 0f32;
-        self.raycast_inner(self.root, ray, &mut len, &mut pivot)
-            .map(|(e, b)| RayHitInfo::new(e, b, len))
+        let hit = self.raycast_inner(self.root, ray, test, &mut nearest, &mut pivot, &mut None);
+        self.raycast_oversize(ray, test, &mut nearest, &mut None)
+            .or(hit)
+            .map(|entity| entity.hit(ray, nearest.t, nearest.normal))
     }
 
-    fn raycast_inner(
+    ///Raycasts oversize set, keeping only hits nearer than nearest.
+    ///Tree is cast first, so hit there rejects far oversize entities by their bound alone.
+    fn raycast_oversize(
         &self,
-        index: usize,
         ray: &Ray,
-        len: &mut f32,
+        test: RayTest,
+        nearest: &mut Nearest,
+        trace: &mut Option<RayTrace<T>>,
+    ) -> Option<&OctreeEntity<T>> {
+        let mut ret = None;
+        for entity in self.oversize.iter() {
+            let hit = entity.ray_test(ray, test);
+            if let Some(trace) = trace {
+                trace.steps.push(TraceStep::Test {
+                    entity: entity.id.entity,
+                    aabb: entity.aabb,
+                    t: hit.map(|(t, ..)| t),
+                });
+            }
+            if let Some((candidate, normal, back)) = hit {
+                if nearest.is_beaten_by(candidate, entity.id.key) && !(test.cull_backfaces && back)
+                {
+                    ret = Some(entity);
+                    *nearest = Nearest {
+                        t: candidate,
+                        key: entity.id.key,
+                        normal,
+                    };
+                }
+            }
+        }
+        ret
+    }
+
+    ///Same as `raycast`, but also records every step of traversal.
+    pub fn raycast_traced(&self, ray: &Ray) -> (Option<RayHitInfo<T>>, RayTrace<T>) {
+        let test = RayTest {
+            cull_backfaces: false,
+            exact: true,
+        };
+        let mut nearest = Nearest::default();
+        let mut pivot = 0f32;
+        let mut trace = Some(RayTrace::default());
+        let hit = self.raycast_inner(self.root, ray, test, &mut nearest, &mut pivot, &mut trace);
+        let hit = self
+            .raycast_oversize(ray, test, &mut nearest, &mut trace)
+            .or(hit)
+            .map(|entity| entity.hit(ray, nearest.t, nearest.normal));
+        let mut trace = trace.expect("Trace is given above");
+        trace.winner = hit.as_ref().map(|hit| (hit.entity, hit.t));
+        (hit, trace)
+    }
+
+    ///Every entity ray hits, sorted by distance and then by key.
+    pub fn raycast_all(&self, ray: &Ray) -> Vec<RayHitInfo<T>> {
+        let mut ret: Vec<_> = self
+            .oversize
+            .iter()
+            .filter_map(|entity| entity.intersects_ray(ray))
+            .collect();
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            if index == Self::NULL_INDEX {
+                continue;
+            }
+            let node = &self.nodes[index];
+            if node.aabb.intersects_ray_raw(ray).is_none() {
+                continue;
+            }
+            for entity in node.entities.iter() {
+                ret.extend(entity.intersects_ray(ray));
+            }
+            stack.extend(node.children());
+        }
+        ret.sort_unstable_by(|a, b| {
+            a.t.total_cmp(&b.t)
+                .then(a.key.cmp(&b.key))
+                .then(a.entity.cmp(&b.entity))
+        });
+        ret
+    }
+
+    ///Pushes hits up to max_t into out from near to far, in the same order as `raycast_all`.
+    ///Nodes are expanded in order of where ray enters them, which is not farther than any entity in them.
+    ///So an entity is pushed only when nothing nearer could be left, and caller could stop early.
+    pub fn ray_entities_ordered(&self, ray: &Ray, max_t: f32, out: &mut Vec<RayHitInfo<T>>) {
+        let mut candidates: BinaryHeap<_> = self
+            .oversize
+            .iter()
+            .filter_map(|entity| entity.intersects_ray(ray).map(RayCandidate::Entity))
+            .collect();
+        if let Some(t) = self.node_entry(self.root, ray) {
+            candidates.push(RayCandidate::Node {
+                t,
+                index: self.root,
+            });
+        }
+        while let Some(candidate) = candidates.pop() {
+            if candidate.t() > max_t {
+                break;
+            }
+            match candidate {
+                RayCandidate::Node { index, .. } => {
+                    let node = &self.nodes[index];
+                    for entity in node.entities.iter() {
+                        if let Some(hit) = entity.intersects_ray(ray) {
+                            candidates.push(RayCandidate::Entity(hit));
+                        }
+                    }
+                    for child in node.children() {
+                        if let Some(t) = self.node_entry(child, ray) {
+                            candidates.push(RayCandidate::Node { t, index: child });
+                        }
+                    }
+                }
+                RayCandidate::Entity(hit) => out.push(hit),
+            }
+        }
+    }
+
+    ///Where ray enters node. Zero if ray starts inside.
+    fn node_entry(&self, index: usize, ray: &Ray) -> Option<f32> {
+        if index == Self::NULL_INDEX {
+            return None;
+        }
+        self.nodes[index]
+            .aabb
+            .intersects_ray_raw(ray)
+            .map(|(t_min, _)| t_min.max(0.))
+    }
+
+    fn raycast_inner(
+        &self,
+        index: usize,
+        ray: &Ray,
+        test: RayTest,
+        nearest: &mut Nearest,
         pivot: &mut f32,
-    ) -> Option<(Entity, AABB)> {
+        trace: &mut Option<RayTrace<T>>,
+    ) -> Option<&OctreeEntity<T>> {
         if index == Self::NULL_INDEX {
             None
         } else {
             let node = &self.nodes[index];
             //Ray should intersect at least node's aabb.
             match node.aabb.intersects_ray_raw(ray) {
-                Some((_, t_max)) => {
+                Some((t_min, t_max)) => {
+                    //Ray starting outside should be followed from where it enters, not from its origin.
+                    *pivot = pivot.max(t_min);
+                    if let Some(trace) = trace {
+                        trace.steps.push(TraceStep::Visit {
+                            index,
+                            aabb: node.aabb,
+                            pivot: *pivot,
+                        });
+                    }
                     let mut ret = None;
                     //Raycast entities in node itself.
                     for entity in node.entities.iter() {
-                        if let Some(candidate) = entity.aabb.intersects_ray(ray) {
-                            if candidate < *len {
-                                ret = Some((entity.entity, entity.aabb));
-                                *len = candidate;
+                        let hit = entity.ray_test(ray, test);
+                        if let Some(trace) = trace {
+                            trace.steps.push(TraceStep::Test {
+                                entity: entity.id.entity,
+                                aabb: entity.aabb,
+                                t: hit.map(|(t, ..)| t),
+                            });
+                        }
+                        if let Some((candidate, normal, back)) = hit {
+                            if nearest.is_beaten_by(candidate, entity.id.key)
+                                && !(test.cull_backfaces && back)
+                            {
+                                ret = Some(entity);
+                                *nearest = Nearest {
+                                    t: candidate,
+                                    key: entity.id.key,
+                                    normal,
+                                };
                             }
                         }
                     }
                     //If node has child.
-                    if node.children_len != 0 {
+                    if node.children_len() != 0 {
                         match ray.octant_at(*pivot, node.aabb) {
                             Some(mut octant) => loop {
+                                if let Some(trace) = trace {
+                                    trace.steps.push(TraceStep::Octant {
+                                        node: index,
+                                        octant,
+                                        pivot: *pivot,
+                                    });
+                                }
                                 let child_index = node.get_child_index(octant);
                                 if child_index == Self::NULL_INDEX {
                                     //If child node doesn't exists, update just pivot.
@@ -351,7 +1469,14 @@ impl Octree {
                                     };
                                 } else {
                                     //Get result of raycast on leaf.
-                                    match self.raycast_inner(child_index, ray, len, pivot) {
+                                    match self.raycast_inner(
+                                        child_index,
+                                        ray,
+                                        test,
+                                        nearest,
+                                        pivot,
+                                        trace,
+                                    ) {
                                         //First success is if and only if the shortest raycast on the leaves.
                                         tmp @ Some(_) => {
                                             ret = tmp;
@@ -368,8 +1493,23 @@ impl Octree {
                                     break;
                                 }
                             },
-                            //Discard if ray is lie on xy, yz, xz plane and x, y, z axis
-                            None => {}
+                            //Ray lies on split plane, so it can't be told which side it goes through.
+                            //Every child it meets is searched, and nearest hit among them wins.
+                            None => {
+                                for child_index in node.children() {
+                                    let mut child_pivot = *pivot;
+                                    if let Some(hit) = self.raycast_inner(
+                                        child_index,
+                                        ray,
+                                        test,
+                                        nearest,
+                                        &mut child_pivot,
+                                        trace,
+                                    ) {
+                                        ret = Some(hit);
+                                    }
+                                }
+                            }
                         }
                     }
                     //Update pivot to Next.
@@ -382,24 +1522,175 @@ impl Octree {
     }
 }
 
-pub struct OctreeNode {
+///How entities are tested against ray.
+#[derive(Clone, Copy)]
+struct RayTest {
+    ///Hits seen from behind are passed through.
+    cull_backfaces: bool,
+    ///Shape is tested after bounding box. Otherwise box alone is hit.
+    exact: bool,
+}
+
+///Nearest hit of raycast so far. Tie goes to lower key, so winner doesn't depend on tree shape.
+#[derive(Clone, Copy)]
+struct Nearest {
+    t: f32,
+    key: u64,
+    normal: Vec3,
+}
+
+impl Default for Nearest {
+    fn default() -> Self {
+        Self {
+            t: f32::INFINITY,
+            key: u64::MAX,
+            normal: Vec3::ZERO,
+        }
+    }
+}
+
+impl Nearest {
+    fn is_beaten_by(&self, t: f32, key: u64) -> bool {
+        t < self.t || (t == self.t && key < self.key)
+    }
+}
+
+///Raycasts every box without tree, for checking octree and for scenes too small for one.
+///Key of hit is its position in boxes, so it agrees with `raycast_aabb` of tree keyed same way.
+pub fn raycast_brute<T: Copy>(boxes: &[(T, AABB)], ray: &Ray) -> Option<RayHitInfo<T>> {
+    let mut nearest = Nearest::default();
+    let mut ret = None;
+    for (key, (entity, aabb)) in boxes.iter().enumerate() {
+        if let Some((t, normal)) = aabb.intersects_ray_normal(ray) {
+            if nearest.is_beaten_by(t, key as u64) {
+                nearest = Nearest {
+                    t,
+                    key: key as u64,
+                    normal,
+                };
+                ret = Some((*entity, *aabb));
+            }
+        }
+    }
+    ret.map(|(entity, aabb)| {
+        RayHitInfo::new(entity, nearest.key, aabb, ray, nearest.t, nearest.normal)
+    })
+}
+
+///Read only view of node, for spatial algorithms built on top of tree.
+#[derive(Clone, Copy)]
+pub struct OctreeNodeRef<'a, T = Entity> {
+    octree: &'a Octree<T>,
+    index: usize,
+    depth: usize,
+}
+
+impl<'a, T: OctreeId> OctreeNodeRef<'a, T> {
+    fn node(&self) -> &'a OctreeNode<T> {
+        &self.octree.nodes[self.index]
+    }
+
+    ///Slot in node pool. Slot of idled node is reused, so it is stable only while node lives.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    ///Root is depth 0.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn aabb(&self) -> AABB {
+        self.node().aabb
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.node().children_len() == 0
+    }
+
+    ///Entities held by node itself, not by its children. Oversize ones are in no node.
+    pub fn entity_count(&self) -> usize {
+        self.node().entities.len()
+    }
+
+    ///Existing children, in octant order.
+    pub fn children(&self) -> impl Iterator<Item = OctreeNodeRef<'a, T>> + 'a {
+        let (octree, depth) = (self.octree, self.depth + 1);
+        self.node().children().map(move |index| OctreeNodeRef {
+            octree,
+            index,
+            depth,
+        })
+    }
+}
+
+///Ray query work item, popped nearest first.
+enum RayCandidate<T> {
+    Node { t: f32, index: usize },
+    Entity(RayHitInfo<T>),
+}
+
+impl<T: OctreeId> RayCandidate<T> {
+    fn t(&self) -> f32 {
+        match self {
+            Self::Node { t, .. } => *t,
+            Self::Entity(hit) => hit.t,
+        }
+    }
+
+    ///Nodes come first on tie, so tied entities are all pushed before any of them is popped.
+    fn rank(&self) -> (u8, Option<(u64, T)>) {
+        match self {
+            Self::Node { .. } => (0, None),
+            Self::Entity(hit) => (1, Some((hit.key, hit.entity))),
+        }
+    }
+}
+
+impl<T: OctreeId> Eq for RayCandidate<T> {}
+
+impl<T: OctreeId> PartialEq for RayCandidate<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: OctreeId> PartialOrd for RayCandidate<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: OctreeId> Ord for RayCandidate<T> {
+    //Reversed, since heap pops greatest.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .t()
+            .total_cmp(&self.t())
+            .then_with(|| other.rank().cmp(&self.rank()))
+    }
+}
+
+pub struct OctreeNode<T = Entity> {
     ///Bound of itself.
     aabb: AABB,
     ///Entities that a few or doesn't fit with childs.
-    entities: BTreeSet<OctreeEntity>,
+    entities: BTreeSet<OctreeEntity<T>>,
     parent: usize,
-    children: [usize; 8],
-    children_len: usize,
+    ///First slot of block of children. Null when node has no child.
+    children_base: usize,
+    ///Which slots of block hold child, indexed by octant.
+    children_mask: u8,
 }
 
-impl OctreeNode {
+impl<T: OctreeId> OctreeNode<T> {
     pub fn new(aabb: AABB, parent: usize) -> Self {
         Self {
             aabb,
             entities: BTreeSet::new(),
             parent,
-            children: [Octree::NULL_INDEX; 8],
-            children_len: 0,
+            children_base: Octree::<T>::NULL_INDEX,
+            children_mask: 0,
         }
     }
 
@@ -412,6 +1703,1338 @@ impl OctreeNode {
     }
 
     pub fn get_child_index(&self, octant: BVec3) -> usize {
-        self.children[Self::octant_to_index(octant)]
+        self.child(Self::octant_to_index(octant))
+    }
+
+    ///Index of child in octant index. Null if there is no child.
+    fn child(&self, octant_index: usize) -> usize {
+        if self.children_mask & (1 << octant_index) != 0 {
+            self.children_base + octant_index
+        } else {
+            Octree::<T>::NULL_INDEX
+        }
+    }
+
+    ///Indices of existing children, in octant order.
+    fn children(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..8)
+            .filter(|i| self.children_mask & (1 << i) != 0)
+            .map(|i| self.children_base + i)
+    }
+
+    fn children_len(&self) -> usize {
+        self.children_mask.count_ones() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{consts::RAY_EPS, rng::SplitMix64};
+
+    fn cube(i: u32, pos: Vec3) -> OctreeEntity {
+        OctreeEntity::new(
+            Entity::from_raw(i),
+            i as u64,
+            &Collider::from_shape(Shape::Cuboid {
+                half_extents: Vec3::splat(0.5),
+            }),
+            &Transform::from_translation(pos),
+        )
+    }
+
+    ///Tree as game builds it, whose cells are centered on integers.
+    fn blueprint_tree() -> EntityOctree {
+        EntityOctree::_from_size_offset(64, Vec3::splat(0.9), 64., Vec3::new(0.5, 31.5, 0.5))
+    }
+
+    #[test]
+    fn overlaps_adjacent_cells() {
+        let mut octree = blueprint_tree();
+        let mut i = 0;
+        for x in -3..=3 {
+            for z in -3..=3 {
+                octree.insert_result(cube(i, Vec3::new(x as f32 * 2., 0., z as f32 * 2.)));
+                i += 1;
+            }
+        }
+        let cube = Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        };
+        let at = |x: f32, y: f32, z: f32| Transform::from_xyz(x, y, z);
+        assert!(!octree.overlaps(&cube, &at(1., 0., 0.)));
+        assert!(!octree.overlaps(&cube, &at(0., 1., 0.)));
+        assert!(!octree.overlaps(&cube, &at(1., 0., 1.)));
+        assert!(octree.overlaps(&cube, &at(0.5, 0., 0.)));
+        assert!(octree.overlaps(&cube, &at(2., 0.9, 2.)));
+        let dome = Shape::CutSphere {
+            radius: 2.5,
+            cut: 0.5,
+        };
+        assert!(octree.overlaps(&dome, &at(1., 0., 1.)));
+        assert!(!octree.overlaps(&dome, &at(1., 1., 1.)));
+        assert!(!octree.overlaps(&dome, &at(20., 0., 1.)));
+    }
+
+    #[test]
+    fn frustum_sorted_matches_brute() {
+        let mut octree = blueprint_tree();
+        let mut rng = SplitMix64::new(12345);
+        let mut all = Vec::new();
+        for i in 0..500 {
+            let cell = Vec3::new(
+                rng.next_f32() * 60. - 30.,
+                rng.next_f32() * 60.,
+                rng.next_f32() * 60. - 30.,
+            )
+            .round();
+            if octree.insert_result(cube(i, cell)) == InsertOutcome::Inserted {
+                all.push((Entity::from_raw(i), AABB::new(cell - 0.5, cell + 0.5)));
+            }
+        }
+        //Box of x in [-10, 10], y over 5 and z under 20, whose last two planes cut nothing.
+        let frustum = Frustum::new([
+            Vec4::new(1., 0., 0., 10.),
+            Vec4::new(-1., 0., 0., 10.),
+            Vec4::new(0., 1., 0., -5.),
+            Vec4::new(0., 0., -1., 20.),
+            Vec4::W,
+            Vec4::W,
+        ]);
+        let camera = Vec3::new(3., 40., -50.);
+        let mut out = Vec::new();
+        octree.query_frustum_sorted(&frustum, camera, &mut out);
+        let mut brute: Vec<_> = all
+            .iter()
+            .filter(|(_, aabb)| !frustum.outside(aabb))
+            .map(|(entity, aabb)| (aabb.center().distance_squared(camera), *entity))
+            .collect();
+        brute.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        let brute: Vec<_> = brute.into_iter().map(|(_, entity)| entity).collect();
+        assert!(!out.is_empty() && out.len() < all.len());
+        assert_eq!(out, brute);
+
+        let mut count = 0;
+        octree.intersect_frustum(&frustum, |_| count += 1);
+        assert_eq!(count, out.len());
+    }
+
+    #[test]
+    fn frustum_counts_inside_outside_and_straddling() {
+        let mut octree = blueprint_tree();
+        let cells: Vec<_> = (0..100)
+            .map(|i| Vec3::new((i % 10) as f32, 0., (i / 10) as f32))
+            .collect();
+        for (i, cell) in cells.iter().enumerate() {
+            octree.insert_result(cube(i as u32, *cell));
+        }
+        let count = |frustum: &Frustum| {
+            let mut found = Vec::new();
+            octree.intersect_frustum(frustum, |entity| found.push(entity));
+            //Each entity is reported once.
+            let len = found.len();
+            found.sort_unstable();
+            found.dedup();
+            assert_eq!(found.len(), len);
+            len
+        };
+        //x in [2.2, 5.8] and z under 3.7. Columns 2 and 6 and row 4 are cut by planes.
+        let frustum = Frustum::new([
+            Vec4::new(1., 0., 0., -2.2),
+            Vec4::new(-1., 0., 0., 5.8),
+            Vec4::new(0., 0., -1., 3.7),
+            Vec4::W,
+            Vec4::W,
+            Vec4::W,
+        ]);
+        let bounds: Vec<_> = cells
+            .iter()
+            .map(|cell| AABB::new(*cell - 0.5, *cell + 0.5))
+            .collect();
+        let inside = bounds.iter().filter(|aabb| frustum.contains(aabb)).count();
+        let outside = bounds.iter().filter(|aabb| frustum.outside(aabb)).count();
+        assert_eq!((inside, outside), (12, 75));
+        assert_eq!(count(&frustum), 25);
+        //Frustum holding whole tree, and one past its edge.
+        assert_eq!(count(&Frustum::new([Vec4::W; 6])), 100);
+        let beyond = Frustum::new([
+            Vec4::new(1., 0., 0., -20.),
+            Vec4::W,
+            Vec4::W,
+            Vec4::W,
+            Vec4::W,
+            Vec4::W,
+        ]);
+        assert_eq!(count(&beyond), 0);
+    }
+
+    #[test]
+    fn blueprint_base_covers_bound() {
+        let bounds = [
+            AABB::new(Vec3::new(-31.5, -0.5, -31.5), Vec3::new(31.5, 62.5, 31.5)),
+            AABB::new(Vec3::new(-8.5, -0.5, -8.5), Vec3::new(8.5, 16.5, 8.5)),
+            AABB::new(Vec3::new(100.5, 3.5, -40.5), Vec3::new(103.5, 4.5, 60.5)),
+            AABB::new(Vec3::splat(-0.5), Vec3::new(1.5, 0.5, 0.5)),
+        ];
+        for bound in bounds {
+            let base = EntityOctree::blueprint_base(&bound);
+            assert!(base.contains(&bound), "{bound:?}");
+            for corner in bound.corners() {
+                assert!(base.min().cmple(corner).all() && base.max().cmpge(corner).all());
+            }
+            //Split planes of root fall on cell bounds of blueprint.
+            let center = base.center() - bound.min();
+            assert_eq!(center, center.round(), "{bound:?}");
+            let octree = EntityOctree::for_blueprint(64, Vec3::splat(0.9), &bound);
+            assert_eq!(octree.check_blueprint_base(&bound), Ok(()));
+        }
+        //Game's bound derives same base as tree used to be built with by hand.
+        assert_eq!(blueprint_tree().check_blueprint_base(&bounds[0]), Ok(()));
+        let shifted =
+            EntityOctree::_from_size_offset(64, Vec3::splat(0.9), 64., Vec3::new(0., 31.5, 0.));
+        assert_eq!(
+            shifted.check_blueprint_base(&bounds[0]),
+            Err(EntityOctree::blueprint_base(&bounds[0]))
+        );
+        let small = EntityOctree::for_blueprint(64, Vec3::splat(0.9), &bounds[1]);
+        assert!(small.check_blueprint_base(&bounds[0]).is_err());
+    }
+
+    #[test]
+    fn merge_disjoint_trees_sums_len() {
+        let mut a = blueprint_tree();
+        let mut b = blueprint_tree();
+        for i in 0..20 {
+            a.insert_result(cube(i, Vec3::new(i as f32, 0., 0.)));
+            b.insert_result(cube(100 + i, Vec3::new(i as f32, 5., -3.)));
+        }
+        assert!(a.merge(b).is_empty());
+        assert_eq!(a.len(), 40);
+        assert_eq!(a.iter().count(), 40);
+        assert_eq!(
+            a.intersect_sorted(AABB::new(Vec3::new(-1., 4., -4.), Vec3::new(30., 6., -2.))),
+            (100..120).map(Entity::from_raw).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn sorted_intersect_ignores_insertion_order() {
+        let mut rng = SplitMix64::new(751);
+        let mut cells = Vec::new();
+        while cells.len() < 150 {
+            let cell = (Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * 30.).round();
+            if !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+        let mut forward = blueprint_tree();
+        for (i, cell) in cells.iter().enumerate() {
+            forward.insert_result(cube(i as u32, *cell));
+        }
+        //Other tree grows through other shapes, and some of them are left behind.
+        let mut backward = blueprint_tree();
+        for (i, cell) in cells.iter().enumerate().rev() {
+            backward.insert_result(cube(i as u32, *cell));
+            let extra = 1000 + i as u32;
+            backward.insert_result(cube(extra, *cell + Vec3::Y * 31.));
+            if i % 2 == 0 {
+                let cell = *cell + Vec3::Y * 31.;
+                assert!(backward.remove(Entity::from_raw(extra), AABB::new(cell - 0.5, cell + 0.5)));
+            }
+        }
+        for _ in 0..100 {
+            let min = (Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * 30.).round();
+            let aabb = AABB::new(min, min + 8.);
+            let sorted = forward.intersect_sorted(aabb);
+            let expected: Vec<_> = (0..cells.len())
+                .filter(|i| aabb._intersects(&AABB::new(cells[*i] - 0.5, cells[*i] + 0.5)))
+                .map(|i| Entity::from_raw(i as u32))
+                .collect();
+            assert_eq!(sorted, expected);
+            let others: Vec<_> = backward
+                .intersect_sorted(aabb)
+                .into_iter()
+                .filter(|entity| entity.index() < 1000)
+                .collect();
+            assert_eq!(others, sorted);
+        }
+    }
+
+    #[test]
+    fn ties_follow_key_not_entity_id() {
+        let mut rng = SplitMix64::new(7523);
+        let mut shuffled = |len: usize| {
+            let mut ret: Vec<_> = (0..len as u32).collect();
+            for i in (1..len).rev() {
+                ret.swap(i, rng.next_u64() as usize % (i + 1));
+            }
+            ret
+        };
+        //Every cell holds two entities with same bound, like decal over block.
+        let cells: Vec<_> = (0..72)
+            .map(|i| Vec3::new((i % 6) as f32, 0., (i / 6 % 6) as f32))
+            .collect();
+        let keys = shuffled(cells.len());
+        //Same scene, but other entities were spawned before, so ids differ.
+        let (plain, churned) = (shuffled(cells.len()), shuffled(cells.len()));
+        let build = |ids: &[u32]| {
+            let mut octree = blueprint_tree();
+            for (i, cell) in cells.iter().enumerate() {
+                let entity = Entity::from_raw(ids[i]);
+                let aabb = AABB::new(*cell - 0.5, *cell + 0.5);
+                let inserted = OctreeEntity::from_aabb(entity, keys[i] as u64, aabb);
+                assert_eq!(octree.insert_result(inserted), InsertOutcome::Inserted);
+            }
+            octree
+        };
+        let (a, b) = (build(&plain), build(&churned));
+        //Key of cell whose entity is given, which is what is compared between trees.
+        let key_of = |ids: &[u32], entity: Entity| {
+            keys[ids.iter().position(|id| *id == entity.index()).unwrap()]
+        };
+        for (i, cell) in cells[..36].iter().enumerate() {
+            let ray = Ray::new(*cell + Vec3::new(0.1, 10., 0.2), Vec3::NEG_Y);
+            let all = a.raycast_all(&ray);
+            assert_eq!(all.len(), 2);
+            assert_eq!(all[0].t, all[1].t);
+            let lower = keys[i].min(keys[i + 36]) as u64;
+            assert_eq!(a.raycast(&ray).unwrap().key, lower);
+            assert_eq!(b.raycast(&ray).unwrap().key, lower);
+            let keys_along = |octree: &EntityOctree, ids: &[u32]| {
+                octree
+                    .raycast_all(&ray)
+                    .iter()
+                    .map(|hit| {
+                        assert_eq!(key_of(ids, hit.entity) as u64, hit.key);
+                        hit.key
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(keys_along(&a, &plain), keys_along(&b, &churned));
+        }
+        for min in [
+            Vec3::new(-1., -1., -1.),
+            Vec3::new(2., -1., 1.),
+            Vec3::new(0.5, -0.5, 3.),
+        ] {
+            let aabb = AABB::new(min, min + 3.);
+            let keys_in = |octree: &EntityOctree, ids: &[u32]| {
+                octree
+                    .intersect_sorted(aabb)
+                    .into_iter()
+                    .map(|entity| key_of(ids, entity))
+                    .collect::<Vec<_>>()
+            };
+            let sorted = keys_in(&a, &plain);
+            assert!(!sorted.is_empty() && sorted.windows(2).all(|pair| pair[0] < pair[1]));
+            assert_eq!(sorted, keys_in(&b, &churned));
+        }
+    }
+
+    #[test]
+    fn ray_clipping_sphere_bound_misses_sphere() {
+        let mut octree = blueprint_tree();
+        let center = Vec3::new(3., 4., 5.);
+        let collider = Collider::from_shape(Shape::Sphere { radius: 1. });
+        let transform = Transform::from_translation(center);
+        let sphere = OctreeEntity::new(Entity::from_raw(0), 0, &collider, &transform);
+        assert_eq!(octree.insert_result(sphere), InsertOutcome::Inserted);
+        //Corner of bound is outside of sphere.
+        let corner = Ray::new(center + Vec3::new(0.9, 0.9, -10.), Vec3::Z);
+        assert!(octree.raycast_aabb(&corner).is_some());
+        assert!(octree.raycast(&corner).is_none());
+        let near_center = Ray::new(center + Vec3::new(0.3, 0.4, -10.), Vec3::Z);
+        let hit = octree.raycast(&near_center).unwrap();
+        assert!((hit.t - (10. - 0.75f32.sqrt())).abs() < 1e-4);
+        assert!(hit.t > octree.raycast_aabb(&near_center).unwrap().t);
+    }
+
+    #[test]
+    fn straddling_box_finds_every_octant() {
+        let mut octree = blueprint_tree();
+        let center = octree.base_aabb().center();
+        //Cube in each octant of root, and one across split planes of root.
+        let mut boxes: Vec<_> = (0..8u32)
+            .map(|corner| {
+                let sign = |bit| if corner & bit == 0 { -1. } else { 1. };
+                let cell = center + Vec3::new(sign(1), sign(2), sign(4)) * 2.;
+                (Entity::from_raw(corner), cell)
+            })
+            .collect();
+        boxes.push((Entity::from_raw(8), center));
+        for (entity, cell) in boxes.iter() {
+            octree.insert_result(cube(entity.index(), *cell));
+        }
+        let query = |min: Vec3, max: Vec3| {
+            let aabb = AABB::new(center + min, center + max);
+            let mut found = Vec::new();
+            octree.intersect(aabb, |entity| found.push(entity));
+            found.sort_unstable();
+            let brute: Vec<_> = boxes
+                .iter()
+                .filter(|(_, cell)| AABB::new(*cell - 0.5, *cell + 0.5)._intersects(&aabb))
+                .map(|(entity, _)| *entity)
+                .collect();
+            assert_eq!(found, brute);
+            found.len()
+        };
+        assert_eq!(query(Vec3::splat(-3.), Vec3::splat(3.)), 9);
+        assert_eq!(query(Vec3::splat(-3.), Vec3::new(3., 3., -1.)), 4);
+        assert_eq!(query(Vec3::splat(-3.), Vec3::new(0.2, 3., 0.2)), 3);
+        assert_eq!(query(Vec3::splat(1.), Vec3::splat(3.)), 1);
+        assert_eq!(query(Vec3::splat(-0.2), Vec3::splat(0.2)), 1);
+    }
+
+    #[test]
+    fn merge_reports_conflicts() {
+        let mut a = blueprint_tree();
+        let mut b = blueprint_tree();
+        a.insert_result(cube(1, Vec3::ZERO));
+        a.insert_result(cube(2, Vec3::X));
+        //Same entity as in a, even though it is elsewhere there.
+        b.insert_result(cube(2, Vec3::new(5., 0., 0.)));
+        b.insert_result(cube(3, Vec3::new(-5., 0., 0.)));
+        b.insert_result(cube(4, Vec3::new(10., 0., 0.)));
+        let conflicts = a.merge(b);
+        assert_eq!(
+            conflicts,
+            [(Entity::from_raw(2), InsertOutcome::AlreadyPresent)]
+        );
+        assert_eq!(a.len(), 4);
+        //Entity that already exists is kept as is.
+        let kept = a
+            .iter()
+            .find(|entity| entity.entity() == Entity::from_raw(2));
+        assert_eq!(kept.map(|entity| entity.aabb().center()), Some(Vec3::X));
+    }
+
+    #[test]
+    fn raycast_from_outside_follows_entry() {
+        let mut octree = blueprint_tree();
+        let target = Vec3::new(-27., 24., 26.);
+        octree.insert_result(cube(1, target));
+        octree.insert_result(cube(2, Vec3::new(-5., 50., 7.)));
+        //Starts below tree, beside octant it enters through.
+        let origin = Vec3::new(-50., -4., 45.);
+        let ray = Ray::new(origin, (target - origin).normalize());
+        let hit = octree.raycast(&ray).map(|hit| hit.entity);
+        assert_eq!(hit, Some(Entity::from_raw(1)));
+    }
+
+    #[test]
+    fn from_aabb_entity_is_hit_as_its_bound() {
+        let mut octree = blueprint_tree();
+        //Marker without collider, flat and not a cell cube.
+        let bound = AABB::new(Vec3::new(2., 3., -1.), Vec3::new(4., 3.5, 1.));
+        octree.insert_result(OctreeEntity::from_aabb(Entity::from_raw(1), 1, bound));
+        octree.insert_result(cube(2, Vec3::new(3., 3., -6.)));
+        assert_eq!(octree.debug_validate(), Ok(()));
+        let front = octree
+            .raycast(&Ray::new(Vec3::new(3., 3.25, 10.), Vec3::NEG_Z))
+            .unwrap();
+        assert_eq!(front.entity, Entity::from_raw(1));
+        assert_eq!(front.normal, Vec3::Z);
+        assert!((front.t - 9.).abs() < 1e-5);
+        assert!(front.point.abs_diff_eq(Vec3::new(3., 3.25, 1.), 1e-5));
+        let top = octree
+            .raycast(&Ray::new(Vec3::new(2.5, 20., 0.5), Vec3::NEG_Y))
+            .unwrap();
+        assert_eq!(top.entity, Entity::from_raw(1));
+        assert_eq!(top.normal, Vec3::Y);
+        assert!((top.point.y - 3.5).abs() < 1e-5);
+        //Just below bound, where unit cube at its center would be hit, reaches cube behind.
+        let below = octree.raycast(&Ray::new(Vec3::new(3., 2.8, 10.), Vec3::NEG_Z));
+        assert_eq!(below.map(|hit| hit.entity), Some(Entity::from_raw(2)));
+        let mut found = Vec::new();
+        octree.intersect(
+            AABB::new(Vec3::new(3.9, 2., 0.9), Vec3::new(5., 3.1, 2.)),
+            |entity| found.push(entity),
+        );
+        assert_eq!(found, [Entity::from_raw(1)]);
+    }
+
+    #[test]
+    fn u32_keys_work_like_entities() {
+        let mut octree = Octree::<u32>::_from_size_offset(8, Vec3::splat(0.9), 64., Vec3::ZERO);
+        let collider = Collider::from_shape(Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        });
+        for i in 0..20u32 {
+            let pos = Vec3::new(i as f32 * 1.5 - 15., 0., 0.);
+            octree.insert_result(OctreeEntity::new(
+                i,
+                i as u64,
+                &collider,
+                &Transform::from_translation(pos),
+            ));
+        }
+        //Probe without collider, keyed past cubes.
+        let probe = AABB::new(Vec3::new(-0.5, 4., -0.5), Vec3::new(0.5, 5., 0.5));
+        octree.insert_result(OctreeEntity::from_aabb(100, 100, probe));
+        assert_eq!(octree.len(), 21);
+        assert_eq!(octree.debug_validate(), Ok(()));
+        let hit = octree
+            .raycast(&Ray::new(Vec3::new(-20., 0., 0.), Vec3::X))
+            .unwrap();
+        assert_eq!(hit.entity, 0);
+        let hit = octree
+            .raycast(&Ray::new(Vec3::new(0., 10., 0.), Vec3::NEG_Y))
+            .unwrap();
+        assert_eq!(hit.entity, 100);
+        let near = octree.intersect_sorted(AABB::new(
+            Vec3::new(-2.4, -1., -1.),
+            Vec3::new(2.4, 4.5, 1.),
+        ));
+        assert_eq!(near, [9, 10, 11, 100]);
+        assert!(octree.remove(100, probe));
+        assert!(!octree.remove(100, probe));
+        let hit = octree
+            .raycast(&Ray::new(Vec3::new(0., 10., 0.), Vec3::NEG_Y))
+            .unwrap();
+        assert_eq!(hit.entity, 10);
+        assert_eq!(octree.len(), 20);
+        assert_eq!(octree.debug_validate(), Ok(()));
+    }
+
+    #[test]
+    fn counters_follow_scripted_operations() {
+        let mut octree = blueprint_tree();
+        assert_eq!(*octree.counters(), OctreeCounters::default());
+        octree.insert_result(cube(1, Vec3::new(3., 3., 3.)));
+        let counters = *octree.counters();
+        assert_eq!(counters.inserts, 1);
+        //Root is there from start, so every other node came from split.
+        assert_eq!(counters.splits, octree.node_count() - 1);
+        assert_eq!(counters.max_depth, octree.stats().max_depth);
+        let far = cube(2, Vec3::new(-20., 40., -20.));
+        octree.insert_result(far.clone());
+        octree.insert_result(cube(3, Vec3::new(200., 3., 3.)));
+        assert_eq!(octree.counters().inserts, 3);
+        assert_eq!(octree.counters().extends, 2);
+
+        assert!(octree.remove_entity(Entity::from_raw(3)));
+        assert!(octree.remove(Entity::from_raw(2), far.aabb()));
+        //Missing entity isn't counted.
+        assert!(!octree.remove_entity(Entity::from_raw(9)));
+        let counters = *octree.counters();
+        assert_eq!(counters.removes, 2);
+        assert!(counters.merges > 0);
+        assert_eq!(
+            counters.splits + counters.extends + 1 - counters.merges,
+            octree.node_count()
+        );
+        assert_eq!(octree.idle_nodes(), octree.stats().idle_nodes);
+        assert_eq!(octree.debug_validate(), Ok(()));
+        octree.reset_counters();
+        assert_eq!(*octree.counters(), OctreeCounters::default());
+    }
+
+    #[test]
+    fn hit_normal_of_straight_down_and_diagonal_rays() {
+        let mut octree = blueprint_tree();
+        let center = Vec3::new(0., 1., 0.);
+        octree.insert_result(cube(1, center));
+        let hit = |origin: Vec3, dir: Vec3| {
+            let hit = octree
+                .raycast(&Ray::new(center + origin, dir.normalize()))
+                .unwrap();
+            (hit.normal, hit.point - center)
+        };
+        let (normal, point) = hit(Vec3::new(0.2, 10., -0.1), Vec3::NEG_Y);
+        assert_eq!(normal, Vec3::Y);
+        assert!(point.abs_diff_eq(Vec3::new(0.2, 0.5, -0.1), 1e-5));
+        //Diagonal down and along x, entering through top and through side.
+        let dir = Vec3::new(1., -1., 0.);
+        let (normal, point) = hit(Vec3::new(-2., 2.2, 0.), dir);
+        assert_eq!(normal, Vec3::Y);
+        assert!(point.abs_diff_eq(Vec3::new(-0.3, 0.5, 0.), 1e-5), "{point}");
+        let (normal, point) = hit(Vec3::new(-2., 1.8, 0.), dir);
+        assert_eq!(normal, Vec3::NEG_X);
+        assert!(point.abs_diff_eq(Vec3::new(-0.5, 0.3, 0.), 1e-5), "{point}");
+        //Diagonal through all three axes.
+        let (normal, point) = hit(Vec3::new(-2., 3., -1.8), Vec3::new(1., -1.5, 1.));
+        assert_eq!(normal, Vec3::Y);
+        assert!(
+            point.abs_diff_eq(Vec3::new(-0.333_333, 0.5, -0.133_333), 1e-5),
+            "{point}"
+        );
+    }
+
+    #[test]
+    fn brute_raycast_agrees_with_tree() {
+        let mut octree = blueprint_tree();
+        let mut rng = SplitMix64::new(0x2545_f491_4f6c_dd1d);
+        let mut boxes = Vec::new();
+        let random = |rng: &mut SplitMix64, scale: f32, shift: f32| {
+            Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * scale + shift
+        };
+        for i in 0..300 {
+            let cell = (random(&mut rng, 60., -30.) + Vec3::Y * 30.).round();
+            //Some large ones, so oversize set takes part too.
+            let radius = if rng.next_u64() % 40 == 0 { 20. } else { 0.5 };
+            let collider = Collider::from_shape(Shape::Sphere { radius });
+            let transform = Transform::from_translation(cell);
+            let key = boxes.len() as u64;
+            let entity = OctreeEntity::new(Entity::from_raw(i), key, &collider, &transform);
+            if octree.insert_result(entity) == InsertOutcome::Inserted {
+                boxes.push((Entity::from_raw(i), collider.aabb(&transform)));
+            }
+        }
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = random(&mut rng, 100., -50.) + Vec3::Y * 30.;
+            let dir = match random(&mut rng, 2., -1.).try_normalize() {
+                Some(dir) => dir,
+                None => continue,
+            };
+            let ray = Ray::new(origin, dir);
+            let tree = octree.raycast_aabb(&ray).map(|hit| (hit.entity, hit.t));
+            let brute = raycast_brute(&boxes, &ray).map(|hit| (hit.entity, hit.t));
+            assert_eq!(tree, brute, "{origin} {dir}");
+            hits += tree.is_some() as usize;
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn culled_raycast_passes_through_back_faces() {
+        let mut octree = blueprint_tree();
+        let center = Vec3::new(3., 10., -2.);
+        octree.insert_result(OctreeEntity::new(
+            Entity::from_raw(1),
+            1,
+            &Collider::from_shape(Shape::Cuboid {
+                half_extents: Vec3::splat(2.),
+            }),
+            &Transform::from_translation(center),
+        ));
+        octree.insert_result(cube(2, center + Vec3::X * 6.));
+        let hit = |ray: &Ray, cull| {
+            octree
+                .raycast_with(ray, cull)
+                .map(|hit| (hit.entity, hit.t, hit.normal))
+        };
+        //From inside, far wall is seen from behind.
+        let inside = Ray::new(center, Vec3::X);
+        assert_eq!(
+            hit(&inside, false),
+            Some((Entity::from_raw(1), 2., Vec3::X))
+        );
+        assert_eq!(
+            hit(&inside, true),
+            Some((Entity::from_raw(2), 5.5, Vec3::NEG_X))
+        );
+        //From outside, near wall faces ray either way.
+        let outside = Ray::new(center - Vec3::X * 10., Vec3::X);
+        for cull in [false, true] {
+            assert_eq!(
+                hit(&outside, cull),
+                Some((Entity::from_raw(1), 8., Vec3::NEG_X))
+            );
+        }
+    }
+
+    #[test]
+    fn oversize_mix_matches_brute() {
+        let mut octree = blueprint_tree();
+        let mut rng = SplitMix64::new(747);
+        let mut boxes = Vec::new();
+        let random =
+            |rng: &mut SplitMix64| Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32());
+        for i in 0..205 {
+            let cell = (random(&mut rng) * 40. - Vec3::new(20., 0., 20.)).round();
+            //Every 41st spans more than quarter of base bound.
+            let half_extents = Vec3::splat(if i % 41 == 0 { 10. } else { 0.5 });
+            let collider = Collider::from_shape(Shape::Cuboid { half_extents });
+            let transform = Transform::from_translation(cell);
+            let entity = OctreeEntity::new(Entity::from_raw(i), i as u64, &collider, &transform);
+            if octree.insert_result(entity) == InsertOutcome::Inserted {
+                boxes.push((Entity::from_raw(i), collider.aabb(&transform)));
+            }
+        }
+        assert_eq!(octree.oversize_len(), 5);
+        assert_eq!(octree.debug_validate(), Ok(()));
+        let check = |octree: &EntityOctree, boxes: &[(Entity, AABB)], rng: &mut SplitMix64| {
+            let mut found = 0;
+            for _ in 0..300 {
+                let min = random(rng) * 50. - Vec3::new(25., 5., 25.);
+                let aabb = AABB::new(min, min + random(rng) * 16. + 0.1);
+                let brute: Vec<_> = boxes
+                    .iter()
+                    .filter(|(_, other)| other._intersects(&aabb))
+                    .map(|(entity, _)| *entity)
+                    .collect();
+                assert_eq!(octree.intersect_sorted(aabb), brute, "{aabb:?}");
+                found += brute.len();
+            }
+            assert!(found > 100);
+        };
+        check(&octree, &boxes, &mut rng);
+        //Removal routes to oversize set by size too.
+        boxes.retain(|(entity, aabb)| {
+            if entity.index() % 41 == 0 {
+                assert!(octree.remove(*entity, *aabb));
+                false
+            } else {
+                true
+            }
+        });
+        assert_eq!(octree.oversize_len(), 0);
+        assert_eq!(octree.len(), boxes.len());
+        assert_eq!(octree.debug_validate(), Ok(()));
+        check(&octree, &boxes, &mut rng);
+    }
+
+    #[test]
+    fn grazing_rays_match_parallel_ones() {
+        let mut octree = blueprint_tree();
+        let mut boxes = Vec::new();
+        //Row of cells along split plane of root and of its children, so rays graze both faces and octants.
+        for (i, x) in (-6..=6).enumerate() {
+            let cell = Vec3::new(x as f32, 32., 0.);
+            let entity = cube(i as u32, cell);
+            boxes.push((entity.entity(), entity.aabb()));
+            octree.insert_result(entity);
+        }
+        let grazes = [0.5, 0.5 - 1e-7, 0.5 + 1e-7, -0.5, 0.];
+        for y in grazes {
+            for z in grazes {
+                let origin = Vec3::new(-20., 32. + y, z);
+                let parallel = Ray::new(origin, Vec3::X);
+                let tree = octree
+                    .raycast_aabb(&parallel)
+                    .map(|hit| (hit.entity, hit.t));
+                let brute = raycast_brute(&boxes, &parallel).map(|hit| (hit.entity, hit.t));
+                assert_eq!(tree, brute, "{origin}");
+                //Tiny component is flushed, so grazing ray takes same path instead of drifting across plane.
+                for tilt in [Vec3::Y, Vec3::Z, Vec3::Y + Vec3::Z] {
+                    for sign in [1., -1.] {
+                        let ray = Ray::new(origin, Vec3::X + tilt * sign * RAY_EPS * 0.5);
+                        assert_eq!(ray.dir(), Vec3::X);
+                        let grazing = octree.raycast_aabb(&ray).map(|hit| (hit.entity, hit.t));
+                        assert_eq!(grazing, tree, "{origin} {tilt} {sign}");
+                    }
+                }
+            }
+        }
+        //Ray along split plane itself has no octant until it heads off plane.
+        let bound = *octree.base_aabb();
+        let on_plane = Ray::new(bound.center() - Vec3::X * 40., Vec3::X);
+        assert_eq!(on_plane.octant_at(0., bound), None);
+        let grazing = Ray::new(on_plane.origin(), Vec3::new(1., RAY_EPS * 0.5, 0.));
+        assert_eq!(grazing.octant_at(0., bound), None);
+    }
+
+    #[test]
+    fn trace_follows_known_scene() {
+        let mut octree = blueprint_tree();
+        let near = cube(1, Vec3::new(-10., 10., -10.));
+        let far = cube(2, Vec3::new(10., 10., -10.));
+        let near_aabb = near.aabb();
+        octree.insert_result(near);
+        octree.insert_result(far);
+        let ray = Ray::new(Vec3::new(-40., 10.2, -10.3), Vec3::X);
+        let (hit, trace) = octree.raycast_traced(&ray);
+        assert_eq!(hit.map(|hit| hit.entity), Some(Entity::from_raw(1)));
+        assert_eq!(trace.winner, Some((Entity::from_raw(1), 29.5)));
+        //Ray goes down to near cell alone, and stops there before far one.
+        let visited: Vec<_> = trace
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                TraceStep::Visit { index, aabb, .. } => Some((*index, *aabb)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(visited[0], (octree.root, octree.nodes[octree.root].aabb));
+        for pair in visited.windows(2) {
+            assert_eq!(octree.nodes[pair[1].0].parent, pair[0].0);
+            assert!(pair[0].1.contains(&pair[1].1));
+        }
+        let (leaf, leaf_aabb) = *visited.last().unwrap();
+        assert!(leaf_aabb.contains(&near_aabb));
+        assert!(octree.nodes[leaf]
+            .entities
+            .iter()
+            .any(|entity| entity.id.key == 1));
+        let tested: Vec<_> = trace
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                TraceStep::Test { entity, t, .. } => Some((*entity, *t)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tested, [(Entity::from_raw(1), Some(29.5))]);
+        match trace.steps[1] {
+            TraceStep::Octant { node, octant, .. } => {
+                assert_eq!((node, octant), (octree.root, BVec3::FALSE))
+            }
+            _ => panic!("Root should choose octant right after visit"),
+        }
+    }
+
+    #[test]
+    fn traced_raycast_matches_plain_one() {
+        let octree = churned_tree();
+        let mut rng = SplitMix64::new(0x6a09_e667_f3bc_c908);
+        let mut hits = 0;
+        for _ in 0..1000 {
+            let random =
+                |rng: &mut SplitMix64| Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32());
+            //Aimed at churned cells, so most rays hit.
+            let origin = random(&mut rng) * 120. - 40.;
+            let dir = match (random(&mut rng) * 40. - origin).try_normalize() {
+                Some(dir) => dir,
+                None => continue,
+            };
+            let ray = Ray::new(origin, dir);
+            let plain = octree
+                .raycast(&ray)
+                .map(|hit| (hit.entity, hit.t, hit.normal));
+            let (traced, trace) = octree.raycast_traced(&ray);
+            let traced = traced.map(|hit| (hit.entity, hit.t, hit.normal));
+            assert_eq!(plain, traced, "{origin} {dir}");
+            assert_eq!(trace.winner, plain.map(|(entity, t, _)| (entity, t)));
+            hits += plain.is_some() as usize;
+        }
+        assert!(hits > 100);
+    }
+
+    ///Tree after random inserts and removes, which leaves idle blocks behind.
+    fn churned_tree() -> EntityOctree {
+        let mut octree = blueprint_tree();
+        let mut rng = SplitMix64::new(756);
+        let mut live = Vec::new();
+        for i in 0..400 {
+            let cell = (Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * 40.).round();
+            if octree.insert_result(cube(i, cell)) == InsertOutcome::Inserted {
+                live.push((Entity::from_raw(i), AABB::new(cell - 0.5, cell + 0.5)));
+            }
+            if rng.next_u64() % 3 == 0 && !live.is_empty() {
+                let (entity, aabb) = live.swap_remove(rng.next_u64() as usize % live.len());
+                assert!(octree.remove(entity, aabb));
+            }
+        }
+        octree
+    }
+
+    #[test]
+    fn debug_validate_passes_valid_trees() {
+        assert_eq!(blueprint_tree().debug_validate(), Ok(()));
+        let octree = churned_tree();
+        assert!(octree.idle != EntityOctree::NULL_INDEX);
+        assert_eq!(octree.debug_validate(), Ok(()));
+    }
+
+    #[test]
+    fn debug_validate_catches_corruption() {
+        let broken = |corrupt: fn(&mut EntityOctree)| {
+            let mut octree = churned_tree();
+            corrupt(&mut octree);
+            octree.debug_validate().unwrap_err()
+        };
+        assert!(broken(|octree| octree.len += 1).starts_with("len is"));
+        let error = broken(|octree| {
+            let child = octree.nodes[octree.root].children().next().unwrap();
+            octree.nodes[child].parent = child;
+        });
+        assert!(error.contains("doesn't point back"));
+        let error = broken(|octree| {
+            let idle = octree.idle;
+            octree.nodes[idle].parent = idle;
+        });
+        assert!(error.starts_with("idle chain loops"));
+        //Entity copied up to parent, which also holds its bound.
+        broken(|octree| {
+            let index = (0..octree.nodes.len())
+                .find(|&index| {
+                    let node = &octree.nodes[index];
+                    !node.entities.is_empty() && node.parent != EntityOctree::NULL_INDEX
+                })
+                .unwrap();
+            let entity = octree.nodes[index].entities.iter().next().unwrap().clone();
+            let parent = octree.nodes[index].parent;
+            octree.nodes[parent].entities.insert(entity);
+        });
+    }
+
+    #[test]
+    fn ray_entities_stream_in_sorted_order() {
+        let mut octree = blueprint_tree();
+        //Tower of stacked blocks, with some beside it that ray passes by.
+        for y in 0..20 {
+            octree.insert_result(cube(y, Vec3::new(3., y as f32, -2.)));
+            octree.insert_result(cube(100 + y, Vec3::new(5., y as f32, -2.)));
+        }
+        let ray = Ray::new(Vec3::new(3., 40., -2.), Vec3::NEG_Y);
+        let mut out = Vec::new();
+        octree.ray_entities_ordered(&ray, f32::INFINITY, &mut out);
+        let streamed: Vec<_> = out.iter().map(|hit| (hit.entity, hit.t)).collect();
+        let sorted: Vec<_> = octree
+            .raycast_all(&ray)
+            .iter()
+            .map(|hit| (hit.entity, hit.t))
+            .collect();
+        assert_eq!(streamed, sorted);
+        let top_down: Vec<_> = (0..20).rev().map(Entity::from_raw).collect();
+        assert_eq!(
+            out.iter().map(|hit| hit.entity).collect::<Vec<_>>(),
+            top_down
+        );
+        //Stops at max_t, keeping nearer part of same order.
+        out.clear();
+        octree.ray_entities_ordered(&ray, 30., &mut out);
+        assert_eq!(out.len(), 10);
+        assert!(out
+            .iter()
+            .map(|hit| hit.entity)
+            .eq(top_down[..10].iter().copied()));
+
+        //Slanted rays through stacked blocks, which cross many node sides.
+        let mut rng = SplitMix64::new(731);
+        for _ in 0..200 {
+            let origin = Vec3::new(rng.next_f32() * 20. - 6., 30., rng.next_f32() * 20. - 12.);
+            let target = Vec3::new(4., rng.next_f32() * 20., -2.);
+            let ray = Ray::new(origin, (target - origin).normalize());
+            out.clear();
+            octree.ray_entities_ordered(&ray, f32::INFINITY, &mut out);
+            let streamed: Vec<_> = out.iter().map(|hit| (hit.entity, hit.t)).collect();
+            let sorted: Vec<_> = octree
+                .raycast_all(&ray)
+                .iter()
+                .map(|hit| (hit.entity, hit.t))
+                .collect();
+            assert_eq!(streamed, sorted);
+        }
+    }
+
+    #[test]
+    fn leaf_at_contains_aim_cell() {
+        let mut octree = blueprint_tree();
+        let mut rng = SplitMix64::new(745);
+        for i in 0..200 {
+            let cell = (Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * 12.).round()
+                - Vec3::new(6., 0., 6.);
+            octree.insert_result(cube(i, cell));
+        }
+        let mut depths = HashSet::new();
+        for x in -8..=8 {
+            for y in 0..=14 {
+                for z in -8..=8 {
+                    let cell = Vec3::new(x as f32, y as f32, z as f32);
+                    let leaf = octree.leaf_at(cell).unwrap();
+                    assert!(
+                        leaf.aabb.min().cmple(cell).all() && leaf.aabb.max().cmpgt(cell).all(),
+                        "{cell}"
+                    );
+                    assert_eq!(leaf.aabb, octree.nodes[leaf.index].aabb);
+                    //Nothing deeper holds cell.
+                    let node = &octree.nodes[leaf.index];
+                    let octant = cell.cmpge(node.aabb.center());
+                    assert_eq!(node.get_child_index(octant), EntityOctree::NULL_INDEX);
+                    depths.insert(leaf.depth);
+                }
+            }
+        }
+        //Populated region is split, so cells fall into leaves of several depths.
+        assert!(depths.len() > 1);
+        assert!(octree.leaf_at(Vec3::new(0., -10., 0.)).is_none());
+    }
+
+    #[test]
+    fn node_count_follows_split_and_unsplit() {
+        let mut octree = blueprint_tree();
+        let empty = octree.node_count();
+        let first = Vec3::new(-10., 10., -10.);
+        octree.insert_result(cube(1, first));
+        let one = octree.node_count();
+        assert!(one > empty);
+        //Cell in another octant of root splits its own chain down.
+        let second = Vec3::new(10., 40., 10.);
+        octree.insert_result(cube(2, second));
+        assert!(octree.node_count() > one);
+        assert_eq!(octree.debug_validate(), Ok(()));
+        assert!(octree.remove(Entity::from_raw(2), AABB::new(second - 0.5, second + 0.5)));
+        assert_eq!(octree.node_count(), one);
+        assert!(octree.remove(Entity::from_raw(1), AABB::new(first - 0.5, first + 0.5)));
+        assert_eq!(octree.node_count(), empty);
+        assert_eq!(octree.debug_validate(), Ok(()));
+    }
+
+    #[test]
+    fn iter_gives_every_live_entity_once() {
+        let mut octree = blueprint_tree();
+        let mut rng = SplitMix64::new(756);
+        let mut live = Vec::new();
+        for i in 0..400 {
+            let cell = (Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * 40.).round();
+            if octree.insert_result(cube(i, cell)) == InsertOutcome::Inserted {
+                live.push(Entity::from_raw(i));
+            }
+            if rng.next_u64() % 3 == 0 && !live.is_empty() {
+                let entity = live.swap_remove(rng.next_u64() as usize % live.len());
+                assert!(octree.remove_entity(entity));
+            }
+            assert_eq!(octree.iter().count(), octree.len());
+        }
+        let mut entities: Vec<_> = octree.iter().map(|entity| entity.entity()).collect();
+        entities.sort_unstable();
+        live.sort_unstable();
+        assert_eq!(entities, live);
+    }
+
+    #[test]
+    fn node_entity_counts_add_up_to_len() {
+        let mut octree = churned_tree();
+        let total = |octree: &EntityOctree| {
+            octree
+                .nodes()
+                .map(|node| node.entity_count())
+                .sum::<usize>()
+        };
+        assert!(octree.len() > 100);
+        assert_eq!(total(&octree), octree.len());
+        for node in octree.nodes() {
+            assert_eq!(node.is_leaf(), node.children().count() == 0);
+            for child in node.children() {
+                assert_eq!(child.depth(), node.depth() + 1);
+                assert!(node.aabb().contains(&child.aabb()));
+            }
+        }
+        //Oversize entity is in no node.
+        let collider = Collider::from_shape(Shape::Cuboid {
+            half_extents: Vec3::splat(20.),
+        });
+        let transform = Transform::from_xyz(0., 20., 0.);
+        let big = OctreeEntity::new(Entity::from_raw(1000), 1000, &collider, &transform);
+        assert_eq!(octree.insert_result(big), InsertOutcome::Inserted);
+        assert_eq!(octree.oversize_len(), 1);
+        assert_eq!(total(&octree) + octree.oversize_len(), octree.len());
+    }
+
+    #[test]
+    fn small_update_stays_in_node() {
+        let mut octree = blueprint_tree();
+        let center = octree.base_aabb().center();
+        //Across split planes of root, so root holds it, with cubes around making children.
+        octree.insert_result(cube(0, center));
+        for i in 1..9 {
+            octree.insert_result(cube(i, center + Vec3::splat(i as f32 * 2.)));
+        }
+        let node_of = |octree: &EntityOctree| octree.indices[&Entity::from_raw(0)].0;
+        let (node, nodes) = (node_of(&octree), octree.node_count());
+        assert_eq!(node, octree.root);
+        let nudged = center + Vec3::new(0.1, -0.2, 0.05);
+        let at = |pos: Vec3| cube(0, pos).aabb;
+        assert!(octree.update(Entity::from_raw(0), at(center), cube(0, nudged)));
+        assert_eq!(node_of(&octree), node);
+        assert_eq!(octree.node_count(), nodes);
+        let found = octree.intersect_sorted(AABB::new(nudged - 0.45, nudged - 0.4));
+        assert_eq!(found, [Entity::from_raw(0)]);
+        //Far move goes down into another octant.
+        let far = center + Vec3::new(-20., 10., -20.);
+        assert!(octree.update(Entity::from_raw(0), at(nudged), cube(0, far)));
+        assert_ne!(node_of(&octree), node);
+        assert!(octree.node_count() > nodes);
+        assert_eq!(
+            octree.intersect_sorted(AABB::new(far - 0.25, far + 0.25)),
+            [Entity::from_raw(0)]
+        );
+        assert_eq!(octree.len(), 9);
+        assert_eq!(octree.debug_validate(), Ok(()));
+    }
+
+    #[test]
+    fn remove_by_entity_after_transform_moved() {
+        let mut octree = blueprint_tree();
+        let mut transforms: Vec<_> = (0..30)
+            .map(|i| Transform::from_xyz((i % 6) as f32 * 3., (i / 6) as f32 * 4., -5.))
+            .collect();
+        for (i, transform) in transforms.iter().enumerate() {
+            octree.insert_result(cube(i as u32, transform.translation));
+        }
+        //Structures are moved without tree knowing, so bounds they give now are off.
+        for transform in transforms.iter_mut() {
+            transform.translation += Vec3::new(10.3, -0.7, 4.1);
+        }
+        for i in 0..15 {
+            assert!(octree.remove_entity(Entity::from_raw(i)));
+            assert!(!octree.remove_entity(Entity::from_raw(i)));
+        }
+        //Walk by drifted bound misses, and falls back to entity.
+        for i in 15..30 {
+            let drifted = transforms[i as usize].translation;
+            assert!(octree.remove(Entity::from_raw(i), AABB::new(drifted - 0.5, drifted + 0.5)));
+            assert_eq!(octree.debug_validate(), Ok(()));
+        }
+        assert_eq!(octree.len(), 0);
+        assert_eq!(octree.iter().count(), 0);
+        assert_eq!(octree.nodes().count(), 0);
+    }
+
+    #[test]
+    fn update_across_octants_keeps_len() {
+        let mut octree = blueprint_tree();
+        for i in 0..50 {
+            octree.insert_result(cube(i, Vec3::new((i % 5) as f32, (i / 5) as f32, 0.)));
+        }
+        let len = octree.len();
+        //Each move crosses into another octant of root, then back deep into crowded corner.
+        let moves = [
+            Vec3::new(-20., 10., -20.),
+            Vec3::new(20., 50., 20.),
+            Vec3::new(-20., 50., 20.),
+            Vec3::new(2., 20., 0.),
+            Vec3::new(4., 12., 1.),
+        ];
+        let mut old = cube(7, Vec3::new(2., 1., 0.)).aabb;
+        for (i, cell) in moves.into_iter().enumerate() {
+            let new = cube(7, cell);
+            let aabb = new.aabb;
+            assert!(octree.update(Entity::from_raw(7), old, new), "{i}");
+            old = aabb;
+            assert_eq!(octree.len(), len);
+            assert_eq!(octree.debug_validate(), Ok(()));
+            let found = octree.intersect_sorted(AABB::new(cell - 0.25, cell + 0.25));
+            assert_eq!(found, [Entity::from_raw(7)]);
+        }
+        //Old spot is left empty.
+        assert!(octree
+            .intersect_sorted(AABB::new(
+                Vec3::new(1.75, 0.75, -0.25),
+                Vec3::new(2.25, 1.25, 0.25)
+            ))
+            .is_empty());
+        let missing = cube(999, Vec3::ZERO);
+        assert!(!octree.update(Entity::from_raw(999), missing.aabb, missing));
+        assert_eq!(octree.len(), len);
+    }
+
+    #[test]
+    fn clear_then_insert_reuses_capacity() {
+        let mut octree = churned_tree();
+        //Far entity extends root past base bound, which clear restores.
+        octree.insert_result(cube(1000, Vec3::splat(200.)));
+        assert_ne!(octree.base_aabb, octree.initial_aabb);
+        let (nodes, indices) = (octree.nodes.capacity(), octree.indices.capacity());
+        let inserts = octree.counters().inserts;
+        octree.clear();
+        //Removed keys of map take up room until cleared, so it could only report more.
+        assert!(octree.indices.capacity() >= indices);
+        let indices = octree.indices.capacity();
+        assert_eq!(octree.len(), 0);
+        assert_eq!(octree.nodes().count(), 0);
+        assert_eq!(octree.base_aabb, octree.initial_aabb);
+        assert_eq!(octree.debug_validate(), Ok(()));
+        for i in 0..100 {
+            let cell = Vec3::new((i % 10) as f32, (i / 10) as f32, 0.);
+            assert_eq!(octree.insert_result(cube(i, cell)), InsertOutcome::Inserted);
+        }
+        assert_eq!(octree.len(), 100);
+        assert_eq!(octree.debug_validate(), Ok(()));
+        assert_eq!(octree.nodes.capacity(), nodes);
+        assert_eq!(octree.indices.capacity(), indices);
+        assert_eq!(octree.counters().inserts, inserts + 100);
+    }
+
+    #[test]
+    fn reserved_tree_doesnt_grow_while_inserting() {
+        let mut rng = SplitMix64::new(735);
+        let cells: Vec<_> = (0..500)
+            .map(|_| {
+                let t = Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32());
+                (Vec3::new(-30., 0., -30.) + t * 60.).round()
+            })
+            .collect();
+        let entities = || {
+            cells
+                .iter()
+                .enumerate()
+                .map(|(i, &cell)| cube(i as u32, cell))
+        };
+        let mut octree = blueprint_tree();
+        octree.reserve(octree.nodes_for(cells.len()));
+        let capacity = octree.nodes.capacity();
+        for (i, entity) in entities().enumerate() {
+            octree.insert_result(entity);
+            assert_eq!(octree.nodes.capacity(), capacity, "{i}");
+        }
+        //Bulk insert reserves same way up front.
+        let mut bulk = blueprint_tree();
+        assert!(bulk._insert_many(entities()).is_empty());
+        assert_eq!(bulk.nodes.capacity(), capacity);
+        assert_eq!(bulk.len(), octree.len());
+    }
+
+    #[test]
+    fn duplicate_insert_moves_entity() {
+        let mut octree = blueprint_tree();
+        let (old, new) = (Vec3::new(-10., 2., 4.), Vec3::new(12., 6., -8.));
+        assert_eq!(octree.insert_result(cube(1, old)), InsertOutcome::Inserted);
+        assert_eq!(
+            octree.insert_result(cube(1, old)),
+            InsertOutcome::AlreadyPresent
+        );
+        assert_eq!(octree.insert_result(cube(1, new)), InsertOutcome::Updated);
+        assert_eq!(octree.len(), 1);
+        let down = |pos: Vec3| Ray::new(pos + Vec3::Y * 10., Vec3::NEG_Y);
+        assert!(octree.raycast(&down(old)).is_none());
+        let hit = octree.raycast(&down(new)).unwrap();
+        assert_eq!(hit.entity, Entity::from_raw(1));
+        assert!((hit.point - (new + Vec3::Y * 0.5)).length() < 1e-4);
+    }
+
+    #[test]
+    fn random_ops_with_duplicates_match_model() {
+        let mut octree = blueprint_tree();
+        let mut model = HashMap::new();
+        let mut rng = SplitMix64::new(722);
+        for _ in 0..2000 {
+            let i = (rng.next_u64() % 40) as u32;
+            let cell = (Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * 30.).round();
+            if rng.next_u64() % 4 == 0 {
+                assert_eq!(
+                    octree.remove_entity(Entity::from_raw(i)),
+                    model.remove(&i).is_some()
+                );
+                continue;
+            }
+            let expected = match model.insert(i, cell) {
+                None => InsertOutcome::Inserted,
+                Some(at) if at == cell => InsertOutcome::AlreadyPresent,
+                Some(_) => InsertOutcome::Updated,
+            };
+            assert_eq!(octree.insert_result(cube(i, cell)), expected);
+            assert_eq!(octree.len(), model.len());
+        }
+        assert_eq!(octree.debug_validate(), Ok(()));
+        //Cells could be shared, as tree holds overlapping entities of leaf size.
+        for (&i, &cell) in model.iter() {
+            let found = octree.intersect_sorted(AABB::new(cell - 0.25, cell + 0.25));
+            assert!(found.contains(&Entity::from_raw(i)));
+        }
+    }
+
+    #[test]
+    fn translated_tree_agrees_with_shifted_rays() {
+        let mut octree = churned_tree();
+        //Oversize one moves with tree too.
+        octree.insert_result(OctreeEntity::from_aabb(
+            Entity::from_raw(1000),
+            1000,
+            AABB::new(Vec3::new(-30., -2., -30.), Vec3::new(30., -1., 30.)),
+        ));
+        assert_eq!(octree.oversize_len(), 1);
+        let offset = Vec3::new(-7., 3., 12.);
+        let mut rng = SplitMix64::new(724);
+        let rays: Vec<_> = (0..500)
+            .map(|_| {
+                let origin = Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * 80. - 20.;
+                let target = Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * 40.;
+                Ray::new(origin, (target - origin).normalize())
+            })
+            .collect();
+        let before: Vec<_> = rays
+            .iter()
+            .map(|ray| {
+                octree
+                    .raycast(ray)
+                    .map(|hit| (hit.entity, hit.t, hit.point))
+            })
+            .collect();
+        let len = octree.len();
+        octree.translate(offset);
+        assert_eq!(octree.len(), len);
+        assert_eq!(octree.debug_validate(), Ok(()));
+        let mut hits = 0;
+        for (ray, before) in rays.iter().zip(before) {
+            let shifted = Ray::new(ray.origin() + offset, ray.dir());
+            let after = octree.raycast(&shifted);
+            match (before, after) {
+                (Some((entity, t, point)), Some(hit)) => {
+                    hits += 1;
+                    assert_eq!(hit.entity, entity);
+                    assert!((hit.t - t).abs() < 1e-3);
+                    assert!((hit.point - (point + offset)).length() < 1e-3);
+                }
+                (None, None) => {}
+                (before, after) => panic!("{before:?} became {:?}", after.map(|hit| hit.entity)),
+            }
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn bounded_rejects_what_unbounded_extends_for() {
+        let far = Vec3::new(0., 100., -70.);
+        let far_box = AABB::new(far - 0.5, far + 0.5);
+        let mut extending = blueprint_tree();
+        extending.insert_result(cube(0, Vec3::ZERO));
+        assert_eq!(
+            extending.insert_result(cube(1, far)),
+            InsertOutcome::Inserted
+        );
+        assert!(extending.base_aabb().contains(&far_box));
+        assert_eq!(extending.intersect_sorted(far_box), [Entity::from_raw(1)]);
+        assert_eq!(extending.debug_validate(), Ok(()));
+
+        let mut bounded = blueprint_tree().bounded(true);
+        bounded.insert_result(cube(0, Vec3::ZERO));
+        let base = *bounded.base_aabb();
+        assert_eq!(
+            bounded.insert_result(cube(1, far)),
+            InsertOutcome::OutOfBounds
+        );
+        assert_eq!(*bounded.base_aabb(), base);
+        assert_eq!(bounded.len(), 1);
+        //Tree made like bounded one is bounded too.
+        assert_eq!(
+            bounded.empty_like().insert_result(cube(1, far)),
+            InsertOutcome::OutOfBounds
+        );
+    }
+
+    #[test]
+    fn insert_result_gives_each_outcome() {
+        let mut octree = blueprint_tree().bounded(true);
+        let cell = Vec3::new(3., 4., 5.);
+        assert_eq!(octree.insert_result(cube(0, cell)), InsertOutcome::Inserted);
+        assert_eq!(
+            octree.insert_result(cube(0, cell)),
+            InsertOutcome::AlreadyPresent
+        );
+        assert_eq!(
+            octree.insert_result(cube(0, cell + Vec3::X)),
+            InsertOutcome::Updated
+        );
+        //Small ones overlapping in octant of smallest node can't be separated.
+        let speck = |i: u32, min: Vec3| {
+            OctreeEntity::from_aabb(Entity::from_raw(i), i as u64, AABB::new(min, min + 0.2))
+        };
+        assert_eq!(
+            octree.insert_result(speck(1, cell - 0.4)),
+            InsertOutcome::Inserted
+        );
+        assert_eq!(
+            octree.insert_result(speck(2, cell - 0.3)),
+            InsertOutcome::TooDeep
+        );
+        assert_eq!(
+            octree.insert_result(cube(3, Vec3::new(0., 100., 0.))),
+            InsertOutcome::OutOfBounds
+        );
+        //Refused move leaves entity where it was.
+        assert_eq!(
+            octree.insert_result(cube(0, Vec3::new(0., -10., 0.))),
+            InsertOutcome::OutOfBounds
+        );
+        assert_eq!(octree.len(), 2);
+        assert_eq!(
+            octree.intersect_sorted(AABB::new(cell + Vec3::X - 0.25, cell + Vec3::X + 0.25)),
+            [Entity::from_raw(0)]
+        );
+        assert_eq!(octree.debug_validate(), Ok(()));
     }
 }
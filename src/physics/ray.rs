@@ -1,10 +1,13 @@
-use crate::physics::aabb::AABB;
+use crate::{consts::RAY_EPS, physics::aabb::AABB};
 
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug, Display},
+};
 
 use bevy::{
-    math::{BVec3, Vec3},
-    prelude::Entity,
+    math::{BVec3, Vec2, Vec3},
+    prelude::{Camera, Entity, GlobalTransform},
 };
 
 ///Caching ray data.
@@ -16,7 +19,10 @@ pub struct Ray {
 }
 
 impl Ray {
+    ///Direction component smaller than `RAY_EPS` is flushed to zero.
+    ///So grazing ray is traversed as parallel one, instead of with huge reciprocal.
     pub fn new(origin: Vec3, dir: Vec3) -> Self {
+        let dir = Vec3::select(dir.abs().cmplt(Vec3::splat(RAY_EPS)), Vec3::ZERO, dir);
         Self {
             origin,
             dir,
@@ -24,6 +30,41 @@ impl Ray {
         }
     }
 
+    ///Ray from camera through cursor, which is in logical pixels from bottom left of viewport
+    ///as `Window::cursor_position` gives. None when camera has no viewport or projection can't be inverted.
+    ///```ignore
+    ///fn pick(windows: Res<Windows>, camera: Query<(&Camera, &GlobalTransform)>, octree: Query<&Octree>) {
+    ///    if let (Ok((camera, transform)), Ok(octree)) = (camera.get_single(), octree.get_single()) {
+    ///        let ray = windows
+    ///            .get_primary()
+    ///            .and_then(|window| window.cursor_position())
+    ///            .and_then(|cursor| Ray::from_screen(camera, transform, cursor));
+    ///        if let Some(hit) = ray.and_then(|ray| octree.raycast(&ray)) {
+    ///            info!("Picked {:?}", hit.entity);
+    ///        }
+    ///    }
+    ///}
+    ///```
+    pub fn from_screen(
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        cursor: Vec2,
+    ) -> Option<Self> {
+        //Degenerate projection unprojects to NaN, which bevy doesn't catch for direction.
+        camera
+            .viewport_to_world(camera_transform, cursor)
+            .filter(|ray| ray.origin.is_finite() && ray.direction.is_finite())
+            .map(|ray| Self::new(ray.origin, ray.direction))
+    }
+
+    pub fn origin(&self) -> Vec3 {
+        self.origin
+    }
+
+    pub fn dir(&self) -> Vec3 {
+        self.dir
+    }
+
     pub fn point(&self, t: f32) -> Vec3 {
         self.origin + self.dir * t
     }
@@ -32,6 +73,35 @@ impl Ray {
         (vec3 - self.origin) * self.recip_dir
     }
 
+    ///Distances where ray enters and leaves sphere. Ones behind origin are negative.
+    pub fn sphere_span(&self, center: Vec3, radius: f32) -> Option<(f32, f32)> {
+        let a = self.dir.length_squared();
+        if a == 0. {
+            return None;
+        }
+        let origin = self.origin - center;
+        let b = origin.dot(self.dir);
+        let c = origin.length_squared() - radius * radius;
+        let discriminant = b * b - a * c;
+        if discriminant < 0. {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        Some(((-b - root) / a, (-b + root) / a))
+    }
+
+    ///Distance to where ray hits sphere. Exit is given if ray starts inside, as in `AABB::intersects_ray`.
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> Option<f32> {
+        let (enter, leave) = self.sphere_span(center, radius)?;
+        if leave <= 0. {
+            None
+        } else if enter <= 0. {
+            Some(leave)
+        } else {
+            Some(enter)
+        }
+    }
+
     ///Extract octant from ray's initial traverse at certain spot.
     /// - None if ray is included on axis and base planes.
     pub fn octant_at(&self, pivot: f32, aabb: AABB) -> Option<BVec3> {
@@ -69,6 +139,13 @@ impl Ray {
     pub fn next_octant(&self, mut octant: BVec3, pivot: f32, bound: AABB) -> BVec3 {
         //Get vector from given octant's center to ray pivot position.
         let check = self.origin + pivot * self.dir - bound.get_octant(octant).center();
+        //Ray only leaves through faces it heads to. Otherwise rounding near edge could turn it back,
+        //and two octants would keep pointing each other.
+        let outward = (check * self.dir).cmpgt(Vec3::ZERO);
+        if !outward.any() {
+            return octant;
+        }
+        let check = Vec3::select(outward, check, Vec3::ZERO);
         //For determine which element of vector is the greatest.
         let check_abs = check.abs();
         //Determining which element is the greatest. It can be more than one.
@@ -113,19 +190,153 @@ impl Ray {
     }
 }
 
-pub struct RayHitInfo {
-    pub entity: Entity,
+pub struct RayHitInfo<T = Entity> {
+    pub entity: T,
+    ///Key entity is inserted with. Breaks ties of distance.
+    pub key: u64,
     pub aabb: AABB,
     ///Distance
     pub t: f32,
+    ///Where ray meets surface.
+    pub point: Vec3,
+    ///Outward normal of surface at point. When ray starts inside, point and normal are of exit.
+    pub normal: Vec3,
 }
 
-impl RayHitInfo {
-    pub fn new(entity: Entity, aabb: AABB, t: f32) -> Self {
+impl<T> RayHitInfo<T> {
+    pub fn new(entity: T, key: u64, aabb: AABB, ray: &Ray, t: f32, normal: Vec3) -> Self {
         Self {
             entity,
+            key,
             aabb,
             t,
+            point: ray.point(t),
+            normal,
         }
     }
 }
+
+///One decision made while raycasting through octree.
+#[derive(Clone, Debug)]
+pub enum TraceStep<T = Entity> {
+    ///Node ray entered. Pivot is distance where traversal stood at that moment.
+    Visit {
+        index: usize,
+        aabb: AABB,
+        pivot: f32,
+    },
+    ///Child octant chosen, first by `octant_at` and then by `next_octant`.
+    Octant {
+        node: usize,
+        octant: BVec3,
+        pivot: f32,
+    },
+    ///Entity tested against ray. Distance if hit.
+    Test {
+        entity: T,
+        aabb: AABB,
+        t: Option<f32>,
+    },
+}
+
+///Steps of a raycast in order they are made, and which entity won.
+#[derive(Clone, Debug)]
+pub struct RayTrace<T = Entity> {
+    pub steps: Vec<TraceStep<T>>,
+    pub winner: Option<(T, f32)>,
+}
+
+//Derive would ask id to have default too.
+impl<T> Default for RayTrace<T> {
+    fn default() -> Self {
+        Self {
+            steps: Vec::new(),
+            winner: None,
+        }
+    }
+}
+
+impl<T> RayTrace<T> {
+    pub fn visited(&self) -> impl Iterator<Item = &AABB> {
+        self.steps.iter().filter_map(|step| match step {
+            TraceStep::Visit { aabb, .. } => Some(aabb),
+            _ => None,
+        })
+    }
+
+    pub fn tested(&self) -> impl Iterator<Item = (&AABB, Option<f32>)> {
+        self.steps.iter().filter_map(|step| match step {
+            TraceStep::Test { aabb, t, .. } => Some((aabb, *t)),
+            _ => None,
+        })
+    }
+}
+
+impl<T: Debug> Display for RayTrace<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in self.steps.iter() {
+            match step {
+                TraceStep::Visit { index, aabb, pivot } => writeln!(
+                    f,
+                    "visit node {index} at pivot {pivot:.3}, {:?}..{:?}",
+                    aabb.min(),
+                    aabb.max()
+                )?,
+                TraceStep::Octant {
+                    node,
+                    octant,
+                    pivot,
+                } => writeln!(
+                    f,
+                    "  node {node} octant {}{}{} at pivot {pivot:.3}",
+                    if octant.x { '+' } else { '-' },
+                    if octant.y { '+' } else { '-' },
+                    if octant.z { '+' } else { '-' }
+                )?,
+                TraceStep::Test { entity, t, .. } => match t {
+                    Some(t) => writeln!(f, "  test {entity:?} hit at {t:.3}")?,
+                    None => writeln!(f, "  test {entity:?} missed")?,
+                },
+            }
+        }
+        match &self.winner {
+            Some((entity, t)) => write!(f, "winner {entity:?} at {t:.3}"),
+            None => write!(f, "no hit"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_octant_leaves_only_toward_heading() {
+        let bound = AABB::new(Vec3::splat(-1.), Vec3::ONE);
+        //Exit point is rounded just past far z side, while ray heads back toward near one.
+        let ray = Ray::new(Vec3::new(0., -0.5, 1e-6), Vec3::new(1., 0., -1.));
+        assert_eq!(
+            ray.next_octant(BVec3::FALSE, 0., bound),
+            BVec3::new(true, false, false)
+        );
+        //Pivot that goes toward no side keeps octant.
+        let ray = Ray::new(Vec3::splat(-0.5), Vec3::X);
+        assert_eq!(ray.next_octant(BVec3::FALSE, 0., bound), BVec3::FALSE);
+    }
+
+    #[test]
+    fn sphere_hit_from_outside_inside_and_past() {
+        let center = Vec3::new(2., -1., 3.);
+        let ray = Ray::new(center - Vec3::X * 5., Vec3::X);
+        assert_eq!(ray.intersects_sphere(center, 2.), Some(3.));
+        assert_eq!(ray.sphere_span(center, 2.), Some((3., 7.)));
+        //Starting inside, exit is given.
+        let ray = Ray::new(center, Vec3::NEG_Y);
+        assert_eq!(ray.intersects_sphere(center, 2.), Some(2.));
+        //Passing beside or heading away misses.
+        let ray = Ray::new(center + Vec3::new(-5., 2.1, 0.), Vec3::X);
+        assert_eq!(ray.intersects_sphere(center, 2.), None);
+        let ray = Ray::new(center + Vec3::X * 5., Vec3::X);
+        assert_eq!(ray.intersects_sphere(center, 2.), None);
+    }
+}
@@ -0,0 +1,354 @@
+use crate::{
+    rng::fnv1a,
+    save::{BuildSnapshot, SaveError, SaveFile},
+    structure::Structure,
+};
+
+use std::{
+    fs, io,
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    tasks::{IoTaskPool, Task},
+};
+
+///Folder of crash recovery ring. Separate from saves, so it could be wiped without care.
+pub const RECOVERY_DIR: &str = "saves/recovery";
+
+const HEADER: &str = "recovery";
+
+///Crash recovery ring. Changed build is snapshotted every interval into one of few rotating files.
+///Ring is deleted on clean exit, so any file found on startup is from a session that didn't end well.
+#[derive(Resource)]
+pub struct RecoveryRing {
+    ///Seconds between snapshots of changed build.
+    pub interval: f32,
+    elapsed: f32,
+    ///Whether build changed since last snapshot.
+    dirty: bool,
+    ///Sequence of next snapshot. Newest snapshot has greatest one.
+    sequence: u64,
+    ///Write in flight. Next snapshot waits for it instead of blocking frame.
+    writing: Option<Task<()>>,
+}
+
+impl Default for RecoveryRing {
+    fn default() -> Self {
+        Self {
+            interval: Self::DEFAULT_INTERVAL,
+            elapsed: 0.,
+            dirty: false,
+            sequence: 0,
+            writing: None,
+        }
+    }
+}
+
+impl RecoveryRing {
+    pub const DEFAULT_INTERVAL: f32 = 30.;
+    ///Number of rotating files.
+    pub const LEN: u64 = 4;
+
+    ///Path of ring slot.
+    pub fn slot_path(slot: u64) -> PathBuf {
+        Path::new(RECOVERY_DIR).join(format!("ring-{slot}.txt"))
+    }
+
+    ///Continues after snapshots already in ring, such as ones of recovered session.
+    pub fn resume() -> Self {
+        let sequence = (0..Self::LEN)
+            .filter_map(|slot| fs::read_to_string(Self::slot_path(slot)).ok())
+            .filter_map(|text| read_meta(&text).ok())
+            .map(|meta| meta.sequence + 1)
+            .max()
+            .unwrap_or(0);
+        Self {
+            sequence,
+            ..default()
+        }
+    }
+
+    ///Blocks until write in flight is done. Only for exit, where there is no next frame anyway.
+    fn finish_writing(&mut self) {
+        if let Some(task) = self.writing.take() {
+            while !task.is_finished() {
+                std::thread::yield_now();
+            }
+        }
+    }
+}
+
+///What snapshot tells about itself.
+#[derive(Clone, Copy, Debug)]
+pub struct SnapshotMeta {
+    pub sequence: u64,
+    ///Seconds since unix epoch.
+    pub written: u64,
+    ///Hash of save part, to tell snapshot cut short by crash.
+    pub hash: u64,
+    pub structures: usize,
+}
+
+///Newest snapshot that could be recovered. Save is read again when accepted.
+pub struct RecoverySnapshot {
+    pub path: PathBuf,
+    pub meta: SnapshotMeta,
+}
+
+impl RecoverySnapshot {
+    ///What is shown to user before recovering.
+    pub fn describe(&self) -> String {
+        let now = unix_now();
+        let minutes = now.saturating_sub(self.meta.written) / 60;
+        format!(
+            "Recover unsaved session?\n{} structures, from {} minutes ago",
+            self.meta.structures, minutes
+        )
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or(0)
+}
+
+///Snapshot is header line followed by save as it is written.
+pub fn encode_snapshot(sequence: u64, written: u64, save: &SaveFile) -> String {
+    let body = save.to_string();
+    format!(
+        "{HEADER}\t{sequence}\t{written}\t{:016x}\t{}\n{body}",
+        fnv1a(&body),
+        save.structures.len()
+    )
+}
+
+///Header of snapshot, without checking save part.
+fn read_meta(text: &str) -> Result<SnapshotMeta, SaveError> {
+    let header = text.lines().next().unwrap_or_default();
+    let corrupt = || SaveError::Corrupt {
+        line: 1,
+        reason: "bad recovery header".to_owned(),
+    };
+    match header.split('\t').collect::<Vec<_>>()[..] {
+        [HEADER, sequence, written, hash, structures] => Ok(SnapshotMeta {
+            sequence: sequence.parse().map_err(|_| corrupt())?,
+            written: written.parse().map_err(|_| corrupt())?,
+            hash: u64::from_str_radix(hash, 16).map_err(|_| corrupt())?,
+            structures: structures.parse().map_err(|_| corrupt())?,
+        }),
+        _ => Err(corrupt()),
+    }
+}
+
+///Reads snapshot, refusing one whose save part doesn't match its hash.
+pub fn decode_snapshot(text: &str) -> Result<(SnapshotMeta, SaveFile), SaveError> {
+    let meta = read_meta(text)?;
+    let body = text.split_once('\n').map_or("", |(_, body)| body);
+    if fnv1a(body) != meta.hash {
+        return Err(SaveError::Corrupt {
+            line: 2,
+            reason: "recovery snapshot doesn't match its hash".to_owned(),
+        });
+    }
+    Ok((meta, SaveFile::parse(body)?))
+}
+
+///Reads snapshot at path again, such as when user accepts recovery.
+pub fn read_snapshot(path: &Path) -> Result<SaveFile, SaveError> {
+    decode_snapshot(&fs::read_to_string(path)?).map(|(_, save)| save)
+}
+
+///Newest valid snapshot of ring. Broken one falls back to older one.
+pub fn newest_snapshot() -> Option<RecoverySnapshot> {
+    newest_of((0..RecoveryRing::LEN).map(RecoveryRing::slot_path))
+}
+
+fn newest_of(paths: impl Iterator<Item = PathBuf>) -> Option<RecoverySnapshot> {
+    let mut snapshots: Vec<_> = paths
+        .filter_map(|path| {
+            let text = fs::read_to_string(&path).ok()?;
+            match decode_snapshot(&text) {
+                Ok((meta, _)) => Some(RecoverySnapshot { path, meta }),
+                Err(e) => {
+                    warn!("Recovery snapshot {} is skipped: {e}", path.display());
+                    None
+                }
+            }
+        })
+        .collect();
+    snapshots.sort_unstable_by_key(|snapshot| snapshot.meta.sequence);
+    snapshots.pop()
+}
+
+///Moves ring aside, so it isn't offered again but isn't lost either.
+pub fn archive_ring() -> io::Result<()> {
+    let archive = format!("{RECOVERY_DIR}-archived-{}", unix_now());
+    fs::rename(RECOVERY_DIR, archive)
+}
+
+fn clear_ring() {
+    match fs::remove_dir_all(RECOVERY_DIR) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to clear recovery ring: {e}"),
+    }
+}
+
+///Writes aside, flushes to disk and then swaps, so crash while writing leaves old slot intact.
+fn write_slot(path: &Path, text: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension("tmp");
+    let mut file = fs::File::create(&temp)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(temp, path)
+}
+
+///Starts ring for session being entered.
+pub fn start_recovery_ring(mut commands: Commands) {
+    commands.insert_resource(RecoveryRing::resume());
+}
+
+///Marks ring dirty when any structure is placed, moved or removed.
+pub fn mark_recovery_dirty(
+    ring: Option<ResMut<RecoveryRing>>,
+    changed: Query<(), (With<Structure>, Or<(Added<Structure>, Changed<Transform>)>)>,
+    removed: RemovedComponents<Structure>,
+) {
+    if let Some(mut ring) = ring {
+        if !ring.dirty && (!changed.is_empty() || removed.iter().next().is_some()) {
+            ring.dirty = true;
+        }
+    }
+}
+
+///Snapshots changed build into next slot. Capture is on frame, but writing is on io pool.
+pub fn write_recovery_snapshot(
+    time: Res<Time>,
+    ring: Option<ResMut<RecoveryRing>>,
+    snapshot: BuildSnapshot,
+) {
+    let mut ring = match ring {
+        Some(ring) => ring,
+        None => return,
+    };
+    ring.elapsed += time.delta_seconds();
+    if !ring.dirty || ring.elapsed < ring.interval {
+        return;
+    }
+    if ring
+        .writing
+        .as_ref()
+        .map_or(false, |task| !task.is_finished())
+    {
+        return;
+    }
+    let save = match snapshot.capture() {
+        Ok(save) => save,
+        Err(e) => {
+            warn!("Recovery snapshot is skipped: {e}");
+            return;
+        }
+    };
+    let text = encode_snapshot(ring.sequence, unix_now(), &save);
+    let path = RecoveryRing::slot_path(ring.sequence % RecoveryRing::LEN);
+    ring.writing = Some(IoTaskPool::get().spawn(async move {
+        if let Err(e) = write_slot(&path, &text) {
+            warn!("Failed to write recovery snapshot {}: {e}", path.display());
+        }
+    }));
+    ring.sequence += 1;
+    ring.elapsed = 0.;
+    ring.dirty = false;
+}
+
+///Deletes ring when session ends cleanly, whether by leaving game or by quitting.
+pub fn clear_recovery_ring(mut commands: Commands, ring: Option<ResMut<RecoveryRing>>) {
+    if let Some(mut ring) = ring {
+        ring.finish_writing();
+        commands.remove_resource::<RecoveryRing>();
+        clear_ring();
+    }
+}
+
+///Deletes ring when app is about to exit. Runs after autosave, which is what survives clean exit.
+pub fn clear_recovery_on_exit(
+    exit: EventReader<AppExit>,
+    commands: Commands,
+    ring: Option<ResMut<RecoveryRing>>,
+) {
+    if !exit.is_empty() {
+        clear_recovery_ring(commands, ring);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{physics::collider::Shape, save::SavedStructure};
+
+    fn save_of(len: usize) -> SaveFile {
+        SaveFile {
+            structures: (0..len)
+                .map(|i| SavedStructure {
+                    transform: Transform::from_xyz(i as f32, 0., 0.),
+                    shape: Shape::Sphere { radius: 0.5 },
+                    meshes: vec![(0, "cube".to_owned())],
+                    material: (0, "stone".to_owned()),
+                    tags: Vec::new(),
+                })
+                .collect(),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn decode_refuses_snapshot_cut_short() {
+        let text = encode_snapshot(3, 100, &save_of(2));
+        let (meta, save) = decode_snapshot(&text).unwrap();
+        assert_eq!((meta.sequence, meta.written, meta.structures), (3, 100, 2));
+        assert_eq!(save.structures.len(), 2);
+        assert!(decode_snapshot(&text[..text.len() - 5]).is_err());
+        assert!(decode_snapshot("recovery\t3\tnope").is_err());
+    }
+
+    #[test]
+    fn corrupted_newest_falls_back_to_older() {
+        let dir = std::env::temp_dir().join(format!("recovery_test_{}", std::process::id()));
+        let paths: Vec<_> = (0..RecoveryRing::LEN)
+            .map(|slot| dir.join(format!("ring-{slot}.txt")))
+            .collect();
+        //Session wrote three snapshots and crashed, so ring wasn't cleared.
+        let saves: Vec<_> = (0..3).map(|i| save_of(i + 1)).collect();
+        for (sequence, save) in saves.iter().enumerate() {
+            let text = encode_snapshot(sequence as u64, 0, save);
+            write_slot(&paths[sequence], &text).unwrap();
+        }
+        let newest = newest_of(paths.iter().cloned()).unwrap();
+        assert_eq!(newest.meta.sequence, 2);
+        //Crash in the middle of newest write.
+        let text = fs::read_to_string(&paths[2]).unwrap();
+        fs::write(&paths[2], &text[..text.len() / 2]).unwrap();
+        let recovered = newest_of(paths.iter().cloned());
+        let loaded = recovered
+            .as_ref()
+            .map(|snapshot| read_snapshot(&snapshot.path));
+        let _ = fs::remove_dir_all(&dir);
+        let recovered = recovered.unwrap();
+        assert_eq!(recovered.path, paths[1]);
+        assert_eq!(recovered.meta.sequence, 1);
+        assert_eq!(recovered.meta.structures, 2);
+        let loaded = loaded.unwrap().unwrap();
+        assert_eq!(fnv1a(&loaded.to_string()), recovered.meta.hash);
+        assert_eq!(loaded.to_string(), saves[1].to_string());
+    }
+}
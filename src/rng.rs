@@ -0,0 +1,164 @@
+use crate::save::PendingLoad;
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+
+///Small seeded generator, so scenes are reproducible without extra dependency.
+#[derive(Clone, Debug)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    ///Uniform in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+///Root seed of session and named streams derived from it.
+///Every stream has its own state, so drawing from one never changes sequence of another.
+#[derive(Resource, Debug)]
+pub struct RngSeeds {
+    root: u64,
+    streams: HashMap<&'static str, SplitMix64>,
+}
+
+impl RngSeeds {
+    pub fn new(root: u64) -> Self {
+        Self {
+            root,
+            streams: HashMap::default(),
+        }
+    }
+
+    ///Seed that differs every run.
+    pub fn from_entropy() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+            hasher.write_u128(now.as_nanos());
+        }
+        Self::new(hasher.finish())
+    }
+
+    pub fn root(&self) -> u64 {
+        self.root
+    }
+
+    ///Whether any stream is drawn from. Root can't be changed after that.
+    pub fn is_consumed(&self) -> bool {
+        !self.streams.is_empty()
+    }
+
+    ///Replaces root. Refused once drawn, since earlier draws would not be reproducible.
+    pub fn set_root(&mut self, root: u64) -> Result<(), String> {
+        if self.is_consumed() {
+            return Err(format!(
+                "streams are already drawn from: {:?}",
+                self.streams.keys().collect::<Vec<_>>()
+            ));
+        }
+        self.root = root;
+        Ok(())
+    }
+
+    ///Stream of given name. Same root and name always give same sequence.
+    pub fn stream(&mut self, name: &'static str) -> &mut SplitMix64 {
+        let root = self.root;
+        self.streams.entry(name).or_insert_with(|| {
+            //Mixed once, so similar names don't give similar sequences.
+            SplitMix64::new(SplitMix64::new(root ^ fnv1a(name)).next_u64())
+        })
+    }
+}
+
+///Hash that is stable across runs and platforms, unlike std hasher.
+pub fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+///Parses seed as it is shown, which is hex with optional 0x.
+pub fn parse_seed(text: &str) -> Option<u64> {
+    u64::from_str_radix(text.trim_start_matches("0x"), 16).ok()
+}
+
+///Establishes seed of session. Seed of loaded save is kept, so its session could be reproduced.
+pub fn establish_seeds(mut commands: Commands, pending: Option<Res<PendingLoad>>) {
+    let seeds = match pending.and_then(|pending| pending.1.seed) {
+        Some(seed) => RngSeeds::new(seed),
+        None => RngSeeds::from_entropy(),
+    };
+    info!("Session seed is {:016x}", seeds.root());
+    commands.insert_resource(seeds);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(seeds: &mut RngSeeds, name: &'static str, count: usize) -> Vec<u64> {
+        let stream = seeds.stream(name);
+        (0..count).map(|_| stream.next_u64()).collect()
+    }
+
+    #[test]
+    fn streams_dont_perturb_each_other() {
+        let mut alone = RngSeeds::new(0xdead_beef);
+        let brush = draw(&mut alone, "brush", 16);
+        //Draws from other streams in between leave brush sequence as it is.
+        let mut mixed = RngSeeds::new(0xdead_beef);
+        let mut interleaved = Vec::new();
+        for _ in 0..16 {
+            draw(&mut mixed, "particles", 3);
+            interleaved.extend(draw(&mut mixed, "brush", 1));
+            draw(&mut mixed, "debris", 1);
+        }
+        assert_eq!(interleaved, brush);
+        //Named streams and roots are apart from each other.
+        assert_ne!(
+            draw(&mut RngSeeds::new(0xdead_beef), "particles", 16),
+            brush
+        );
+        assert_ne!(draw(&mut RngSeeds::new(0xdead_bef0), "brush", 16), brush);
+    }
+
+    #[test]
+    fn root_is_fixed_once_drawn() {
+        let mut seeds = RngSeeds::new(1);
+        assert!(!seeds.is_consumed());
+        assert_eq!(seeds.set_root(2), Ok(()));
+        let first = draw(&mut seeds, "demo", 4);
+        assert!(seeds.is_consumed());
+        assert!(seeds.set_root(3).is_err());
+        assert_eq!(seeds.root(), 2);
+        assert_eq!(draw(&mut RngSeeds::new(2), "demo", 4), first);
+    }
+
+    #[test]
+    fn seed_text_and_names_are_stable() {
+        assert_eq!(parse_seed("0x00ff"), Some(0xff));
+        assert_eq!(parse_seed("DEADbeef"), Some(0xdead_beef));
+        assert_eq!(parse_seed("xyz"), None);
+        assert_eq!(parse_seed(&format!("{:016x}", u64::MAX)), Some(u64::MAX));
+        //Known FNV-1a values, so saved seeds keep meaning across builds.
+        assert_eq!(fnv1a(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
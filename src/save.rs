@@ -0,0 +1,741 @@
+use crate::{
+    asset::{Meshes, StandardMaterials},
+    environment::SceneEnvironment,
+    physics::{
+        collider::{Collider, Shape},
+        octree::Octree,
+    },
+    rng::{parse_seed, RngSeeds},
+    states::in_game::compass::Waypoint,
+    structure::{Structure, StructureDesc, WorldMutator},
+    tag::{StructureTags, TagRegistry},
+    task::*,
+};
+
+use std::{
+    fmt::{self, Display, Write as _},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    app::AppExit,
+    asset::{Asset, HandleId},
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::{hashbrown, HashMap},
+};
+
+pub const AUTOSAVE_PATH: &str = "saves/autosave.txt";
+
+const HEADER: &str = "game_made_with_rust save 1";
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    ///File exists but couldn't be understood.
+    Corrupt {
+        line: usize,
+        reason: String,
+    },
+    ///Handle in world has no name to be saved with.
+    Unnamed(&'static str),
+    ///Saved name isn't loaded anymore, such as asset being renamed.
+    UnknownAsset(String),
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Corrupt { line, reason } => write!(f, "corrupt at line {line}: {reason}"),
+            Self::Unnamed(kind) => write!(f, "{kind} has no name to be saved with"),
+            Self::UnknownAsset(name) => write!(f, "unknown asset {name}"),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+///Structure as it is written in save. Assets are referred by group index and name.
+#[derive(Clone, Debug)]
+pub struct SavedStructure {
+    pub transform: Transform,
+    pub shape: Shape,
+    pub meshes: Vec<(usize, String)>,
+    pub material: (usize, String),
+    ///Tags by name, since ids differ between runs.
+    pub tags: Vec<String>,
+}
+
+///Everything needed to restore a build.
+#[derive(Clone, Debug, Default)]
+pub struct SaveFile {
+    pub environment: SceneEnvironment,
+    ///Session seed when saved. Older saves have none.
+    pub seed: Option<u64>,
+    ///Waypoint of compass, if one is set.
+    pub waypoint: Option<Vec3>,
+    pub structures: Vec<SavedStructure>,
+}
+
+impl SaveFile {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        //Write whole file aside first, so crash while writing doesn't corrupt old save.
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, self.to_string())?;
+        fs::rename(temp, path)?;
+        Ok(())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SaveError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            _ => return Err(corrupt(1, "unknown header")),
+        }
+        let mut ret = Self::default();
+        for (index, line) in lines {
+            let line_no = index + 1;
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields[..] {
+                [""] => {}
+                ["environment", clear_color, ambient, brightness, sun] => {
+                    ret.environment = SceneEnvironment {
+                        clear_color: parse_color(clear_color, line_no)?,
+                        ambient: parse_color(ambient, line_no)?,
+                        ambient_brightness: parse_floats::<1>(brightness, line_no)?[0],
+                        sun_enabled: sun == "1",
+                    };
+                }
+                ["seed", seed] => {
+                    ret.seed = Some(parse_seed(seed).ok_or_else(|| corrupt(line_no, "bad seed"))?);
+                }
+                ["waypoint", point] => {
+                    ret.waypoint = Some(Vec3::from_array(parse_floats::<3>(point, line_no)?));
+                }
+                //Older saves have no tags field.
+                ["structure", transform, shape, material, meshes, ref tags @ ..]
+                    if tags.len() <= 1 =>
+                {
+                    let [tx, ty, tz, rx, ry, rz, rw, sx, sy, sz] =
+                        parse_floats::<10>(transform, line_no)?;
+                    //Zero rotation can't be normalized.
+                    let rotation = Vec4::new(rx, ry, rz, rw)
+                        .try_normalize()
+                        .ok_or_else(|| corrupt(line_no, "zero rotation"))?;
+                    let transform = Transform {
+                        translation: Vec3::new(tx, ty, tz),
+                        rotation: Quat::from_vec4(rotation),
+                        scale: Vec3::new(sx, sy, sz),
+                    };
+                    let shape = parse_shape(shape, line_no)?;
+                    if !shape.has_finite_bound(&transform) {
+                        return Err(corrupt(line_no, "shape too large"));
+                    }
+                    ret.structures.push(SavedStructure {
+                        transform,
+                        shape,
+                        meshes: meshes
+                            .split(' ')
+                            .map(|mesh| parse_asset(mesh, line_no))
+                            .collect::<Result<_, _>>()?,
+                        material: parse_asset(material, line_no)?,
+                        tags: tags
+                            .iter()
+                            .flat_map(|tags| tags.split(' '))
+                            .filter(|tag| !tag.is_empty())
+                            .map(str::to_owned)
+                            .collect(),
+                    });
+                }
+                _ => return Err(corrupt(line_no, "unknown entry")),
+            }
+        }
+        Ok(ret)
+    }
+
+    ///Captures structures in world. Every asset should have name.
+    pub fn capture<'a>(
+        environment: SceneEnvironment,
+        seed: Option<u64>,
+        waypoint: Option<Vec3>,
+        structures: impl Iterator<
+            Item = (
+                &'a Transform,
+                &'a Collider,
+                &'a Structure,
+                &'a StructureTags,
+            ),
+        >,
+        meshes: &Meshes,
+        standard_materials: &StandardMaterials,
+        tags: &TagRegistry,
+    ) -> Result<Self, SaveError> {
+        let mesh_names = names_of(&meshes[..]);
+        let material_names = names_of(&standard_materials[..]);
+        //Placement order, so same build is written same however its entities are numbered.
+        let mut structures: Vec<_> = structures.collect();
+        structures.sort_unstable_by_key(|(_, _, structure, _)| structure.placement());
+        let structures = structures
+            .into_iter()
+            .map(|(transform, collider, structure, structure_tags)| {
+                let desc = structure.desc(*transform, collider.clone(), structure_tags);
+                Ok(SavedStructure {
+                    transform: desc.transform,
+                    shape: collider.shape(),
+                    meshes: desc
+                        .meshes
+                        .iter()
+                        .map(|mesh| mesh_names.get(&mesh.id()).cloned())
+                        .collect::<Option<_>>()
+                        .ok_or(SaveError::Unnamed("mesh"))?,
+                    material: material_names
+                        .get(&desc.material.id())
+                        .cloned()
+                        .ok_or(SaveError::Unnamed("material"))?,
+                    tags: desc
+                        .tags
+                        .iter()
+                        .map(|tag| tags.name(*tag).to_owned())
+                        .collect(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            environment,
+            seed,
+            waypoint,
+            structures,
+        })
+    }
+
+    ///Resolves saved names into handles. Unknown tags are interned, so they survive next save.
+    pub fn descs(
+        &self,
+        meshes: &Meshes,
+        standard_materials: &StandardMaterials,
+        tags: &mut TagRegistry,
+    ) -> Result<Vec<StructureDesc>, SaveError> {
+        self.structures
+            .iter()
+            .map(|saved| {
+                Ok(StructureDesc {
+                    transform: saved.transform,
+                    collider: Collider::from_shape(saved.shape.clone()),
+                    meshes: saved
+                        .meshes
+                        .iter()
+                        .map(|asset| resolve(&meshes[..], asset))
+                        .collect::<Result<_, _>>()?,
+                    material: resolve(&standard_materials[..], &saved.material)?,
+                    tags: saved.tags.iter().map(|tag| tags.intern(tag)).collect(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Display for SaveFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        let environment = &self.environment;
+        writeln!(
+            f,
+            "environment\t{}\t{}\t{}\t{}",
+            write_floats(&environment.clear_color.as_rgba_f32()),
+            write_floats(&environment.ambient.as_rgba_f32()),
+            environment.ambient_brightness,
+            if environment.sun_enabled { 1 } else { 0 }
+        )?;
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed\t{seed:016x}")?;
+        }
+        if let Some(waypoint) = self.waypoint {
+            writeln!(f, "waypoint\t{}", write_floats(&waypoint.to_array()))?;
+        }
+        for saved in self.structures.iter() {
+            let t = &saved.transform;
+            let shape = match saved.shape {
+                Shape::Sphere { radius } => format!("sphere {radius}"),
+                Shape::CutSphere { radius, cut } => format!("cut_sphere {radius} {cut}"),
+                Shape::Cuboid { half_extents } => {
+                    format!("cuboid {}", write_floats(&half_extents.to_array()))
+                }
+            };
+            let meshes = saved
+                .meshes
+                .iter()
+                .map(|(group, name)| format!("{group}:{name}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(
+                f,
+                "structure\t{} {} {}\t{shape}\t{}:{}\t{meshes}\t{}",
+                write_floats(&t.translation.to_array()),
+                write_floats(&t.rotation.to_array()),
+                write_floats(&t.scale.to_array()),
+                saved.material.0,
+                saved.material.1,
+                saved.tags.join(" "),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn corrupt(line: usize, reason: impl Into<String>) -> SaveError {
+    SaveError::Corrupt {
+        line,
+        reason: reason.into(),
+    }
+}
+
+fn write_floats(floats: &[f32]) -> String {
+    let mut ret = String::new();
+    for (i, float) in floats.iter().enumerate() {
+        if i != 0 {
+            ret.push(' ');
+        }
+        //Debug format of float round trips exactly.
+        let _ = write!(ret, "{float:?}");
+    }
+    ret
+}
+
+fn parse_floats<const N: usize>(text: &str, line: usize) -> Result<[f32; N], SaveError> {
+    let floats = text
+        .split(' ')
+        .map(|float| float.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| corrupt(line, e.to_string()))?;
+    match <[f32; N]>::try_from(floats) {
+        Ok(floats) if floats.iter().all(|float| float.is_finite()) => Ok(floats),
+        _ => Err(corrupt(line, format!("expected {N} finite numbers"))),
+    }
+}
+
+fn parse_color(text: &str, line: usize) -> Result<Color, SaveError> {
+    let [r, g, b, a] = parse_floats::<4>(text, line)?;
+    Ok(Color::rgba(r, g, b, a))
+}
+
+fn parse_shape(text: &str, line: usize) -> Result<Shape, SaveError> {
+    match text.split_once(' ') {
+        Some(("sphere", params)) => {
+            let [radius] = parse_floats::<1>(params, line)?;
+            Ok(Shape::Sphere { radius })
+        }
+        Some(("cut_sphere", params)) => {
+            let [radius, cut] = parse_floats::<2>(params, line)?;
+            Ok(Shape::CutSphere { radius, cut })
+        }
+        Some(("cuboid", params)) => {
+            let half_extents = parse_floats::<3>(params, line)?;
+            Ok(Shape::Cuboid {
+                half_extents: Vec3::from_array(half_extents),
+            })
+        }
+        _ => Err(corrupt(line, format!("unknown shape {text}"))),
+    }
+}
+
+fn parse_asset(text: &str, line: usize) -> Result<(usize, String), SaveError> {
+    text.split_once(':')
+        .and_then(|(group, name)| Some((group.parse().ok()?, name.to_owned())))
+        .ok_or_else(|| corrupt(line, format!("bad asset {text}")))
+}
+
+fn resolve<T: Asset>(
+    groups: &[hashbrown::HashMap<&'static str, Handle<T>>],
+    (group, name): &(usize, String),
+) -> Result<Handle<T>, SaveError> {
+    groups
+        .get(*group)
+        .and_then(|map| map.get(name.as_str()))
+        .cloned()
+        .ok_or_else(|| SaveError::UnknownAsset(format!("{group}:{name}")))
+}
+
+///Reverse lookup of handle container groups.
+fn names_of<T: Asset>(
+    groups: &[hashbrown::HashMap<&'static str, Handle<T>>],
+) -> HashMap<HandleId, (usize, String)> {
+    let mut ret = HashMap::default();
+    for (group, map) in groups.iter().enumerate() {
+        for (name, handle) in map.iter() {
+            ret.insert(handle.id(), (group, name.to_string()));
+        }
+    }
+    ret
+}
+
+///Button that continues from save at path.
+#[derive(Component)]
+pub struct LoadSaveButton(pub PathBuf);
+
+///Save read from path, which is spawned once game is set up.
+#[derive(Resource)]
+pub struct PendingLoad(pub PathBuf, pub SaveFile);
+
+///Spawns pending load through world mutator. Runs after setup, since octree should exist.
+pub fn load_pending(
+    mut commands: Commands,
+    pending: Option<Res<PendingLoad>>,
+    mut mutator: WorldMutator,
+    meshes: Res<Meshes>,
+    standard_materials: Res<StandardMaterials>,
+    mut tags: ResMut<TagRegistry>,
+    mut results: EventWriter<TaskResultEvent>,
+) {
+    let pending = match pending {
+        Some(pending) => pending,
+        None => return,
+    };
+    commands.remove_resource::<PendingLoad>();
+    let PendingLoad(path, save) = &*pending;
+    match save.descs(&meshes, &standard_materials, &mut tags) {
+        Ok(descs) => {
+            let total = descs.len();
+            mutator.reserve(total);
+            let placed = descs
+                .into_iter()
+                .filter_map(|desc| mutator.spawn_structure(desc))
+                .count();
+            results.send(TaskResultEvent::Done {
+                kind: TaskKind::Load,
+                message: format!("loaded {placed} of {total} structures"),
+            });
+        }
+        //Game is already entered with nothing built, so user should know.
+        Err(e) => results.send(TaskResultEvent::Failed(
+            TaskError::new(TaskKind::Load, e)
+                .with_path(path)
+                .with_retry(TaskRetry::Load(path.clone()))
+                .unrecoverable(),
+        )),
+    }
+}
+
+///Access needed to capture current build.
+#[derive(SystemParam)]
+pub struct BuildSnapshot<'w, 's> {
+    octree: Query<'w, 's, (), With<Octree>>,
+    structures: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Collider,
+            &'static Structure,
+            &'static StructureTags,
+        ),
+    >,
+    environment: Res<'w, SceneEnvironment>,
+    seeds: Option<Res<'w, RngSeeds>>,
+    waypoint: Res<'w, Waypoint>,
+    meshes: Res<'w, Meshes>,
+    standard_materials: Res<'w, StandardMaterials>,
+    tags: Res<'w, TagRegistry>,
+}
+
+impl BuildSnapshot<'_, '_> {
+    pub fn has_build(&self) -> bool {
+        !self.octree.is_empty()
+    }
+
+    pub fn capture(&self) -> Result<SaveFile, SaveError> {
+        SaveFile::capture(
+            *self.environment,
+            self.seeds.as_ref().map(|seeds| seeds.root()),
+            self.waypoint.0,
+            self.structures.iter(),
+            &self.meshes,
+            &self.standard_materials,
+            &self.tags,
+        )
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), TaskError> {
+        self.capture()
+            .and_then(|save| save.write(path))
+            .map_err(|e| {
+                TaskError::new(TaskKind::Save, e)
+                    .with_path(path)
+                    .with_retry(TaskRetry::Save(path.to_owned()))
+            })
+    }
+}
+
+///Autosaves build when app is about to exit.
+pub fn autosave_on_exit(exit: EventReader<AppExit>, snapshot: BuildSnapshot) {
+    if exit.is_empty() || !snapshot.has_build() {
+        return;
+    }
+    //There is no next frame to show dialog, so quit isn't blocked by failure.
+    match snapshot.save(Path::new(AUTOSAVE_PATH)) {
+        Ok(()) => info!("Autosaved to {AUTOSAVE_PATH}"),
+        Err(e) => error!("{e}"),
+    }
+}
+
+///Key that saves build while ctrl is held.
+pub const SAVE_KEY: KeyCode = KeyCode::S;
+
+///Saves build on ctrl+S into autosave, which is what main menu continues from.
+pub fn save_on_key(
+    input: Res<Input<KeyCode>>,
+    snapshot: BuildSnapshot,
+    mut results: EventWriter<TaskResultEvent>,
+) {
+    if !input.just_pressed(SAVE_KEY) || !input.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        return;
+    }
+    let path = Path::new(AUTOSAVE_PATH);
+    results.send(match snapshot.save(path) {
+        Ok(()) => TaskResultEvent::Done {
+            kind: TaskKind::Save,
+            message: format!("saved to {}", path.display()),
+        },
+        Err(e) => TaskResultEvent::Failed(e),
+    });
+}
+
+///Submits save and load again when requested from error dialog.
+pub fn retry_tasks(
+    mut commands: Commands,
+    mut retries: EventReader<RetryTaskEvent>,
+    snapshot: BuildSnapshot,
+    mut results: EventWriter<TaskResultEvent>,
+) {
+    for RetryTaskEvent(retry) in retries.iter() {
+        let result = match retry {
+            TaskRetry::Save(path) => snapshot.save(path).map(|()| TaskResultEvent::Done {
+                kind: TaskKind::Save,
+                message: format!("saved to {}", path.display()),
+            }),
+            TaskRetry::Load(path) => SaveFile::read(path)
+                .map(|save| {
+                    commands.insert_resource(PendingLoad(path.clone(), save));
+                    TaskResultEvent::Done {
+                        kind: TaskKind::Load,
+                        message: format!("read {}", path.display()),
+                    }
+                })
+                .map_err(|e| {
+                    TaskError::new(TaskKind::Load, e)
+                        .with_path(path)
+                        .with_retry(retry.clone())
+                        .unrecoverable()
+                }),
+        };
+        results.send(result.unwrap_or_else(TaskResultEvent::Failed));
+    }
+}
+
+///Path of autosave, if it exists and could be loaded.
+pub fn loadable_autosave() -> Option<PathBuf> {
+    match SaveFile::read(AUTOSAVE_PATH) {
+        Ok(_) => Some(PathBuf::from(AUTOSAVE_PATH)),
+        Err(SaveError::Io(e)) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            error!("Autosave can't be continued: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag::TagId;
+
+    const CUBE: &str = "cube";
+    const STONE: &str = "stone";
+
+    fn saved(translation: Vec3) -> SavedStructure {
+        SavedStructure {
+            transform: Transform::from_translation(translation),
+            shape: Shape::Cuboid {
+                half_extents: Vec3::splat(0.5),
+            },
+            meshes: vec![(0, CUBE.to_owned())],
+            material: (0, STONE.to_owned()),
+            tags: Vec::new(),
+        }
+    }
+
+    fn structure_line(transform: &str, shape: &str) -> String {
+        format!("{HEADER}\nstructure\t{transform}\t{shape}\t0:{STONE}\t0:{CUBE}\t\n")
+    }
+
+    fn is_corrupt_at(result: Result<SaveFile, SaveError>, at: usize) -> bool {
+        matches!(result, Err(SaveError::Corrupt { line, .. }) if line == at)
+    }
+
+    #[test]
+    fn parse_rejects_zero_rotation() {
+        let text = structure_line("0 0 0 0 0 0 0 1 1 1", "sphere 0.5");
+        assert!(is_corrupt_at(SaveFile::parse(&text), 2));
+        //Rotation that isn't unit is normalized.
+        let text = structure_line("0 0 0 0 0 0 2 1 1 1", "sphere 0.5");
+        let save = SaveFile::parse(&text).unwrap();
+        assert_eq!(save.structures[0].transform.rotation, Quat::IDENTITY);
+    }
+
+    #[test]
+    fn parse_rejects_infinite_bound() {
+        for (transform, shape) in [
+            ("0 0 0 0 0 0 1 10 10 10", "sphere 3e38"),
+            ("0 0 0 0 0 0 1 1 1 1", "cut_sphere 1 3.4e38"),
+            ("0 0 0 0 0 0 1 3e38 3e38 3e38", "cuboid 1 1 1"),
+            ("3.4e38 0 0 0 0 0 1 1 1 1", "cuboid 3e38 1 1"),
+        ] {
+            let text = structure_line(transform, shape);
+            assert!(is_corrupt_at(SaveFile::parse(&text), 2), "{shape}");
+        }
+    }
+
+    #[test]
+    fn save_round_trips() {
+        let mut structures = vec![saved(Vec3::new(1., 2., -3.)), saved(Vec3::ZERO)];
+        structures[0].transform.rotation = Quat::from_rotation_y(0.7);
+        structures[0].transform.scale = Vec3::new(2., 1., 0.5);
+        structures[0].meshes.push((1, "trim".to_owned()));
+        structures[0].tags = vec!["wall".to_owned(), "red".to_owned()];
+        structures[1].shape = Shape::CutSphere {
+            radius: 0.5,
+            cut: 0.25,
+        };
+        structures.push(SavedStructure {
+            shape: Shape::Sphere { radius: 0.5 },
+            ..saved(Vec3::Y)
+        });
+        let save = SaveFile {
+            environment: SceneEnvironment {
+                ambient_brightness: 0.3,
+                sun_enabled: false,
+                ..default()
+            },
+            seed: Some(0xdead_beef),
+            waypoint: Some(Vec3::new(4., 0.5, -7.25)),
+            structures,
+        };
+        let text = save.to_string();
+        let parsed = SaveFile::parse(&text).unwrap();
+        assert_eq!(parsed.environment, save.environment);
+        assert_eq!(parsed.seed, save.seed);
+        assert_eq!(parsed.waypoint, save.waypoint);
+        assert_eq!(parsed.structures.len(), save.structures.len());
+        for (parsed, saved) in parsed.structures.iter().zip(save.structures.iter()) {
+            let (a, b) = (parsed.transform, saved.transform);
+            assert_eq!(a.translation, b.translation);
+            assert_eq!(a.scale, b.scale);
+            //Rotation is normalized again when read.
+            assert!(a.rotation.abs_diff_eq(b.rotation, 1e-6));
+            assert_eq!(format!("{:?}", parsed.shape), format!("{:?}", saved.shape));
+            assert_eq!(parsed.meshes, saved.meshes);
+            assert_eq!(parsed.material, saved.material);
+            assert_eq!(parsed.tags, saved.tags);
+        }
+    }
+
+    #[test]
+    fn parse_save_without_tags() {
+        let text =
+            format!("{HEADER}\nstructure\t0 0 0 0 0 0 1 1 1 1\tsphere 0.5\t0:{STONE}\t0:{CUBE}\n");
+        let save = SaveFile::parse(&text).unwrap();
+        assert_eq!(save.structures.len(), 1);
+        assert!(save.structures[0].tags.is_empty());
+        assert_eq!(save.seed, None);
+    }
+
+    #[test]
+    fn parse_rejects_unknown_lines() {
+        assert!(is_corrupt_at(SaveFile::parse("not a save"), 1));
+        let text = format!("{HEADER}\n\nblock\t1 2 3\n");
+        assert!(is_corrupt_at(SaveFile::parse(&text), 3));
+        let text = structure_line("0 0 0 0 0 0 1 1 1", "sphere 0.5");
+        assert!(is_corrupt_at(SaveFile::parse(&text), 2));
+        let text = structure_line("0 0 0 0 0 0 1 1 1 1", "cone 0.5");
+        assert!(is_corrupt_at(SaveFile::parse(&text), 2));
+    }
+
+    #[test]
+    fn tags_survive_save_by_name() {
+        let mut meshes = Meshes::default();
+        meshes[0].insert(CUBE, Handle::weak(HandleId::random::<Mesh>()));
+        let mut standard_materials = StandardMaterials::default();
+        standard_materials[0].insert(STONE, Handle::weak(HandleId::random::<StandardMaterial>()));
+        let mut structures = vec![saved(Vec3::ZERO), saved(Vec3::X)];
+        structures[0].tags = vec!["rampart".to_owned(), "defensive".to_owned()];
+        structures[1].tags = vec!["moat".to_owned()];
+        let text = SaveFile {
+            structures,
+            ..default()
+        }
+        .to_string();
+        let mut first = TagRegistry::default();
+        let mut second = TagRegistry::default();
+        //Next run interns something else first, so custom tags get other ids.
+        second.intern("banner");
+        let resolve = |tags: &mut TagRegistry| {
+            let descs = SaveFile::parse(&text)
+                .unwrap()
+                .descs(&meshes, &standard_materials, tags)
+                .unwrap();
+            descs.into_iter().map(|desc| desc.tags).collect::<Vec<_>>()
+        };
+        let (a, b) = (resolve(&mut first), resolve(&mut second));
+        assert_ne!(a[0][0], b[0][0]);
+        assert_eq!(a[0][1], TagId::DEFENSIVE);
+        assert_eq!(b[0][1], TagId::DEFENSIVE);
+        //Loading again in same run gives same ids.
+        assert_eq!(resolve(&mut second), b);
+        let names = |tags: &TagRegistry, ids: &[Vec<TagId>]| {
+            ids.iter()
+                .map(|ids| {
+                    ids.iter()
+                        .map(|id| tags.name(*id).to_owned())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let expected = [vec!["rampart", "defensive"], vec!["moat"]];
+        assert_eq!(names(&first, &a), expected);
+        assert_eq!(names(&second, &b), expected);
+    }
+
+    #[test]
+    fn exit_then_continue_restores_block_count() {
+        let mut meshes = Meshes::default();
+        meshes[0].insert(CUBE, Handle::weak(HandleId::random::<Mesh>()));
+        let mut standard_materials = StandardMaterials::default();
+        standard_materials[0].insert(STONE, Handle::weak(HandleId::random::<StandardMaterial>()));
+        let save = SaveFile {
+            structures: (0..5).map(|i| saved(Vec3::X * i as f32)).collect(),
+            ..default()
+        };
+        let path = std::env::temp_dir().join(format!("save_test_{}.txt", std::process::id()));
+        save.write(&path).unwrap();
+        let read = SaveFile::read(&path);
+        let _ = fs::remove_file(&path);
+        let descs = read
+            .unwrap()
+            .descs(&meshes, &standard_materials, &mut TagRegistry::default())
+            .unwrap();
+        assert_eq!(descs.len(), 5);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::consts::PI;
+
+use std::time::{Duration, Instant};
+
+use bevy::{prelude::*, window::PresentMode};
+
+///Batch setup for user settings.
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>()
+            .init_resource::<KeyBindings>()
+            .init_resource::<Lighting>()
+            .init_resource::<FrameLimiter>()
+            .add_system_to_stage(CoreStage::PostUpdate, apply_present_mode)
+            .add_system_to_stage(CoreStage::Last, limit_frame_rate);
+    }
+}
+
+///User preferences shared across states.
+#[derive(Resource)]
+pub struct Settings {
+    ///Subtle camera bob and tilt while moving.
+    pub camera_bob: bool,
+    ///Wireframe of buildable area.
+    pub show_build_boundary: bool,
+    ///How many recent placements are shown as faint ghosts while repeating placement. 0 disables.
+    pub placement_trail: usize,
+    ///Placements after which plane lock releases itself. None keeps it until toggled off.
+    pub plane_lock_release: Option<usize>,
+    ///Compass strip and position readout at top of screen.
+    pub show_compass: bool,
+    pub vsync: bool,
+    ///Max frames per second. None is uncapped.
+    pub frame_cap: Option<f32>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            camera_bob: false,
+            show_build_boundary: true,
+            placement_trail: 0,
+            plane_lock_release: None,
+            show_compass: true,
+            vsync: true,
+            frame_cap: None,
+        }
+    }
+}
+
+///Sun as player likes it, such as to brighten dark builds.
+#[derive(Resource, Clone, Copy, PartialEq, Debug)]
+pub struct Lighting {
+    pub illuminance: f32,
+    pub color: Color,
+    ///Turn about y axis in radians.
+    pub yaw: f32,
+    ///Tilt in radians. Negative points sun down.
+    pub pitch: f32,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            illuminance: 32000.,
+            color: Color::WHITE,
+            yaw: PI * 0.25,
+            pitch: -PI * 0.4,
+        }
+    }
+}
+
+impl Lighting {
+    pub fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::ZYX, 0., self.yaw, self.pitch)
+    }
+
+    ///Writes lighting to sun.
+    pub fn apply(&self, light: &mut DirectionalLight, transform: &mut Transform) {
+        light.illuminance = self.illuminance;
+        light.color = self.color;
+        transform.rotation = self.rotation();
+    }
+}
+
+///Inputs bound to actions, so they could be swapped such as for left-handed use.
+#[derive(Resource)]
+pub struct KeyBindings {
+    pub place: MouseButton,
+    pub remove: MouseButton,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            place: MouseButton::Left,
+            remove: MouseButton::Right,
+        }
+    }
+}
+
+impl KeyBindings {
+    ///Parses "left", "right", "middle" or number of extra button.
+    pub fn parse_mouse_button(name: &str) -> Option<MouseButton> {
+        match name {
+            "left" => Some(MouseButton::Left),
+            "right" => Some(MouseButton::Right),
+            "middle" => Some(MouseButton::Middle),
+            other => other.parse().ok().map(MouseButton::Other),
+        }
+    }
+}
+
+///Applies vsync setting to primary window whenever settings change.
+fn apply_present_mode(settings: Res<Settings>, mut windows: ResMut<Windows>) {
+    if !settings.is_changed() {
+        return;
+    }
+    let present_mode = if settings.vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    };
+    if let Some(window) = windows.get_primary_mut() {
+        if window.present_mode() != present_mode {
+            window.set_present_mode(present_mode);
+        }
+    }
+}
+
+///Keeps when last frame ended to cap frame rate.
+#[derive(Resource)]
+pub struct FrameLimiter {
+    last: Instant,
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self {
+            last: Instant::now(),
+        }
+    }
+}
+
+impl FrameLimiter {
+    ///How long to wait at now to keep given frame rate. None if frame is already late enough.
+    ///Rate below one frame per second isn't a cap, and its frame time could overflow `Duration`.
+    pub fn remaining(&self, now: Instant, fps: f32) -> Option<Duration> {
+        if !(fps.is_finite() && fps >= 1.) {
+            return None;
+        }
+        Duration::from_secs_f32(fps.recip()).checked_sub(now.saturating_duration_since(self.last))
+    }
+}
+
+///Sleeps rest of frame when frame rate is capped.
+fn limit_frame_rate(settings: Res<Settings>, mut limiter: ResMut<FrameLimiter>) {
+    if let Some(fps) = settings.frame_cap {
+        if let Some(remaining) = limiter.remaining(Instant::now(), fps) {
+            std::thread::sleep(remaining);
+        }
+    }
+    limiter.last = Instant::now();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limiter_waits_rest_of_frame() {
+        let limiter = FrameLimiter::default();
+        let after = |millis| limiter.last + Duration::from_millis(millis);
+        //Frame time of float rate is a bit off from whole milliseconds.
+        let close = |remaining: Option<Duration>, millis| {
+            let remaining = remaining.unwrap().as_secs_f64() * 1000.;
+            (remaining - millis as f64).abs() < 0.01
+        };
+        assert!(close(limiter.remaining(after(4), 100.), 6));
+        assert!(close(limiter.remaining(after(0), 1.), 1000));
+        assert!(limiter.remaining(after(11), 100.).is_none());
+        assert!(limiter.remaining(after(25), 60.).is_none());
+        //Clock that went back is taken as no time passed.
+        assert!(close(
+            limiter.remaining(after(0) - Duration::from_millis(5), 100.),
+            10
+        ));
+    }
+
+    #[test]
+    fn limiter_ignores_rates_that_arent_cap() {
+        let limiter = FrameLimiter::default();
+        for fps in [0., -30., 0.5, 1e-40, f32::NAN, f32::INFINITY] {
+            assert!(limiter.remaining(limiter.last, fps).is_none(), "{fps}");
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use bevy::{prelude::*, time::FixedTimestep};
+
+///Seconds between simulation ticks.
+pub const SIMULATION_STEP: f64 = 1. / 60.;
+
+///Stage of fixed step systems, such as collision and rigid bodies. It runs right after Update.
+///Each frame it ticks as many times as steps fit in elapsed time, none or several,
+///so simulation doesn't depend on frame rate. Camera and UI stay per frame in core stages.
+#[derive(StageLabel)]
+pub struct SimulationStage;
+
+///Ticks simulated so far.
+#[derive(Resource, Default)]
+pub struct SimulationTicks(pub u64);
+
+///Batch setup for fixed step simulation.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimulationTicks>()
+            .add_stage_after(
+                CoreStage::Update,
+                SimulationStage,
+                SystemStage::parallel().with_run_criteria(FixedTimestep::step(SIMULATION_STEP)),
+            )
+            .add_system_to_stage(SimulationStage, count_ticks);
+    }
+}
+
+fn count_ticks(mut ticks: ResMut<SimulationTicks>) {
+    ticks.0 += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, Instant};
+
+    use bevy::time::FixedTimesteps;
+
+    ///Ticks after running frames of given length for three seconds.
+    fn ticks_over_three_seconds(frame: Duration) -> u64 {
+        let mut app = App::new();
+        let start = Instant::now();
+        //Time is driven by hand instead of by time plugin.
+        app.insert_resource(Time::new(start))
+            .init_resource::<FixedTimesteps>()
+            .add_plugin(SimulationPlugin);
+        let frames = (3. / frame.as_secs_f64()).round() as u32;
+        //First update only starts clock, as time has no frame before it.
+        for i in 0..=frames {
+            app.world
+                .resource_mut::<Time>()
+                .update_with_instant(start + frame * i);
+            app.update();
+        }
+        app.world.resource::<SimulationTicks>().0
+    }
+
+    #[test]
+    fn ticks_dont_depend_on_frame_rate() {
+        let expected = (3. / SIMULATION_STEP).round() as u64;
+        for millis in [6, 33, 100, 250] {
+            let ticks = ticks_over_three_seconds(Duration::from_millis(millis));
+            //Step that doesn't fit yet waits for next frame.
+            assert!(ticks.abs_diff(expected) <= 1, "{millis}ms: {ticks}");
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{
+    consts::{OCTANT_EPS, OVERLAP_EPS, POINT_EPS, RAY_EPS},
+    demo::DemoPreset,
+    physics::{
+        aabb::AABB,
+        octree::{EntityOctree, Octree},
+    },
+    save::AUTOSAVE_PATH,
+    states::in_game::{PlacementTrail, Selection, BLUEPRINT_BOUND},
+    vox::VoxModel,
+};
+
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+///Labeled group of numeric contracts.
+#[derive(Clone, Debug)]
+pub struct SpecSection {
+    pub title: &'static str,
+    pub entries: Vec<(&'static str, String)>,
+}
+
+impl SpecSection {
+    fn new(title: &'static str) -> Self {
+        Self {
+            title,
+            entries: Vec::new(),
+        }
+    }
+
+    fn entry(mut self, label: &'static str, value: impl Display) -> Self {
+        self.entries.push((label, value.to_string()));
+        self
+    }
+}
+
+///Sections that are rendered together.
+pub struct Specs(pub Vec<SpecSection>);
+
+impl Display for Specs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, section) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", section.title)?;
+            for (label, value) in section.entries.iter() {
+                writeln!(f, "{label}: {value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Specs {
+    ///Written next to saves.
+    pub fn path() -> PathBuf {
+        Path::new(AUTOSAVE_PATH).with_file_name("specs.txt")
+    }
+
+    ///Numeric contracts template and stamp authors rely on, read from values game runs with.
+    ///Octree is live one, if game is in progress.
+    pub fn physics(octree: Option<&Octree>) -> Self {
+        let bound = BLUEPRINT_BOUND;
+        let cells = bound.length().round();
+        //Base tree is built with, which could differ from live one after it grows or shifts.
+        let base = EntityOctree::blueprint_base(&bound);
+        let mut sections = vec![
+            SpecSection::new("grid").entry("cell size", 1).entry(
+                "cell center",
+                "integer coordinates, so cell bounds are on halves",
+            ),
+            SpecSection::new("blueprint")
+                .entry("bound min", bound.min())
+                .entry("bound max", bound.max())
+                .entry("cells", format!("{} x {} x {}", cells.x, cells.y, cells.z))
+                .entry("octree base min", base.min())
+                .entry("octree base max", base.max()),
+        ];
+        sections.push(match octree {
+            Some(octree) => SpecSection::new("octree")
+                .entry("base bound min", octree.base_aabb().min())
+                .entry("base bound max", octree.base_aabb().max())
+                .entry("min leaf extent", octree.min_leaf_extent())
+                .entry("oversize fraction", octree.oversize_fraction())
+                .entry("entities", octree.len())
+                .entry("oversize entities", octree.oversize_len())
+                .entry("nodes", octree.node_count()),
+            None => SpecSection::new("octree").entry("state", "no game in progress"),
+        });
+        sections.extend([
+            SpecSection::new("epsilons")
+                .entry("point", POINT_EPS)
+                .entry("ray direction", RAY_EPS)
+                .entry("octant split", OCTANT_EPS)
+                .entry("overlap", OVERLAP_EPS)
+                .entry("min bound extent", AABB::MIN_EXTENT),
+            SpecSection::new("shapes")
+                .entry("Sphere", "radius")
+                .entry("CutSphere", "radius, cut (blueprint only)")
+                .entry("Cuboid", "half extents"),
+            SpecSection::new("placement")
+                .entry(
+                    "scale",
+                    format!(
+                        "{} to {} by {}",
+                        Selection::MIN_SCALE,
+                        Selection::MAX_SCALE,
+                        Selection::SCALE_STEP
+                    ),
+                )
+                .entry("trail max", PlacementTrail::MAX_LEN),
+            SpecSection::new("limits")
+                .entry("demo pieces", DemoPreset::MAX_PIECES)
+                .entry("vox voxels", VoxModel::MAX_VOXELS),
+        ]);
+        Self(sections)
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::physics::{
+        collider::{Collider, Shape},
+        octree::OctreeEntity,
+    };
+
+    use bevy::prelude::*;
+
+    fn entry<'a>(specs: &'a Specs, title: &str, label: &str) -> Option<&'a str> {
+        let section = specs.0.iter().find(|section| section.title == title)?;
+        section
+            .entries
+            .iter()
+            .find(|(name, _)| *name == label)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn report_has_every_constant() {
+        let specs = Specs::physics(None);
+        for (label, value) in [
+            ("point", POINT_EPS),
+            ("ray direction", RAY_EPS),
+            ("octant split", OCTANT_EPS),
+            ("overlap", OVERLAP_EPS),
+            ("min bound extent", AABB::MIN_EXTENT),
+        ] {
+            assert_eq!(
+                entry(&specs, "epsilons", label),
+                Some(value.to_string().as_str())
+            );
+        }
+        let max_voxels = VoxModel::MAX_VOXELS.to_string();
+        assert_eq!(
+            entry(&specs, "limits", "vox voxels"),
+            Some(max_voxels.as_str())
+        );
+        let max_pieces = DemoPreset::MAX_PIECES.to_string();
+        assert_eq!(
+            entry(&specs, "limits", "demo pieces"),
+            Some(max_pieces.as_str())
+        );
+        assert_eq!(
+            entry(&specs, "octree", "state"),
+            Some("no game in progress")
+        );
+        //Every entry is rendered under its section.
+        let text = specs.to_string();
+        for section in specs.0.iter() {
+            assert!(text.contains(&format!("[{}]", section.title)));
+            for (label, value) in section.entries.iter() {
+                assert!(text.contains(&format!("{label}: {value}\n")), "{label}");
+            }
+        }
+    }
+
+    #[test]
+    fn report_follows_live_octree() {
+        let mut octree = EntityOctree::for_blueprint(64, Vec3::splat(0.9), &BLUEPRINT_BOUND);
+        assert_eq!(
+            entry(&Specs::physics(Some(&octree)), "octree", "entities"),
+            Some("0")
+        );
+        for i in 0..20 {
+            octree.insert_result(OctreeEntity::new(
+                Entity::from_raw(i),
+                i as u64,
+                &Collider::from_shape(Shape::Sphere { radius: 0.5 }),
+                &Transform::from_xyz(i as f32, 3., -2.),
+            ));
+        }
+        let specs = Specs::physics(Some(&octree));
+        assert_eq!(entry(&specs, "octree", "entities"), Some("20"));
+        let nodes = octree.node_count().to_string();
+        assert_eq!(entry(&specs, "octree", "nodes"), Some(nodes.as_str()));
+        let extent = octree.min_leaf_extent().to_string();
+        assert_eq!(
+            entry(&specs, "octree", "min leaf extent"),
+            Some(extent.as_str())
+        );
+    }
+}
@@ -1,49 +1,172 @@
 use crate::{
     asset::*,
+    console::ConsoleInput,
     consts::*,
-    physics::{aabb::AABB, octree::Octree, ray::Ray},
+    environment::*,
+    func::set_if_changed,
+    physics::{
+        aabb::AABB,
+        octree::{EntityOctree, Octree, OctreeEntity},
+        ray::Ray,
+    },
+    recovery::*,
+    rng::establish_seeds,
+    save::*,
+    settings::{KeyBindings, Lighting, Settings},
+    single_or_return,
     states::*,
+    tag::*,
+    toggles::*,
+    trace::*,
     ui::*,
 };
 
 use bevy::input::mouse::MouseWheel;
-use bevy::{input::mouse::MouseMotion, prelude::*, window::CursorGrabMode};
+use bevy::{
+    asset::LoadState, input::mouse::MouseMotion, prelude::*, render::view::VisibilitySystems,
+    window::CursorGrabMode,
+};
 
 use crate::physics::collider::{Collider, Shape};
-use crate::physics::octree::OctreeEntity;
 use crate::physics::ray::RayHitInfo;
+use crate::structure::*;
 use bevy_polyline::prelude::*;
+use pause_menu::pause_requested;
+
+use std::collections::VecDeque;
 
-const BLUEPRINT_BOUND: AABB =
+pub mod compass;
+#[cfg(feature = "coords")]
+pub mod coords;
+pub mod framing;
+#[cfg(feature = "octree_debug")]
+pub mod octree_debug;
+pub mod pause_menu;
+#[cfg(feature = "top_view")]
+pub mod top_view;
+
+///Where structures could be placed. Cells are centered on integers, so bound is on halves.
+pub const BLUEPRINT_BOUND: AABB =
     unsafe { AABB::new_unchecked(Vec3::new(-31.5, -0.5, -31.5), Vec3::new(31.5, 62.5, 31.5)) };
 
+///Ground collider is box this thick right under blueprint, so its top is floor of blueprint.
+const GROUND_THICKNESS: f32 = 0.1;
+
+///Nudge of hit point back into hit cell, relative to its largest coordinate.
+///Spacing of f32 grows with magnitude, so fixed nudge alone gets lost in rounding far from origin.
+const SNAP_REL_EPS: f32 = 1e-5;
+
+///Systems that keep selection ghost following camera. Others order against these.
+#[derive(SystemLabel, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GhostSystem {
+    ///Raycast and snapping. Nothing cosmetic belongs here.
+    LookAt,
+}
+
 ///Batch setup for In game.
 pub struct InGamePlugin;
 
 impl Plugin for InGamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set_to_stage(
-            CoreStage::PreUpdate,
-            SystemSet::on_enter(PreUpdateStageState::InGame).with_system(setup),
-        )
-        .add_system_set_to_stage(
-            CoreStage::PreUpdate,
-            SystemSet::on_update(PreUpdateStageState::InGame)
-                .with_system(grab_cursor)
-                .with_system(camera_look_at),
-        )
-        .add_system_set_to_stage(
-            CoreStage::PreUpdate,
-            SystemSet::on_pause(PreUpdateStageState::InGame).with_system(show_cursor),
-        )
-        .add_system_set_to_stage(
-            CoreStage::Update,
-            SystemSet::on_update(UpdateStageState::InGame)
-                .with_system(move_camera)
-                .with_system(place)
-                .with_system(replace)
-                .with_system(close_requested),
-        );
+        register_toggle(app, AUTOSAVE_TOGGLE);
+        app.add_event::<ShiftBuild>()
+            .add_event::<PlacementCommitted>()
+            .add_event::<StructurePlaced>()
+            .add_event::<StructureRemoved>()
+            .init_resource::<ScreenshotMode>()
+            .init_resource::<MutationObservers>()
+            .init_resource::<PlacementIndex>()
+            .init_resource::<SceneEnvironment>()
+            .init_resource::<BoundsDebug>()
+            .init_resource::<QueryTrace>()
+            .init_resource::<TagRegistry>()
+            .init_resource::<StructureIndex>()
+            .init_resource::<OctreeCounterLog>()
+            .init_resource::<SpareOctree>()
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_enter(PreUpdateStageState::InGame)
+                    .with_system(prepare_deferred_assets.before(setup))
+                    .with_system(setup)
+                    .with_system(establish_seeds)
+                    .with_system(start_recovery_ring)
+                    .with_system(apply_scene_environment),
+            )
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_update(PreUpdateStageState::InGame)
+                    .with_system(grab_cursor)
+                    .with_system(turn_camera.after(ConsoleInput).before(GhostSystem::LookAt))
+                    .with_system(toggle_plane_lock.before(GhostSystem::LookAt))
+                    .with_system(camera_look_at.label(GhostSystem::LookAt))
+                    .with_system(update_plane_lock_grid.after(GhostSystem::LookAt))
+                    .with_system(update_preview_material.after(GhostSystem::LookAt)),
+            )
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_pause(PreUpdateStageState::InGame)
+                    .with_system(show_cursor)
+                    .with_system(pause_screenshot_mode),
+            )
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_exit(PreUpdateStageState::InGame)
+                    .with_system(restore_default_environment)
+                    .with_system(stash_octree)
+                    .with_system(clear_recovery_ring),
+            )
+            .add_system_set_to_stage(
+                CoreStage::Update,
+                SystemSet::on_update(UpdateStageState::InGame)
+                    .with_system(load_pending)
+                    .with_system(apply_lighting)
+                    .with_system(finish_preparing_assets)
+                    .with_system(move_camera)
+                    .with_system(scale_selection.before(place))
+                    .with_system(place)
+                    .with_system(update_placement_trail.after(place))
+                    .with_system(spawn_placement_ring.after(place))
+                    .with_system(animate_placement_rings)
+                    .with_system(replace)
+                    .with_system(shift_build)
+                    .with_system(save_on_key)
+                    .with_system(toggle_build_boundary)
+                    .with_system(reanchor_crosshair)
+                    .with_system(screenshot_mode.after(pause_requested))
+                    .with_system(pause_requested),
+            )
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::on_update(PostUpdateStageState::InGame)
+                    .with_system(
+                        sync_render_aabb
+                            .after(VisibilitySystems::CalculateBounds)
+                            .before(VisibilitySystems::CheckVisibility),
+                    )
+                    .with_system(draw_bounds_debug)
+                    .with_system(show_query_trace)
+                    .with_system(fade_trace_flashes),
+            )
+            .add_system_set_to_stage(
+                CoreStage::Last,
+                SystemSet::on_update(LastStageState::InGame)
+                    .with_system(cross_check_observers)
+                    .with_system(check_octree_base)
+                    .with_system(log_octree_counters)
+                    .with_system(warn_unnamed_structures)
+                    .with_system(mark_recovery_dirty)
+                    .with_system(write_recovery_snapshot.after(mark_recovery_dirty)),
+            )
+            //Exit could be requested from exit popup, which isn't in game state.
+            .add_system_to_stage(
+                CoreStage::Last,
+                autosave_on_exit.with_run_criteria(toggleable(AUTOSAVE_TOGGLE)),
+            )
+            .add_system_to_stage(
+                CoreStage::Last,
+                clear_recovery_on_exit.after(autosave_on_exit),
+            )
+            .add_system_to_stage(CoreStage::Update, retry_tasks);
     }
 }
 
@@ -56,8 +179,19 @@ fn setup(
     standard_materials: Res<StandardMaterials>,
     polylines: Res<Polylines>,
     polyline_materials: Res<PolylineMaterials>,
-    windows: Res<Windows>,
+    viewport: Res<ViewportInfo>,
+    environment: Res<SceneEnvironment>,
+    lighting: Res<Lighting>,
+    mut observers: ResMut<MutationObservers>,
+    mut index: ResMut<StructureIndex>,
+    mut placements: ResMut<PlacementIndex>,
+    mut spare: ResMut<SpareOctree>,
 ) {
+    //Structures of previous game are already cleared with state.
+    observers.clear();
+    index.on_cleared();
+    //Same build placed again gets same keys, whatever happened before.
+    *placements = PlacementIndex::default();
     //camera
     commands.spawn((
         Camera3dBundle {
@@ -66,53 +200,49 @@ fn setup(
         },
         state.mark(),
         LookAt(None),
+        CameraBob::default(),
+        CameraRoll::default(),
+        SkipWhenDegenerate,
+        Name::new("Main camera"),
     ));
     //crosshair
-    let window = windows.primary();
     commands.spawn((
         ImageBundle {
             image: textures[IMAGE_UI][CROSSHAIR].clone().into(),
             style: Style {
-                size: Size::new(Val::Px(32.), Val::Px(32.)),
+                size: Size::new(Val::Px(CROSSHAIR_SIZE), Val::Px(CROSSHAIR_SIZE)),
                 position_type: PositionType::Absolute,
-                position: UiRect::new(
-                    Val::Px(window.width() * 0.5 - 16.),
-                    Val::Undefined,
-                    Val::Undefined,
-                    Val::Px(window.height() * 0.5 - 16.),
-                ),
+                position: Crosshair::position(&viewport),
                 ..default()
             },
             ..default()
         },
         state.mark(),
+        UiRoot,
+        Crosshair,
+        Name::new("Crosshair"),
     ));
     //directional light
-    commands.spawn((
-        DirectionalLightBundle {
-            directional_light: DirectionalLight {
-                illuminance: 32000.0,
-                ..default()
-            },
-            transform: Transform {
-                rotation: Quat::from_euler(EulerRot::ZYX, 0., PI * 0.25, -PI * 0.4),
+    if environment.sun_enabled {
+        let mut sun = DirectionalLightBundle::default();
+        lighting.apply(&mut sun.directional_light, &mut sun.transform);
+        commands.spawn((sun, state.mark(), Sun, Name::new("Sun")));
+    }
+    //plane
+    let ground = commands
+        .spawn((
+            PbrBundle {
+                mesh: meshs[MESH_BUILT_IN][PLANE].clone(),
+                material: standard_materials[S_MAT_BUILT_IN][SEA_GREEN].clone(),
+                transform: Transform::from_scale(Vec3::new(100., 1., 100.))
+                    .with_translation(Vec3::new(0., -0.5, 0.)),
                 ..default()
             },
-            ..default()
-        },
-        state.mark(),
-    ));
-    //plane
-    commands.spawn((
-        PbrBundle {
-            mesh: meshs[MESH_BUILT_IN][PLANE].clone(),
-            material: standard_materials[S_MAT_BUILT_IN][SEA_GREEN].clone(),
-            transform: Transform::from_scale(Vec3::new(100., 1., 100.))
-                .with_translation(Vec3::new(0., -0.5, 0.)),
-            ..default()
-        },
-        state.mark(),
-    ));
+            state.mark(),
+            Fixture,
+            Name::new("Ground"),
+        ))
+        .id();
     //x axis line
     commands.spawn((
         PolylineBundle {
@@ -122,6 +252,7 @@ fn setup(
             ..default()
         },
         state.mark(),
+        Name::new("X axis"),
     ));
     //y axis line
     commands.spawn((
@@ -133,6 +264,7 @@ fn setup(
             ..default()
         },
         state.mark(),
+        Name::new("Y axis"),
     ));
     // z axis line
     commands.spawn((
@@ -144,33 +276,106 @@ fn setup(
             ..default()
         },
         state.mark(),
+        Name::new("Z axis"),
     ));
-    //Octree
+    //build boundary
     commands.spawn((
-        Octree::from_size_offset(64, Vec3::splat(0.9), 64., Vec3::new(0.5, 31.5, 0.5)),
+        PolylineBundle {
+            polyline: polylines[UNIT_BOX].clone(),
+            material: polyline_materials[GRAY].clone(),
+            transform: Transform::from_translation(BLUEPRINT_BOUND.center())
+                .with_scale(BLUEPRINT_BOUND.length()),
+            ..default()
+        },
+        state.mark(),
+        BuildBoundary,
+        Name::new("Build boundary"),
+    ));
+    //plane lock grid
+    commands.spawn((
+        PolylineBundle {
+            polyline: polylines[GRID_PATCH].clone(),
+            material: polyline_materials[GRAY].clone(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        },
         state.mark(),
+        PlaneLockGrid,
+        Name::new("Plane lock grid"),
     ));
+    //face indicator
+    commands.spawn((
+        PolylineBundle {
+            polyline: polylines[UNIT_SQUARE].clone(),
+            material: polyline_materials[YELLOW].clone(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        },
+        state.mark(),
+        FaceIndicator,
+        Name::new("Face indicator"),
+    ));
+    //Octree. Not bounded, as ground lies under its base and refusing it would leave nothing to hit
+    //below blueprint. Cells are snapped within blueprint, and shift checks blueprint bound itself.
+    //Tree of previous game is cleared and reused, so its node pool isn't allocated again.
+    let mut octree = match spare.0.take() {
+        Some(mut octree) => {
+            octree.clear();
+            octree.reset_counters();
+            octree
+        }
+        None => EntityOctree::for_blueprint(64, Vec3::splat(0.9), &BLUEPRINT_BOUND),
+    };
+    octree.insert_result(ground_entry(ground));
+    //Not marked, as it is taken by `stash_octree` instead of despawned with state.
+    commands.spawn((octree, Name::new("Octree")));
     //selection
-    let selection = Selection::new(
-        vec![
+    let selection = Selection::builder()
+        .with_meshes(vec![
             meshs[MESH_WEAPON][GUN_TOWER_0_BASE].clone(),
             meshs[MESH_WEAPON][GUN_TOWER_0_TOWER].clone(),
             meshs[MESH_WEAPON][GUN_TOWER_0_GUN].clone(),
-        ],
-        standard_materials[S_MAT_BUILT_IN][WHITE].clone(),
-        standard_materials[S_MAT_BUILT_IN][WHITE_TRANS].clone(),
-        Collider::from_shape(Shape::CutSphere {
+        ])
+        .with_material(standard_materials[S_MAT_BUILT_IN][WHITE].clone())
+        .with_material_trans(standard_materials[S_MAT_BUILT_IN][WHITE_TRANS].clone())
+        .with_material_invalid(standard_materials[S_MAT_BUILT_IN][RED_TRANS].clone())
+        .with_collider(Collider::from_shape(Shape::CutSphere {
             radius: 2.5,
             cut: 0.5,
-        }),
-    );
+        }))
+        .with_tags(vec![TagId::DEFENSIVE])
+        .build();
     let children = selection.create_transparent();
+    //placement trail
+    let faint = standard_materials[S_MAT_BUILT_IN][WHITE_FAINT].clone();
+    for index in 0..PlacementTrail::MAX_LEN {
+        let children = selection.create_ghost(&faint);
+        commands
+            .spawn((
+                TransformBundle::default(),
+                VisibilityBundle {
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                },
+                TrailGhost(index),
+                state.mark(),
+                //Named once here, since ghosts are reused.
+                Name::new(format!("Preview slot {index}")),
+            ))
+            .add_children(|parent| {
+                for bundle in children {
+                    parent.spawn(bundle);
+                }
+            });
+    }
     commands
         .spawn((
             TransformBundle::default(),
             VisibilityBundle::default(),
             selection,
+            PlacementTrail::default(),
             state.mark(),
+            Name::new("Selection"),
         ))
         .add_children(|parent| {
             for bundle in children {
@@ -179,18 +384,139 @@ fn setup(
         });
 }
 
-///locks cursor to window while in game.
-fn grab_cursor(mut windows: ResMut<Windows>) {
+///Octree of last game, kept for next one to reuse.
+#[derive(Resource, Default)]
+struct SpareOctree(Option<EntityOctree>);
+
+///Takes octree out of leaving game. Octree is only around in game, as others tell game by it.
+fn stash_octree(world: &mut World) {
+    let mut octrees = world.query_filtered::<Entity, With<EntityOctree>>();
+    if let Some(entity) = octrees.iter(world).next() {
+        let octree = world.entity_mut(entity).remove::<EntityOctree>();
+        world.despawn(entity);
+        world.resource_mut::<SpareOctree>().0 = octree;
+    }
+}
+
+///Mark notice shown while deferred assets are loading.
+#[derive(Component)]
+struct PreparingAssets;
+
+///Makes sure deferred assets are at least started, since game could be entered before main menu starts them.
+///Handles exist right away, so nothing panics while they load.
+fn prepare_deferred_assets(
+    mut commands: Commands,
+    state: Res<GlobalState>,
+    asset_server: Res<AssetServer>,
+    fonts: Res<Fonts>,
+    mut deferred: ResMut<DeferredAssets>,
+    mut meshes: ResMut<Meshes>,
+) {
+    deferred.start(&asset_server, &mut meshes);
+    if deferred.load_state(&asset_server) == LoadState::Loaded {
+        return;
+    }
+    commands.spawn((
+        create_text("Preparing assets...", &fonts, 24., TEXT_COLOR_BRIGHT).with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(16.),
+                bottom: Val::Px(16.),
+                ..default()
+            },
+            ..default()
+        }),
+        state.mark(),
+        UiRoot,
+        PreparingAssets,
+        Name::new("Preparing assets"),
+    ));
+}
+
+///Removes notice once deferred assets are loaded. Failed ones are missing, not waited for.
+fn finish_preparing_assets(
+    mut commands: Commands,
+    notice: Query<Entity, With<PreparingAssets>>,
+    asset_server: Res<AssetServer>,
+    deferred: Res<DeferredAssets>,
+) {
+    if notice.is_empty() {
+        return;
+    }
+    match deferred.load_state(&asset_server) {
+        LoadState::Loaded => {}
+        LoadState::Failed => {
+            for path in deferred.failed(&asset_server) {
+                warn!("Deferred asset is missing: {path}");
+            }
+        }
+        _ => return,
+    }
+    for entity in notice.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+///Mark directional light that lighting settings drive.
+#[derive(Component)]
+pub struct Sun;
+
+///Applies lighting settings to sun whenever they change.
+fn apply_lighting(
+    lighting: Res<Lighting>,
+    mut sun: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+) {
+    if !lighting.is_changed() {
+        return;
+    }
+    for (mut light, mut transform) in sun.iter_mut() {
+        lighting.apply(&mut light, &mut transform);
+    }
+}
+
+const CROSSHAIR_SIZE: f32 = 32.;
+
+///Mark crosshair image, which is anchored to center of viewport.
+#[derive(Component)]
+struct Crosshair;
+
+impl Crosshair {
+    fn position(viewport: &ViewportInfo) -> UiRect {
+        let center = viewport.center() - CROSSHAIR_SIZE * 0.5;
+        UiRect::new(
+            Val::Px(center.x),
+            Val::Undefined,
+            Val::Undefined,
+            Val::Px(center.y),
+        )
+    }
+}
+
+///Keeps crosshair at center when viewport is resized.
+fn reanchor_crosshair(
+    viewport: Res<ViewportInfo>,
+    mut crosshairs: Query<&mut Style, With<Crosshair>>,
+) {
+    if !viewport.is_changed() {
+        return;
+    }
+    for mut style in crosshairs.iter_mut() {
+        style.position = Crosshair::position(&viewport);
+    }
+}
+
+///locks cursor to window while in game. Minimized window releases it until restored.
+fn grab_cursor(mut windows: ResMut<Windows>, viewport: Res<ViewportInfo>) {
     let window = windows.primary_mut();
     let cursor_visible = window.cursor_visible();
-    if window.is_focused() {
+    if window.is_focused() && !viewport.degenerate {
         //if window is focused and cursor is visible, lock.
         if cursor_visible {
             window.set_cursor_grab_mode(CursorGrabMode::Locked);
             window.set_cursor_visibility(false);
         }
     }
-    //if window isn't focused and cursor is invisible, release.
+    //if window isn't focused or is minimized and cursor is invisible, release.
     else if !cursor_visible {
         window.set_cursor_grab_mode(CursorGrabMode::None);
         window.set_cursor_visibility(true);
@@ -204,12 +530,18 @@ fn show_cursor(mut windows: ResMut<Windows>) {
     window.set_cursor_visibility(true);
 }
 
-///Camera control system.
-fn move_camera(
-    mut query: Query<&mut Transform, With<Camera>>,
+///Roll put on camera rotation over yaw and pitch, by bob and screenshot mode.
+///Stripped before either changes, and put back after.
+#[derive(Component, Default)]
+pub struct CameraRoll(f32);
+
+///Turns camera by mouse motion of this frame. Look at runs after it in PreUpdate,
+///so ghost and what click hits follow this frame's turn instead of previous one.
+///Translation stays in `move_camera` in Update, which moves along turned rotation.
+fn turn_camera(
+    mut query: Query<(&mut Transform, &CameraRoll), With<Camera>>,
     input: Res<Input<KeyCode>>,
     mut mouse: EventReader<MouseMotion>,
-    time: Res<Time>,
 ) {
     //mouse motion to angular delta.
     let mut motion = Vec2::ZERO;
@@ -217,9 +549,16 @@ fn move_camera(
         mouse.iter().for_each(|m| motion += m.delta);
         motion *= -RADIANS * 0.08;
     }
-
-    let delta = time.delta_seconds() * 10.0;
-    for mut transform in query.iter_mut() {
+    let level = input.just_pressed(KeyCode::R);
+    if motion == Vec2::ZERO && !level {
+        return;
+    }
+    for (mut transform, roll) in query.iter_mut() {
+        transform.rotation *= Quat::from_rotation_z(-roll.0);
+        //Drops roll drifted by euler math, keeping where camera looks.
+        if level {
+            transform.rotation = level_rotation(transform.forward());
+        }
         //camera rotation by mouse motion.
         if motion != Vec2::ZERO {
             let euler = transform.rotation.to_euler(EulerRot::YXZ);
@@ -230,6 +569,23 @@ fn move_camera(
                 0.0,
             );
         }
+        transform.rotation *= Quat::from_rotation_z(roll.0);
+    }
+}
+
+///Camera control system. Rotation is already turned by `turn_camera`.
+fn move_camera(
+    mut query: Query<(&mut Transform, &mut CameraBob, &mut CameraRoll), With<Camera>>,
+    input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    screenshot: Res<ScreenshotMode>,
+) {
+    let delta = time.delta_seconds() * 10.0;
+    for (mut transform, mut bob, mut roll) in query.iter_mut() {
+        //Strip bob and roll of previous frame to get logical transform.
+        transform.translation -= bob.offset;
+        transform.rotation *= Quat::from_rotation_z(-roll.0);
         //Accumulate move direction from keyboard inputs.
         let front = transform.forward();
         let right = transform.right();
@@ -253,9 +609,66 @@ fn move_camera(
         if input.pressed(KeyCode::LShift) {
             to_move -= up;
         }
+        //Ctrl is held for shortcuts such as ctrl+S, which shouldn't move camera.
+        if input.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+            to_move = Vec3::ZERO;
+        }
         //apply
-        transform.translation = (transform.translation + to_move.clamp_length_max(1.0) * delta)
+        let to_move = to_move.clamp_length_max(1.0);
+        transform.translation = (transform.translation + to_move * delta)
             .clamp(BLUEPRINT_BOUND.min() + 0.5, BLUEPRINT_BOUND.max() - 0.5);
+        //Bob is applied on top of logical transform.
+        if settings.camera_bob {
+            bob.update(to_move, right, time.delta_seconds());
+        } else {
+            *bob = CameraBob::default();
+        }
+        transform.translation += bob.offset;
+        roll.0 = bob.roll + screenshot.roll;
+        transform.rotation *= Quat::from_rotation_z(roll.0);
+    }
+}
+
+///Rotation that looks along forward with up toward world Y.
+///Yaw and pitch are derived from forward again, so no roll is left.
+fn level_rotation(forward: Vec3) -> Quat {
+    let yaw = (-forward.x).atan2(-forward.z);
+    let pitch = forward.y.clamp(-1., 1.).asin();
+    Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.)
+}
+
+///Additive camera offset for movement feedback. Logical camera position is unaffected.
+#[derive(Component, Default)]
+pub struct CameraBob {
+    phase: f32,
+    ///Blend factor of bob. Fades out while stationary.
+    weight: f32,
+    offset: Vec3,
+    roll: f32,
+}
+
+impl CameraBob {
+    const FREQUENCY: f32 = 9.0;
+    const AMPLITUDE: f32 = 0.05;
+    const TILT: f32 = 1.5 * RADIANS;
+    const FADE: f32 = 8.0;
+
+    ///Advances bob phase by move direction and elapsed seconds.
+    pub fn update(&mut self, to_move: Vec3, right: Vec3, delta: f32) {
+        let moving = to_move != Vec3::ZERO;
+        let target = if moving { 1.0 } else { 0.0 };
+        self.weight += (target - self.weight) * (delta * Self::FADE).min(1.0);
+        if !moving && self.weight < 0.001 {
+            //Snap back to neutral.
+            *self = Self::default();
+            return;
+        }
+        if moving {
+            self.phase = (self.phase + delta * Self::FREQUENCY) % TAU;
+        }
+        self.offset = Vec3::Y * self.phase.sin() * Self::AMPLITUDE * self.weight;
+        //Lean into strafe.
+        self.roll = -to_move.dot(right).clamp(-1.0, 1.0) * Self::TILT * self.weight;
     }
 }
 
@@ -265,49 +678,119 @@ pub struct LookAt(Option<RayHitInfo>);
 #[derive(Component)]
 pub struct Selection {
     valid: bool,
+    ///Uniform scale of structure to be placed.
+    scale: f32,
     meshes: Vec<Handle<Mesh>>,
     material: Handle<StandardMaterial>,
     material_trans: Handle<StandardMaterial>,
+    ///Preview material while selection can't be placed.
+    material_invalid: Handle<StandardMaterial>,
+    ///Validity that preview material shows. None until first shown.
+    shown_valid: Option<bool>,
     collider: Collider,
+    ///Tags given to placed structure.
+    tags: Vec<TagId>,
+    lock: Option<PlaneLock>,
+    ///Cell of latest placement, which plane lock goes through.
+    last_placed: Option<Vec3>,
 }
 
 impl Selection {
+    pub const SCALE_STEP: f32 = 0.25;
+    pub const MIN_SCALE: f32 = 0.5;
+    pub const MAX_SCALE: f32 = 4.;
+
     pub fn new(
         meshes: Vec<Handle<Mesh>>,
         material: Handle<StandardMaterial>,
         material_trans: Handle<StandardMaterial>,
+        material_invalid: Handle<StandardMaterial>,
         collider: Collider,
     ) -> Self {
         Self {
             valid: false,
+            scale: 1.,
             meshes,
             material,
             material_trans,
+            material_invalid,
+            shown_valid: None,
             collider,
+            tags: Vec::new(),
+            lock: None,
+            last_placed: None,
         }
     }
 
+    pub fn builder() -> SelectionBuilder {
+        SelectionBuilder::default()
+    }
+
     pub fn create_transparent(&self) -> Vec<PbrBundle> {
-        self.meshes
-            .iter()
-            .map(|mesh| PbrBundle {
-                mesh: mesh.clone(),
-                material: self.material_trans.clone(),
-                ..default()
-            })
-            .collect()
+        self.create_ghost(&self.material_trans)
     }
 
-    pub fn create(&self) -> Vec<PbrBundle> {
+    ///Preview meshes with given material.
+    pub fn create_ghost(&self, material: &Handle<StandardMaterial>) -> Vec<PbrBundle> {
         self.meshes
             .iter()
             .map(|mesh| PbrBundle {
                 mesh: mesh.clone(),
-                material: self.material.clone(),
+                material: material.clone(),
                 ..default()
             })
             .collect()
     }
+
+    ///Description of structure to be placed at transform.
+    pub fn desc(&self, transform: Transform) -> StructureDesc {
+        StructureDesc {
+            transform,
+            collider: self.collider.clone(),
+            meshes: self.meshes.clone(),
+            material: self.material.clone(),
+            tags: self.tags.clone(),
+        }
+    }
+
+    ///Records placement and releases plane lock after given placements.
+    pub fn record_placement(&mut self, cell: Vec3, lock_release: Option<usize>) {
+        self.last_placed = Some(cell);
+        if let Some(lock) = &mut self.lock {
+            lock.placed += 1;
+            if lock_release.map_or(false, |release| lock.placed >= release) {
+                self.lock = None;
+            }
+        }
+    }
+
+    ///Scales by given steps. Result is snapped to step and kept in bounds.
+    pub fn scale_by(&mut self, steps: i32) {
+        let scale = self.scale + steps as f32 * Self::SCALE_STEP;
+        self.scale = ((scale / Self::SCALE_STEP).round() * Self::SCALE_STEP)
+            .clamp(Self::MIN_SCALE, Self::MAX_SCALE);
+    }
+}
+
+///Scales selection with brackets. Placed structure carries its scale through transform.
+fn scale_selection(
+    mut selection: Query<(&mut Selection, &mut Transform)>,
+    input: Res<Input<KeyCode>>,
+) {
+    let mut steps = 0;
+    if input.just_pressed(KeyCode::RBracket) {
+        steps += 1;
+    }
+    if input.just_pressed(KeyCode::LBracket) {
+        steps -= 1;
+    }
+    if steps == 0 {
+        return;
+    }
+    for (mut selection, mut transform) in selection.iter_mut() {
+        selection.scale_by(steps);
+        transform.scale = Vec3::splat(selection.scale);
+    }
 }
 
 fn _select(
@@ -317,16 +800,23 @@ fn _select(
         &mut Selection,
     )>,
 ) {
-    let _ = selected.single_mut();
+    let _ = single_or_return!(mut selected);
 }
 
 ///Prepare and store data about where camera looking at.
+///Critical path of ghost. It runs in PreUpdate, so it's done before budgeted work of Update could delay it.
+///Cosmetic readers of result, such as plane lock grid and preview tint, run after it.
 fn camera_look_at(
     mut camera: Query<(&Transform, &mut LookAt), With<Camera>>,
     octree: Query<&Octree>,
     mut selection: Query<(&mut Selection, &mut Transform), Without<Camera>>,
+    mut indicator: Query<
+        (&mut Transform, &mut Visibility),
+        (With<FaceIndicator>, Without<Camera>, Without<Selection>),
+    >,
     mut mouse_wheel: EventReader<MouseWheel>,
     mut rotate: Local<i32>,
+    mut trace: ResMut<QueryTrace>,
 ) {
     let mut accum = 0.;
     for delta in mouse_wheel.iter() {
@@ -339,57 +829,343 @@ fn camera_look_at(
     }
     let y_rot = (*rotate % 4) as f32 * 90f32.to_radians();
 
-    let (camera_transform, mut look_at) = camera.single_mut();
+    let (camera_transform, mut look_at) = single_or_return!(mut camera);
     let camera_pos = camera_transform.translation;
     let camera_forward = camera_transform.forward();
-    let octree = octree.single();
-    let (mut selection, mut transform) = selection.single_mut();
+    let octree = single_or_return!(octree);
+    let (mut selection, mut transform) = single_or_return!(mut selection);
     //Get raycast hit point.
     let ray = Ray::new(camera_pos, camera_forward);
-    look_at.0 = match octree.raycast(&ray) {
-        Some(hit_info) => {
-            let pos = ray.point(hit_info.t + 0.001);
-            let face = hit_info.aabb.face(pos);
-            transform.translation = pos.round() + face;
-            transform.rotation =
-                Quat::from_rotation_arc(Vec3::Y, face) * Quat::from_rotation_y(y_rot);
-            selection.valid = true;
-            Some(hit_info)
-        }
-        //If no result, checks root of tree's bound.
-        None => match BLUEPRINT_BOUND.intersects_ray(&ray) {
-            Some(len) => {
-                let pos = ray.point(len + 0.001);
-                let face = -BLUEPRINT_BOUND.face(pos);
-                transform.translation = pos.round() + face;
-                transform.rotation =
-                    Quat::from_rotation_arc(Vec3::Y, face) * Quat::from_rotation_y(y_rot);
-                selection.valid = true;
-                None
-            }
-            None => {
-                selection.valid = false;
-                None
-            }
+    let hit = if trace.armed {
+        let (hit, traced) = octree.raycast_traced(&ray);
+        trace.record(ray, traced);
+        hit
+    } else {
+        octree.raycast(&ray)
+    };
+    let snapped = snap_cell(&ray, hit.as_ref(), selection.lock);
+    match snapped {
+        Some((cell, face)) => {
+            let ghost = Transform {
+                translation: standing_center(cell, face, selection.scale),
+                rotation: ghost_rotation(face, y_rot),
+                ..*transform
+            };
+            //Ghost mostly stays in same cell, so it shouldn't look moved to change readers.
+            set_if_changed(&mut transform, ghost);
+            //Structure can't go into placed one. Preview turns invalid material instead.
+            selection.valid = !octree.overlaps(&selection.collider.shape(), &ghost);
+        }
+        None => selection.valid = false,
+    }
+    //Only when placement attaches to hit block, not when locked plane took over.
+    let shown = snapped.filter(|snapped| {
+        hit.is_some()
+            && (selection.lock.is_none() || snap_cell(&ray, hit.as_ref(), None) == Some(*snapped))
+    });
+    for (mut indicator_transform, mut visibility) in indicator.iter_mut() {
+        if let Some((cell, face)) = shown {
+            set_if_changed(
+                &mut indicator_transform,
+                face_indicator_transform(cell, face),
+            );
+        }
+        if visibility.is_visible != shown.is_some() {
+            visibility.is_visible = shown.is_some();
+        }
+    }
+    look_at.0 = hit;
+}
+
+///Mark outline on face that selection attaches to.
+#[derive(Component)]
+struct FaceIndicator;
+
+///Outline lying on face between cell and block it attaches to, slightly lifted and inset to stay visible.
+fn face_indicator_transform(cell: Vec3, face: Vec3) -> Transform {
+    Transform::from_translation(cell - face * (0.5 - POINT_EPS * 10.))
+        .with_rotation(Quat::from_rotation_arc(Vec3::Y, face))
+        .with_scale(Vec3::splat(0.9))
+}
+
+///Face of cell that normal mostly points to. Tie goes to former axis.
+///Used instead of `AABB::face` of hit bound, since sphere and turned shapes are hit off their
+///bound, and normal already tells which face ray entered through at edge or corner.
+fn axis_face(normal: Vec3) -> Vec3 {
+    let abs = normal.abs();
+    if abs.x >= abs.y && abs.x >= abs.z {
+        Vec3::X * normal.x.signum()
+    } else if abs.y >= abs.z {
+        Vec3::Y * normal.y.signum()
+    } else {
+        Vec3::Z * normal.z.signum()
+    }
+}
+
+///Ground as octree holds it. It covers only floor of blueprint, so ray passing by it still
+///falls back to bound of blueprint. Key is last, so structure wins tie with ground.
+fn ground_entry(ground: Entity) -> OctreeEntity {
+    let half_extents = BLUEPRINT_BOUND.length() * 0.5;
+    let center = BLUEPRINT_BOUND.center();
+    OctreeEntity::new(
+        ground,
+        u64::MAX,
+        &Collider::from_shape(Shape::Cuboid {
+            half_extents: Vec3::new(half_extents.x, GROUND_THICKNESS * 0.5, half_extents.z),
+        }),
+        &Transform::from_xyz(
+            center.x,
+            BLUEPRINT_BOUND.min().y - GROUND_THICKNESS * 0.5,
+            center.z,
+        ),
+    )
+}
+
+///Cell where selection snaps to and face it stands on, or None if there is nowhere to place.
+///Locked plane has priority over snapping. Off plane snap falls back to ray meeting plane.
+fn snap_cell(ray: &Ray, hit: Option<&RayHitInfo>, lock: Option<PlaneLock>) -> Option<(Vec3, Vec3)> {
+    //Cell next to hit face and the face. Cell hit is found a bit behind face, as point is on border.
+    //Nudge is along face normal, not ray, so grazing ray doesn't slide point across face.
+    let beside = |point: Vec3, face: Vec3| {
+        let eps = POINT_EPS.max(point.abs().max_element() * SNAP_REL_EPS);
+        ((point - face * eps).round() + face, face)
+    };
+    let snapped = match hit {
+        Some(hit_info) => Some(beside(hit_info.point, axis_face(hit_info.normal))),
+        //If no result, checks root of tree's bound. Ray leaves it, so placement faces inward.
+        None => BLUEPRINT_BOUND
+            .intersects_ray_normal(ray)
+            .map(|(t, normal)| beside(ray.point(t), -normal)),
+    };
+    match lock {
+        Some(lock) => match snapped {
+            Some((cell, face)) if lock.contains(cell) => Some((cell, face)),
+            _ => lock
+                .project(ray)
+                .filter(|cell| BLUEPRINT_BOUND.contains_point(*cell))
+                .map(|cell| (cell, Vec3::Y)),
         },
+        None => snapped,
+    }
+}
+
+///Center of selection standing on face beside cell. Structure fills its cell at scale 1,
+///so scaled one is moved along face until it just touches block it attaches to.
+fn standing_center(cell: Vec3, face: Vec3, scale: f32) -> Vec3 {
+    cell + face * (scale - 1.) * 0.5
+}
+
+///Rotation of selection ghost standing on face, turned about face by y_rot.
+fn ghost_rotation(face: Vec3, y_rot: f32) -> Quat {
+    Quat::from_rotation_arc(Vec3::Y, face) * Quat::from_rotation_y(y_rot)
+}
+
+///Unit cube without meshes. Default handles draw nothing until set.
+impl Default for Selection {
+    fn default() -> Self {
+        Self::new(
+            Vec::new(),
+            default(),
+            default(),
+            default(),
+            Collider::from_shape(Shape::Cuboid {
+                half_extents: Vec3::splat(0.5),
+            }),
+        )
+    }
+}
+
+///Sets selection by name, since handles of same type are easy to swap by position.
+#[derive(Default)]
+pub struct SelectionBuilder(Selection);
+
+impl SelectionBuilder {
+    pub fn with_meshes(mut self, meshes: Vec<Handle<Mesh>>) -> Self {
+        self.0.meshes = meshes;
+        self
+    }
+
+    ///Material of placed structure.
+    pub fn with_material(mut self, material: Handle<StandardMaterial>) -> Self {
+        self.0.material = material;
+        self
+    }
+
+    ///Material of preview.
+    pub fn with_material_trans(mut self, material: Handle<StandardMaterial>) -> Self {
+        self.0.material_trans = material;
+        self
+    }
+
+    ///Material of preview while it can't be placed.
+    pub fn with_material_invalid(mut self, material: Handle<StandardMaterial>) -> Self {
+        self.0.material_invalid = material;
+        self
+    }
+
+    pub fn with_collider(mut self, collider: Collider) -> Self {
+        self.0.collider = collider;
+        self
+    }
+
+    pub fn with_tags(mut self, tags: Vec<TagId>) -> Self {
+        self.0.tags = tags;
+        self
+    }
+
+    ///Warns when materials look like swapped or missing, but builds anyway.
+    pub fn build(self) -> Selection {
+        let selection = self.0;
+        if selection.material == selection.material_trans {
+            warn!("Selection preview uses same material as placed structure");
+        }
+        if selection.material_trans == selection.material_invalid {
+            warn!("Selection preview can't tell invalid placement apart");
+        }
+        selection
+    }
+}
+
+///Tints preview while selection can't be placed.
+///Materials are swapped only when validity flips, so render state isn't dirtied every frame.
+fn update_preview_material(
+    mut selection: Query<(&mut Selection, &Children)>,
+    mut materials: Query<&mut Handle<StandardMaterial>>,
+) {
+    let (mut selection, children) = single_or_return!(mut selection);
+    let valid = selection.valid;
+    if selection.shown_valid == Some(valid) {
+        return;
+    }
+    selection.shown_valid = Some(valid);
+    let material = if valid {
+        &selection.material_trans
+    } else {
+        &selection.material_invalid
+    };
+    let mut iter = materials.iter_many_mut(children.iter());
+    while let Some(mut handle) = iter.fetch_next() {
+        *handle = material.clone();
+    }
+}
+
+///Constrains placement to plane of cells perpendicular to axis.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlaneLock {
+    ///0, 1, 2 for x, y, z.
+    axis: usize,
+    ///Coordinate of locked cells on axis.
+    level: f32,
+    ///Placements made while locked.
+    placed: usize,
+}
+
+impl PlaneLock {
+    pub fn new(axis: usize, through: Vec3) -> Self {
+        Self {
+            axis,
+            level: through[axis].round(),
+            placed: 0,
+        }
+    }
+
+    pub fn axis(&self) -> Vec3 {
+        Vec3::AXES[self.axis]
+    }
+
+    ///Whether cell is on locked plane.
+    pub fn contains(&self, cell: Vec3) -> bool {
+        (cell[self.axis] - self.level).abs() < 0.5
+    }
+
+    ///Cell where ray meets plane. None if ray is parallel to plane or plane is behind.
+    pub fn project(&self, ray: &Ray) -> Option<Vec3> {
+        let t = ray.t(Vec3::splat(self.level))[self.axis];
+        if !t.is_finite() || t < 0. {
+            return None;
+        }
+        let mut cell = ray.point(t).round();
+        cell[self.axis] = self.level;
+        Some(cell)
+    }
+}
+
+///X, Y or Z locks placement to plane through latest placed or hovered cell.
+///Same key again releases lock.
+fn toggle_plane_lock(
+    mut selection: Query<(&mut Selection, &Transform)>,
+    input: Res<Input<KeyCode>>,
+) {
+    let axis = if input.just_pressed(KeyCode::X) {
+        0
+    } else if input.just_pressed(KeyCode::Y) {
+        1
+    } else if input.just_pressed(KeyCode::Z) {
+        2
+    } else {
+        return;
     };
+    let (mut selection, transform) = single_or_return!(mut selection);
+    if selection.lock.map(|lock| lock.axis) == Some(axis) {
+        selection.lock = None;
+        return;
+    }
+    let hovered = selection.valid.then_some(transform.translation);
+    selection.lock = selection
+        .last_placed
+        .or(hovered)
+        .map(|through| PlaneLock::new(axis, through));
+}
+
+///Mark grid patch that shows locked plane around selection.
+#[derive(Component)]
+struct PlaneLockGrid;
+
+///Shows grid patch on locked plane, below layer of locked cells.
+fn update_plane_lock_grid(
+    selection: Query<(&Selection, &Transform), Without<PlaneLockGrid>>,
+    mut grid: Query<(&mut Transform, &mut Visibility), With<PlaneLockGrid>>,
+) {
+    let (selection, selection_transform) = single_or_return!(selection);
+    for (mut transform, mut visibility) in grid.iter_mut() {
+        match selection.lock {
+            Some(lock) if selection.valid => {
+                let axis = lock.axis();
+                let patch = Transform {
+                    translation: selection_transform.translation - axis * 0.5,
+                    rotation: Quat::from_rotation_arc(Vec3::Y, axis),
+                    ..*transform
+                };
+                set_if_changed(&mut transform, patch);
+                if !visibility.is_visible {
+                    visibility.is_visible = true;
+                }
+            }
+            _ => {
+                if visibility.is_visible {
+                    visibility.is_visible = false;
+                }
+            }
+        }
+    }
 }
 
 ///Places cube where camera looking at. Temporary.
 fn place(
-    mut commands: Commands,
-    mut octree: Query<&mut Octree>,
-    state: Res<GlobalState>,
-    selection: Query<(&Selection, &Transform)>,
+    mut mutator: WorldMutator,
+    mut selection: Query<(&mut Selection, &Transform, &mut PlacementTrail)>,
     input: Res<Input<MouseButton>>,
+    bindings: Res<KeyBindings>,
+    settings: Res<Settings>,
     time: Res<Time>,
     mut press_time: Local<f32>,
+    mut committed: EventWriter<PlacementCommitted>,
 ) {
-    //Checks only when left click.
-    let mut place = input.just_pressed(MouseButton::Left);
+    //Checks only when place button is clicked.
+    let mut place = input.just_pressed(bindings.place);
     if !place {
         //Repeat place if button is pressed long enough.
-        if input.pressed(MouseButton::Left) {
+        if input.pressed(bindings.place) {
             *press_time += time.delta_seconds();
             if *press_time >= 1. {
                 place = true;
@@ -400,48 +1176,86 @@ fn place(
         }
     }
 
-    let (selection, &transform) = selection.single();
+    let (mut selection, &transform, mut trail) = single_or_return!(mut selection);
+    if !input.pressed(bindings.place) && !trail.is_empty() {
+        trail.clear();
+    }
     if place {
         if selection.valid {
             //If there's a result, spawn a selection.
-            let children = selection.create();
-            let entity = commands
-                .spawn((
-                    TransformBundle {
-                        local: transform,
-                        ..default()
-                    },
-                    VisibilityBundle::default(),
-                    state.mark(),
-                    selection.collider.clone(),
-                ))
-                .with_children(|parent| {
-                    for bundle in children {
-                        parent.spawn(bundle);
-                    }
-                })
-                .id();
-            octree
-                .single_mut()
-                .insert(OctreeEntity::new(entity, &selection.collider, &transform));
+            if mutator.spawn_structure(selection.desc(transform)).is_some() {
+                trail.push(transform, settings.placement_trail);
+                selection.record_placement(transform.translation, settings.plane_lock_release);
+                committed.send(PlacementCommitted(transform));
+            }
+        }
+    }
+}
+
+///Recent placements while holding place button, oldest first.
+#[derive(Component, Default)]
+pub struct PlacementTrail(VecDeque<Transform>);
+
+impl PlacementTrail {
+    ///Ghost entities are spawned up front, so trail is capped.
+    pub const MAX_LEN: usize = 8;
+
+    ///Pushes newest placement and drops oldest ones beyond len, which is capped to `MAX_LEN`.
+    pub fn push(&mut self, transform: Transform, len: usize) {
+        let len = len.min(Self::MAX_LEN);
+        self.0.push_back(transform);
+        while self.0.len() > len {
+            self.0.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+///Index of trail ghost. 0 shows newest placement.
+#[derive(Component)]
+struct TrailGhost(usize);
+
+///Moves trail ghosts onto recent placements and hides unused ones.
+fn update_placement_trail(
+    trail: Query<&PlacementTrail, Changed<PlacementTrail>>,
+    mut ghosts: Query<(&TrailGhost, &mut Transform, &mut Visibility), Without<PlacementTrail>>,
+) {
+    let trail = match trail.get_single() {
+        Ok(trail) => trail,
+        Err(_) => return,
+    };
+    for (ghost, mut transform, mut visibility) in ghosts.iter_mut() {
+        match trail.0.iter().rev().nth(ghost.0) {
+            Some(placed) => {
+                *transform = *placed;
+                visibility.is_visible = true;
+            }
+            None => visibility.is_visible = false,
         }
     }
 }
 
 ///Replaces cube where camera looking at. Temporary.
 fn replace(
-    mut commands: Commands,
-    mut octree: Query<&mut Octree>,
+    mut mutator: WorldMutator,
     camera: Query<&LookAt, With<Camera>>,
     input: Res<Input<MouseButton>>,
+    bindings: Res<KeyBindings>,
     time: Res<Time>,
     mut press_time: Local<f32>,
 ) {
-    //Checks only when right click.
-    let mut replace = input.just_pressed(MouseButton::Right);
+    //Checks only when remove button is clicked.
+    let mut replace = input.just_pressed(bindings.remove);
     if !replace {
         //Repeat place if button is pressed long enough.
-        if input.pressed(MouseButton::Right) {
+        if input.pressed(bindings.remove) {
             *press_time += time.delta_seconds();
             if *press_time >= 1. {
                 replace = true;
@@ -453,11 +1267,1185 @@ fn replace(
     }
 
     if replace {
-        if let Some(hit_info) = &camera.single().0 {
+        if let Some(hit_info) = &single_or_return!(camera).0 {
             //If there's a result, despawn a cube.
-            if octree.single_mut().remove(hit_info.entity, hit_info.aabb){
-                commands.entity(hit_info.entity).despawn_recursive();
+            mutator.despawn_structure(hit_info.entity);
+        }
+    }
+}
+
+///Sent when place commits a structure.
+pub struct PlacementCommitted(pub Transform);
+
+///Expanding ring that flashes where structure is placed.
+#[derive(Component)]
+struct PlacementRing {
+    age: f32,
+}
+
+impl PlacementRing {
+    const LIFETIME: f32 = 0.2;
+    ///Oldest rings are removed beyond this, so spamming place doesn't pile them up.
+    const MAX_COUNT: usize = 8;
+    const START_RADIUS: f32 = 0.5;
+    const END_RADIUS: f32 = 3.;
+}
+
+///Spawns ring at placement. Each ring has own material to fade independently.
+fn spawn_placement_ring(
+    mut commands: Commands,
+    mut committed: EventReader<PlacementCommitted>,
+    rings: Query<(Entity, &PlacementRing)>,
+    polylines: Res<Polylines>,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+    state: Res<GlobalState>,
+) {
+    if committed.is_empty() {
+        return;
+    }
+    //Oldest last, so it is popped first.
+    let mut alive: Vec<_> = rings.iter().collect();
+    alive.sort_unstable_by(|(_, a), (_, b)| a.age.total_cmp(&b.age));
+    for PlacementCommitted(transform) in committed.iter() {
+        while alive.len() >= PlacementRing::MAX_COUNT {
+            match alive.pop() {
+                Some((entity, _)) => commands.entity(entity).despawn(),
+                None => break,
+            }
+        }
+        commands.spawn((
+            PolylineBundle {
+                polyline: polylines[UNIT_CIRCLE].clone(),
+                material: polyline_materials.add(PolylineMaterial {
+                    color: Color::WHITE,
+                    perspective: true,
+                    ..default()
+                }),
+                transform: Transform::from_translation(transform.translation)
+                    .with_scale(Vec3::splat(PlacementRing::START_RADIUS)),
+                ..default()
+            },
+            PlacementRing { age: 0. },
+            state.mark(),
+            Name::new("Placement ring"),
+        ));
+    }
+}
+
+///Expands and fades rings, then despawns them when lifetime is over.
+fn animate_placement_rings(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut rings: Query<(
+        Entity,
+        &mut PlacementRing,
+        &mut Transform,
+        &Handle<PolylineMaterial>,
+    )>,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+) {
+    for (entity, mut ring, mut transform, material) in rings.iter_mut() {
+        ring.age += time.delta_seconds();
+        if ring.age >= PlacementRing::LIFETIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let t = ring.age / PlacementRing::LIFETIME;
+        transform.scale = Vec3::splat(
+            PlacementRing::START_RADIUS
+                + (PlacementRing::END_RADIUS - PlacementRing::START_RADIUS) * t,
+        );
+        if let Some(material) = polyline_materials.get_mut(material) {
+            material.color.set_a(1. - t);
+        }
+    }
+}
+
+///Errors when new octree doesn't have base derived from blueprint bound. Only used for debug.
+///Tree built by hand could miss edge of bound, so raycast snaps where tree doesn't cover.
+fn check_octree_base(octrees: Query<&Octree, Added<Octree>>) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    for octree in octrees.iter() {
+        if let Err(expected) = octree.check_blueprint_base(&BLUEPRINT_BOUND) {
+            let base = octree.base_aabb();
+            error!(
+                "Octree base {} - {} doesn't match blueprint bound {} - {}, which needs {} - {}",
+                base.min(),
+                base.max(),
+                BLUEPRINT_BOUND.min(),
+                BLUEPRINT_BOUND.max(),
+                expected.min(),
+                expected.max()
+            );
+        }
+    }
+}
+
+///Whether octree counters are logged every second. Switched from console.
+#[derive(Resource, Default)]
+pub struct OctreeCounterLog(pub bool);
+
+///Seconds between logs of octree counters.
+const COUNTER_LOG_INTERVAL: f32 = 1.;
+
+///Logs what octree did, and how many nodes it has, while log is on.
+fn log_octree_counters(
+    log: Res<OctreeCounterLog>,
+    octree: Query<&Octree>,
+    time: Res<Time>,
+    mut elapsed: Local<f32>,
+) {
+    if !log.0 {
+        *elapsed = 0.;
+        return;
+    }
+    *elapsed += time.delta_seconds();
+    if *elapsed < COUNTER_LOG_INTERVAL {
+        return;
+    }
+    *elapsed = 0.;
+    let octree = single_or_return!(octree);
+    info!(
+        "octree: {}, nodes {} (idle {})",
+        octree.counters(),
+        octree.node_count(),
+        octree.idle_nodes()
+    );
+}
+
+///Request to move every placed structure. None centers build horizontally on blueprint.
+pub struct ShiftBuild(pub Option<Vec3>);
+
+///Shifts every placed structure by offset. Refuses if any would leave blueprint bound.
+fn shift_build(
+    mut events: EventReader<ShiftBuild>,
+    input: Res<Input<KeyCode>>,
+    mut octree: Query<&mut Octree>,
+    mut structures: Query<(Entity, &mut Transform, &Collider), Without<Selection>>,
+    fixtures: Query<(), With<Fixture>>,
+) {
+    let mut requests: Vec<Option<Vec3>> = events.iter().map(|e| e.0).collect();
+    if input.just_pressed(KeyCode::Home) {
+        requests.push(None);
+    }
+    for request in requests {
+        let offset = match request {
+            Some(offset) => offset,
+            None => {
+                //Fold bounds of whole build.
+                let mut min = Vec3::splat(f32::INFINITY);
+                let mut max = Vec3::splat(f32::NEG_INFINITY);
+                for (_, transform, collider) in structures.iter() {
+                    let aabb = collider.aabb(transform);
+                    min = min.min(aabb.min());
+                    max = max.max(aabb.max());
+                }
+                if min.cmpgt(max).any() {
+                    continue;
+                }
+                //Keep height so that build stays on the ground.
+                let offset = (BLUEPRINT_BOUND.center() - (min + max) * 0.5).round();
+                Vec3::new(offset.x, 0., offset.z)
+            }
+        };
+        if offset == Vec3::ZERO {
+            continue;
+        }
+        let outside: Vec<Entity> = structures
+            .iter()
+            .filter(|(_, transform, collider)| {
+                !BLUEPRINT_BOUND.contains(&(collider.aabb(transform) + offset))
+            })
+            .map(|(entity, _, _)| entity)
+            .collect();
+        if !outside.is_empty() {
+            warn!(
+                "Shift by {offset} refused. {} structures would leave blueprint: {outside:?}",
+                outside.len()
+            );
+            continue;
+        }
+        //Fetched before moving anything, so structures and octree never disagree.
+        let mut octree = single_or_return!(mut octree);
+        for (_, mut transform, _) in structures.iter_mut() {
+            transform.translation += offset;
+        }
+        //Whole tree moves at once, then fixtures are put back where they were.
+        let fixed: Vec<_> = octree
+            .iter()
+            .filter(|entity| fixtures.contains(entity.entity()))
+            .cloned()
+            .collect();
+        octree.translate(offset);
+        for entity in fixed {
+            octree.update(entity.entity(), entity.aabb() + offset, entity);
+        }
+    }
+}
+
+///Composer mode for beauty shots. Ui and selection are hidden and camera can roll.
+#[derive(Resource, Default)]
+pub struct ScreenshotMode {
+    active: bool,
+    roll: f32,
+}
+
+impl ScreenshotMode {
+    const ROLL_STEP: f32 = 5.0 * RADIANS;
+}
+
+///Shift + F12 enters screenshot mode, Q/E rolls camera and Esc exits.
+fn screenshot_mode(
+    mut mode: ResMut<ScreenshotMode>,
+    mut ui_hidden: ResMut<UiHidden>,
+    mut selection: Query<&mut Visibility, With<Selection>>,
+    input: Res<Input<KeyCode>>,
+) {
+    if !mode.active {
+        if input.just_pressed(KeyCode::F12) && input.any_pressed([KeyCode::LShift, KeyCode::RShift])
+        {
+            mode.active = true;
+            ui_hidden.0 = true;
+            for mut visibility in selection.iter_mut() {
+                visibility.is_visible = false;
+            }
+        }
+        return;
+    }
+    if input.just_pressed(KeyCode::Q) {
+        mode.roll += ScreenshotMode::ROLL_STEP;
+    }
+    if input.just_pressed(KeyCode::E) {
+        mode.roll -= ScreenshotMode::ROLL_STEP;
+    }
+    if input.just_pressed(KeyCode::Escape) {
+        exit_screenshot_mode(&mut mode, &mut ui_hidden, &mut selection);
+    }
+}
+
+///Leave screenshot mode when in game is paused, so popups are not hidden.
+fn pause_screenshot_mode(
+    mut mode: ResMut<ScreenshotMode>,
+    mut ui_hidden: ResMut<UiHidden>,
+    mut selection: Query<&mut Visibility, With<Selection>>,
+) {
+    if mode.active {
+        exit_screenshot_mode(&mut mode, &mut ui_hidden, &mut selection);
+    }
+}
+
+fn exit_screenshot_mode(
+    mode: &mut ScreenshotMode,
+    ui_hidden: &mut UiHidden,
+    selection: &mut Query<&mut Visibility, With<Selection>>,
+) {
+    *mode = ScreenshotMode::default();
+    ui_hidden.0 = false;
+    for mut visibility in selection.iter_mut() {
+        visibility.is_visible = true;
+    }
+}
+
+///Mark wireframe of buildable area.
+#[derive(Component)]
+pub struct BuildBoundary;
+
+///F2 toggles visibility of build boundary.
+fn toggle_build_boundary(
+    mut settings: ResMut<Settings>,
+    input: Res<Input<KeyCode>>,
+    mut boundary: Query<(&mut Visibility, ChangeTrackers<BuildBoundary>)>,
+) {
+    if input.just_pressed(KeyCode::F2) {
+        settings.show_build_boundary = !settings.show_build_boundary;
+    }
+    for (mut visibility, tracker) in boundary.iter_mut() {
+        if settings.is_changed() || tracker.is_added() {
+            visibility.is_visible = settings.show_build_boundary;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{func::TimeBudget, rng::SplitMix64, structure::tests::mutation_world};
+
+    use std::time::Duration;
+
+    use bevy::asset::HandleId;
+
+    #[test]
+    fn scaled_selection_stands_beside_face() {
+        let cube = Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        };
+        let block = Transform::IDENTITY;
+        for face in [Vec3::X, Vec3::NEG_Y, Vec3::Z] {
+            //Cell beside block on face.
+            let cell = face;
+            for scale in [Selection::MIN_SCALE, 1., 1.5, 2., Selection::MAX_SCALE] {
+                let standing = Transform::from_translation(standing_center(cell, face, scale))
+                    .with_scale(Vec3::splat(scale));
+                assert!(!cube.overlaps(&block, &cube, &standing), "{face} {scale}");
+                //Pushed into block deeper than tolerance.
+                let pushed = Transform {
+                    translation: standing.translation - face * 0.01,
+                    ..standing
+                };
+                assert!(cube.overlaps(&block, &cube, &pushed), "{face} {scale}");
+                //Centered on cell, scaled up one goes into block.
+                let centered = Transform {
+                    translation: cell,
+                    ..standing
+                };
+                assert_eq!(cube.overlaps(&block, &cube, &centered), scale > 1.);
             }
         }
     }
+
+    #[test]
+    fn bob_returns_to_neutral_when_stationary() {
+        let mut bob = CameraBob::default();
+        for _ in 0..37 {
+            bob.update(Vec3::X, Vec3::X, 1. / 60.);
+        }
+        assert!(bob.offset != Vec3::ZERO && bob.roll != 0.);
+        for _ in 0..60 {
+            bob.update(Vec3::ZERO, Vec3::X, 1. / 60.);
+        }
+        assert_eq!(bob.offset, Vec3::ZERO);
+        assert_eq!(bob.roll, 0.);
+        assert_eq!(bob.weight, 0.);
+    }
+
+    #[test]
+    fn level_rotation_drops_roll_but_keeps_look() {
+        let mut rng = SplitMix64::new(752);
+        let mut angle = || (rng.next_f32() - 0.5) * 3.;
+        for _ in 0..200 {
+            let (yaw, pitch, roll) = (angle() * 2., angle(), angle() * 2.);
+            let rolled = Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll);
+            let forward = rolled * Vec3::NEG_Z;
+            let level = level_rotation(forward);
+            assert!((level * Vec3::X).y.abs() < 1e-5, "{yaw} {pitch} {roll}");
+            assert!((level * Vec3::Y).y >= 0.);
+            assert!((level * Vec3::NEG_Z).abs_diff_eq(forward, 1e-5));
+        }
+    }
+
+    ///World with ground and blocks at cells in octree, as game has after placing them.
+    fn shift_world(cells: &[Vec3]) -> (World, Vec<Entity>) {
+        let mut world = World::new();
+        world.init_resource::<Events<ShiftBuild>>();
+        world.init_resource::<Input<KeyCode>>();
+        let mut octree = EntityOctree::for_blueprint(64, Vec3::splat(0.9), &BLUEPRINT_BOUND);
+        let ground = world.spawn(Fixture).id();
+        octree.insert_result(ground_entry(ground));
+        let collider = Collider::from_shape(Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        });
+        let blocks = cells
+            .iter()
+            .enumerate()
+            .map(|(i, &cell)| {
+                let transform = Transform::from_translation(cell);
+                let block = world.spawn((transform, collider.clone())).id();
+                octree.insert_result(OctreeEntity::new(block, i as u64, &collider, &transform));
+                block
+            })
+            .collect();
+        world.spawn(octree);
+        (world, blocks)
+    }
+
+    fn shift(world: &mut World, offset: Vec3) {
+        world.send_event(ShiftBuild(Some(offset)));
+        SystemStage::single(shift_build).run(world);
+    }
+
+    fn hit_below(world: &mut World, point: Vec3) -> Option<Entity> {
+        let octree = world.query::<&EntityOctree>().single(world);
+        let ray = Ray::new(point + Vec3::Y * 20., Vec3::NEG_Y);
+        octree.raycast(&ray).map(|hit| hit.entity)
+    }
+
+    #[test]
+    fn shift_build_moves_structures_but_not_ground() {
+        let cells = [
+            Vec3::new(-30., 0., 0.),
+            Vec3::new(-30., 1., 0.),
+            Vec3::new(4., 0., 7.),
+        ];
+        let (mut world, blocks) = shift_world(&cells);
+        let offset = Vec3::new(5., 0., -3.);
+        shift(&mut world, offset);
+        for (&block, &cell) in blocks.iter().zip(cells.iter()) {
+            let moved = world.get::<Transform>(block).unwrap().translation;
+            assert_eq!(moved, cell + offset);
+        }
+        assert_eq!(hit_below(&mut world, cells[1] + offset), Some(blocks[1]));
+        assert_eq!(hit_below(&mut world, cells[2] + offset), Some(blocks[2]));
+        //Ground stays where it was, so old spot hits it and not what used to be there.
+        let ground = hit_below(&mut world, cells[2]).unwrap();
+        assert!(world.get::<Fixture>(ground).is_some());
+        let octree = world.query::<&EntityOctree>().single(&world);
+        assert_eq!(octree.len(), cells.len() + 1);
+        assert_eq!(octree.debug_validate(), Ok(()));
+    }
+
+    #[test]
+    fn shift_build_refuses_leaving_blueprint() {
+        let cells = [Vec3::new(-30., 0., 0.), Vec3::new(4., 0., 7.)];
+        let (mut world, blocks) = shift_world(&cells);
+        //First block would go past min x of bound.
+        shift(&mut world, Vec3::new(-2., 0., 0.));
+        for (&block, &cell) in blocks.iter().zip(cells.iter()) {
+            assert_eq!(world.get::<Transform>(block).unwrap().translation, cell);
+        }
+        assert_eq!(hit_below(&mut world, cells[0]), Some(blocks[0]));
+        assert_eq!(hit_below(&mut world, cells[1]), Some(blocks[1]));
+    }
+
+    #[test]
+    fn screenshot_mode_restores_camera_and_ui() {
+        let mut world = World::new();
+        world.init_resource::<ScreenshotMode>();
+        world.init_resource::<UiHidden>();
+        world.init_resource::<Input<KeyCode>>();
+        world.init_resource::<Time>();
+        world.init_resource::<Settings>();
+        let start = Transform::from_xyz(1., 2., 3.).with_rotation(Quat::from_euler(
+            EulerRot::YXZ,
+            0.7,
+            -0.3,
+            0.,
+        ));
+        let camera = world
+            .spawn((
+                Camera::default(),
+                start,
+                CameraBob::default(),
+                CameraRoll::default(),
+            ))
+            .id();
+        let selection = world
+            .spawn((Selection::default(), Visibility::default()))
+            .id();
+        let mut stage = SystemStage::single_threaded()
+            .with_system(screenshot_mode)
+            .with_system(move_camera.after(screenshot_mode));
+        let mut frame = |world: &mut World, keys: &[KeyCode]| {
+            let mut input = world.resource_mut::<Input<KeyCode>>();
+            input.clear();
+            for &key in keys {
+                input.press(key);
+            }
+            stage.run(world);
+            let mut input = world.resource_mut::<Input<KeyCode>>();
+            for &key in keys {
+                input.release(key);
+            }
+        };
+
+        frame(&mut world, &[KeyCode::LShift, KeyCode::F12]);
+        assert!(world.resource::<UiHidden>().0);
+        assert!(!world.get::<Visibility>(selection).unwrap().is_visible);
+        frame(&mut world, &[KeyCode::Q]);
+        frame(&mut world, &[KeyCode::Q]);
+        let rolled = world.get::<Transform>(camera).unwrap();
+        let tilt = (2. * ScreenshotMode::ROLL_STEP).sin() * start.up().y;
+        assert!((rolled.right().y - tilt).abs() < 1e-4);
+        assert!(rolled.forward().abs_diff_eq(start.forward(), 1e-4));
+
+        frame(&mut world, &[KeyCode::Escape]);
+        assert!(!world.resource::<ScreenshotMode>().active);
+        assert!(!world.resource::<UiHidden>().0);
+        assert!(world.get::<Visibility>(selection).unwrap().is_visible);
+        let restored = world.get::<Transform>(camera).unwrap();
+        assert_eq!(restored.translation, start.translation);
+        assert!(restored.rotation.abs_diff_eq(start.rotation, 1e-5));
+        //Roll keys do nothing out of the mode.
+        frame(&mut world, &[KeyCode::Q]);
+        assert_eq!(world.resource::<ScreenshotMode>().roll, 0.);
+    }
+
+    #[test]
+    fn trail_never_exceeds_configured_length() {
+        let mut trail = PlacementTrail::default();
+        let at = |i: usize| Transform::from_xyz(i as f32, 0., 0.);
+        //Length setting changes while placing, including beyond cap and zero.
+        for (i, len) in [3, 3, 3, 3, 5, 20, 20, 20, 20, 20, 20, 20, 20, 2, 0, 1]
+            .into_iter()
+            .enumerate()
+        {
+            trail.push(at(i), len);
+            assert!(trail.0.len() <= len.min(PlacementTrail::MAX_LEN), "{i}");
+            if len > 0 {
+                assert_eq!(trail.0.back(), Some(&at(i)));
+            }
+        }
+        assert_eq!(trail.0.len(), 1);
+        //Kept ones are latest, oldest first.
+        for i in 0..20 {
+            trail.push(at(i), 4);
+        }
+        let kept: Vec<_> = trail.0.iter().map(|placed| placed.translation.x).collect();
+        assert_eq!(kept, [16., 17., 18., 19.]);
+    }
+
+    ///World where selection at cell could be placed, with what placing writes to.
+    fn placing_world(cell: Vec3) -> World {
+        let mut world = mutation_world(EntityOctree::for_blueprint(
+            64,
+            Vec3::splat(0.9),
+            &BLUEPRINT_BOUND,
+        ));
+        world.init_resource::<Events<PlacementCommitted>>();
+        world.init_resource::<Input<MouseButton>>();
+        world.init_resource::<Input<KeyCode>>();
+        world.init_resource::<KeyBindings>();
+        world.init_resource::<Settings>();
+        world.init_resource::<Time>();
+        let mut selection = Selection::default();
+        selection.valid = true;
+        world.spawn((
+            selection,
+            Transform::from_translation(cell),
+            PlacementTrail::default(),
+        ));
+        world
+    }
+
+    #[test]
+    fn scaled_placement_takes_scaled_bound() {
+        let cell = Vec3::new(3., 2., -5.);
+        let mut world = placing_world(cell);
+        let mut stage = SystemStage::single_threaded()
+            .with_system(scale_selection)
+            .with_system(place.after(scale_selection));
+        //Each bracket press is one scale step, so four of them double it.
+        for _ in 0..4 {
+            let mut input = world.resource_mut::<Input<KeyCode>>();
+            input.clear();
+            input.release(KeyCode::RBracket);
+            input.press(KeyCode::RBracket);
+            stage.run(&mut world);
+        }
+        world.resource_mut::<Input<KeyCode>>().clear();
+        world
+            .resource_mut::<Input<MouseButton>>()
+            .press(MouseButton::Left);
+        stage.run(&mut world);
+
+        let placed = world
+            .query_filtered::<(Entity, &Transform), With<Structure>>()
+            .iter(&world)
+            .map(|(entity, &transform)| (entity, transform))
+            .collect::<Vec<_>>();
+        assert_eq!(placed.len(), 1);
+        let (entity, transform) = placed[0];
+        assert_eq!(transform.translation, cell);
+        assert_eq!(transform.scale, Vec3::splat(2.));
+        let octree = world.query::<&EntityOctree>().single(&world);
+        let entry = octree.iter().find(|entry| entry.entity() == entity);
+        assert_eq!(
+            entry.map(|entry| entry.aabb()),
+            Some(AABB::new(cell - 1., cell + 1.))
+        );
+    }
+
+    #[test]
+    fn builder_matches_positional_new() {
+        let mesh = Handle::weak(HandleId::random::<Mesh>());
+        let [material, trans, invalid] =
+            [(); 3].map(|_| Handle::weak(HandleId::random::<StandardMaterial>()));
+        let collider = || Collider::from_shape(Shape::Sphere { radius: 0.75 });
+        let new = Selection::new(
+            vec![mesh.clone()],
+            material.clone(),
+            trans.clone(),
+            invalid.clone(),
+            collider(),
+        );
+        //Setters in different order than positions, which is what builder is for.
+        let built = Selection::builder()
+            .with_collider(collider())
+            .with_material_invalid(invalid)
+            .with_material_trans(trans)
+            .with_meshes(vec![mesh])
+            .with_material(material)
+            .build();
+        assert_eq!(built.meshes, new.meshes);
+        assert_eq!(built.material, new.material);
+        assert_eq!(built.material_trans, new.material_trans);
+        assert_eq!(built.material_invalid, new.material_invalid);
+        let transform = Transform::from_xyz(1., 2., 3.);
+        assert_eq!(
+            built.collider.aabb(&transform),
+            new.collider.aabb(&transform)
+        );
+        assert_eq!(
+            (built.valid, built.scale, built.shown_valid, built.lock),
+            (new.valid, new.scale, new.shown_valid, new.lock)
+        );
+        assert_eq!(built.tags, new.tags);
+        assert_eq!(built.last_placed, new.last_placed);
+    }
+
+    #[test]
+    fn held_aim_doesnt_reissue_preview_material() {
+        #[derive(Resource, Default)]
+        struct Writes(usize);
+        fn count_writes(
+            changed: Query<(), Changed<Handle<StandardMaterial>>>,
+            mut writes: ResMut<Writes>,
+        ) {
+            writes.0 += changed.iter().count();
+        }
+
+        let mut world = World::new();
+        world.init_resource::<Writes>();
+        let material = || Handle::weak(HandleId::random::<StandardMaterial>());
+        let (trans, invalid) = (material(), material());
+        let mut selection = Selection::default();
+        selection.material_trans = trans.clone();
+        selection.material_invalid = invalid.clone();
+        selection.valid = true;
+        let parts = [(); 3].map(|_| world.spawn(Handle::<StandardMaterial>::default()).id());
+        let selection = world.spawn(selection).push_children(&parts).id();
+        let mut stage = SystemStage::single_threaded()
+            .with_system(update_preview_material)
+            .with_system(count_writes.after(update_preview_material));
+        let mut frame = |world: &mut World, valid| {
+            world.get_mut::<Selection>(selection).unwrap().valid = valid;
+            world.resource_mut::<Writes>().0 = 0;
+            stage.run(world);
+            world.resource::<Writes>().0
+        };
+        //First frame shows validity, which also counts freshly spawned parts.
+        assert_eq!(frame(&mut world, true), 3);
+        for _ in 0..10 {
+            assert_eq!(frame(&mut world, true), 0);
+        }
+        assert_eq!(frame(&mut world, false), 3);
+        assert!(parts
+            .iter()
+            .all(|part| world.get::<Handle<StandardMaterial>>(*part) == Some(&invalid)));
+        for _ in 0..10 {
+            assert_eq!(frame(&mut world, false), 0);
+        }
+        assert_eq!(frame(&mut world, true), 3);
+        assert!(parts
+            .iter()
+            .all(|part| world.get::<Handle<StandardMaterial>>(*part) == Some(&trans)));
+    }
+
+    #[test]
+    fn ghost_follows_camera_before_heavy_work() {
+        ///Ghost positions heavy system saw, one per frame.
+        #[derive(Resource, Default)]
+        struct Seen(Vec<Vec3>);
+        fn heavy(selection: Query<&Transform, With<Selection>>, mut seen: ResMut<Seen>) {
+            let budget = TimeBudget::new(Duration::from_millis(5));
+            while budget.start_unit() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            seen.0.push(selection.single().translation);
+        }
+
+        let mut app = App::new();
+        app.add_event::<MouseWheel>()
+            .init_resource::<QueryTrace>()
+            .init_resource::<Seen>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                camera_look_at.label(GhostSystem::LookAt),
+            )
+            .add_system(heavy);
+        let cells = [
+            Vec3::new(2., 3., -4.),
+            Vec3::new(-7., 10., 1.),
+            Vec3::new(12., 1., 8.),
+        ];
+        let mut octree = EntityOctree::for_blueprint(64, Vec3::splat(0.9), &BLUEPRINT_BOUND);
+        let collider = Collider::from_shape(Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        });
+        for (i, cell) in cells.iter().enumerate() {
+            let entity = Entity::from_raw(1000 + i as u32);
+            let transform = Transform::from_translation(*cell);
+            octree.insert_result(OctreeEntity::new(entity, i as u64, &collider, &transform));
+        }
+        app.world.spawn(octree);
+        let camera = app
+            .world
+            .spawn((Camera::default(), Transform::default(), LookAt(None)))
+            .id();
+        app.world
+            .spawn((Selection::default(), Transform::default()));
+        //Camera aims at another block every frame, and heavy work sees ghost on it in that same frame.
+        for cell in cells {
+            let eye = cell + Vec3::new(0.1, 0.2, 5.);
+            *app.world.get_mut::<Transform>(camera).unwrap() =
+                Transform::from_translation(eye).looking_at(cell, Vec3::Y);
+            app.update();
+        }
+        let expected: Vec<_> = cells.iter().map(|cell| *cell + Vec3::Z).collect();
+        assert_eq!(app.world.resource::<Seen>().0, expected);
+    }
+
+    #[test]
+    fn lighting_change_updates_sun() {
+        let mut world = World::new();
+        world.init_resource::<Lighting>();
+        let sun = world
+            .spawn((DirectionalLight::default(), Transform::default(), Sun))
+            .id();
+        let mut stage = SystemStage::single(apply_lighting);
+        let sun_of = |world: &World| {
+            let light = world.get::<DirectionalLight>(sun).unwrap();
+            let rotation = world.get::<Transform>(sun).unwrap().rotation;
+            (light.illuminance, light.color, rotation)
+        };
+        stage.run(&mut world);
+        let lighting = Lighting::default();
+        assert_eq!(
+            sun_of(&world),
+            (lighting.illuminance, lighting.color, lighting.rotation())
+        );
+        let dusk = Lighting {
+            illuminance: 4000.,
+            color: Color::rgb(1., 0.6, 0.3),
+            yaw: PI,
+            pitch: -PI * 0.05,
+        };
+        world.insert_resource(dusk);
+        stage.run(&mut world);
+        assert_eq!(
+            sun_of(&world),
+            (dusk.illuminance, dusk.color, dusk.rotation())
+        );
+        //Sun left alone while settings don't change.
+        world.get_mut::<DirectionalLight>(sun).unwrap().illuminance = 1.;
+        stage.run(&mut world);
+        assert_eq!(sun_of(&world).0, 1.);
+    }
+
+    #[test]
+    fn face_indicator_lies_on_hit_face() {
+        let mut world = World::new();
+        world.init_resource::<QueryTrace>();
+        world.init_resource::<Events<MouseWheel>>();
+        let block = Vec3::new(3., 5., -2.);
+        let mut octree = EntityOctree::for_blueprint(64, Vec3::splat(0.9), &BLUEPRINT_BOUND);
+        octree.insert_result(OctreeEntity::new(
+            Entity::from_raw(1000),
+            0,
+            &Collider::from_shape(Shape::Cuboid {
+                half_extents: Vec3::splat(0.5),
+            }),
+            &Transform::from_translation(block),
+        ));
+        world.spawn(octree);
+        let camera = world
+            .spawn((Camera::default(), Transform::default(), LookAt(None)))
+            .id();
+        world.spawn((Selection::default(), Transform::default()));
+        let indicator = world
+            .spawn((
+                FaceIndicator,
+                Transform::default(),
+                Visibility { is_visible: false },
+            ))
+            .id();
+        let mut stage = SystemStage::single(camera_look_at);
+        let mut aim = |world: &mut World, eye: Vec3, target: Vec3| {
+            *world.get_mut::<Transform>(camera).unwrap() =
+                Transform::from_translation(eye).looking_at(target, Vec3::Y);
+            stage.run(world);
+            let visible = world.get::<Visibility>(indicator).unwrap().is_visible;
+            (*world.get::<Transform>(indicator).unwrap(), visible)
+        };
+        for normal in [
+            Vec3::X,
+            Vec3::NEG_X,
+            Vec3::Y,
+            Vec3::NEG_Y,
+            Vec3::Z,
+            Vec3::NEG_Z,
+        ] {
+            //Slightly off center, so ray isn't along any axis.
+            let target = block + normal * 0.5 + normal.any_orthonormal_vector() * 0.2;
+            let (transform, visible) = aim(&mut world, block + normal * 6., target);
+            assert!(visible, "{normal}");
+            //Square is drawn on its local xz plane, so its up is plane normal.
+            assert!(
+                (transform.rotation * Vec3::Y).abs_diff_eq(normal, 1e-5),
+                "{normal}"
+            );
+            let face_center = block + normal * 0.5;
+            for corner in [Vec3::new(-0.5, 0., -0.5), Vec3::new(0.5, 0., 0.5)] {
+                let corner = transform.transform_point(corner);
+                let lift = (corner - face_center).dot(normal);
+                assert!(lift > 0. && lift < 0.05, "{normal} {lift}");
+                //Inset, so outline stays within face.
+                let along = (corner - face_center).reject_from(normal);
+                assert!(along.abs().max_element() < 0.5, "{normal} {along}");
+            }
+        }
+        //Looking away from block hides it.
+        let (_, visible) = aim(&mut world, block + Vec3::X * 6., block + Vec3::X * 12.);
+        assert!(!visible);
+    }
+
+    #[test]
+    fn look_at_before_setup_doesnt_panic() {
+        let mut world = World::new();
+        world.init_resource::<QueryTrace>();
+        world.init_resource::<Events<MouseWheel>>();
+        let mut stage = SystemStage::single(camera_look_at);
+        //Nothing spawned yet, and then camera and selection without octree.
+        stage.run(&mut world);
+        world.spawn((Camera::default(), Transform::default(), LookAt(None)));
+        let selection = world
+            .spawn((Selection::default(), Transform::from_xyz(1., 2., 3.)))
+            .id();
+        stage.run(&mut world);
+        let entity = world.entity(selection);
+        assert!(!entity.get::<Selection>().unwrap().valid);
+        assert_eq!(
+            entity.get::<Transform>().unwrap().translation,
+            Vec3::new(1., 2., 3.)
+        );
+    }
+
+    #[test]
+    fn flick_and_click_place_where_turned_camera_aims() {
+        //Empty app, so placing world isn't missing what default app would have put in its own.
+        let mut app = App::empty();
+        app.world = placing_world(Vec3::ZERO);
+        app.add_default_stages()
+            .add_event::<MouseWheel>()
+            .add_event::<MouseMotion>()
+            .init_resource::<QueryTrace>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                turn_camera.before(GhostSystem::LookAt),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                camera_look_at.label(GhostSystem::LookAt),
+            )
+            .add_system(place);
+        let (ahead, left) = (Vec3::new(0., 1., -5.), Vec3::new(-5., 1., 5.));
+        let collider = Collider::from_shape(Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        });
+        let mut octree = app.world.query::<&mut EntityOctree>();
+        let mut octree = octree.single_mut(&mut app.world);
+        for (i, cell) in [ahead, left].into_iter().enumerate() {
+            let entity = Entity::from_raw(1000 + i as u32);
+            let transform = Transform::from_translation(cell);
+            octree.insert_result(OctreeEntity::new(entity, i as u64, &collider, &transform));
+        }
+        app.world.spawn((
+            Camera::default(),
+            Transform::from_xyz(0., 1., 5.),
+            LookAt(None),
+            CameraRoll::default(),
+        ));
+        let placed = |world: &mut World| {
+            world
+                .query_filtered::<&Transform, With<Structure>>()
+                .iter(world)
+                .map(|transform| transform.translation)
+                .collect::<Vec<_>>()
+        };
+        app.update();
+        assert!(placed(&mut app.world).is_empty());
+        //Quarter turn to left, whose motion arrives in same frame as click.
+        app.world.send_event(MouseMotion {
+            delta: Vec2::new(-90. / 0.08, 0.),
+        });
+        app.world
+            .resource_mut::<Input<MouseButton>>()
+            .press(MouseButton::Left);
+        app.update();
+        assert_eq!(placed(&mut app.world), [left + Vec3::X]);
+    }
+
+    #[test]
+    fn rebound_place_button_places() {
+        let cell = Vec3::new(-4., 1., 6.);
+        let mut world = placing_world(cell);
+        world.resource_mut::<KeyBindings>().place = MouseButton::Right;
+        let mut stage = SystemStage::single(place);
+        let mut click = |world: &mut World, button| {
+            let mut input = world.resource_mut::<Input<MouseButton>>();
+            input.release_all();
+            input.clear();
+            input.press(button);
+            stage.run(world);
+            world
+                .query_filtered::<&Transform, With<Structure>>()
+                .iter(world)
+                .map(|transform| transform.translation)
+                .collect::<Vec<_>>()
+        };
+        //Left is no longer bound to anything placing.
+        assert!(click(&mut world, MouseButton::Left).is_empty());
+        assert_eq!(click(&mut world, MouseButton::Right), [cell]);
+    }
+
+    #[test]
+    fn placement_rings_despawn_after_lifetime() {
+        //Rings need material assets, which only asset server could make.
+        let mut app = App::new();
+        app.add_plugin(AssetPlugin::default())
+            .add_asset::<PolylineMaterial>()
+            .add_event::<PlacementCommitted>()
+            .insert_resource(GlobalState::new(AppState::InGame));
+        let world = &mut app.world;
+        let mut polylines = Polylines::default();
+        polylines.insert(UNIT_CIRCLE, default());
+        world.insert_resource(polylines);
+        let mut time = Time::default();
+        let start = time.startup();
+        time.update_with_instant(start);
+        world.insert_resource(time);
+        let mut stage = SystemStage::single_threaded()
+            .with_system(spawn_placement_ring)
+            .with_system(animate_placement_rings.after(spawn_placement_ring));
+        let mut elapsed = Duration::ZERO;
+        let mut frame = |world: &mut World, millis, placed| {
+            elapsed += Duration::from_millis(millis);
+            world
+                .resource_mut::<Time>()
+                .update_with_instant(start + elapsed);
+            if placed {
+                world.send_event(PlacementCommitted(Transform::IDENTITY));
+            }
+            stage.run(world);
+            world.query::<&PlacementRing>().iter(world).count()
+        };
+
+        //Placing every frame keeps only latest ones.
+        for _ in 0..20 {
+            assert!(frame(world, 5, true) <= PlacementRing::MAX_COUNT);
+        }
+        assert_eq!(frame(world, 5, false), PlacementRing::MAX_COUNT);
+        assert_eq!(frame(world, 100, false), PlacementRing::MAX_COUNT);
+        //Each is gone once it is as old as lifetime.
+        assert_eq!(frame(world, 100, false), 0);
+        assert_eq!(frame(world, 5, true), 1);
+        assert_eq!(frame(world, 199, false), 1);
+        assert_eq!(frame(world, 10, false), 0);
+    }
+
+    #[test]
+    fn plane_lock_projects_oblique_rays() {
+        let through = Vec3::new(2., 5., -3.);
+        let dirs = [
+            Vec3::new(1., -1., 0.5),
+            Vec3::new(-0.3, -1., -2.),
+            Vec3::new(0.2, 0.7, -1.),
+        ];
+        for axis in 0..3 {
+            let lock = PlaneLock::new(axis, through);
+            for dir in dirs {
+                for side in [-1., 1.] {
+                    let dir = dir.normalize();
+                    //Start off plane on given side, looking toward it.
+                    let mut origin = Vec3::new(-7., 12., 4.);
+                    origin[axis] = through[axis] - side * 9.;
+                    let dir = if dir[axis] * side > 0. { dir } else { -dir };
+                    let ray = Ray::new(origin, dir);
+                    let cell = lock.project(&ray).unwrap();
+                    assert_eq!(cell[axis], through[axis], "{axis} {dir}");
+                    //Nearest cell to where ray meets plane.
+                    let exact = origin + dir * ((through[axis] - origin[axis]) / dir[axis]);
+                    assert!((cell - exact).abs().max_element() <= 0.5, "{axis} {dir}");
+                    //Plane behind doesn't count.
+                    assert_eq!(lock.project(&Ray::new(origin, -dir)), None);
+                }
+            }
+            let mut parallel = Vec3::ONE;
+            parallel[axis] = 0.;
+            assert_eq!(lock.project(&Ray::new(Vec3::ZERO, parallel)), None);
+        }
+    }
+
+    #[test]
+    fn locked_snap_stays_on_plane() {
+        //Stacks on layers other than locked one, so many rays hit off plane.
+        let mut cells = Vec::new();
+        for x in -4..=4 {
+            for y in 0..3 {
+                cells.push(Vec3::new((x * 2) as f32, y as f32, (x % 3) as f32));
+            }
+        }
+        let (mut world, _) = shift_world(&cells);
+        let octree = world.query::<&EntityOctree>().single(&world);
+        let mut rng = SplitMix64::new(735);
+        let mut off_plane = 0;
+        for axis in 0..3 {
+            let lock = PlaneLock::new(axis, Vec3::new(1., 1., 0.));
+            for _ in 0..300 {
+                let mut unit = || rng.next_f32() * 2. - 1.;
+                let origin = Vec3::new(unit() * 20., 8. + unit() * 4., unit() * 20.);
+                let target = Vec3::new(unit() * 8., unit() * 2. + 1., unit() * 3.);
+                let ray = Ray::new(origin, (target - origin).normalize());
+                let hit = octree.raycast(&ray);
+                if let Some((cell, _)) = snap_cell(&ray, hit.as_ref(), Some(lock)) {
+                    assert_eq!(cell[axis], lock.level, "{axis} {origin} {target}");
+                }
+                let unlocked = snap_cell(&ray, hit.as_ref(), None);
+                if unlocked.map_or(false, |(cell, _)| !lock.contains(cell)) {
+                    off_plane += 1;
+                }
+            }
+        }
+        assert!(off_plane > 100, "{off_plane}");
+    }
+
+    ///Hit on unit cube at cell, as shape raycast reports it.
+    fn cell_hit(cell: Vec3, ray: &Ray) -> Option<RayHitInfo> {
+        let aabb = AABB::new(cell - 0.5, cell + 0.5);
+        let (t, normal) = aabb.intersects_ray_normal(ray)?;
+        Some(RayHitInfo::new(
+            Entity::from_raw(0),
+            0,
+            aabb,
+            ray,
+            t,
+            normal,
+        ))
+    }
+
+    ///Ray that comes down onto face of cell at slope, reaching it off center.
+    fn ray_onto_face(cell: Vec3, face: Vec3, slope: f32) -> Ray {
+        let across = face.any_orthonormal_vector();
+        let target = cell + face * 0.5 + across * 0.3 + face.cross(across) * -0.2;
+        let dir = (across - face * slope).normalize();
+        Ray::new(target - dir * 5., dir)
+    }
+
+    #[test]
+    fn grazing_rays_snap_beside_hit_face() {
+        for cell in [
+            Vec3::new(3., 2., -4.),
+            Vec3::new(100., 2., -100.),
+            Vec3::new(-99., 57., 100.),
+        ] {
+            for face in [
+                Vec3::X,
+                Vec3::NEG_X,
+                Vec3::Y,
+                Vec3::NEG_Y,
+                Vec3::Z,
+                Vec3::NEG_Z,
+            ] {
+                for slope in [1., 0.1, 0.01, 0.001] {
+                    let ray = ray_onto_face(cell, face, slope);
+                    let hit = cell_hit(cell, &ray);
+                    assert_eq!(hit.as_ref().map(|hit| hit.normal), Some(face));
+                    let snapped = snap_cell(&ray, hit.as_ref(), None);
+                    assert_eq!(snapped, Some((cell + face, face)), "{cell} {face} {slope}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn far_hits_snap_beside_hit_face() {
+        //Spacing of f32 here is wider than fixed nudge.
+        for cell in [
+            Vec3::new(50_000., 3., -7.),
+            Vec3::new(-5., -50_000., 50_000.),
+        ] {
+            for face in [
+                Vec3::X,
+                Vec3::NEG_X,
+                Vec3::Y,
+                Vec3::NEG_Y,
+                Vec3::Z,
+                Vec3::NEG_Z,
+            ] {
+                let ray = ray_onto_face(cell, face, 1.);
+                let snapped = snap_cell(&ray, cell_hit(cell, &ray).as_ref(), None);
+                assert_eq!(snapped, Some((cell + face, face)), "{cell} {face}");
+            }
+        }
+    }
+
+    #[test]
+    fn snap_matches_f64_reference() {
+        let mut rng = SplitMix64::new(762);
+        let mut checked = 0;
+        for _ in 0..5000 {
+            let mut unit = || rng.next_f32() * 2. - 1.;
+            let cell = (Vec3::new(unit(), unit(), unit()) * 120.).round();
+            let target = cell + Vec3::new(unit(), unit(), unit()) * 0.5;
+            //Any angle, grazing ones included, from right beside cube to across blueprint.
+            let dir = Vec3::new(unit(), unit(), unit()).normalize();
+            let distance = 1. + (unit() + 1.) * 40.;
+            let ray = Ray::new(target - dir * distance, dir);
+
+            //Slab entry of cube in f64.
+            let origin = ray.origin().as_dvec3();
+            let dir = ray.dir().as_dvec3();
+            let (mut enter, mut axis) = (f64::NEG_INFINITY, 0);
+            for i in (0..3).filter(|&i| dir[i] != 0.) {
+                let near = cell[i] as f64 - 0.5 * dir[i].signum();
+                let t = (near - origin[i]) / dir[i];
+                if t > enter {
+                    (enter, axis) = (t, i);
+                }
+            }
+            let point = origin + dir * enter;
+            let lateral = (point - cell.as_dvec3()).abs();
+            //Origin inside cube, or entry too near edge for f32 to tell faces apart.
+            if enter <= 0. || (0..3).any(|i| i != axis && lateral[i] > 0.5 - 1e-3) {
+                continue;
+            }
+            let mut face = Vec3::ZERO;
+            face[axis] = -dir[axis].signum() as f32;
+
+            let snapped = snap_cell(&ray, cell_hit(cell, &ray).as_ref(), None);
+            let (origin, dir) = (ray.origin(), ray.dir());
+            assert_eq!(snapped, Some((cell + face, face)), "{cell} {origin} {dir}");
+            checked += 1;
+        }
+        assert!(checked > 4000, "{checked}");
+    }
+
+    #[test]
+    fn leaving_game_keeps_octree_for_next_one() {
+        let mut world = World::new();
+        world.init_resource::<SpareOctree>();
+        let mut octree = EntityOctree::for_blueprint(64, Vec3::splat(0.9), &BLUEPRINT_BOUND);
+        let collider = Collider::from_shape(Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        });
+        for i in 0..50 {
+            let transform = Transform::from_xyz((i % 10) as f32 * 3. - 15., (i / 10) as f32, 0.);
+            octree.insert_result(OctreeEntity::new(
+                Entity::from_raw(i),
+                i as u64,
+                &collider,
+                &transform,
+            ));
+        }
+        let nodes = octree.node_count();
+        world.spawn((octree, Name::new("Octree")));
+
+        SystemStage::single(stash_octree).run(&mut world);
+        assert_eq!(world.entities().len(), 0);
+        let spare = world.resource::<SpareOctree>().0.as_ref();
+        let spare = spare.expect("Octree should be kept");
+        assert_eq!((spare.len(), spare.node_count()), (50, nodes));
+    }
 }
@@ -0,0 +1,400 @@
+use super::*;
+
+use crate::func::VisuallyEq;
+
+///Width of compass strip in pixels.
+const STRIP_WIDTH: f32 = 360.;
+const STRIP_HEIGHT: f32 = 28.;
+///Degrees of headings strip shows at once, centered on camera heading.
+const STRIP_SPAN: u32 = 180;
+///Degrees between ticks.
+const TICK_STEP: u32 = 15;
+///Most ticks strip could show at once. Tick nodes are spawned once and reassigned.
+const TICK_POOL: usize = (STRIP_SPAN / TICK_STEP) as usize + 1;
+const TICK_WIDTH: f32 = 30.;
+const MARKER_SIZE: f32 = 8.;
+///Turn of camera in degrees below which strip isn't moved.
+const HEADING_EPS: f32 = 0.25;
+
+///Batch setup for compass strip and position readout at top of screen.
+pub struct CompassPlugin;
+
+impl Plugin for CompassPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Waypoint>()
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_enter(PreUpdateStageState::InGame).with_system(establish_waypoint),
+            )
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::on_update(PostUpdateStageState::InGame)
+                    .with_system(toggle_compass)
+                    .with_system(update_compass_strip)
+                    .with_system(update_compass_readout),
+            );
+    }
+}
+
+///Point compass leads to instead of blueprint origin. Saved with build.
+#[derive(Resource, Clone, Copy, Default, PartialEq, Debug)]
+pub struct Waypoint(pub Option<Vec3>);
+
+///Mark top of compass hierarchy.
+#[derive(Component)]
+struct CompassRoot;
+
+///Pooled tick of strip.
+#[derive(Component)]
+struct CompassTick;
+
+///Mark diamond toward waypoint or origin.
+#[derive(Component)]
+struct CompassMarker;
+
+///Mark position readout under strip.
+#[derive(Component)]
+struct CompassReadout;
+
+///Compass heading of direction in degrees. North is -Z and east is +X.
+///Vertical direction has none.
+pub fn heading(dir: Vec3) -> Option<f32> {
+    if dir.x.abs() < POINT_EPS && dir.z.abs() < POINT_EPS {
+        return None;
+    }
+    Some(dir.x.atan2(-dir.z).to_degrees().rem_euclid(360.))
+}
+
+///Turn from one heading to another in degrees, wrapped into -180..180.
+pub fn heading_delta(from: f32, to: f32) -> f32 {
+    (to - from + 180.).rem_euclid(360.) - 180.
+}
+
+///Headings of ticks strip shows at camera heading, with their offsets from strip center in pixels.
+fn visible_ticks(camera: f32) -> impl Iterator<Item = (u32, f32)> {
+    let half = STRIP_SPAN as f32 * 0.5;
+    let step = TICK_STEP as f32;
+    let first = ((camera - half) / step).ceil() as i32;
+    let last = ((camera + half) / step).floor() as i32;
+    //Ticks are counted from camera without wrapping, so offset is plain difference.
+    (first..=last).map(move |i| {
+        let unwrapped = (i * TICK_STEP as i32) as f32;
+        (
+            (i * TICK_STEP as i32).rem_euclid(360) as u32,
+            (unwrapped - camera) / STRIP_SPAN as f32 * STRIP_WIDTH,
+        )
+    })
+}
+
+///Offset of marker for target heading. Target out of strip sticks to edge it is toward.
+fn marker_offset(camera: f32, target: f32) -> f32 {
+    let half = STRIP_SPAN as f32 * 0.5;
+    heading_delta(camera, target).clamp(-half, half) / STRIP_SPAN as f32 * STRIP_WIDTH
+}
+
+fn tick_label(heading: u32) -> &'static str {
+    match heading {
+        0 => "N",
+        45 => "NE",
+        90 => "E",
+        135 => "SE",
+        180 => "S",
+        225 => "SW",
+        270 => "W",
+        315 => "NW",
+        _ => "|",
+    }
+}
+
+///Same as camera, waypoint of loaded save is restored when game is entered.
+fn establish_waypoint(mut waypoint: ResMut<Waypoint>, pending: Option<Res<PendingLoad>>) {
+    *waypoint = Waypoint(pending.and_then(|pending| pending.1.waypoint));
+}
+
+fn spawn_compass(commands: &mut Commands, state: &GlobalState, fonts: &Res<Fonts>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Percent(50.),
+                        top: Val::Px(4.),
+                        ..default()
+                    },
+                    margin: UiRect {
+                        left: Val::Px(-STRIP_WIDTH * 0.5),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(STRIP_WIDTH), Val::Auto),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            state.mark(),
+            UiRoot,
+            CompassRoot,
+            Name::new("Compass"),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(STRIP_WIDTH), Val::Px(STRIP_HEIGHT)),
+                        overflow: Overflow::Hidden,
+                        ..default()
+                    },
+                    background_color: Color::rgba(0., 0., 0., 0.4).into(),
+                    ..default()
+                })
+                .with_children(|strip| {
+                    for _ in 0..TICK_POOL {
+                        strip
+                            .spawn((
+                                NodeBundle {
+                                    style: Style {
+                                        position_type: PositionType::Absolute,
+                                        size: Size::new(Val::Px(TICK_WIDTH), Val::Px(STRIP_HEIGHT)),
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        ..default()
+                                    },
+                                    ..default()
+                                },
+                                CompassTick,
+                            ))
+                            .with_children(|tick| {
+                                tick.spawn(create_text("|", fonts, 16., TEXT_COLOR_BRIGHT));
+                            });
+                    }
+                    //Square turned by quarter of right angle is diamond.
+                    strip.spawn((
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                position: UiRect {
+                                    bottom: Val::Px(2.),
+                                    ..default()
+                                },
+                                size: Size::new(Val::Px(MARKER_SIZE), Val::Px(MARKER_SIZE)),
+                                ..default()
+                            },
+                            transform: Transform::from_rotation(Quat::from_rotation_z(FRAC_PI_4)),
+                            ..default()
+                        },
+                        CompassMarker,
+                    ));
+                });
+            parent.spawn((
+                create_text("", fonts, 16., TEXT_COLOR_BRIGHT),
+                CompassReadout,
+            ));
+        });
+}
+
+///Compass is spawned while enabled in settings, and despawned when disabled.
+fn toggle_compass(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    state: Res<GlobalState>,
+    fonts: Res<Fonts>,
+    roots: Query<Entity, With<CompassRoot>>,
+) {
+    if settings.show_compass && roots.is_empty() {
+        spawn_compass(&mut commands, &state, &fonts);
+    } else if !settings.show_compass {
+        for root in roots.iter() {
+            commands.entity(root).despawn_recursive();
+        }
+    }
+}
+
+///Slides ticks by camera heading and puts marker toward waypoint, or origin without one.
+///Nodes are touched only when heading turns, so strip costs nothing while camera holds still.
+fn update_compass_strip(
+    waypoint: Res<Waypoint>,
+    camera: Query<&Transform, (With<Camera>, With<LookAt>)>,
+    mut ticks: Query<(
+        &mut Style,
+        &mut Visibility,
+        &Children,
+        ChangeTrackers<CompassTick>,
+    )>,
+    mut marker: Query<
+        (&mut Style, &mut BackgroundColor, &mut Visibility),
+        (With<CompassMarker>, Without<CompassTick>),
+    >,
+    mut texts: Query<&mut Text>,
+    mut shown: Local<Option<(f32, Option<f32>)>>,
+) {
+    let transform = single_or_return!(camera);
+    let camera_heading = heading(transform.forward()).unwrap_or(0.);
+    let target = waypoint.0.unwrap_or(Vec3::ZERO);
+    let target_heading = heading(target - transform.translation);
+    let turned = |a: f32, b: f32| heading_delta(a, b).abs() >= HEADING_EPS;
+    let moved = match *shown {
+        Some((heading, marker)) => {
+            turned(heading, camera_heading)
+                || match (marker, target_heading) {
+                    (Some(a), Some(b)) => turned(a, b),
+                    (a, b) => a.is_some() != b.is_some(),
+                }
+        }
+        None => true,
+    };
+    //Newly spawned strip is laid out whatever was shown before.
+    let spawned = ticks.iter().any(|(.., tracker)| tracker.is_added());
+    if !moved && !spawned && !waypoint.is_changed() {
+        return;
+    }
+    *shown = Some((camera_heading, target_heading));
+    //Pool holds as many ticks as strip could show, so each one takes next heading.
+    let mut visible = visible_ticks(camera_heading);
+    for (mut style, mut visibility, children, _) in ticks.iter_mut() {
+        let next = visible.next();
+        if visibility.is_visible != next.is_some() {
+            visibility.is_visible = next.is_some();
+        }
+        if let Some((heading, offset)) = next {
+            style.position.left = Val::Px(STRIP_WIDTH * 0.5 + offset - TICK_WIDTH * 0.5);
+            if let Some(mut text) = children.first().and_then(|c| texts.get_mut(*c).ok()) {
+                let label = tick_label(heading);
+                if text.sections[0].value != label {
+                    text.sections[0].value = label.to_owned();
+                }
+            }
+        }
+    }
+    if let Ok((mut style, mut color, mut visibility)) = marker.get_single_mut() {
+        if visibility.is_visible != target_heading.is_some() {
+            visibility.is_visible = target_heading.is_some();
+        }
+        if let Some(target_heading) = target_heading {
+            let offset = marker_offset(camera_heading, target_heading);
+            style.position.left = Val::Px(STRIP_WIDTH * 0.5 + offset - MARKER_SIZE * 0.5);
+        }
+        //Waypoint and origin are told apart by color.
+        let marker_color = if waypoint.0.is_some() {
+            Color::ORANGE
+        } else {
+            Color::CYAN
+        };
+        if !color.0.visually_eq(&marker_color) {
+            color.0 = marker_color;
+        }
+    }
+}
+
+///Shows cells of camera and of structure under crosshair. Text is set only when either changes.
+fn update_compass_readout(
+    camera: Query<(&Transform, &LookAt), With<Camera>>,
+    mut readout: Query<(&mut Text, ChangeTrackers<CompassReadout>)>,
+    mut shown: Local<Option<(IVec3, Option<IVec3>)>>,
+) {
+    let (transform, look_at) = single_or_return!(camera);
+    let (mut text, tracker) = match readout.get_single_mut() {
+        Ok(readout) => readout,
+        //Compass is disabled.
+        Err(_) => return,
+    };
+    let camera_cell = transform.translation.round().as_ivec3();
+    let aim_cell = look_at
+        .0
+        .as_ref()
+        .map(|hit| hit.aabb.center().round().as_ivec3());
+    if *shown == Some((camera_cell, aim_cell)) && !tracker.is_added() {
+        return;
+    }
+    *shown = Some((camera_cell, aim_cell));
+    text.sections[0].value = match aim_cell {
+        Some(aim) => format!(
+            "at {} {} {}, aim {} {} {}",
+            camera_cell.x, camera_cell.y, camera_cell.z, aim.x, aim.y, aim.z
+        ),
+        None => format!("at {} {} {}", camera_cell.x, camera_cell.y, camera_cell.z),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn heading_wraps_around_north() {
+        for (dir, expected) in [
+            (Vec3::NEG_Z, 0.),
+            (Vec3::X, 90.),
+            (Vec3::Z, 180.),
+            (Vec3::NEG_X, 270.),
+            (
+                Vec3::new(-0.01, 0.5, -1.),
+                360. - 0.01f32.atan().to_degrees(),
+            ),
+        ] {
+            assert!(close(heading(dir).unwrap(), expected), "{dir}");
+        }
+        assert_eq!(heading(Vec3::Y), None);
+        assert_eq!(heading(Vec3::new(1e-4, -1., 0.)), None);
+        assert!(close(heading_delta(350., 10.), 20.));
+        assert!(close(heading_delta(10., 350.), -20.));
+        assert!(close(heading_delta(90., 45.), -45.));
+        assert!(close(heading_delta(720., 0.), 0.));
+    }
+
+    #[test]
+    fn marker_leads_toward_target_across_north() {
+        let pixels = |degrees: f32| degrees / STRIP_SPAN as f32 * STRIP_WIDTH;
+        assert!(close(marker_offset(350., 10.), pixels(20.)));
+        assert!(close(marker_offset(10., 350.), pixels(-20.)));
+        assert!(close(marker_offset(0., 0.), 0.));
+        //Target behind sticks to edge it is nearer to.
+        assert!(close(marker_offset(0., 170.), pixels(90.)));
+        assert!(close(marker_offset(0., 190.), pixels(-90.)));
+        //Origin seen from east of it is to west.
+        let toward_origin = heading(Vec3::ZERO - Vec3::new(10., 3., 0.)).unwrap();
+        assert!(close(toward_origin, 270.));
+        assert!(close(marker_offset(270., toward_origin), 0.));
+    }
+
+    #[test]
+    fn full_spin_keeps_ticks_consistent() {
+        let half = STRIP_WIDTH * 0.5;
+        for step in 0..1440 {
+            let camera = step as f32 * 0.25;
+            let ticks: Vec<_> = visible_ticks(camera).collect();
+            assert!(!ticks.is_empty() && ticks.len() <= TICK_POOL, "{camera}");
+            for (tick, offset) in ticks.iter() {
+                assert_eq!(tick % TICK_STEP, 0);
+                assert!(*tick < 360);
+                assert!(offset.abs() <= half + 1e-3, "{camera} {tick} {offset}");
+                //Offset is turn from camera to tick, except at edge where both sides are same.
+                let expected =
+                    heading_delta(camera, *tick as f32) / STRIP_SPAN as f32 * STRIP_WIDTH;
+                assert!(
+                    close(*offset, expected) || close(offset.abs(), half),
+                    "{camera} {tick}"
+                );
+            }
+            //Ticks are spaced evenly from left to right.
+            for pair in ticks.windows(2) {
+                assert!(close(
+                    pair[1].1 - pair[0].1,
+                    TICK_STEP as f32 / STRIP_SPAN as f32 * STRIP_WIDTH
+                ));
+            }
+            //Spinning once more shows same strip.
+            let again: Vec<_> = visible_ticks(camera + 360.).collect();
+            assert_eq!(again.len(), ticks.len());
+            for (a, b) in again.iter().zip(ticks.iter()) {
+                assert_eq!(a.0, b.0);
+                assert!((a.1 - b.1).abs() < 0.01);
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use super::*;
+
+use crate::physics::{frustum::Frustum, octree::LeafInfo};
+
+///Key that toggles coordinate overlay.
+pub const COORD_OVERLAY_KEY: KeyCode = KeyCode::F3;
+
+///Batch setup for aim cell readout and its octree leaf.
+pub struct CoordOverlayPlugin;
+
+impl Plugin for CoordOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CoordOverlay>().add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::on_update(PostUpdateStageState::InGame)
+                .with_system(toggle_coord_overlay)
+                .with_system(update_coord_overlay.after(toggle_coord_overlay)),
+        );
+    }
+}
+
+///Whether overlay is shown.
+#[derive(Resource, Default)]
+pub struct CoordOverlay(pub bool);
+
+///Mark text of overlay.
+#[derive(Component)]
+struct CoordText;
+
+///Mark wireframe of leaf aim cell falls into.
+#[derive(Component)]
+struct LeafWireframe;
+
+fn toggle_coord_overlay(input: Res<Input<KeyCode>>, mut overlay: ResMut<CoordOverlay>) {
+    if input.just_pressed(COORD_OVERLAY_KEY) {
+        overlay.0 = !overlay.0;
+    }
+}
+
+///What overlay tells about cell. Overlapping is number of entities whose bound overlaps cell.
+fn coord_readout(cell: Vec3, leaf: Option<&LeafInfo>, overlapping: usize) -> String {
+    let mut ret = format!("cell {} {} {}", cell.x, cell.y, cell.z);
+    if overlapping > 0 {
+        ret.push_str(&format!(", overlapped by {overlapping}"));
+    }
+    match leaf {
+        Some(leaf) => ret.push_str(&format!(
+            "\nleaf #{} depth {}, {} entities\n{} - {}",
+            leaf.index,
+            leaf.depth,
+            leaf.entities,
+            leaf.aabb.min(),
+            leaf.aabb.max()
+        )),
+        None => ret.push_str("\noutside of octree"),
+    }
+    ret
+}
+
+///Shows aim cell and highlights octree leaf it falls into, with how many entities camera could see.
+///Systems switched off from console are listed on top.
+///Spawned on demand, despawned when hidden.
+fn update_coord_overlay(
+    mut commands: Commands,
+    overlay: Res<CoordOverlay>,
+    state: Res<GlobalState>,
+    fonts: Res<Fonts>,
+    polylines: Res<Polylines>,
+    polyline_materials: Res<PolylineMaterials>,
+    selection: Query<(&Selection, &Transform)>,
+    camera: Query<(&Camera, &GlobalTransform), With<LookAt>>,
+    octree: Query<&Octree>,
+    toggles: Res<SystemToggles>,
+    mut texts: Query<(Entity, &mut Text), With<CoordText>>,
+    mut wireframes: Query<
+        (Entity, &mut Transform, &mut Visibility),
+        (With<LeafWireframe>, Without<Selection>),
+    >,
+) {
+    if !overlay.0 {
+        if overlay.is_changed() {
+            for (entity, _) in texts.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            for (entity, _, _) in wireframes.iter() {
+                commands.entity(entity).despawn();
+            }
+        }
+        return;
+    }
+    let (selection, selection_transform) = single_or_return!(selection);
+    let octree = single_or_return!(octree);
+    let cell = selection_transform.translation;
+    let leaf = selection.valid.then(|| octree.leaf_at(cell)).flatten();
+    let mut readout = if selection.valid {
+        let mut overlapping = 0;
+        octree.intersect(AABB::from_size_offset(1., cell), |_| overlapping += 1);
+        coord_readout(cell, leaf.as_ref(), overlapping)
+    } else {
+        "no aim cell".to_owned()
+    };
+    if let Ok((camera, global)) = camera.get_single() {
+        let frustum = Frustum::from_view_projection(
+            &(camera.projection_matrix() * global.compute_matrix().inverse()),
+        );
+        let mut in_view = 0;
+        octree.intersect_frustum(&frustum, |_| in_view += 1);
+        readout.push_str(&format!("\n{in_view} in view"));
+    }
+    //Switched off systems come first, so missing behavior is explained before anything else.
+    let disabled: Vec<_> = toggles
+        .iter()
+        .filter_map(|(name, enabled)| (!enabled).then_some(name))
+        .collect();
+    if !disabled.is_empty() {
+        readout = format!("DISABLED: {}\n{readout}", disabled.join(", "));
+    }
+    match texts.get_single_mut() {
+        Ok((_, mut text)) => {
+            if text.sections[0].value != readout {
+                text.sections[0].value = readout;
+            }
+        }
+        Err(_) => {
+            commands.spawn((
+                create_text(readout, &fonts, 18., TEXT_COLOR_BRIGHT).with_style(Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(8.),
+                        top: Val::Px(8.),
+                        ..default()
+                    },
+                    ..default()
+                }),
+                state.mark(),
+                UiRoot,
+                CoordText,
+                Name::new("Coordinate readout"),
+            ));
+        }
+    }
+    let leaf_transform = leaf
+        .map(|leaf| Transform::from_translation(leaf.aabb.center()).with_scale(leaf.aabb.length()));
+    match wireframes.get_single_mut() {
+        Ok((_, mut transform, mut visibility)) => {
+            if let Some(leaf_transform) = leaf_transform {
+                if *transform != leaf_transform {
+                    *transform = leaf_transform;
+                }
+            }
+            if visibility.is_visible != leaf_transform.is_some() {
+                visibility.is_visible = leaf_transform.is_some();
+            }
+        }
+        Err(_) => {
+            commands.spawn((
+                PolylineBundle {
+                    polyline: polylines[UNIT_BOX].clone(),
+                    material: polyline_materials[GREEN].clone(),
+                    transform: leaf_transform.unwrap_or_default(),
+                    visibility: Visibility {
+                        is_visible: leaf_transform.is_some(),
+                    },
+                    ..default()
+                },
+                state.mark(),
+                LeafWireframe,
+                Name::new("Aim leaf wireframe"),
+            ));
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use super::*;
+
+///Key that flies camera back until whole build is in view.
+pub const FRAME_BUILD_KEY: KeyCode = KeyCode::F;
+
+///Batch setup for framing whole build.
+pub struct FramingPlugin;
+
+impl Plugin for FramingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set_to_stage(
+            CoreStage::Update,
+            SystemSet::on_update(UpdateStageState::InGame)
+                .with_system(frame_build)
+                .with_system(fly_camera.after(frame_build).before(move_camera)),
+        );
+    }
+}
+
+///Camera moving to framing position. Removed when arrived.
+#[derive(Component)]
+pub struct CameraFlight {
+    ///Logical position, without bob.
+    from: Vec3,
+    to: Vec3,
+    elapsed: f32,
+}
+
+impl CameraFlight {
+    ///Seconds of flight.
+    const DURATION: f32 = 0.4;
+}
+
+///Distance from center of box at which camera sees whole of it.
+///Box is bounded by sphere, which fits narrower of vertical and horizontal field of view.
+pub fn frame_distance(aabb: &AABB, fov_y: f32, aspect: f32) -> f32 {
+    let radius = aabb.length().length() * 0.5;
+    let half_y = fov_y * 0.5;
+    let half_x = (half_y.tan() * aspect).atan();
+    radius / half_y.min(half_x).sin()
+}
+
+///Starts flight to where build is framed, keeping direction camera looks.
+///Camera can't leave blueprint, so build larger than it could be framed partly.
+fn frame_build(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    octree: Query<&Octree>,
+    fixtures: Query<(), With<Fixture>>,
+    camera: Query<(Entity, &Transform, &Projection, &CameraBob), With<LookAt>>,
+    viewport: Res<ViewportInfo>,
+) {
+    if !input.just_pressed(FRAME_BUILD_KEY) {
+        return;
+    }
+    let (entity, transform, projection, bob) = single_or_return!(camera);
+    let octree = single_or_return!(octree);
+    //Ground is as large as blueprint, so only structures are framed.
+    let bound = match octree
+        .iter()
+        .filter(|entity| !fixtures.contains(entity.entity()))
+        .map(|entity| entity.aabb())
+        .reduce(|a, b| a.union(&b))
+    {
+        Some(bound) => bound,
+        //Nothing to frame.
+        None => return,
+    };
+    //Projection follows raw window size, whose aspect is nonsense while minimized.
+    let distance = match projection {
+        Projection::Perspective(perspective) => {
+            frame_distance(&bound, perspective.fov, viewport.aspect())
+        }
+        //Orthographic view is framed by its scale, not distance.
+        Projection::Orthographic(_) => return,
+    };
+    let to = (bound.center() - transform.forward() * distance)
+        .clamp(BLUEPRINT_BOUND.min() + 0.5, BLUEPRINT_BOUND.max() - 0.5);
+    commands.entity(entity).insert(CameraFlight {
+        from: transform.translation - bob.offset,
+        to,
+        elapsed: 0.,
+    });
+}
+
+///Moves camera along flight, eased at both ends. Runs before `move_camera`, so keys still move it.
+fn fly_camera(
+    mut commands: Commands,
+    mut cameras: Query<(Entity, &mut Transform, &CameraBob, &mut CameraFlight)>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, bob, mut flight) in cameras.iter_mut() {
+        flight.elapsed += time.delta_seconds();
+        let progress = (flight.elapsed / CameraFlight::DURATION).min(1.);
+        let eased = progress * progress * (3. - 2. * progress);
+        //Bob goes on top, as `move_camera` strips it.
+        transform.translation = flight.from.lerp(flight.to, eased) + bob.offset;
+        if progress >= 1. {
+            commands.entity(entity).remove::<CameraFlight>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    #[test]
+    fn frame_distance_fits_narrower_fov() {
+        let aabb = AABB::from_size_offset(2., Vec3::new(3., 4., 5.));
+        let radius = 3f32.sqrt();
+        let square = frame_distance(&aabb, FRAC_PI_2, 1.);
+        assert!((square - radius / FRAC_PI_4.sin()).abs() < 1e-4);
+        //Wide view is limited by vertical field of view.
+        assert!((frame_distance(&aabb, FRAC_PI_2, 2.) - square).abs() < 1e-4);
+        //Tall view is limited by horizontal one, so bound sphere just touches its sides.
+        let tall = frame_distance(&aabb, FRAC_PI_2, 0.5);
+        assert!(tall > square);
+        assert!(((radius / tall).asin() - 0.5f32.atan()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn frame_distance_scales_with_size() {
+        let small = AABB::from_size_offset(1., Vec3::ZERO);
+        let large = AABB::from_size_offset(4., Vec3::ZERO);
+        let ratio = frame_distance(&large, 1., 1.5) / frame_distance(&small, 1., 1.5);
+        assert!((ratio - 4.).abs() < 1e-4);
+    }
+}
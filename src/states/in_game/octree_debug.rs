@@ -0,0 +1,155 @@
+use super::*;
+
+use std::hash::Hash;
+
+use bevy::{ecs::system::SystemParam, utils::HashMap};
+
+///Key that toggles node wireframes. With shift, it toggles entity bounds instead.
+pub const OCTREE_DEBUG_KEY: KeyCode = KeyCode::F6;
+
+///Node colors by depth, repeating for deeper ones.
+const DEPTH_COLORS: [&str; 4] = [RED, YELLOW, GREEN, BLUE];
+const ENTITY_COLOR: &str = GRAY;
+
+///Batch setup for octree wireframes. Only for development.
+pub struct OctreeDebugPlugin;
+
+impl Plugin for OctreeDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OctreeDebug>().add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::on_update(PostUpdateStageState::InGame)
+                .with_system(toggle_octree_debug)
+                .with_system(draw_octree_nodes.after(toggle_octree_debug))
+                .with_system(draw_octree_entities.after(toggle_octree_debug)),
+        );
+    }
+}
+
+///What octree debug draws.
+#[derive(Resource, Default)]
+pub struct OctreeDebug {
+    ///Every live node.
+    pub enabled: bool,
+    ///Bound of every entity, apart from nodes.
+    pub entities: bool,
+}
+
+///Mark wireframe of node or entity bound.
+#[derive(Component)]
+struct OctreeWireframe;
+
+fn toggle_octree_debug(input: Res<Input<KeyCode>>, mut debug: ResMut<OctreeDebug>) {
+    if input.just_pressed(OCTREE_DEBUG_KEY) {
+        if input.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+            debug.entities = !debug.entities;
+        } else {
+            debug.enabled = !debug.enabled;
+        }
+    }
+}
+
+///Wireframes of octree debug and what spawning them needs.
+#[derive(SystemParam)]
+struct Wireframes<'w, 's> {
+    commands: Commands<'w, 's>,
+    existing: Query<
+        'w,
+        's,
+        (
+            &'static mut Transform,
+            &'static mut Handle<PolylineMaterial>,
+        ),
+        With<OctreeWireframe>,
+    >,
+    polylines: Res<'w, Polylines>,
+    polyline_materials: Res<'w, PolylineMaterials>,
+    state: Res<'w, GlobalState>,
+}
+
+impl Wireframes<'_, '_> {
+    ///Keeps one wireframe per wanted key in drawn. Existing ones are moved and recolored,
+    ///new keys are spawned and keys not wanted anymore are despawned.
+    ///Wireframe gone with state is spawned again.
+    fn sync<K: Eq + Hash + Copy>(
+        &mut self,
+        drawn: &mut HashMap<K, Entity>,
+        wanted: impl Iterator<Item = (K, AABB, &'static str)>,
+        name: &str,
+    ) {
+        let mut stale = std::mem::take(drawn);
+        for (key, aabb, color) in wanted {
+            let transform = Transform::from_translation(aabb.center()).with_scale(aabb.length());
+            let material = &self.polyline_materials[color];
+            let entity = match stale.remove(&key) {
+                Some(entity) if self.existing.contains(entity) => {
+                    let (mut own_transform, mut own_material) = self
+                        .existing
+                        .get_mut(entity)
+                        .expect("Wireframe is checked above");
+                    set_if_changed(&mut own_transform, transform);
+                    if *own_material != *material {
+                        *own_material = material.clone();
+                    }
+                    entity
+                }
+                _ => self
+                    .commands
+                    .spawn((
+                        PolylineBundle {
+                            polyline: self.polylines[UNIT_BOX].clone(),
+                            material: material.clone(),
+                            transform,
+                            ..default()
+                        },
+                        OctreeWireframe,
+                        self.state.mark(),
+                        Name::new(name.to_owned()),
+                    ))
+                    .id(),
+            };
+            drawn.insert(key, entity);
+        }
+        for (_, entity) in stale {
+            if let Some(mut entity) = self.commands.get_entity(entity) {
+                entity.despawn();
+            }
+        }
+    }
+}
+
+///Draws every live node, colored by depth. Wireframes are kept by node slot across frames.
+fn draw_octree_nodes(
+    debug: Res<OctreeDebug>,
+    octree: Query<&Octree>,
+    mut wireframes: Wireframes,
+    mut drawn: Local<HashMap<usize, Entity>>,
+) {
+    let octree = octree.get_single().ok().filter(|_| debug.enabled);
+    let wanted = octree.into_iter().flat_map(|octree| {
+        octree.nodes().map(|node| {
+            (
+                node.index(),
+                node.aabb(),
+                DEPTH_COLORS[node.depth() % DEPTH_COLORS.len()],
+            )
+        })
+    });
+    wireframes.sync(&mut drawn, wanted, "Octree node");
+}
+
+///Draws stored bound of every entity, oversize ones included.
+fn draw_octree_entities(
+    debug: Res<OctreeDebug>,
+    octree: Query<&Octree>,
+    mut wireframes: Wireframes,
+    mut drawn: Local<HashMap<Entity, Entity>>,
+) {
+    let octree = octree.get_single().ok().filter(|_| debug.entities);
+    let wanted = octree.into_iter().flat_map(|octree| {
+        octree
+            .iter()
+            .map(|entity| (entity.entity(), entity.aabb(), ENTITY_COLOR))
+    });
+    wireframes.sync(&mut drawn, wanted, "Octree entity bound");
+}
@@ -0,0 +1,158 @@
+use super::*;
+
+use crate::task::TaskResultEvent;
+
+use std::path::Path;
+
+use bevy::window::WindowCloseRequested;
+
+///Batch setup for pause menu, which is stacked on in game by Esc.
+pub struct PauseMenuPlugin;
+
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set_to_stage(
+            CoreStage::PreUpdate,
+            SystemSet::on_enter(PreUpdateStageState::Paused).with_system(setup_pause_menu),
+        )
+        .add_system_set_to_stage(
+            CoreStage::Update,
+            SystemSet::on_update(UpdateStageState::Paused)
+                .with_system(pause_menu_button)
+                .with_system(pause_menu_keys.after(pause_menu_button)),
+        );
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum PauseButton {
+    Resume,
+    MainMenu,
+    Quit,
+}
+
+///Window close goes to exit popup, and Esc pauses. Esc only reveals ui while it is hidden.
+pub fn pause_requested(
+    mut closed: EventReader<WindowCloseRequested>,
+    mut state: ResMut<GlobalState>,
+    input: Res<Input<KeyCode>>,
+    hidden: Res<UiHidden>,
+) {
+    if primary_close_requested(&mut closed) {
+        state.push_exit();
+    } else if !hidden.0 && input.just_pressed(KeyCode::Escape) {
+        state.push(InGameState::Paused);
+    }
+}
+
+///Setup pause menu. Game is left as it is under it, and cursor is released by in game on pause.
+fn setup_pause_menu(mut commands: Commands, state: Res<GlobalState>, fonts: Res<Fonts>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(30.0), Val::Auto),
+                    position_type: PositionType::Absolute,
+                    position: UiRect::new(
+                        Val::Percent(35.0),
+                        Val::Undefined,
+                        Val::Percent(25.0),
+                        Val::Undefined,
+                    ),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: UI_BACKGROUND_COLOR,
+                ..default()
+            },
+            state.mark(),
+            UiRoot,
+            Name::new("Pause menu"),
+        ))
+        .with_children(|parent| {
+            parent.spawn(create_text(PAUSED_TEXT, &fonts, 30.0, TEXT_COLOR_DARK));
+            for (text, action) in [
+                (RESUME_TEXT, PauseButton::Resume),
+                (MAIN_MENU_TEXT, PauseButton::MainMenu),
+                (QUIT_TEXT, PauseButton::Quit),
+            ] {
+                parent
+                    .spawn((
+                        create_button(),
+                        ActivationCooldown::default(),
+                        button_name(text),
+                        action,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(create_text(text, &fonts, 30.0, TEXT_COLOR_BRIGHT));
+                    });
+            }
+        });
+}
+
+///Pause menu interaction system.
+///Leaving to main menu clears build and recovery ring, so build is autosaved first as quitting does.
+fn pause_menu_button(
+    mut interaction_query: Query<
+        (
+            Entity,
+            &Interaction,
+            &mut BackgroundColor,
+            &PauseButton,
+            &mut ActivationCooldown,
+        ),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut state: ResMut<GlobalState>,
+    mut guard: ResMut<NavigationGuard>,
+    toggles: Res<SystemToggles>,
+    snapshot: BuildSnapshot,
+    mut results: EventWriter<TaskResultEvent>,
+) {
+    for (entity, interaction, mut color, action, mut cooldown) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked if !guard.try_navigate(entity, &mut cooldown) => {}
+            Interaction::Clicked => match action {
+                PauseButton::Resume => state.pop(),
+                PauseButton::MainMenu => {
+                    if toggles.is_enabled(AUTOSAVE_TOGGLE) && snapshot.has_build() {
+                        //Build stays when save fails, so nothing is lost behind error dialog.
+                        if let Err(e) = snapshot.save(Path::new(AUTOSAVE_PATH)) {
+                            results.send(TaskResultEvent::Failed(e));
+                            continue;
+                        }
+                        info!("Autosaved to {AUTOSAVE_PATH}");
+                    }
+                    state.replace(AppState::MainMenu);
+                }
+                PauseButton::Quit => state.push_exit(),
+            },
+            Interaction::Hovered => {
+                *color = BUTTON_COLOR_HOVER;
+            }
+            Interaction::None => {
+                *color = BUTTON_COLOR_NONE;
+            }
+        }
+    }
+}
+
+///Esc resumes, and window close goes to exit popup same as in game.
+///Button pressed in same frame wins.
+fn pause_menu_keys(
+    mut closed: EventReader<WindowCloseRequested>,
+    mut state: ResMut<GlobalState>,
+    input: Res<Input<KeyCode>>,
+) {
+    let close = primary_close_requested(&mut closed);
+    if state.should_change() {
+        return;
+    }
+    if close {
+        state.push_exit();
+    } else if input.just_pressed(KeyCode::Escape) {
+        state.pop();
+    }
+}
@@ -0,0 +1,191 @@
+use super::*;
+
+use bevy::{
+    render::camera::{RenderTarget, ScalingMode},
+    window::{CreateWindow, WindowId},
+};
+
+///Key that opens and closes top view window.
+pub const TOP_VIEW_KEY: KeyCode = KeyCode::F4;
+
+///Batch setup for secondary window that shows build from above. Only for development.
+pub struct TopViewPlugin;
+
+impl Plugin for TopViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TopView>()
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::on_update(PostUpdateStageState::InGame)
+                    .with_system(toggle_top_view)
+                    .with_system(close_requested_top_view),
+            )
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::on_exit(PostUpdateStageState::InGame).with_system(close_top_view),
+            );
+    }
+}
+
+///Window and camera of top view while it is open.
+#[derive(Resource, Default)]
+pub struct TopView(Option<(WindowId, Entity)>);
+
+///Camera that renders top view into its own window.
+#[derive(Component)]
+struct TopViewCamera;
+
+fn toggle_top_view(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    state: Res<GlobalState>,
+    mut top_view: ResMut<TopView>,
+    mut create: EventWriter<CreateWindow>,
+    mut windows: ResMut<Windows>,
+) {
+    if !input.just_pressed(TOP_VIEW_KEY) {
+        return;
+    }
+    if top_view.0.is_some() {
+        close(&mut commands, &mut top_view, &mut windows);
+        return;
+    }
+    let id = WindowId::new();
+    create.send(CreateWindow {
+        id,
+        descriptor: WindowDescriptor {
+            title: "Top view".to_owned(),
+            width: 480.,
+            height: 480.,
+            ..default()
+        },
+    });
+    //Camera could target window before it exists. It renders once window is created.
+    let center = BLUEPRINT_BOUND.center();
+    let camera = commands
+        .spawn((
+            Camera3dBundle {
+                camera: Camera {
+                    target: RenderTarget::Window(id),
+                    ..default()
+                },
+                projection: Projection::Orthographic(OrthographicProjection {
+                    scaling_mode: ScalingMode::FixedVertical(BLUEPRINT_BOUND.length().z),
+                    ..default()
+                }),
+                transform: Transform::from_translation(
+                    center + Vec3::Y * BLUEPRINT_BOUND.length().y,
+                )
+                .looking_at(center, Vec3::Z),
+                ..default()
+            },
+            //Ui belongs to main window only.
+            UiCameraConfig { show_ui: false },
+            state.mark(),
+            TopViewCamera,
+            Name::new("Top view camera"),
+        ))
+        .id();
+    top_view.0 = Some((id, camera));
+}
+
+///Closing top view window closes only it, not the game.
+fn close_requested_top_view(
+    mut commands: Commands,
+    mut closed: EventReader<WindowCloseRequested>,
+    mut top_view: ResMut<TopView>,
+    mut windows: ResMut<Windows>,
+) {
+    let id = match top_view.0 {
+        Some((id, _)) => id,
+        None => return,
+    };
+    if closed.iter().any(|e| e.id == id) {
+        close(&mut commands, &mut top_view, &mut windows);
+    }
+}
+
+fn close_top_view(
+    mut commands: Commands,
+    mut top_view: ResMut<TopView>,
+    mut windows: ResMut<Windows>,
+) {
+    close(&mut commands, &mut top_view, &mut windows);
+}
+
+fn close(commands: &mut Commands, top_view: &mut TopView, windows: &mut Windows) {
+    if let Some((id, camera)) = top_view.0.take() {
+        if let Some(window) = windows.get_mut(id) {
+            window.close();
+        }
+        if let Some(mut camera) = commands.get_entity(camera) {
+            camera.despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_view_adds_camera_for_second_window() {
+        let mut world = World::new();
+        world.init_resource::<TopView>();
+        world.init_resource::<Input<KeyCode>>();
+        world.init_resource::<Windows>();
+        world.init_resource::<Events<CreateWindow>>();
+        world.init_resource::<Events<WindowCloseRequested>>();
+        world.insert_resource(GlobalState::new(AppState::InGame));
+        world.spawn(Camera3dBundle::default());
+        let mut stage = SystemStage::single_threaded()
+            .with_system(toggle_top_view)
+            .with_system(close_requested_top_view.after(toggle_top_view));
+        let press = |world: &mut World| {
+            let mut input = world.resource_mut::<Input<KeyCode>>();
+            input.release_all();
+            input.clear();
+            input.press(TOP_VIEW_KEY);
+        };
+        let targets = |world: &mut World| {
+            world
+                .query::<&Camera>()
+                .iter(world)
+                .map(|camera| camera.target.clone())
+                .collect::<Vec<_>>()
+        };
+        let primary = RenderTarget::Window(WindowId::primary());
+
+        press(&mut world);
+        stage.run(&mut world);
+        let (id, camera) = world.resource::<TopView>().0.unwrap();
+        let created: Vec<_> = world
+            .resource::<Events<CreateWindow>>()
+            .iter_current_update_events()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(created, [id]);
+        let targets_now = targets(&mut world);
+        assert_eq!(targets_now.len(), 2);
+        assert!(targets_now.contains(&primary));
+        assert!(targets_now.contains(&RenderTarget::Window(id)));
+        assert!(world.get::<TopViewCamera>(camera).is_some());
+
+        //Closing top view window leaves main camera alone.
+        world.resource_mut::<Input<KeyCode>>().clear();
+        world.send_event(WindowCloseRequested { id });
+        stage.run(&mut world);
+        assert!(world.resource::<TopView>().0.is_none());
+        assert!(world.get_entity(camera).is_none());
+        assert_eq!(targets(&mut world), [primary.clone()]);
+
+        //Key opens it again and then closes it.
+        press(&mut world);
+        stage.run(&mut world);
+        assert_eq!(targets(&mut world).len(), 2);
+        press(&mut world);
+        stage.run(&mut world);
+        assert!(world.resource::<TopView>().0.is_none());
+        assert_eq!(targets(&mut world), [primary]);
+    }
+}
@@ -1,4 +1,9 @@
-use crate::{asset::Fonts, func::*, states::*, ui::*};
+use crate::{
+    asset::Fonts, environment::SceneEnvironment, func::*, recovery::*, save::*, states::*,
+    timings::StartupTimings, ui::*,
+};
+
+use std::{path::PathBuf, time::Instant};
 
 use bevy::prelude::*;
 
@@ -15,24 +20,67 @@ impl Plugin for MainMenuPlugin {
             CoreStage::Update,
             SystemSet::on_update(UpdateStageState::MainMenu)
                 .with_system(button)
+                .with_system(continue_button)
+                .with_system(recovery_button)
                 .with_system(close_requested),
         );
     }
 }
 
 ///Setup system in Main menu.
-fn setup(mut commands: Commands, state: Res<GlobalState>, res: Res<Fonts>) {
+fn setup(
+    mut commands: Commands,
+    state: Res<GlobalState>,
+    res: Res<Fonts>,
+    mut timings: ResMut<StartupTimings>,
+) {
+    //Only the first setup belongs to startup.
+    let _span = if timings.interactive().is_none() {
+        Some(timings.span("main menu setup"))
+    } else {
+        None
+    };
     //ui camera
-    commands.spawn((Camera2dBundle::default(), state.mark()));
+    commands.spawn((
+        Camera2dBundle::default(),
+        state.mark(),
+        Name::new("Ui camera"),
+    ));
+    //continue button, disabled when there is nothing to continue
+    let autosave = loadable_autosave();
+    let mut continue_button = commands.spawn((
+        create_button(),
+        ActivationCooldown::default(),
+        button_name(CONTINUE_TEXT),
+        state.mark(),
+        HierarchyMark::<0>,
+        UiRoot,
+    ));
+    let text_color = match autosave {
+        Some(path) => {
+            continue_button.insert(LoadSaveButton(path));
+            TEXT_COLOR_BRIGHT
+        }
+        None => {
+            continue_button.insert((ButtonDisabled, BUTTON_COLOR_DISABLED));
+            TEXT_COLOR_DISABLED
+        }
+    };
+    continue_button.with_children(|parent| {
+        parent.spawn(create_text(CONTINUE_TEXT, &res, 30.0, text_color));
+    });
     //play button
     commands
         .spawn((
             create_button(),
+            ActivationCooldown::default(),
+            button_name(PLAY_TEXT),
             state.mark(),
             Action::<for<'a> fn(&'a mut GlobalState)>::new(|g: &mut GlobalState| {
                 g.replace(AppState::InGame)
             }),
             HierarchyMark::<0>,
+            UiRoot,
         ))
         .with_children(|parent| {
             parent.spawn(create_text(PLAY_TEXT, &res, 30.0, TEXT_COLOR_BRIGHT));
@@ -41,31 +89,178 @@ fn setup(mut commands: Commands, state: Res<GlobalState>, res: Res<Fonts>) {
     commands
         .spawn((
             create_button(),
+            ActivationCooldown::default(),
+            button_name(EXIT_TEXT),
             state.mark(),
             Action::<for<'a> fn(&'a mut GlobalState)>::new(|g: &mut GlobalState| g.push_exit()),
             HierarchyMark::<0>,
+            UiRoot,
         ))
         .with_children(|parent| {
             parent.spawn(create_text(EXIT_TEXT, &res, 30.0, TEXT_COLOR_BRIGHT));
         });
+    //Ring left behind means last session didn't end cleanly.
+    if let Some(snapshot) = newest_snapshot() {
+        spawn_recovery_dialog(&mut commands, &snapshot, &state, &res);
+    }
+}
+
+///Mark root of recovery dialog.
+#[derive(Component)]
+struct RecoveryDialog;
+
+#[derive(Component)]
+enum RecoveryButton {
+    Recover(PathBuf),
+    Discard,
+}
+
+///Setup dialog that offers newest snapshot of recovery ring.
+fn spawn_recovery_dialog(
+    commands: &mut Commands,
+    snapshot: &RecoverySnapshot,
+    state: &GlobalState,
+    fonts: &Res<Fonts>,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(60.0), Val::Auto),
+                    position_type: PositionType::Absolute,
+                    position: UiRect::new(
+                        Val::Percent(20.0),
+                        Val::Undefined,
+                        Val::Percent(30.0),
+                        Val::Undefined,
+                    ),
+                    flex_wrap: FlexWrap::Wrap,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    align_content: AlignContent::SpaceAround,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: UI_BACKGROUND_COLOR,
+                ..default()
+            },
+            state.mark(),
+            RecoveryDialog,
+            UiRoot,
+            Name::new("Recovery dialog"),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_basis: Val::Percent(100.0),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(create_text(
+                        snapshot.describe(),
+                        fonts,
+                        24.0,
+                        TEXT_COLOR_DARK,
+                    ));
+                });
+            for (text, action) in [
+                (RECOVER_TEXT, RecoveryButton::Recover(snapshot.path.clone())),
+                (DISCARD_TEXT, RecoveryButton::Discard),
+            ] {
+                parent
+                    .spawn((
+                        create_button(),
+                        ActivationCooldown::default(),
+                        button_name(text),
+                        action,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(create_text(text, fonts, 20.0, TEXT_COLOR_BRIGHT));
+                    });
+            }
+        });
+}
+
+///Recovery dialog interaction system.
+///Recovering loads snapshot same as continuing from save. Discarding archives ring.
+fn recovery_button(
+    mut commands: Commands,
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &mut BackgroundColor,
+            &RecoveryButton,
+            &mut ActivationCooldown,
+        ),
+        (Changed<Interaction>, With<Button>),
+    >,
+    dialogs: Query<Entity, With<RecoveryDialog>>,
+    mut state: ResMut<GlobalState>,
+    mut environment: ResMut<SceneEnvironment>,
+    mut results: EventWriter<TaskResultEvent>,
+) {
+    for (interaction, mut color, action, mut cooldown) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked if !cooldown.try_activate(Instant::now()) => {}
+            Interaction::Clicked => {
+                match action {
+                    RecoveryButton::Recover(path) => match read_snapshot(path) {
+                        Ok(save) => {
+                            *environment = save.environment;
+                            commands.insert_resource(PendingLoad(path.clone(), save));
+                            state.replace(AppState::InGame);
+                        }
+                        Err(e) => results.send(TaskResultEvent::Failed(
+                            TaskError::new(TaskKind::Load, e).with_path(path),
+                        )),
+                    },
+                    RecoveryButton::Discard => {
+                        if let Err(e) = archive_ring() {
+                            warn!("Failed to archive recovery ring: {e}");
+                        }
+                    }
+                }
+                for dialog in dialogs.iter() {
+                    commands.entity(dialog).despawn_recursive();
+                }
+            }
+            Interaction::Hovered => {
+                *color = BUTTON_COLOR_HOVER;
+            }
+            Interaction::None => {
+                *color = BUTTON_COLOR_NONE;
+            }
+        }
+    }
 }
 
 ///Buttons interaction system.
 fn button(
     mut interaction_query: Query<
         (
+            Entity,
             &Interaction,
             &mut BackgroundColor,
             &Action<for<'a> fn(&'a mut GlobalState)>,
+            &mut ActivationCooldown,
             &HierarchyMark<0>,
         ),
         (Changed<Interaction>, With<Button>),
     >,
     mut state: ResMut<GlobalState>,
+    mut guard: ResMut<NavigationGuard>,
 ) {
-    for (interaction, mut color, func, _) in interaction_query.iter_mut() {
+    for (entity, interaction, mut color, func, mut cooldown, _) in interaction_query.iter_mut() {
         match *interaction {
-            Interaction::Clicked => func.run(&mut *state),
+            Interaction::Clicked => {
+                if guard.try_navigate(entity, &mut cooldown) {
+                    func.run(&mut *state)
+                }
+            }
             Interaction::Hovered => {
                 *color = BUTTON_COLOR_HOVER.into();
             }
@@ -75,3 +270,146 @@ fn button(
         }
     }
 }
+
+///Continue button interaction system. Loads save and enters game with it.
+fn continue_button(
+    mut commands: Commands,
+    mut interaction_query: Query<
+        (
+            Entity,
+            &Interaction,
+            &mut BackgroundColor,
+            &LoadSaveButton,
+            &mut ActivationCooldown,
+        ),
+        (Changed<Interaction>, With<Button>, Without<ButtonDisabled>),
+    >,
+    mut state: ResMut<GlobalState>,
+    mut guard: ResMut<NavigationGuard>,
+    mut environment: ResMut<SceneEnvironment>,
+    mut results: EventWriter<TaskResultEvent>,
+) {
+    for (entity, interaction, mut color, LoadSaveButton(path), mut cooldown) in
+        interaction_query.iter_mut()
+    {
+        match *interaction {
+            Interaction::Clicked if !guard.try_navigate(entity, &mut cooldown) => {}
+            Interaction::Clicked => match SaveFile::read(path) {
+                Ok(save) => {
+                    *environment = save.environment;
+                    commands.insert_resource(PendingLoad(path.clone(), save));
+                    state.replace(AppState::InGame);
+                }
+                //Could be changed after menu is shown. Menu is still usable.
+                Err(e) => results.send(TaskResultEvent::Failed(
+                    TaskError::new(TaskKind::Load, e).with_path(path),
+                )),
+            },
+            Interaction::Hovered => {
+                *color = BUTTON_COLOR_HOVER;
+            }
+            Interaction::None => {
+                *color = BUTTON_COLOR_NONE;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DOUBLE_CLICKED: AtomicUsize = AtomicUsize::new(0);
+    static FIRST_CLICKED: AtomicUsize = AtomicUsize::new(0);
+    static SECOND_CLICKED: AtomicUsize = AtomicUsize::new(0);
+
+    fn menu_world() -> World {
+        let mut world = World::new();
+        world.insert_resource(GlobalState::new(AppState::MainMenu));
+        world.init_resource::<NavigationGuard>();
+        world
+    }
+
+    ///Menu button whose action counts activations instead of changing state.
+    fn spawn_button(world: &mut World, action: fn(&mut GlobalState)) -> Entity {
+        world
+            .spawn((
+                Button,
+                Interaction::None,
+                BUTTON_COLOR_NONE,
+                Action::<for<'a> fn(&'a mut GlobalState)>::new(action),
+                ActivationCooldown::default(),
+                HierarchyMark::<0>,
+            ))
+            .id()
+    }
+
+    ///Sets interactions, then runs frame from when guard is reset.
+    fn frame(world: &mut World, stage: &mut SystemStage, interactions: &[(Entity, Interaction)]) {
+        for &(button, interaction) in interactions {
+            *world.get_mut::<Interaction>(button).unwrap() = interaction;
+        }
+        *world.resource_mut::<NavigationGuard>() = NavigationGuard::default();
+        stage.run(world);
+    }
+
+    #[test]
+    fn double_click_activates_once() {
+        let mut world = menu_world();
+        let play = spawn_button(&mut world, |_| {
+            DOUBLE_CLICKED.fetch_add(1, Ordering::Relaxed);
+        });
+        let mut stage = SystemStage::single(button);
+        //Press and release twice in consecutive frames, well within cooldown.
+        for interaction in [
+            Interaction::Clicked,
+            Interaction::Hovered,
+            Interaction::Clicked,
+            Interaction::Hovered,
+        ] {
+            frame(&mut world, &mut stage, &[(play, interaction)]);
+        }
+        assert_eq!(DOUBLE_CLICKED.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn one_navigation_fires_per_frame() {
+        let mut world = menu_world();
+        let first = spawn_button(&mut world, |_| {
+            FIRST_CLICKED.fetch_add(1, Ordering::Relaxed);
+        });
+        let second = spawn_button(&mut world, |_| {
+            SECOND_CLICKED.fetch_add(1, Ordering::Relaxed);
+        });
+        let mut stage = SystemStage::single(button);
+        frame(
+            &mut world,
+            &mut stage,
+            &[
+                (first, Interaction::Clicked),
+                (second, Interaction::Clicked),
+            ],
+        );
+        let clicked = |count: &AtomicUsize| count.load(Ordering::Relaxed);
+        assert_eq!(clicked(&FIRST_CLICKED) + clicked(&SECOND_CLICKED), 1);
+        //Dropped one didn't start its cooldown, so it could fire next frame.
+        let dropped = if clicked(&FIRST_CLICKED) == 0 {
+            first
+        } else {
+            second
+        };
+        frame(
+            &mut world,
+            &mut stage,
+            &[
+                (first, Interaction::Hovered),
+                (second, Interaction::Hovered),
+            ],
+        );
+        frame(&mut world, &mut stage, &[(dropped, Interaction::Clicked)]);
+        assert_eq!(clicked(&FIRST_CLICKED), 1);
+        assert_eq!(clicked(&SECOND_CLICKED), 1);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{
+    asset::{AssetCheck, Fonts, Paths},
+    states::*,
+    ui::*,
+};
+
+use std::time::Instant;
+
+use bevy::{app::AppExit, prelude::*};
+
+pub const QUIT_TEXT: &str = "Quit";
+
+pub struct MissingAssetsPlugin;
+
+///Batch setup for screen shown when assets can't be found. Uses nothing but built in font.
+impl Plugin for MissingAssetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set_to_stage(
+            CoreStage::PreUpdate,
+            SystemSet::on_enter(PreUpdateStageState::MissingAssets).with_system(setup),
+        )
+        .add_system_set_to_stage(
+            CoreStage::Update,
+            SystemSet::on_update(UpdateStageState::MissingAssets)
+                .with_system(quit_button)
+                .with_system(exit_close_requested),
+        );
+    }
+}
+
+///Mark quit button.
+#[derive(Component)]
+struct QuitButton;
+
+///What is shown to user. Lists missing files and where they were searched.
+fn missing_assets_message(paths: &Paths, check: &AssetCheck) -> String {
+    let mut message = format!(
+        "Game files are missing.\nSearched in: {}\n",
+        paths.assets.display()
+    );
+    for missing in check.missing.iter() {
+        if missing.as_os_str().is_empty() {
+            message.push_str("  - assets folder itself\n");
+        } else {
+            message.push_str(&format!("  - {}\n", missing.display()));
+        }
+    }
+    message.push_str("Run the game next to its assets folder, or pass --assets <dir>.");
+    message
+}
+
+///Setup system of missing assets screen.
+fn setup(
+    mut commands: Commands,
+    state: Res<GlobalState>,
+    fonts: Res<Fonts>,
+    paths: Res<Paths>,
+    check: Res<AssetCheck>,
+) {
+    let message = missing_assets_message(&paths, &check);
+    error!("{message}");
+    //ui camera
+    commands.spawn((
+        Camera2dBundle::default(),
+        state.mark(),
+        Name::new("Ui camera"),
+    ));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            state.mark(),
+            UiRoot,
+            Name::new("Missing assets screen"),
+        ))
+        .with_children(|parent| {
+            parent.spawn(create_text(message, &fonts, 24.0, TEXT_COLOR_BRIGHT));
+            parent
+                .spawn((
+                    create_button(),
+                    ActivationCooldown::default(),
+                    button_name(QUIT_TEXT),
+                    QuitButton,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(create_text(QUIT_TEXT, &fonts, 30.0, TEXT_COLOR_BRIGHT));
+                });
+        });
+}
+
+///Quits right away. There is nothing to lose, so exit popup is skipped.
+fn quit_button(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &mut ActivationCooldown),
+        (Changed<Interaction>, With<QuitButton>),
+    >,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, mut color, mut cooldown) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                if cooldown.try_activate(Instant::now()) {
+                    exit.send(AppExit);
+                }
+            }
+            Interaction::Hovered => {
+                *color = BUTTON_COLOR_HOVER;
+            }
+            Interaction::None => {
+                *color = BUTTON_COLOR_NONE;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn empty_assets_folder_is_explained() {
+        let dir = std::env::temp_dir().join(format!("empty-assets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = Paths {
+            asset_folder: dir.display().to_string(),
+            assets: dir.clone(),
+        };
+        let check = AssetCheck::run(&paths);
+        assert!(!check.is_ok());
+        assert!(check.is_missing(Path::new("fonts/Schluber.otf")));
+        assert!(check.is_missing(Path::new("textures/ui/button.png")));
+        let message = missing_assets_message(&paths, &check);
+        assert!(message.contains(&dir.display().to_string()), "{message}");
+        for missing in ["fonts/Schluber.otf", "textures/ui"] {
+            assert!(message.contains(missing), "{message}");
+        }
+
+        //Folder that isn't there is told as such.
+        std::fs::remove_dir(&dir).unwrap();
+        let check = AssetCheck::run(&paths);
+        assert_eq!(check.missing, [PathBuf::new()]);
+        let message = missing_assets_message(&paths, &check);
+        assert!(message.contains("assets folder itself"), "{message}");
+    }
+}
@@ -1,7 +1,9 @@
 pub mod in_game;
 pub mod main_menu;
+pub mod missing_assets;
 
 use crate::{
+    asset::AssetCheck,
     ui::{exit_close_requested, exit_esc, exit_no_button, exit_yes_button, setup_exit},
     unreachable_release,
 };
@@ -14,15 +16,23 @@ macro_rules! stage_states {
         #[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
         pub enum $global_name $locals
         $(
-            stage_states!(@ $stage_name $locals $global);
+            stage_states!(@ $global_name $stage_name $locals $global);
         )*
     };
-    (@ $stage_name:ident {$($locals:ident),*} {$($global:ident),*}) => {
+    (@ $global_name:ident $stage_name:ident {$($(#[$meta:meta])* $locals:ident),*} {$($global:ident),*}) => {
         #[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
         pub enum $stage_name {
-            $($locals,)*
+            $($(#[$meta])* $locals,)*
             $($global),*
         }
+
+        impl From<$global_name> for $stage_name {
+            fn from(state: $global_name) -> Self {
+                match state {
+                    $($(#[$meta])* $global_name::$locals => Self::$locals),*
+                }
+            }
+        }
     }
 }
 
@@ -35,7 +45,11 @@ stage_states!(
     LastStageState;
     {
         MainMenu,
-        InGame
+        InGame,
+        Paused,
+        #[cfg(feature = "inspect")]
+        Inspecting,
+        MissingAssets
     }
     {
         AppExit
@@ -54,12 +68,12 @@ pub trait PushState {
 
 impl PopState for AppState {
     fn pop(self) -> Option<Self> {
-        // match self {
-        //     AppState::MainMenu(Some(m)) => Some(AppState::MainMenu(m.pop())),
-        //     AppState::InGame(Some(i)) => Some(AppState::InGame(i.pop())),
-        //     _ => None,
-        // }
-        None
+        match self {
+            AppState::Paused => Some(AppState::InGame),
+            #[cfg(feature = "inspect")]
+            AppState::Inspecting => Some(AppState::InGame),
+            _ => None,
+        }
     }
 }
 
@@ -84,28 +98,26 @@ impl PopState for AppState {
 //         }
 //     }
 // }
-//
-// #[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
-// pub enum InGameState {
-//     AppExit,
-// }
-//
-// impl PopState for InGameState {
-//     fn pop(self) -> Option<Self> {
-//         match self {
-//             InGameState::AppExit => None,
-//         }
-//     }
-// }
-//
-// impl PushState for InGameState {
-//     fn push(self, parent: &mut AppState) {
-//         match *parent {
-//             AppState::InGame(None) => *parent = AppState::InGame(Some(self)),
-//             _ => unreachable_release!("There is no space to push"),
-//         }
-//     }
-// }
+
+///Minor states stacked on in game.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum InGameState {
+    Paused,
+    ///Gameplay frozen for inspecting octree, without pause menu.
+    #[cfg(feature = "inspect")]
+    Inspecting,
+}
+
+impl PushState for InGameState {
+    fn push(self, parent: &mut AppState) {
+        match (*parent, self) {
+            (AppState::InGame, InGameState::Paused) => *parent = AppState::Paused,
+            #[cfg(feature = "inspect")]
+            (AppState::InGame, InGameState::Inspecting) => *parent = AppState::Inspecting,
+            _ => unreachable_release!("There is no space to push"),
+        }
+    }
+}
 
 ///Intentionally privacy for external mods to avoid disruptive mistakes.
 mod global {
@@ -134,12 +146,12 @@ mod global {
         }
 
         ///increment only hierarchy.
-        fn _increment(&mut self) {
+        fn increment(&mut self) {
             self.value += 1
         }
 
         ///decrement only hierarchy.
-        fn _decrement(&mut self) {
+        fn decrement(&mut self) {
             self.value -= 1
         }
 
@@ -199,12 +211,7 @@ mod global {
 
         ///Force major state. Equivalent to Schedule::replace.
         pub fn replace(&mut self, to: AppState) {
-            if match (self.app_state, to) {
-                (AppState::MainMenu, AppState::MainMenu) | (AppState::InGame, AppState::InGame) => {
-                    true
-                }
-                _ => false,
-            } {
+            if self.app_state == to {
                 unreachable_release!(
                     "Already in that major state or target state hierarchy is not 0"
                 );
@@ -217,12 +224,12 @@ mod global {
         }
 
         ///Stacks minor state. Equivalent to Schedule::push
-        pub fn _push<Child: PushState>(&mut self, child: Child) {
+        pub fn push<Child: PushState>(&mut self, child: Child) {
             if self.state_change_way != StateChangeWay::None {
                 unreachable_release!("Already in state transition");
             }
             child.push(&mut self.app_state);
-            self.hierarchy._increment();
+            self.hierarchy.increment();
             self.state_change_way = StateChangeWay::Push;
         }
 
@@ -236,7 +243,7 @@ mod global {
         }
 
         ///Releases minor state. Equivalent to Schedule::pop
-        pub fn _pop(&mut self) {
+        pub fn pop(&mut self) {
             if self.state_change_way != StateChangeWay::None {
                 unreachable_release!("Already in state transition");
             }
@@ -244,7 +251,7 @@ mod global {
                 Some(a) => a,
                 _ => unreachable_release!("Already in that state"),
             };
-            self.hierarchy._decrement();
+            self.hierarchy.decrement();
             self.state_change_way = StateChangeWay::Pop;
         }
 
@@ -278,18 +285,23 @@ pub struct StatesPlugin;
 
 impl Plugin for StatesPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(GlobalState::new(AppState::MainMenu))
+        //Nothing could be shown without assets, so it's checked before main menu.
+        let initial = match app.world.get_resource::<AssetCheck>() {
+            Some(check) if !check.is_ok() => AppState::MissingAssets,
+            _ => AppState::MainMenu,
+        };
+        app.insert_resource(GlobalState::new(initial))
             //First
             .add_system_to_stage(CoreStage::First, manage_state.at_start())
-            .add_state_to_stage(CoreStage::First, FirstStageState::MainMenu)
+            .add_state_to_stage(CoreStage::First, FirstStageState::from(initial))
             //PreUpdate
-            .add_state_to_stage(CoreStage::PreUpdate, PreUpdateStageState::MainMenu)
+            .add_state_to_stage(CoreStage::PreUpdate, PreUpdateStageState::from(initial))
             //Update
-            .add_state_to_stage(CoreStage::Update, UpdateStageState::MainMenu)
+            .add_state_to_stage(CoreStage::Update, UpdateStageState::from(initial))
             //PostUpdate
-            .add_state_to_stage(CoreStage::PostUpdate, PostUpdateStageState::MainMenu)
+            .add_state_to_stage(CoreStage::PostUpdate, PostUpdateStageState::from(initial))
             //Last
-            .add_state_to_stage(CoreStage::Last, LastStageState::MainMenu)
+            .add_state_to_stage(CoreStage::Last, LastStageState::from(initial))
             //Exit
             .add_system_set_to_stage(
                 CoreStage::PreUpdate,
@@ -344,48 +356,21 @@ fn manage_state(
             else {
                 match change_way {
                     //Replace major to major.
-                    StateChangeWay::Replace => match *state {
-                        AppState::MainMenu => {
-                            first.replace(FirstStageState::MainMenu).unwrap();
-                            pre_update.replace(PreUpdateStageState::MainMenu).unwrap();
-                            update.replace(UpdateStageState::MainMenu).unwrap();
-                            post_update.replace(PostUpdateStageState::MainMenu).unwrap();
-                            last.replace(LastStageState::MainMenu).unwrap();
-                        }
-                        AppState::InGame => {
-                            first.replace(FirstStageState::InGame).unwrap();
-                            pre_update.replace(PreUpdateStageState::InGame).unwrap();
-                            update.replace(UpdateStageState::InGame).unwrap();
-                            post_update.replace(PostUpdateStageState::InGame).unwrap();
-                            last.replace(LastStageState::InGame).unwrap();
-                        }
-                    },
+                    StateChangeWay::Replace => {
+                        first.replace((*state).into()).unwrap();
+                        pre_update.replace((*state).into()).unwrap();
+                        update.replace((*state).into()).unwrap();
+                        post_update.replace((*state).into()).unwrap();
+                        last.replace((*state).into()).unwrap();
+                    }
                     //Push minor state.
-                    // StateChangeWay::Push => match *state {
-                    //     AppState::MainMenu(Some(m)) => {
-                    //         first.push(FirstStageState::MainMenu(Some(m))).unwrap();
-                    //         pre_update
-                    //             .push(PreUpdateStageState::MainMenu(Some(m)))
-                    //             .unwrap();
-                    //         update.push(UpdateStageState::MainMenu(Some(m))).unwrap();
-                    //         post_update
-                    //             .push(PostUpdateStageState::MainMenu(Some(m)))
-                    //             .unwrap();
-                    //         last.push(LastStageState::MainMenu(Some(m))).unwrap();
-                    //     }
-                    //     AppState::InGame(Some(i)) => {
-                    //         first.push(FirstStageState::InGame(Some(i))).unwrap();
-                    //         pre_update
-                    //             .push(PreUpdateStageState::InGame(Some(i)))
-                    //             .unwrap();
-                    //         update.push(UpdateStageState::InGame(Some(i))).unwrap();
-                    //         post_update
-                    //             .push(PostUpdateStageState::InGame(Some(i)))
-                    //             .unwrap();
-                    //         last.push(LastStageState::InGame(Some(i))).unwrap();
-                    //     }
-                    //     _ => unreachable_release!("State is interrupted"),
-                    // },
+                    StateChangeWay::Push => {
+                        first.push((*state).into()).unwrap();
+                        pre_update.push((*state).into()).unwrap();
+                        update.push((*state).into()).unwrap();
+                        post_update.push((*state).into()).unwrap();
+                        last.push((*state).into()).unwrap();
+                    }
                     //Pop minor or exit state.
                     StateChangeWay::Pop => {
                         first.pop().unwrap();
@@ -422,3 +407,28 @@ fn clear_state(world: &mut World, system_state: &mut ClearStateSystemState) {
     //Applying commands to world immediately.
     system_state.apply(world);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_asset_check_starts_at_missing_assets() {
+        let missing = AssetCheck {
+            missing: vec![Default::default()],
+        };
+        for (check, initial) in [
+            (Some(missing), UpdateStageState::MissingAssets),
+            (Some(AssetCheck::default()), UpdateStageState::MainMenu),
+            (None, UpdateStageState::MainMenu),
+        ] {
+            let mut app = App::new();
+            if let Some(check) = check {
+                app.insert_resource(check);
+            }
+            app.add_plugin(StatesPlugin);
+            let state = app.world.resource::<State<UpdateStageState>>();
+            assert_eq!(*state.current(), initial);
+        }
+    }
+}
@@ -0,0 +1,692 @@
+use crate::{
+    asset::{PolylineMaterials, Polylines, BLUE, RED, UNIT_BOX},
+    physics::{
+        aabb::AABB,
+        collider::Collider,
+        octree::{InsertOutcome, Octree, OctreeEntity},
+    },
+    single_or_return,
+    states::GlobalState,
+    tag::{StructureIndex, StructureTags, TagId},
+};
+
+use bevy::{
+    ecs::{archetype::ArchetypeId, entity::Entities, system::SystemParam},
+    prelude::*,
+    render::primitives::Aabb,
+    utils::HashSet,
+};
+
+use bevy_polyline::prelude::*;
+
+///Everything needed to spawn a placed structure again.
+#[derive(Clone)]
+pub struct StructureDesc {
+    pub transform: Transform,
+    pub collider: Collider,
+    pub meshes: Vec<Handle<Mesh>>,
+    pub material: Handle<StandardMaterial>,
+    pub tags: Vec<TagId>,
+}
+
+///Mark root of placed structure. Keeps how its children are built.
+#[derive(Component)]
+pub struct Structure {
+    meshes: Vec<Handle<Mesh>>,
+    material: Handle<StandardMaterial>,
+    ///Order of placement in session. Unlike entity id, it doesn't depend on what else was spawned.
+    placement: u64,
+}
+
+impl Structure {
+    pub fn placement(&self) -> u64 {
+        self.placement
+    }
+
+    ///Description that spawns same structure again.
+    pub fn desc(
+        &self,
+        transform: Transform,
+        collider: Collider,
+        tags: &StructureTags,
+    ) -> StructureDesc {
+        StructureDesc {
+            transform,
+            collider,
+            meshes: self.meshes.clone(),
+            material: self.material.clone(),
+            tags: tags.0.clone(),
+        }
+    }
+}
+
+///Mark entity that octree holds without being structure, such as ground.
+///Raycast hits it, but it isn't counted, removed or moved with build.
+#[derive(Component)]
+pub struct Fixture;
+
+///Bookkeeping that should react whenever structure is added or removed.
+pub trait MutationObserver: Send + Sync + 'static {
+    fn on_added(&mut self, entity: Entity, desc: &StructureDesc);
+
+    fn on_removed(&mut self, entity: Entity, desc: &StructureDesc);
+
+    ///Every structure is gone with world, such as when entering game again.
+    fn on_cleared(&mut self);
+
+    ///Compares own state with ground truth. Only used for debug.
+    ///Octree holds fixtures besides structures.
+    fn verify(&self, _octree: &Octree, _structures: usize, _fixtures: usize) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+///Registered observers of world mutation.
+#[derive(Resource)]
+pub struct MutationObservers(Vec<Box<dyn MutationObserver>>);
+
+impl Default for MutationObservers {
+    fn default() -> Self {
+        let mut ret = Self(Vec::new());
+        if cfg!(debug_assertions) {
+            ret.register(StructureCount::default());
+        }
+        ret
+    }
+}
+
+impl MutationObservers {
+    pub fn register(&mut self, observer: impl MutationObserver) {
+        self.0.push(Box::new(observer));
+    }
+
+    pub fn clear(&mut self) {
+        for observer in self.0.iter_mut() {
+            observer.on_cleared();
+        }
+    }
+}
+
+///Sent by world mutator when structure is placed.
+pub struct StructurePlaced {
+    pub entity: Entity,
+    ///Kind of collider shape.
+    pub kind: &'static str,
+    pub cell: Vec3,
+}
+
+///Sent by world mutator when structure is removed.
+pub struct StructureRemoved {
+    pub entity: Entity,
+    pub kind: &'static str,
+    pub cell: Vec3,
+}
+
+///Counts placements, so each structure gets distinct name and key. Restarts every session.
+#[derive(Resource, Default)]
+pub struct PlacementIndex(u64);
+
+///The only way to add or remove placed structure, so every bookkeeping stays in sync.
+#[derive(SystemParam)]
+pub struct WorldMutator<'w, 's> {
+    commands: Commands<'w, 's>,
+    octree: Query<'w, 's, &'static mut Octree>,
+    structures: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Collider,
+            &'static Structure,
+            &'static StructureTags,
+        ),
+    >,
+    observers: ResMut<'w, MutationObservers>,
+    index: ResMut<'w, StructureIndex>,
+    placements: ResMut<'w, PlacementIndex>,
+    state: Res<'w, GlobalState>,
+    placed: EventWriter<'w, 's, StructurePlaced>,
+    removed: EventWriter<'w, 's, StructureRemoved>,
+}
+
+impl WorldMutator<'_, '_> {
+    ///Return is None if octree refused structure.
+    pub fn spawn_structure(&mut self, desc: StructureDesc) -> Option<Entity> {
+        let mut octree = single_or_return!(mut self.octree, None);
+        self.placements.0 += 1;
+        let name = format!("{}#{}", desc.collider.shape().kind(), self.placements.0);
+        let entity = self
+            .commands
+            .spawn((
+                TransformBundle {
+                    local: desc.transform,
+                    ..default()
+                },
+                VisibilityBundle::default(),
+                self.state.mark(),
+                desc.collider.clone(),
+                Structure {
+                    meshes: desc.meshes.clone(),
+                    material: desc.material.clone(),
+                    placement: self.placements.0,
+                },
+                StructureTags(desc.tags.clone()),
+            ))
+            .with_children(|parent| {
+                for (index, mesh) in desc.meshes.iter().enumerate() {
+                    parent.spawn((
+                        PbrBundle {
+                            mesh: mesh.clone(),
+                            material: desc.material.clone(),
+                            ..default()
+                        },
+                        Name::new(format!("{name} part {index}")),
+                    ));
+                }
+            })
+            .insert(Name::new(name))
+            .id();
+        let outcome = octree.insert_result(OctreeEntity::new(
+            entity,
+            self.placements.0,
+            &desc.collider,
+            &desc.transform,
+        ));
+        if outcome != InsertOutcome::Inserted {
+            self.commands.entity(entity).despawn_recursive();
+            return None;
+        }
+        self.index.on_added(entity, &desc);
+        for observer in self.observers.0.iter_mut() {
+            observer.on_added(entity, &desc);
+        }
+        self.placed.send(StructurePlaced {
+            entity,
+            kind: desc.collider.shape().kind(),
+            cell: desc.transform.translation,
+        });
+        Some(entity)
+    }
+
+    ///Prepares for spawning many structures at once, such as loading.
+    pub fn reserve(&mut self, structures: usize) {
+        let mut octree = single_or_return!(mut self.octree);
+        let nodes = octree.nodes_for(structures);
+        octree.reserve(nodes);
+    }
+
+    ///Return is how removed structure was, if it existed.
+    pub fn despawn_structure(&mut self, entity: Entity) -> Option<StructureDesc> {
+        let (transform, collider, structure, tags) = self.structures.get(entity).ok()?;
+        let desc = structure.desc(*transform, collider.clone(), tags);
+        //Stored bound could differ from one of current transform, so entity goes by index alone.
+        single_or_return!(mut self.octree, None).remove_entity(entity);
+        self.commands.entity(entity).despawn_recursive();
+        self.index.on_removed(entity, &desc);
+        for observer in self.observers.0.iter_mut() {
+            observer.on_removed(entity, &desc);
+        }
+        self.removed.send(StructureRemoved {
+            entity,
+            kind: desc.collider.shape().kind(),
+            cell: desc.transform.translation,
+        });
+        Some(desc)
+    }
+}
+
+///Counts structures to cross check caches in debug build.
+#[derive(Default)]
+struct StructureCount(usize);
+
+impl MutationObserver for StructureCount {
+    fn on_added(&mut self, _entity: Entity, _desc: &StructureDesc) {
+        self.0 += 1;
+    }
+
+    fn on_removed(&mut self, _entity: Entity, _desc: &StructureDesc) {
+        self.0 -= 1;
+    }
+
+    fn on_cleared(&mut self) {
+        self.0 = 0;
+    }
+
+    fn verify(&self, octree: &Octree, structures: usize, fixtures: usize) -> Result<(), String> {
+        if self.0 + fixtures != octree.len() || self.0 != structures {
+            Err(format!(
+                "observed {} structures, but octree has {} besides {fixtures} fixtures and world has {}",
+                self.0,
+                octree.len().saturating_sub(fixtures),
+                structures
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+///Periodically verifies observers against ground truth.
+pub fn cross_check_observers(
+    observers: Res<MutationObservers>,
+    index: Res<StructureIndex>,
+    octree: Query<&Octree>,
+    structures: Query<(), With<Structure>>,
+    fixtures: Query<(), With<Fixture>>,
+    tags: Query<(Entity, &StructureTags)>,
+    time: Res<Time>,
+    mut elapsed: Local<f32>,
+) {
+    *elapsed += time.delta_seconds();
+    if *elapsed < 1. {
+        return;
+    }
+    *elapsed = 0.;
+    let count = structures.iter().count();
+    let fixture_count = fixtures.iter().count();
+    for octree in octree.iter() {
+        for observer in observers.0.iter() {
+            if let Err(e) = observer.verify(octree, count, fixture_count) {
+                error!("Structure bookkeeping mismatch: {e}");
+            }
+        }
+    }
+    if cfg!(debug_assertions) {
+        if let Err(e) = index.verify(tags.iter()) {
+            error!("Structure index mismatch: {e}");
+        }
+        //Counts could agree while tree holds despawned entity in place of live one.
+        for octree in octree.iter() {
+            let stale: Vec<_> = octree
+                .iter()
+                .map(|entity| entity.entity())
+                .filter(|entity| !structures.contains(*entity) && !fixtures.contains(*entity))
+                .collect();
+            if !stale.is_empty() {
+                error!("Octree holds entities that aren't structures or fixtures: {stale:?}");
+            }
+        }
+    }
+}
+
+///Warns once per archetype about structure without name. Only used for debug.
+pub fn warn_unnamed_structures(
+    unnamed: Query<Entity, (With<Structure>, Without<Name>)>,
+    entities: &Entities,
+    mut warned: Local<HashSet<ArchetypeId>>,
+) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    for entity in unnamed.iter() {
+        if let Some(location) = entities.get(entity) {
+            if warned.insert(location.archetype_id) {
+                warn!("Structure {entity:?} has no name. Spawn it through WorldMutator");
+            }
+        }
+    }
+}
+
+///Bounds of world space aabb in local space of transform, as render culling expects.
+///Rotated box is bounded again, so it could be larger than given one.
+pub fn render_aabb(aabb: &AABB, transform: &GlobalTransform) -> Aabb {
+    let affine = transform.affine();
+    if affine.matrix3.determinant() == 0. {
+        //Zero scaled entity isn't visible anyway.
+        return Aabb::from_min_max(Vec3::ZERO, Vec3::ZERO);
+    }
+    let inverse = affine.inverse();
+    let (min, max) = aabb.corners().into_iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), corner| {
+            let local = inverse.transform_point3(corner);
+            (min.min(local), max.max(local))
+        },
+    );
+    Aabb::from_min_max(min, max)
+}
+
+///Render bounds of structure parts cover both mesh and collider.
+///Otherwise part could be culled while its collider is still on screen.
+pub fn sync_render_aabb(
+    roots: Query<
+        (
+            &Transform,
+            &Collider,
+            ChangeTrackers<Transform>,
+            ChangeTrackers<Collider>,
+        ),
+        With<Structure>,
+    >,
+    mut parts: Query<(&Parent, &Handle<Mesh>, &mut Aabb, ChangeTrackers<Aabb>)>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for (parent, mesh, mut aabb, aabb_tracker) in parts.iter_mut() {
+        let (transform, collider, transform_tracker, collider_tracker) =
+            match roots.get(parent.get()) {
+                Ok(root) => root,
+                Err(_) => continue,
+            };
+        //Added aabb is what bevy computed from mesh.
+        if !(aabb_tracker.is_added()
+            || transform_tracker.is_changed()
+            || collider_tracker.is_changed())
+        {
+            continue;
+        }
+        //Parts are spawned with identity transform, so they share space of root.
+        let bound = render_aabb(
+            &collider.aabb(transform),
+            &GlobalTransform::from(*transform),
+        );
+        *aabb = match meshes.get(mesh).and_then(Mesh::compute_aabb) {
+            Some(mesh_aabb) => Aabb::from_min_max(
+                mesh_aabb.min().min(bound.min()).into(),
+                mesh_aabb.max().max(bound.max()).into(),
+            ),
+            None => bound,
+        };
+    }
+}
+
+///Whether physics and render bounds of structures are drawn.
+#[derive(Resource, Default)]
+pub struct BoundsDebug(pub bool);
+
+///Mark wireframe drawn by bounds debug.
+#[derive(Component)]
+pub struct BoundsWireframe;
+
+///Draws physics aabb in red and render aabb in blue, so mismatch could be spotted.
+pub fn draw_bounds_debug(
+    mut commands: Commands,
+    debug: Res<BoundsDebug>,
+    roots: Query<(&Transform, &Collider), With<Structure>>,
+    parts: Query<(&Parent, &GlobalTransform, &Aabb)>,
+    changed: Query<(), Or<(Changed<Aabb>, (With<Structure>, Changed<Transform>))>>,
+    removed: RemovedComponents<Structure>,
+    wireframes: Query<Entity, With<BoundsWireframe>>,
+    polylines: Res<Polylines>,
+    polyline_materials: Res<PolylineMaterials>,
+    state: Res<GlobalState>,
+) {
+    let unchanged = changed.is_empty() && removed.iter().next().is_none();
+    if !debug.is_changed() && (!debug.0 || unchanged) {
+        return;
+    }
+    for entity in wireframes.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !debug.0 {
+        return;
+    }
+    let mut spawn = |transform: Transform, material: &str| {
+        commands.spawn((
+            PolylineBundle {
+                polyline: polylines[UNIT_BOX].clone(),
+                material: polyline_materials[material].clone(),
+                transform,
+                ..default()
+            },
+            BoundsWireframe,
+            state.mark(),
+        ));
+    };
+    for (transform, collider) in roots.iter() {
+        let aabb = collider.aabb(transform);
+        spawn(
+            Transform::from_translation(aabb.center()).with_scale(aabb.length()),
+            RED,
+        );
+    }
+    for (parent, global, aabb) in parts.iter() {
+        if roots.contains(parent.get()) {
+            let local = Transform::from_translation(aabb.center.into())
+                .with_scale((aabb.half_extents * 2.).into());
+            spawn(global.mul_transform(local).compute_transform(), BLUE);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use crate::{
+        physics::{collider::Shape, octree::EntityOctree},
+        states::AppState,
+    };
+
+    use std::sync::{Arc, Mutex};
+
+    use bevy::ecs::system::SystemState;
+
+    #[derive(Clone, Copy, Eq, PartialEq, Debug)]
+    enum Seen {
+        Added(Entity),
+        Removed(Entity),
+        Cleared,
+    }
+
+    ///Records what it is told. Log is shared, as observers are boxed into resource.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Seen>>>);
+
+    impl MutationObserver for Recorder {
+        fn on_added(&mut self, entity: Entity, _desc: &StructureDesc) {
+            self.0.lock().unwrap().push(Seen::Added(entity));
+        }
+
+        fn on_removed(&mut self, entity: Entity, _desc: &StructureDesc) {
+            self.0.lock().unwrap().push(Seen::Removed(entity));
+        }
+
+        fn on_cleared(&mut self) {
+            self.0.lock().unwrap().push(Seen::Cleared);
+        }
+    }
+
+    fn desc(translation: Vec3, shape: Shape) -> StructureDesc {
+        StructureDesc {
+            transform: Transform::from_translation(translation),
+            collider: Collider::from_shape(shape),
+            meshes: vec![default()],
+            material: default(),
+            tags: vec![TagId::DEFENSIVE],
+        }
+    }
+
+    ///Checks every observer, index and tree against world like `cross_check_observers` does.
+    fn verify(world: &mut World) {
+        let structures = world.query::<&Structure>().iter(world).count();
+        let octree = world.query::<&Octree>().single(world);
+        for observer in world.resource::<MutationObservers>().0.iter() {
+            assert_eq!(observer.verify(octree, structures, 0), Ok(()));
+        }
+        let tags: Vec<_> = world
+            .query::<(Entity, &StructureTags)>()
+            .iter(world)
+            .map(|(entity, tags)| (entity, tags.clone()))
+            .collect();
+        let index = world.resource::<StructureIndex>();
+        assert_eq!(
+            index.verify(tags.iter().map(|(entity, tags)| (*entity, tags))),
+            Ok(())
+        );
+        assert_eq!(index.count_with_tag(TagId::DEFENSIVE), structures);
+    }
+
+    ///World that world mutator could work on, in game, with tree structures go into.
+    ///Shared by tests of systems that place or remove structures.
+    pub(crate) fn mutation_world(octree: EntityOctree) -> World {
+        let mut world = World::new();
+        world.init_resource::<MutationObservers>();
+        world.init_resource::<StructureIndex>();
+        world.init_resource::<PlacementIndex>();
+        world.insert_resource(GlobalState::new(AppState::InGame));
+        world.init_resource::<Events<StructurePlaced>>();
+        world.init_resource::<Events<StructureRemoved>>();
+        world.spawn(octree);
+        world
+    }
+
+    fn game_tree() -> EntityOctree {
+        EntityOctree::new(64, Vec3::splat(0.9), AABB::from_size(64.))
+    }
+
+    #[test]
+    fn observers_see_every_mutation() {
+        let recorder = Recorder::default();
+        //Count is registered in debug build only, so test has it regardless.
+        let mut observers = MutationObservers(Vec::new());
+        observers.register(StructureCount::default());
+        observers.register(recorder.clone());
+        let mut world = mutation_world(game_tree());
+        world.insert_resource(observers);
+        let mut mutator = SystemState::<WorldMutator>::new(&mut world);
+        let cube = Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        };
+        //Tiny ones in same spot can't be separated, so second one is refused.
+        let speck = Shape::Sphere { radius: 0.1 };
+        let mut mutate = |world: &mut World, f: &dyn Fn(&mut WorldMutator) -> Option<Entity>| {
+            let ret = f(&mut mutator.get_mut(world));
+            mutator.apply(world);
+            verify(world);
+            ret
+        };
+
+        let a = mutate(&mut world, &|m| {
+            m.spawn_structure(desc(Vec3::ZERO, cube.clone()))
+        });
+        let b = mutate(&mut world, &|m| {
+            m.spawn_structure(desc(Vec3::X, cube.clone()))
+        });
+        let c = mutate(&mut world, &|m| {
+            m.spawn_structure(desc(Vec3::splat(0.2), speck.clone()))
+        });
+        let refused = mutate(&mut world, &|m| {
+            m.spawn_structure(desc(Vec3::splat(0.2), speck.clone()))
+        });
+        let (a, b, c) = (a.unwrap(), b.unwrap(), c.unwrap());
+        assert!(refused.is_none());
+        assert!(mutate(&mut world, &|m| m.despawn_structure(b).map(|_| b)).is_some());
+        //Already gone, so nothing is observed.
+        assert!(mutate(&mut world, &|m| m.despawn_structure(b).map(|_| b)).is_none());
+        //Refused one is despawned without being seen.
+        assert_eq!(world.query::<&Structure>().iter(&world).count(), 2);
+        assert_eq!(world.resource::<Events<StructurePlaced>>().len(), 3);
+        assert_eq!(world.resource::<Events<StructureRemoved>>().len(), 1);
+
+        //Entering game again clears bookkeeping after old world is gone with state.
+        let octree = world
+            .query_filtered::<Entity, With<Octree>>()
+            .single(&world);
+        for entity in [a, c, octree] {
+            world.despawn(entity);
+        }
+        world.resource_mut::<MutationObservers>().clear();
+        world.resource_mut::<StructureIndex>().on_cleared();
+        world.spawn(EntityOctree::new(
+            64,
+            Vec3::splat(0.9),
+            AABB::from_size(64.),
+        ));
+        verify(&mut world);
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                Seen::Added(a),
+                Seen::Added(b),
+                Seen::Added(c),
+                Seen::Removed(b),
+                Seen::Cleared
+            ]
+        );
+    }
+
+    #[test]
+    fn placed_structures_are_named_by_kind_and_placement() {
+        let mut world = mutation_world(game_tree());
+        let mut mutator = SystemState::<WorldMutator>::new(&mut world);
+        let mut spawn = |world: &mut World, shape| {
+            let mut desc = desc(Vec3::new(3., 0., 3.), shape);
+            desc.meshes = vec![default(); 2];
+            let ret = mutator.get_mut(world).spawn_structure(desc);
+            mutator.apply(world);
+            ret.unwrap()
+        };
+        let cube = spawn(
+            &mut world,
+            Shape::Cuboid {
+                half_extents: Vec3::splat(0.5),
+            },
+        );
+        let tower = spawn(
+            &mut world,
+            Shape::CutSphere {
+                radius: 2.5,
+                cut: 0.5,
+            },
+        );
+        let name = |world: &World, entity| world.get::<Name>(entity).unwrap().to_string();
+        for (root, expected) in [(cube, "Cuboid#1"), (tower, "CutSphere#2")] {
+            assert_eq!(name(&world, root), expected);
+            let parts = world.get::<Children>(root).unwrap();
+            let parts: Vec<_> = parts.iter().map(|&part| name(&world, part)).collect();
+            assert_eq!(
+                parts,
+                [format!("{expected} part 0"), format!("{expected} part 1")]
+            );
+        }
+        //Nothing for debug lint to warn about.
+        assert_eq!(
+            world
+                .query_filtered::<(), (With<Structure>, Without<Name>)>()
+                .iter(&world)
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn render_aabb_covers_world_bound_in_local_space() {
+        let bound = AABB::new(Vec3::new(1., 2., 3.), Vec3::new(2., 4., 7.));
+        //Translation alone moves box as is.
+        let local = render_aabb(&bound, &GlobalTransform::from_xyz(1., 1., 1.));
+        assert_eq!(Vec3::from(local.min()), Vec3::new(0., 1., 2.));
+        assert_eq!(Vec3::from(local.max()), Vec3::new(1., 3., 6.));
+
+        let transform = GlobalTransform::from(Transform {
+            translation: Vec3::new(1.5, 3., 5.),
+            rotation: Quat::from_rotation_y(0.6) * Quat::from_rotation_x(0.3),
+            scale: Vec3::new(2., 0.5, 1.),
+        });
+        let local = render_aabb(&bound, &transform);
+        let (min, max) = (Vec3::from(local.min()), Vec3::from(local.max()));
+        //Every corner of bound taken back to local space is inside, and some touch each face.
+        let inverse = transform.affine().inverse();
+        let corners = bound
+            .corners()
+            .map(|corner| inverse.transform_point3(corner));
+        for corner in corners {
+            assert!((corner - min).min_element() > -1e-4 && (max - corner).min_element() > -1e-4);
+        }
+        for axis in 0..3 {
+            assert!(corners
+                .iter()
+                .any(|corner| (corner[axis] - min[axis]).abs() < 1e-4));
+            assert!(corners
+                .iter()
+                .any(|corner| (corner[axis] - max[axis]).abs() < 1e-4));
+        }
+
+        //Flattened entity isn't visible, so its bound is empty.
+        let flat = GlobalTransform::from_scale(Vec3::new(1., 0., 1.));
+        assert_eq!(
+            Vec3::from(render_aabb(&bound, &flat).half_extents),
+            Vec3::ZERO
+        );
+    }
+}
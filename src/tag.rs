@@ -0,0 +1,191 @@
+use crate::structure::{MutationObserver, StructureDesc};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+///Interned gameplay tag, such as "defensive". Only stable within a run, so saves keep names.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct TagId(u32);
+
+impl TagId {
+    pub const FOUNDATION: Self = Self(0);
+    pub const DEFENSIVE: Self = Self(1);
+    pub const DECORATIVE: Self = Self(2);
+}
+
+///Tags that always exist, in order of their ids.
+const BUILT_IN_TAGS: [&str; 3] = ["foundation", "defensive", "decorative"];
+
+///Interns tag names. Built-in tags are interned first, so their ids are constants.
+#[derive(Resource)]
+pub struct TagRegistry {
+    names: Vec<String>,
+    ids: HashMap<String, TagId>,
+}
+
+impl Default for TagRegistry {
+    fn default() -> Self {
+        let mut ret = Self {
+            names: Vec::new(),
+            ids: HashMap::default(),
+        };
+        for name in BUILT_IN_TAGS {
+            ret.intern(name);
+        }
+        ret
+    }
+}
+
+impl TagRegistry {
+    ///Id of name, which is given a new one if it is not seen yet.
+    pub fn intern(&mut self, name: &str) -> TagId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = TagId(self.names.len() as u32);
+        self.names.push(name.to_owned());
+        self.ids.insert(name.to_owned(), id);
+        id
+    }
+
+    ///Id of name, if it is ever interned.
+    pub fn get(&self, name: &str) -> Option<TagId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: TagId) -> &str {
+        &self.names[id.0 as usize]
+    }
+
+    ///Every tag in order of interning.
+    pub fn iter(&self) -> impl Iterator<Item = (TagId, &str)> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (TagId(id as u32), name.as_str()))
+    }
+}
+
+///Tags of placed structure, given from its description at placement.
+#[derive(Component, Clone, Default, Debug)]
+pub struct StructureTags(pub Vec<TagId>);
+
+impl StructureTags {
+    pub fn contains(&self, tag: TagId) -> bool {
+        self.0.contains(&tag)
+    }
+}
+
+///Whether entity is structure with tag of given name.
+pub fn has_tag(
+    entity: Entity,
+    name: &str,
+    registry: &TagRegistry,
+    query: &Query<&StructureTags>,
+) -> bool {
+    match (registry.get(name), query.get(entity)) {
+        (Some(tag), Ok(tags)) => tags.contains(tag),
+        _ => false,
+    }
+}
+
+///Structures by tag. Kept in sync by world mutator, so lookup doesn't walk world.
+#[derive(Resource, Default)]
+pub struct StructureIndex {
+    by_tag: HashMap<TagId, HashSet<Entity>>,
+}
+
+impl StructureIndex {
+    pub fn entities_with_tag(&self, tag: TagId) -> impl Iterator<Item = Entity> + '_ {
+        self.by_tag.get(&tag).into_iter().flatten().copied()
+    }
+
+    pub fn count_with_tag(&self, tag: TagId) -> usize {
+        self.by_tag.get(&tag).map_or(0, HashSet::len)
+    }
+
+    ///Compares index with tags in world. Only used for debug.
+    pub fn verify<'a>(
+        &self,
+        structures: impl Iterator<Item = (Entity, &'a StructureTags)>,
+    ) -> Result<(), String> {
+        let mut counts = HashMap::<TagId, usize>::default();
+        for (entity, tags) in structures {
+            for tag in tags.0.iter() {
+                if !self
+                    .by_tag
+                    .get(tag)
+                    .map_or(false, |set| set.contains(&entity))
+                {
+                    return Err(format!("{entity:?} is missing from index of its tag"));
+                }
+                *counts.entry(*tag).or_default() += 1;
+            }
+        }
+        match self
+            .by_tag
+            .iter()
+            .find(|(tag, set)| counts.get(tag).copied().unwrap_or(0) != set.len())
+        {
+            Some((tag, set)) => Err(format!(
+                "index has {} structures for {tag:?}, but world has {}",
+                set.len(),
+                counts.get(tag).copied().unwrap_or(0)
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl MutationObserver for StructureIndex {
+    fn on_added(&mut self, entity: Entity, desc: &StructureDesc) {
+        for tag in desc.tags.iter() {
+            self.by_tag.entry(*tag).or_default().insert(entity);
+        }
+    }
+
+    fn on_removed(&mut self, entity: Entity, desc: &StructureDesc) {
+        for tag in desc.tags.iter() {
+            if let Some(entities) = self.by_tag.get_mut(tag) {
+                entities.remove(&entity);
+                if entities.is_empty() {
+                    self.by_tag.remove(tag);
+                }
+            }
+        }
+    }
+
+    fn on_cleared(&mut self) {
+        self.by_tag.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning_is_stable() {
+        let mut registry = TagRegistry::default();
+        for (id, name) in [
+            (TagId::FOUNDATION, "foundation"),
+            (TagId::DEFENSIVE, "defensive"),
+            (TagId::DECORATIVE, "decorative"),
+        ] {
+            assert_eq!(registry.get(name), Some(id));
+            assert_eq!(registry.intern(name), id);
+        }
+        assert_eq!(registry.get("rampart"), None);
+        let rampart = registry.intern("rampart");
+        let moat = registry.intern("moat");
+        assert_ne!(rampart, moat);
+        assert_eq!(registry.intern("rampart"), rampart);
+        assert_eq!(registry.name(moat), "moat");
+        assert_eq!(
+            registry.iter().map(|(_, name)| name).collect::<Vec<_>>(),
+            ["foundation", "defensive", "decorative", "rampart", "moat"]
+        );
+    }
+}
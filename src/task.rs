@@ -0,0 +1,438 @@
+use crate::{asset::Fonts, states::GlobalState, ui::*};
+
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use bevy::prelude::*;
+
+pub const DISMISS_TEXT: &str = "Dismiss";
+pub const RETRY_TEXT: &str = "Retry";
+pub const OPEN_FOLDER_TEXT: &str = "Open folder";
+pub const DETAILS_TEXT: &str = "Details";
+
+///Batch setup for reporting results of background tasks.
+pub struct TaskPlugin;
+
+impl Plugin for TaskPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TaskResultEvent>()
+            .add_event::<RetryTaskEvent>()
+            .add_system_to_stage(CoreStage::Update, report_task_results)
+            .add_system_to_stage(CoreStage::Update, error_dialog_button);
+    }
+}
+
+///What kind of background work is done.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum TaskKind {
+    Save,
+    Load,
+}
+
+impl Display for TaskKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Save => write!(f, "Save"),
+            Self::Load => write!(f, "Load"),
+        }
+    }
+}
+
+///How to submit failed task again.
+#[derive(Clone, Debug)]
+pub enum TaskRetry {
+    Save(PathBuf),
+    Load(PathBuf),
+}
+
+///Failure of background task, in form that could be shown to user.
+#[derive(Clone, Debug)]
+pub struct TaskError {
+    pub kind: TaskKind,
+    pub source: String,
+    pub path: Option<PathBuf>,
+    ///Whether user could carry on. Otherwise modal dialog is shown.
+    pub recoverable: bool,
+    pub retry: Option<TaskRetry>,
+}
+
+impl TaskError {
+    ///Recoverable error without retry.
+    pub fn new(kind: TaskKind, source: impl Display) -> Self {
+        Self {
+            kind,
+            source: source.to_string(),
+            path: None,
+            recoverable: true,
+            retry: None,
+        }
+    }
+
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_retry(mut self, retry: TaskRetry) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    pub fn unrecoverable(mut self) -> Self {
+        self.recoverable = false;
+        self
+    }
+}
+
+impl Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed: {}", self.kind, self.source)?;
+        if let Some(path) = &self.path {
+            write!(f, " ({})", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+///Completion of background task. Every task reports through this, so failures look same.
+pub enum TaskResultEvent {
+    Done { kind: TaskKind, message: String },
+    Failed(TaskError),
+}
+
+///Request from error dialog to submit task again. Handled by module owning the task.
+pub struct RetryTaskEvent(pub TaskRetry);
+
+///Mark root of error dialog.
+#[derive(Component)]
+pub struct ErrorDialog;
+
+///Mark full error text that is collapsed until expanded.
+#[derive(Component)]
+struct ErrorDetails;
+
+#[derive(Component)]
+enum ErrorDialogButton {
+    Retry(TaskRetry),
+    OpenFolder(PathBuf),
+    ToggleDetails,
+    Dismiss,
+}
+
+///Routes task results. Recoverable failures are only logged, since there is no toast yet.
+fn report_task_results(
+    mut commands: Commands,
+    mut events: EventReader<TaskResultEvent>,
+    dialogs: Query<Entity, With<ErrorDialog>>,
+    state: Res<GlobalState>,
+    fonts: Res<Fonts>,
+) {
+    for event in events.iter() {
+        match event {
+            TaskResultEvent::Done { kind, message } => info!("{kind}: {message}"),
+            TaskResultEvent::Failed(e) if e.recoverable => warn!("{e}"),
+            TaskResultEvent::Failed(e) => {
+                error!("{e}");
+                //Only latest failure is shown.
+                for dialog in dialogs.iter() {
+                    commands.entity(dialog).despawn_recursive();
+                }
+                spawn_error_dialog(&mut commands, e, &state, &fonts);
+            }
+        }
+    }
+}
+
+///Setup modal error dialog. Actions depend on what failed.
+fn spawn_error_dialog(
+    commands: &mut Commands,
+    error: &TaskError,
+    state: &GlobalState,
+    fonts: &Res<Fonts>,
+) {
+    let mut actions = Vec::new();
+    if let Some(retry) = &error.retry {
+        actions.push((RETRY_TEXT, ErrorDialogButton::Retry(retry.clone())));
+    }
+    if let Some(folder) = error.path.as_deref().and_then(Path::parent) {
+        actions.push((
+            OPEN_FOLDER_TEXT,
+            ErrorDialogButton::OpenFolder(folder.to_owned()),
+        ));
+    }
+    actions.push((DETAILS_TEXT, ErrorDialogButton::ToggleDetails));
+    actions.push((DISMISS_TEXT, ErrorDialogButton::Dismiss));
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(60.0), Val::Auto),
+                    position_type: PositionType::Absolute,
+                    position: UiRect::new(
+                        Val::Percent(20.0),
+                        Val::Undefined,
+                        Val::Percent(30.0),
+                        Val::Undefined,
+                    ),
+                    flex_wrap: FlexWrap::Wrap,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    align_content: AlignContent::SpaceAround,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: UI_BACKGROUND_COLOR,
+                ..default()
+            },
+            state.mark(),
+            ErrorDialog,
+            UiRoot,
+            Name::new(format!("Error dialog: {}", error.kind)),
+        ))
+        .with_children(|parent| {
+            //summary
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_basis: Val::Percent(100.0),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(create_text(
+                        format!("{} failed", error.kind),
+                        fonts,
+                        30.0,
+                        TEXT_COLOR_DARK,
+                    ));
+                });
+            //details, hidden until expanded
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_basis: Val::Percent(100.0),
+                            max_size: Size::new(Val::Undefined, Val::Px(160.0)),
+                            overflow: Overflow::Hidden,
+                            display: Display::None,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ErrorDetails,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(create_text(
+                        format!("{error}\n{error:#?}"),
+                        fonts,
+                        16.0,
+                        TEXT_COLOR_DARK,
+                    ));
+                });
+            for (text, action) in actions {
+                parent
+                    .spawn((
+                        create_button(),
+                        ActivationCooldown::default(),
+                        button_name(text),
+                        action,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(create_text(text, fonts, 20.0, TEXT_COLOR_BRIGHT));
+                    });
+            }
+        });
+}
+
+///Interaction with error dialog buttons.
+fn error_dialog_button(
+    mut commands: Commands,
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &mut BackgroundColor,
+            &ErrorDialogButton,
+            &mut ActivationCooldown,
+        ),
+        (Changed<Interaction>, With<Button>),
+    >,
+    dialogs: Query<Entity, With<ErrorDialog>>,
+    mut details: Query<&mut Style, With<ErrorDetails>>,
+    mut retries: EventWriter<RetryTaskEvent>,
+) {
+    for (interaction, mut color, action, mut cooldown) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked if !cooldown.try_activate(Instant::now()) => {}
+            Interaction::Clicked => match action {
+                ErrorDialogButton::Retry(retry) => {
+                    retries.send(RetryTaskEvent(retry.clone()));
+                    for dialog in dialogs.iter() {
+                        commands.entity(dialog).despawn_recursive();
+                    }
+                }
+                ErrorDialogButton::OpenFolder(folder) => open_folder(folder),
+                ErrorDialogButton::ToggleDetails => {
+                    for mut style in details.iter_mut() {
+                        style.display = match style.display {
+                            Display::None => Display::Flex,
+                            Display::Flex => Display::None,
+                        };
+                    }
+                }
+                ErrorDialogButton::Dismiss => {
+                    for dialog in dialogs.iter() {
+                        commands.entity(dialog).despawn_recursive();
+                    }
+                }
+            },
+            Interaction::Hovered => {
+                *color = BUTTON_COLOR_HOVER;
+            }
+            Interaction::None => {
+                *color = BUTTON_COLOR_NONE;
+            }
+        }
+    }
+}
+
+///Opens folder with platform file browser.
+fn open_folder(folder: &Path) {
+    let program = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    if let Err(e) = std::process::Command::new(program).arg(folder).spawn() {
+        warn!("Failed to open {}: {e}", folder.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asset::FONT_SCHLUBER, states::AppState};
+
+    use bevy::window::{WindowCloseRequested, WindowId};
+
+    const SAVE_PATH: &str = "saves/autosave.ron";
+
+    fn report_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<Events<TaskResultEvent>>();
+        world.init_resource::<Events<RetryTaskEvent>>();
+        world.insert_resource(GlobalState::new(AppState::InGame));
+        let mut fonts = Fonts::default();
+        fonts.insert(FONT_SCHLUBER, default());
+        world.insert_resource(fonts);
+        world
+    }
+
+    ///Dialogs shown after reporting.
+    fn report(world: &mut World, event: TaskResultEvent) -> Vec<Entity> {
+        world.send_event(event);
+        SystemStage::single(report_task_results).run(world);
+        world
+            .query_filtered::<Entity, With<ErrorDialog>>()
+            .iter(world)
+            .collect()
+    }
+
+    fn save_error() -> TaskError {
+        TaskError::new(TaskKind::Save, "disk full")
+            .with_path(SAVE_PATH)
+            .with_retry(TaskRetry::Save(SAVE_PATH.into()))
+    }
+
+    fn actions(world: &mut World) -> Vec<&'static str> {
+        let mut ret = world
+            .query::<&ErrorDialogButton>()
+            .iter(world)
+            .map(|action| match action {
+                ErrorDialogButton::Retry(_) => RETRY_TEXT,
+                ErrorDialogButton::OpenFolder(_) => OPEN_FOLDER_TEXT,
+                ErrorDialogButton::ToggleDetails => DETAILS_TEXT,
+                ErrorDialogButton::Dismiss => DISMISS_TEXT,
+            })
+            .collect::<Vec<_>>();
+        ret.sort_unstable();
+        ret
+    }
+
+    #[test]
+    fn only_unrecoverable_failure_opens_dialog() {
+        let mut world = report_world();
+        let done = TaskResultEvent::Done {
+            kind: TaskKind::Save,
+            message: "saved".to_owned(),
+        };
+        assert!(report(&mut world, done).is_empty());
+        assert!(report(&mut world, TaskResultEvent::Failed(save_error())).is_empty());
+
+        let save = report(
+            &mut world,
+            TaskResultEvent::Failed(save_error().unrecoverable()),
+        );
+        assert_eq!(save.len(), 1);
+        assert_eq!(
+            actions(&mut world),
+            [DETAILS_TEXT, DISMISS_TEXT, OPEN_FOLDER_TEXT, RETRY_TEXT]
+        );
+        //Only latest failure is shown, with what could be done about it.
+        let load = TaskError::new(TaskKind::Load, "bad version").unrecoverable();
+        let load = report(&mut world, TaskResultEvent::Failed(load));
+        assert_eq!(load.len(), 1);
+        assert_ne!(load, save);
+        assert_eq!(actions(&mut world), [DETAILS_TEXT, DISMISS_TEXT]);
+    }
+
+    #[test]
+    fn retry_submits_task_again() {
+        let mut world = report_world();
+        report(
+            &mut world,
+            TaskResultEvent::Failed(save_error().unrecoverable()),
+        );
+        let retry = world
+            .query::<(Entity, &ErrorDialogButton)>()
+            .iter(&world)
+            .find(|(_, action)| matches!(action, ErrorDialogButton::Retry(_)))
+            .map(|(button, _)| button)
+            .unwrap();
+        *world.get_mut::<Interaction>(retry).unwrap() = Interaction::Clicked;
+        SystemStage::single(error_dialog_button).run(&mut world);
+
+        let retries = world.resource::<Events<RetryTaskEvent>>();
+        let sent = retries
+            .get_reader()
+            .iter(retries)
+            .map(|RetryTaskEvent(retry)| retry.clone())
+            .collect::<Vec<_>>();
+        assert!(matches!(&sent[..], [TaskRetry::Save(path)] if path == Path::new(SAVE_PATH)));
+        assert_eq!(world.query::<&ErrorDialog>().iter(&world).count(), 0);
+    }
+
+    #[test]
+    fn dialog_doesnt_block_quit() {
+        let mut world = report_world();
+        world.init_resource::<Events<WindowCloseRequested>>();
+        world.init_resource::<Input<KeyCode>>();
+        world.init_resource::<UiHidden>();
+        report(
+            &mut world,
+            TaskResultEvent::Failed(save_error().unrecoverable()),
+        );
+        world.send_event(WindowCloseRequested {
+            id: WindowId::primary(),
+        });
+        SystemStage::single(close_requested).run(&mut world);
+        assert!(world.resource::<GlobalState>().is_exit());
+    }
+}
@@ -0,0 +1,446 @@
+//!Opt-in stream of build events for overlays and dashboards, enabled by `--telemetry-socket <port>`.
+//!
+//!Protocol is write-only. Clients connect to `127.0.0.1:<port>` over TCP and receive one JSON object
+//!per line. Nothing clients send is ever read. Every object has `event` field, which is one of:
+//!- `placed`, `removed`: `id`, `type` and `cell` of structure.
+//!- `save`: `message` of completed save.
+//!- `state`: `from` and `to` of state transition.
+//!- `stats`: `uptime`, `structures` and `dropped`, every ten seconds.
+//!
+//!Game never waits for clients. Events that don't fit in channel are dropped and counted,
+//!and client that can't keep up or is gone is disconnected.
+
+use crate::{
+    console::param::Param,
+    states::LastStageState,
+    structure::{Structure, StructurePlaced, StructureRemoved},
+    task::{TaskKind, TaskResultEvent},
+};
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write as _},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use bevy::{app::AppExit, prelude::*};
+
+///Lines that could wait for listener thread. Beyond this, events are dropped.
+const CHANNEL_LEN: usize = 1024;
+///How often listener looks for new clients while no event comes.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+///Client that doesn't take a line within this is disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+///Port 0 would be picked by system, which clients couldn't know.
+const PORT: Param = Param::count("telemetry port").range(1., u16::MAX as f64);
+///Seconds between stats events.
+const STATS_INTERVAL: f32 = 10.;
+
+///Batch setup for telemetry. Does nothing unless port is given.
+pub struct TelemetryPlugin {
+    pub port: Option<u16>,
+}
+
+impl TelemetryPlugin {
+    ///Reads `--telemetry-socket <port>` from command line arguments.
+    pub fn from_args<'a>(args: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut port = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--telemetry-socket" {
+                //Logger isn't set up yet.
+                match args.next().map(|port| PORT.parse_value(port)) {
+                    Some(Ok(parsed)) => port = Some(parsed as u16),
+                    Some(Err(e)) => eprintln!("{e}, telemetry is off"),
+                    None => eprintln!("--telemetry-socket needs a port, telemetry is off"),
+                }
+            }
+        }
+        Self { port }
+    }
+}
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        let port = match self.port {
+            Some(port) => port,
+            None => return,
+        };
+        match TelemetryServer::start(port) {
+            Ok(server) => {
+                info!("Telemetry is published on {}", server.addr);
+                app.insert_resource(server)
+                    .add_system_to_stage(CoreStage::Last, publish_telemetry)
+                    .add_system_to_stage(CoreStage::Last, publish_telemetry_stats)
+                    .add_system_to_stage(
+                        CoreStage::Last,
+                        stop_telemetry_on_exit
+                            .after(publish_telemetry)
+                            .after(publish_telemetry_stats),
+                    );
+            }
+            Err(e) => warn!("Failed to start telemetry on port {port}: {e}"),
+        }
+    }
+}
+
+///Event as it is sent to clients.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TelemetryEvent {
+    Placed {
+        entity: Entity,
+        kind: &'static str,
+        cell: Vec3,
+    },
+    Removed {
+        entity: Entity,
+        kind: &'static str,
+        cell: Vec3,
+    },
+    Save {
+        message: String,
+    },
+    State {
+        from: LastStageState,
+        to: LastStageState,
+    },
+    Stats {
+        uptime: f64,
+        structures: usize,
+        dropped: u64,
+    },
+}
+
+impl TelemetryEvent {
+    ///One line of JSON, without line break.
+    pub fn to_json(&self) -> String {
+        match self {
+            Self::Placed { entity, kind, cell } => structure_json("placed", *entity, kind, *cell),
+            Self::Removed { entity, kind, cell } => structure_json("removed", *entity, kind, *cell),
+            Self::Save { message } => {
+                format!(r#"{{"event":"save","message":{}}}"#, json_string(message))
+            }
+            Self::State { from, to } => format!(
+                r#"{{"event":"state","from":{},"to":{}}}"#,
+                json_string(&format!("{from:?}")),
+                json_string(&format!("{to:?}"))
+            ),
+            Self::Stats {
+                uptime,
+                structures,
+                dropped,
+            } => format!(
+                r#"{{"event":"stats","uptime":{},"structures":{structures},"dropped":{dropped}}}"#,
+                json_number(*uptime as f32)
+            ),
+        }
+    }
+}
+
+fn structure_json(event: &str, entity: Entity, kind: &str, cell: Vec3) -> String {
+    format!(
+        r#"{{"event":"{event}","id":{},"type":{},"cell":[{},{},{}]}}"#,
+        json_string(&format!("{entity:?}")),
+        json_string(kind),
+        json_number(cell.x),
+        json_number(cell.y),
+        json_number(cell.z)
+    )
+}
+
+///JSON has no NaN or infinity, so those become null.
+fn json_number(value: f32) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_owned()
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len() + 2);
+    ret.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(ret, "\\u{:04x}", c as u32);
+            }
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+///Game side of telemetry. Owns listener thread, which stops when this is stopped or dropped.
+#[derive(Resource)]
+pub struct TelemetryServer {
+    pub addr: SocketAddr,
+    sender: Option<SyncSender<String>>,
+    thread: Option<JoinHandle<()>>,
+    ///Events dropped because channel was full.
+    dropped: u64,
+}
+
+impl TelemetryServer {
+    ///Binds localhost only. Port 0 picks free one, which is `addr`.
+    pub fn start(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_LEN);
+        let thread = std::thread::Builder::new()
+            .name("telemetry".to_owned())
+            .spawn(move || serve(listener, receiver))?;
+        Ok(Self {
+            addr,
+            sender: Some(sender),
+            thread: Some(thread),
+            dropped: 0,
+        })
+    }
+
+    ///Queues event without waiting. Full channel drops it.
+    pub fn send(&mut self, event: &TelemetryEvent) {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return,
+        };
+        match sender.try_send(event.to_json()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => self.dropped += 1,
+            //Thread is gone, such as by panic. Nothing more could be sent.
+            Err(TrySendError::Disconnected(_)) => self.sender = None,
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    ///Lets thread flush what is queued, then waits for it to end.
+    pub fn stop(&mut self) {
+        //Closed channel is what tells thread to end.
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                warn!("Telemetry thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for TelemetryServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+///Listener thread. Accepts clients and fans every line out to all of them until channel closes.
+fn serve(listener: TcpListener, receiver: Receiver<String>) {
+    let mut clients = Vec::<TcpStream>::new();
+    loop {
+        loop {
+            match listener.accept() {
+                Ok((client, _)) => {
+                    let ready = client
+                        .set_nonblocking(false)
+                        .and_then(|_| client.set_write_timeout(Some(WRITE_TIMEOUT)));
+                    if ready.is_ok() {
+                        clients.push(client);
+                    }
+                }
+                //Would block when nobody is waiting.
+                Err(_) => break,
+            }
+        }
+        match receiver.recv_timeout(ACCEPT_INTERVAL) {
+            Ok(mut line) => {
+                line.push('\n');
+                //Client that fails to take line is dropped alone.
+                clients.retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+///Serializes events of frame into channel. Runs last, so everything of frame is seen.
+fn publish_telemetry(
+    mut server: ResMut<TelemetryServer>,
+    mut placed: EventReader<StructurePlaced>,
+    mut removed: EventReader<StructureRemoved>,
+    mut results: EventReader<TaskResultEvent>,
+    state: Res<State<LastStageState>>,
+    mut last_state: Local<Option<LastStageState>>,
+) {
+    for e in placed.iter() {
+        server.send(&TelemetryEvent::Placed {
+            entity: e.entity,
+            kind: e.kind,
+            cell: e.cell,
+        });
+    }
+    for e in removed.iter() {
+        server.send(&TelemetryEvent::Removed {
+            entity: e.entity,
+            kind: e.kind,
+            cell: e.cell,
+        });
+    }
+    for result in results.iter() {
+        if let TaskResultEvent::Done {
+            kind: TaskKind::Save,
+            message,
+        } = result
+        {
+            server.send(&TelemetryEvent::Save {
+                message: message.clone(),
+            });
+        }
+    }
+    let current = *state.current();
+    match *last_state {
+        Some(from) if from != current => server.send(&TelemetryEvent::State { from, to: current }),
+        _ => {}
+    }
+    *last_state = Some(current);
+}
+
+fn publish_telemetry_stats(
+    mut server: ResMut<TelemetryServer>,
+    structures: Query<(), With<Structure>>,
+    time: Res<Time>,
+    mut elapsed: Local<f32>,
+) {
+    *elapsed += time.delta_seconds();
+    if *elapsed >= STATS_INTERVAL {
+        *elapsed = 0.;
+        let stats = TelemetryEvent::Stats {
+            uptime: time.elapsed_seconds_f64(),
+            structures: structures.iter().count(),
+            dropped: server.dropped(),
+        };
+        server.send(&stats);
+    }
+}
+
+///Flushes and joins listener thread when app is about to exit.
+fn stop_telemetry_on_exit(exit: EventReader<AppExit>, mut server: ResMut<TelemetryServer>) {
+    if !exit.is_empty() {
+        server.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read as _;
+
+    fn port(args: &[&str]) -> Option<u16> {
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+        TelemetryPlugin::from_args(&args).port
+    }
+
+    #[test]
+    fn port_is_read_from_args() {
+        assert_eq!(port(&["game", "--telemetry-socket", "9000"]), Some(9000));
+        assert_eq!(port(&["game"]), None);
+        assert_eq!(port(&["game", "--telemetry-socket"]), None);
+        for bad in ["0", "65536", "-1", "80.5", "port"] {
+            assert_eq!(port(&["--telemetry-socket", bad]), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn json_is_escaped() {
+        let save = TelemetryEvent::Save {
+            message: "Saved \"a\\b\"\n\u{1}".to_owned(),
+        };
+        assert_eq!(
+            save.to_json(),
+            r#"{"event":"save","message":"Saved \"a\\b\"\n\u0001"}"#
+        );
+        let stats = TelemetryEvent::Stats {
+            uptime: f64::NAN,
+            structures: 3,
+            dropped: 2,
+        };
+        assert_eq!(
+            stats.to_json(),
+            r#"{"event":"stats","uptime":null,"structures":3,"dropped":2}"#
+        );
+    }
+
+    #[test]
+    fn clients_receive_scripted_events() {
+        let server = TelemetryServer::start(0).unwrap();
+        let mut clients = [
+            TcpStream::connect(server.addr).unwrap(),
+            TcpStream::connect(server.addr).unwrap(),
+        ];
+        //Listener looks for clients between lines.
+        std::thread::sleep(ACCEPT_INTERVAL * 4);
+        let mut world = World::new();
+        world.insert_resource(server);
+        world.init_resource::<Events<StructurePlaced>>();
+        world.init_resource::<Events<StructureRemoved>>();
+        world.init_resource::<Events<TaskResultEvent>>();
+        world.insert_resource(State::new(LastStageState::MainMenu));
+        let mut stage = SystemStage::single(publish_telemetry);
+        stage.run(&mut world);
+
+        world.insert_resource(State::new(LastStageState::InGame));
+        world.send_event(StructurePlaced {
+            entity: Entity::from_raw(1),
+            kind: "cuboid",
+            cell: Vec3::new(1., 2., -3.),
+        });
+        world.send_event(StructurePlaced {
+            entity: Entity::from_raw(2),
+            kind: "sphere",
+            cell: Vec3::new(0.5, 0., 0.),
+        });
+        stage.run(&mut world);
+        world.send_event(StructureRemoved {
+            entity: Entity::from_raw(1),
+            kind: "cuboid",
+            cell: Vec3::new(1., 2., -3.),
+        });
+        world.send_event(TaskResultEvent::Done {
+            kind: TaskKind::Load,
+            message: "Loaded".to_owned(),
+        });
+        world.send_event(TaskResultEvent::Done {
+            kind: TaskKind::Save,
+            message: "Saved".to_owned(),
+        });
+        stage.run(&mut world);
+        world.resource_mut::<TelemetryServer>().stop();
+        assert_eq!(world.resource::<TelemetryServer>().dropped(), 0);
+
+        for client in clients.iter_mut() {
+            let mut received = String::new();
+            client.read_to_string(&mut received).unwrap();
+            assert_eq!(
+                received.lines().collect::<Vec<_>>(),
+                [
+                    r#"{"event":"placed","id":"1v0","type":"cuboid","cell":[1,2,-3]}"#,
+                    r#"{"event":"placed","id":"2v0","type":"sphere","cell":[0.5,0,0]}"#,
+                    r#"{"event":"state","from":"MainMenu","to":"InGame"}"#,
+                    r#"{"event":"removed","id":"1v0","type":"cuboid","cell":[1,2,-3]}"#,
+                    r#"{"event":"save","message":"Saved"}"#,
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use crate::{
+    asset::{critical_load_state, Fonts, Images},
+    states::*,
+};
+
+use std::{
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::{self, Write as _},
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{asset::LoadState, prelude::*};
+
+///Stats CSV that each startup appends its breakdown to, so regressions over runs are visible.
+pub const STATS_PATH: &str = "stats/startup.csv";
+
+const STATS_HEADER: &str = "started,phase,start_ms,duration_ms";
+
+///Batch setup for startup instrumentation.
+pub struct StartupTimingsPlugin;
+
+impl Plugin for StartupTimingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StartupTimings>()
+            .add_system_to_stage(CoreStage::Last, first_frame)
+            .add_system_set_to_stage(
+                CoreStage::Update,
+                SystemSet::on_update(UpdateStageState::MainMenu).with_system(interactive),
+            );
+    }
+}
+
+///A measured startup phase.
+#[derive(Clone, Debug)]
+pub struct Phase {
+    pub name: &'static str,
+    ///Offset from process start.
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+///Records where startup time goes until main menu becomes interactive.
+#[derive(Resource)]
+pub struct StartupTimings {
+    origin: Instant,
+    ///Wall clock of origin, which tells runs apart in stats.
+    started: SystemTime,
+    phases: Vec<Phase>,
+    interactive: Option<Duration>,
+}
+
+impl Default for StartupTimings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StartupTimings {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            started: SystemTime::now(),
+            phases: Vec::new(),
+            interactive: None,
+        }
+    }
+
+    ///Starts timing a phase. Phase is recorded when returned guard is dropped.
+    pub fn span(&mut self, name: &'static str) -> Span {
+        Span {
+            timings: self,
+            name,
+            start: Instant::now(),
+        }
+    }
+
+    ///Records a zero length phase at now.
+    pub fn mark(&mut self, name: &'static str) {
+        let now = Instant::now();
+        self.record(name, now, Duration::ZERO);
+    }
+
+    fn record(&mut self, name: &'static str, start: Instant, duration: Duration) {
+        self.phases.push(Phase {
+            name,
+            start: start - self.origin,
+            duration,
+        });
+    }
+
+    pub fn _phases(&self) -> &[Phase] {
+        &self.phases
+    }
+
+    ///Offset from process start when main menu became interactive.
+    pub fn interactive(&self) -> Option<Duration> {
+        self.interactive
+    }
+
+    ///Single line summary of all phases.
+    pub fn report(&self) -> String {
+        let mut ret = String::from("startup:");
+        for phase in self.phases.iter() {
+            let _ = write!(
+                ret,
+                " {} {:.1}ms @{:.1}ms |",
+                phase.name,
+                phase.duration.as_secs_f64() * 1000.,
+                phase.start.as_secs_f64() * 1000.
+            );
+        }
+        match self.interactive {
+            Some(interactive) => {
+                let _ = write!(
+                    ret,
+                    " interactive @{:.1}ms",
+                    interactive.as_secs_f64() * 1000.
+                );
+            }
+            None => ret.push_str(" not interactive yet"),
+        }
+        ret
+    }
+
+    ///Rows of stats CSV, one per phase and last one for interactive if reached. No header.
+    pub fn csv_rows(&self) -> String {
+        let started = self
+            .started
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let interactive = self.interactive.map(|at| Phase {
+            name: "interactive",
+            start: at,
+            duration: Duration::ZERO,
+        });
+        let mut ret = String::new();
+        for phase in self.phases.iter().chain(interactive.as_ref()) {
+            let _ = writeln!(
+                ret,
+                "{started},{},{:.3},{:.3}",
+                phase.name,
+                phase.start.as_secs_f64() * 1000.,
+                phase.duration.as_secs_f64() * 1000.
+            );
+        }
+        ret
+    }
+
+    ///Appends rows to stats CSV, writing header first if file is new.
+    pub fn append_csv(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{STATS_HEADER}")?;
+        }
+        file.write_all(self.csv_rows().as_bytes())
+    }
+}
+
+///Records phase on drop.
+pub struct Span<'a> {
+    timings: &'a mut StartupTimings,
+    name: &'static str,
+    start: Instant,
+}
+
+impl Drop for Span<'_> {
+    fn drop(&mut self) {
+        self.timings
+            .record(self.name, self.start, self.start.elapsed());
+    }
+}
+
+///Marks when first frame is reached.
+fn first_frame(mut timings: ResMut<StartupTimings>, mut done: Local<bool>) {
+    if !*done {
+        timings.mark("first frame");
+        *done = true;
+    }
+}
+
+///Marks when main menu runs with all required assets loaded, then reports once.
+fn interactive(
+    mut timings: ResMut<StartupTimings>,
+    asset_server: Res<AssetServer>,
+    fonts: Res<Fonts>,
+    images: Res<Images>,
+) {
+    if timings.interactive.is_some() {
+        return;
+    }
+    if critical_load_state(&asset_server, &fonts, &images) == LoadState::Loaded {
+        timings.interactive = Some(timings.origin.elapsed());
+        info!("{}", timings.report());
+        if let Err(e) = timings.append_csv(Path::new(STATS_PATH)) {
+            warn!("Startup stats not written to {STATS_PATH}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_are_ordered_and_apart() {
+        let mut timings = StartupTimings::new();
+        {
+            let _span = timings.span("plugin build");
+        }
+        timings.mark("first frame");
+        {
+            let _span = timings.span("main menu setup");
+        }
+        let phases = timings._phases();
+        let names: Vec<_> = phases.iter().map(|phase| phase.name).collect();
+        assert_eq!(names, ["plugin build", "first frame", "main menu setup"]);
+        for pair in phases.windows(2) {
+            assert!(pair[0].start + pair[0].duration <= pair[1].start);
+        }
+        assert!(timings.report().ends_with("not interactive yet"));
+    }
+
+    #[test]
+    fn csv_has_row_per_phase_and_interactive() {
+        let mut timings = StartupTimings::new();
+        timings.mark("first frame");
+        assert_eq!(timings.csv_rows().lines().count(), 1);
+        timings.interactive = Some(Duration::from_millis(1500));
+        let rows = timings.csv_rows();
+        let rows: Vec<Vec<_>> = rows.lines().map(|row| row.split(',').collect()).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows
+            .iter()
+            .all(|row| row.len() == STATS_HEADER.split(',').count() && row[0] == rows[0][0]));
+        assert_eq!(rows[0][1], "first frame");
+        assert_eq!(rows[1][1..], ["interactive", "1500.000", "0.000"]);
+    }
+}
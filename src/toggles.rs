@@ -0,0 +1,110 @@
+//!Switches that pause single systems from console, such as `sys off autosave`.
+//!
+//!System opts in by run criteria `toggleable(name)`, and its name is registered on app so console
+//!could list it. Toggles live for session only and aren't saved.
+
+use std::collections::BTreeMap;
+
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
+///Writing of autosave when game is quit.
+pub const AUTOSAVE_TOGGLE: &str = "autosave";
+
+///Whether each registered system runs. Sorted by name for listing.
+#[derive(Resource, Default)]
+pub struct SystemToggles(BTreeMap<&'static str, bool>);
+
+impl SystemToggles {
+    ///Registers enabled toggle. Registering again keeps current state.
+    pub fn register(&mut self, name: &'static str) {
+        self.0.entry(name).or_insert(true);
+    }
+
+    ///Unregistered name is always enabled.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.0.get(name).copied().unwrap_or(true)
+    }
+
+    ///Error lists valid names when name isn't registered.
+    pub fn set(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        match self.0.get_mut(name) {
+            Some(state) => {
+                *state = enabled;
+                Ok(())
+            }
+            None => Err(format!(
+                "unknown system {name}, valid ones are: {}",
+                self.names().collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.0.keys().copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, bool)> + '_ {
+        self.0.iter().map(|(name, enabled)| (*name, *enabled))
+    }
+}
+
+///Registers toggle on app, whichever plugin is built first.
+pub fn register_toggle(app: &mut App, name: &'static str) {
+    app.world
+        .get_resource_or_insert_with(SystemToggles::default)
+        .register(name);
+}
+
+///Run criteria of system that could be switched off by name.
+pub fn toggleable(name: &'static str) -> impl FnMut(Res<SystemToggles>) -> ShouldRun {
+    move |toggles: Res<SystemToggles>| {
+        if toggles.is_enabled(name) {
+            ShouldRun::Yes
+        } else {
+            ShouldRun::No
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Fired(u32);
+
+    fn fire(mut fired: ResMut<Fired>) {
+        fired.0 += 1;
+    }
+
+    #[test]
+    fn switched_off_system_stops_and_resumes() {
+        let mut app = App::new();
+        app.init_resource::<Fired>()
+            .add_system(fire.with_run_criteria(toggleable("weapons")));
+        register_toggle(&mut app, "weapons");
+        register_toggle(&mut app, AUTOSAVE_TOGGLE);
+        let fired = |app: &mut App, enabled| {
+            if let Some(enabled) = enabled {
+                let mut toggles = app.world.resource_mut::<SystemToggles>();
+                toggles.set("weapons", enabled).unwrap();
+            }
+            for _ in 0..3 {
+                app.update();
+            }
+            app.world.resource::<Fired>().0
+        };
+        assert_eq!(fired(&mut app, None), 3);
+        assert_eq!(fired(&mut app, Some(false)), 3);
+        assert_eq!(fired(&mut app, Some(true)), 6);
+
+        let mut toggles = app.world.resource_mut::<SystemToggles>();
+        assert_eq!(
+            toggles.set("weapon", false),
+            Err("unknown system weapon, valid ones are: autosave, weapons".to_owned())
+        );
+        //Unknown name is left alone and still counts as enabled.
+        assert!(toggles.is_enabled("weapon"));
+        assert_eq!(toggles.names().count(), 2);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{
+    asset::*,
+    console::Console,
+    physics::ray::{Ray, RayTrace},
+    states::GlobalState,
+};
+
+use bevy::prelude::*;
+
+use bevy_polyline::prelude::*;
+
+///Records traversal of next crosshair raycast, so surprising hit could be explained.
+#[derive(Resource, Default)]
+pub struct QueryTrace {
+    ///Next raycast is traced when set. Cleared once traced.
+    pub armed: bool,
+    pub last: Option<(Ray, RayTrace)>,
+}
+
+impl QueryTrace {
+    pub fn record(&mut self, ray: Ray, trace: RayTrace) {
+        self.armed = false;
+        self.last = Some((ray, trace));
+    }
+}
+
+///Mark entity that draws query trace.
+#[derive(Component)]
+pub struct TraceWireframe;
+
+///Tested entity bound that fades out.
+#[derive(Component)]
+pub struct TraceFlash {
+    age: f32,
+}
+
+impl TraceFlash {
+    const LIFETIME: f32 = 1.5;
+}
+
+///Draws last trace and dumps its decisions to console.
+///Visited nodes are graded from red to green by visit order.
+pub fn show_query_trace(
+    mut commands: Commands,
+    trace: Res<QueryTrace>,
+    wireframes: Query<Entity, With<TraceWireframe>>,
+    polylines: Res<Polylines>,
+    named_materials: Res<PolylineMaterials>,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+    mut console: ResMut<Console>,
+    state: Res<GlobalState>,
+) {
+    if !trace.is_changed() {
+        return;
+    }
+    for entity in wireframes.iter() {
+        commands.entity(entity).despawn();
+    }
+    let (ray, trace) = match &trace.last {
+        Some(last) => last,
+        None => return,
+    };
+    for line in trace.to_string().lines() {
+        console.print(line);
+    }
+    let visited = trace.visited().count();
+    for (i, aabb) in trace.visited().enumerate() {
+        let order = i as f32 / (visited.max(2) - 1) as f32;
+        commands.spawn((
+            PolylineBundle {
+                polyline: polylines[UNIT_BOX].clone(),
+                material: polyline_materials.add(PolylineMaterial {
+                    color: Color::rgb(1. - order, order, 0.),
+                    perspective: true,
+                    ..default()
+                }),
+                transform: Transform::from_translation(aabb.center()).with_scale(aabb.length()),
+                ..default()
+            },
+            TraceWireframe,
+            state.mark(),
+            Name::new(format!("Trace node {i}")),
+        ));
+    }
+    for (aabb, t) in trace.tested() {
+        commands.spawn((
+            PolylineBundle {
+                polyline: polylines[UNIT_BOX].clone(),
+                material: polyline_materials.add(PolylineMaterial {
+                    color: if t.is_some() {
+                        Color::WHITE
+                    } else {
+                        Color::GRAY
+                    },
+                    perspective: true,
+                    ..default()
+                }),
+                transform: Transform::from_translation(aabb.center()).with_scale(aabb.length()),
+                ..default()
+            },
+            TraceWireframe,
+            TraceFlash { age: 0. },
+            state.mark(),
+            Name::new("Trace test"),
+        ));
+    }
+    //Ray until winner, or through whole visited region.
+    let len = trace
+        .winner
+        .map(|(_, t)| t)
+        .or_else(|| {
+            trace
+                .visited()
+                .map(|aabb| aabb.length().length())
+                .reduce(f32::max)
+        })
+        .unwrap_or(1.);
+    commands.spawn((
+        PolylineBundle {
+            polyline: polylines[UNIT_X].clone(),
+            material: named_materials[BLUE].clone(),
+            transform: Transform::from_translation(ray.origin())
+                .with_rotation(Quat::from_rotation_arc(Vec3::X, ray.dir().normalize()))
+                .with_scale(Vec3::splat(len * ray.dir().length())),
+            ..default()
+        },
+        TraceWireframe,
+        state.mark(),
+        Name::new("Trace ray"),
+    ));
+}
+
+///Fades bounds of tested entities, then despawns them.
+pub fn fade_trace_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flashes: Query<(Entity, &mut TraceFlash, &Handle<PolylineMaterial>)>,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+) {
+    for (entity, mut flash, material) in flashes.iter_mut() {
+        flash.age += time.delta_seconds();
+        if flash.age >= TraceFlash::LIFETIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if let Some(material) = polyline_materials.get_mut(material) {
+            material.color.set_a(1. - flash.age / TraceFlash::LIFETIME);
+        }
+    }
+}
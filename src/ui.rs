@@ -4,47 +4,276 @@ use crate::{
     states::*,
 };
 
-use bevy::{app::AppExit, input::Input, prelude::*, window::WindowCloseRequested};
+use std::time::{Duration, Instant};
 
+use bevy::{
+    app::AppExit,
+    input::Input,
+    prelude::*,
+    window::{WindowCloseRequested, WindowCreated, WindowId, WindowResized},
+};
+
+pub const CONTINUE_TEXT: &str = "Continue";
 pub const PLAY_TEXT: &str = "Play";
 pub const EXIT_TEXT: &str = "Exit";
 pub const ARE_YOU_SURE_TEXT: &str = "Are you sure?";
 pub const YES_TEXT: &str = "Yes";
 pub const NO_TEXT: &str = "No";
+pub const RECOVER_TEXT: &str = "Recover";
+pub const DISCARD_TEXT: &str = "Discard";
+pub const PAUSED_TEXT: &str = "Paused";
+pub const RESUME_TEXT: &str = "Resume";
+pub const MAIN_MENU_TEXT: &str = "Main Menu";
+pub const QUIT_TEXT: &str = "Quit";
 
 pub const UI_BACKGROUND_COLOR: BackgroundColor = BackgroundColor(Color::WHITE);
 
 pub const TEXT_COLOR_BRIGHT: Color = Color::YELLOW;
 pub const TEXT_COLOR_DARK: Color = Color::BLACK;
+pub const TEXT_COLOR_DISABLED: Color = Color::DARK_GRAY;
 
 pub const BUTTON_COLOR_NONE: BackgroundColor = BackgroundColor(Color::BLACK);
 pub const BUTTON_COLOR_HOVER: BackgroundColor = BackgroundColor(Color::GRAY);
+pub const BUTTON_COLOR_DISABLED: BackgroundColor = BackgroundColor(Color::rgb(0.15, 0.15, 0.15));
+
+///Smallest logical viewport size that ui and camera math works with.
+pub const MIN_VIEWPORT: Vec2 = Vec2::new(160., 90.);
+
+///Batch setup for ui helpers shared across states.
+pub struct UiManagingPlugin;
+
+impl Plugin for UiManagingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UiHidden>()
+            .init_resource::<ViewportInfo>()
+            .init_resource::<NavigationGuard>()
+            .add_system_to_stage(CoreStage::First, reset_navigation_guard)
+            .add_system_to_stage(CoreStage::PreUpdate, update_viewport_info)
+            .add_system_to_stage(CoreStage::Update, apply_ui_hidden)
+            .add_system_to_stage(CoreStage::Update, deactivate_degenerate_cameras);
+    }
+}
+
+///Hides every ui root while true.
+#[derive(Resource, Default)]
+pub struct UiHidden(pub bool);
+
+///Mark top of ui hierarchy that respects `UiHidden`.
+#[derive(Component)]
+pub struct UiRoot;
+
+///Applies `UiHidden` to ui roots when it is changed or root is newly spawned.
+fn apply_ui_hidden(
+    hidden: Res<UiHidden>,
+    mut roots: Query<(&mut Visibility, ChangeTrackers<UiRoot>)>,
+) {
+    for (mut visibility, tracker) in roots.iter_mut() {
+        if hidden.is_changed() || tracker.is_added() {
+            visibility.is_visible = !hidden.0;
+        }
+    }
+}
+
+///Primary window size clamped to `MIN_VIEWPORT`. Read this instead of window size for layout math.
+#[derive(Resource, Clone, Copy, PartialEq, Debug)]
+pub struct ViewportInfo {
+    pub width: f32,
+    pub height: f32,
+    ///Whether actual size is below minimum, such as when minimized.
+    pub degenerate: bool,
+}
+
+impl Default for ViewportInfo {
+    fn default() -> Self {
+        Self::new(MIN_VIEWPORT.x, MIN_VIEWPORT.y)
+    }
+}
+
+impl FromWorld for ViewportInfo {
+    fn from_world(world: &mut World) -> Self {
+        match world
+            .get_resource::<Windows>()
+            .and_then(|windows| windows.get_primary())
+        {
+            Some(window) => Self::new(window.width(), window.height()),
+            None => Self::default(),
+        }
+    }
+}
+
+impl ViewportInfo {
+    pub fn new(width: f32, height: f32) -> Self {
+        //NaN also counts as degenerate.
+        let degenerate = !(width >= MIN_VIEWPORT.x && height >= MIN_VIEWPORT.y);
+        Self {
+            width: width.max(MIN_VIEWPORT.x),
+            height: height.max(MIN_VIEWPORT.y),
+            degenerate,
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height)
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.size() * 0.5
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.width / self.height
+    }
+}
+
+///Follows primary window creation and resize.
+fn update_viewport_info(
+    mut created: EventReader<WindowCreated>,
+    mut resized: EventReader<WindowResized>,
+    windows: Res<Windows>,
+    mut viewport: ResMut<ViewportInfo>,
+) {
+    let new = if let Some(e) = resized
+        .iter()
+        .filter(|e| e.id == WindowId::primary())
+        .last()
+    {
+        ViewportInfo::new(e.width, e.height)
+    } else if created.iter().any(|e| e.id == WindowId::primary()) {
+        match windows.get_primary() {
+            Some(window) => ViewportInfo::new(window.width(), window.height()),
+            None => return,
+        }
+    } else {
+        return;
+    };
+    if *viewport != new {
+        *viewport = new;
+    }
+}
+
+///Mark camera that should stop rendering while viewport is degenerate.
+#[derive(Component)]
+pub struct SkipWhenDegenerate;
+
+///Rendering into zero sized target is invalid, so turn such cameras off until viewport recovers.
+fn deactivate_degenerate_cameras(
+    viewport: Res<ViewportInfo>,
+    mut cameras: Query<(&mut Camera, ChangeTrackers<SkipWhenDegenerate>)>,
+) {
+    for (mut camera, tracker) in cameras.iter_mut() {
+        if viewport.is_changed() || tracker.is_added() {
+            camera.is_active = !viewport.degenerate;
+        }
+    }
+}
 
 ///Mark hierarchy info of ui
 #[derive(Component)]
 pub struct HierarchyMark<const N: u32>;
 
+///Ignores further activations of button for a while after one is accepted.
+///Measured in real time, so double click is caught even when frames are slow.
+#[derive(Component)]
+pub struct ActivationCooldown {
+    duration: Duration,
+    last: Option<Instant>,
+}
+
+impl Default for ActivationCooldown {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(250))
+    }
+}
+
+impl ActivationCooldown {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            last: None,
+        }
+    }
+
+    pub fn is_ready(&self, now: Instant) -> bool {
+        match self.last {
+            Some(last) => now.saturating_duration_since(last) >= self.duration,
+            None => true,
+        }
+    }
+
+    ///Whether activation is accepted. Accepted one starts cooldown.
+    pub fn try_activate(&mut self, now: Instant) -> bool {
+        let ready = self.is_ready(now);
+        if ready {
+            self.last = Some(now);
+        }
+        ready
+    }
+}
+
+///Allows at most one navigation per frame across every button.
+///Navigation is anything that changes state or opens modal.
+#[derive(Resource, Default)]
+pub struct NavigationGuard {
+    fired: Option<Entity>,
+}
+
+impl NavigationGuard {
+    ///Whether button could navigate now. First one in frame wins, others are dropped.
+    pub fn try_navigate(&mut self, button: Entity, cooldown: &mut ActivationCooldown) -> bool {
+        let now = Instant::now();
+        if !cooldown.is_ready(now) {
+            return false;
+        }
+        if let Some(fired) = self.fired {
+            debug!("Navigation of {button:?} dropped, {fired:?} already navigated this frame");
+            return false;
+        }
+        self.fired = Some(button);
+        cooldown.try_activate(now)
+    }
+}
+
+fn reset_navigation_guard(mut guard: ResMut<NavigationGuard>) {
+    //Avoid marking changed every frame.
+    if guard.fired.is_some() {
+        guard.fired = None;
+    }
+}
+
+///Mark button that can't be pressed. Its interaction systems should filter this out.
+#[derive(Component)]
+pub struct ButtonDisabled;
+
 ///Mark ui is for exit.
 #[derive(Component)]
 pub struct AppExitMark;
 
-///Go to exit state when requested.
+///Whether primary window is requested to close. Other windows are closed on their own.
+pub fn primary_close_requested(closed: &mut EventReader<WindowCloseRequested>) -> bool {
+    //Every event is read, so none is left over for next frame.
+    closed.iter().fold(false, |requested, e| {
+        requested || e.id == WindowId::primary()
+    })
+}
+
+///Go to exit state when requested. Esc only reveals ui while it is hidden.
 pub fn close_requested(
-    closed: EventReader<WindowCloseRequested>,
+    mut closed: EventReader<WindowCloseRequested>,
     mut state: ResMut<GlobalState>,
     input: Res<Input<KeyCode>>,
+    hidden: Res<UiHidden>,
 ) {
-    if !closed.is_empty() || input.just_pressed(KeyCode::Escape) {
+    if primary_close_requested(&mut closed) || (!hidden.0 && input.just_pressed(KeyCode::Escape)) {
         state.push_exit()
     }
 }
 
 ///Force app exit via close request on exit state.
 pub fn exit_close_requested(
-    closed: EventReader<WindowCloseRequested>,
+    mut closed: EventReader<WindowCloseRequested>,
     mut event: EventWriter<AppExit>,
 ) {
-    if !closed.is_empty() {
+    if primary_close_requested(&mut closed) {
         event.send(AppExit)
     }
 }
@@ -60,18 +289,25 @@ pub fn exit_esc(mut state: ResMut<GlobalState>, input: Res<Input<KeyCode>>) {
 pub fn exit_no_button(
     mut interaction_query: Query<
         (
+            Entity,
             &Interaction,
             &mut BackgroundColor,
             &Action<fn(&mut GlobalState)>,
+            &mut ActivationCooldown,
             &AppExitMark,
         ),
         (Changed<Interaction>, With<Button>),
     >,
     mut state: ResMut<GlobalState>,
+    mut guard: ResMut<NavigationGuard>,
 ) {
-    for (interaction, mut color, func, _) in interaction_query.iter_mut() {
+    for (entity, interaction, mut color, func, mut cooldown, _) in interaction_query.iter_mut() {
         match *interaction {
-            Interaction::Clicked => func.run(&mut *state),
+            Interaction::Clicked => {
+                if guard.try_navigate(entity, &mut cooldown) {
+                    func.run(&mut *state)
+                }
+            }
             Interaction::Hovered => {
                 *color = BUTTON_COLOR_HOVER;
             }
@@ -89,15 +325,20 @@ pub fn exit_yes_button(
             &Interaction,
             &mut BackgroundColor,
             &Action<fn(&mut EventWriter<AppExit>)>,
+            &mut ActivationCooldown,
             &AppExitMark,
         ),
         (Changed<Interaction>, With<Button>),
     >,
     mut event: EventWriter<AppExit>,
 ) {
-    for (interaction, mut color, func, _) in interaction_query.iter_mut() {
+    for (interaction, mut color, func, mut cooldown, _) in interaction_query.iter_mut() {
         match *interaction {
-            Interaction::Clicked => func.run(&mut event),
+            Interaction::Clicked => {
+                if cooldown.try_activate(Instant::now()) {
+                    func.run(&mut event)
+                }
+            }
             Interaction::Hovered => {
                 *color = BUTTON_COLOR_HOVER;
             }
@@ -126,6 +367,11 @@ pub fn create_button() -> ButtonBundle {
     }
 }
 
+///Name of button, such as "Button: Play".
+pub fn button_name(text: &str) -> Name {
+    Name::new(format!("Button: {text}"))
+}
+
 ///Shortcut to create text.
 pub fn create_text(
     text: impl Into<String>,
@@ -177,6 +423,8 @@ pub fn setup_exit(mut commands: Commands, state: Res<GlobalState>, fonts: Res<Fo
                 ..default()
             },
             state.mark(),
+            UiRoot,
+            Name::new("Exit popup"),
         ))
         .with_children(|parent| {
             //Container for text.
@@ -203,6 +451,8 @@ pub fn setup_exit(mut commands: Commands, state: Res<GlobalState>, fonts: Res<Fo
             parent
                 .spawn((
                     create_button(),
+                    ActivationCooldown::default(),
+                    button_name(YES_TEXT),
                     Action::<for<'a> fn(&'a mut EventWriter<AppExit>)>::new(
                         |e: &mut EventWriter<AppExit>| e.send(AppExit),
                     ),
@@ -215,6 +465,8 @@ pub fn setup_exit(mut commands: Commands, state: Res<GlobalState>, fonts: Res<Fo
             parent
                 .spawn((
                     create_button(),
+                    ActivationCooldown::default(),
+                    button_name(NO_TEXT),
                     Action::<for<'a> fn(&'a mut GlobalState)>::new(|g: &mut GlobalState| {
                         g.pop_exit()
                     }),
@@ -225,3 +477,45 @@ pub fn setup_exit(mut commands: Commands, state: Res<GlobalState>, fonts: Res<Fo
                 });
         });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn viewport_clamps_extreme_sizes() {
+        for (width, height, degenerate) in [
+            (1280., 720., false),
+            (0., 0., true),
+            (10000., 100., false),
+            (100., 10000., true),
+            (f32::NAN, 720., true),
+            (1280., 720., false),
+        ] {
+            let viewport = ViewportInfo::new(width, height);
+            assert_eq!(viewport.degenerate, degenerate);
+            assert!(viewport.size().cmpge(MIN_VIEWPORT).all());
+            assert!(viewport.aspect().is_finite() && viewport.aspect() > 0.);
+        }
+    }
+
+    #[test]
+    fn cameras_follow_degenerate_viewport() {
+        let mut world = World::new();
+        world.insert_resource(ViewportInfo::new(0., 0.));
+        let tagged = world.spawn((Camera::default(), SkipWhenDegenerate)).id();
+        let untagged = world.spawn(Camera::default()).id();
+        let mut stage = SystemStage::single(deactivate_degenerate_cameras);
+        let active = |world: &World, entity| world.get::<Camera>(entity).unwrap().is_active;
+
+        stage.run(&mut world);
+        assert!(!active(&world, tagged));
+        assert!(active(&world, untagged));
+        *world.resource_mut::<ViewportInfo>() = ViewportInfo::new(1280., 720.);
+        stage.run(&mut world);
+        assert!(active(&world, tagged));
+        *world.resource_mut::<ViewportInfo>() = ViewportInfo::new(1280., 0.);
+        stage.run(&mut world);
+        assert!(!active(&world, tagged));
+    }
+}
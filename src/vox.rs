@@ -0,0 +1,248 @@
+use crate::asset::PALETTE;
+
+use std::{
+    fmt::{self, Display},
+    io,
+    path::Path,
+};
+
+use bevy::{prelude::*, utils::HashSet};
+
+///Failure of reading `.vox` file.
+#[derive(Debug)]
+pub enum VoxError {
+    Io(io::Error),
+    ///Not a vox file, or cut short.
+    Malformed(String),
+    ///File has no model.
+    Empty,
+}
+
+impl Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Malformed(reason) => write!(f, "malformed vox: {reason}"),
+            Self::Empty => write!(f, "vox has no model"),
+        }
+    }
+}
+
+impl From<io::Error> for VoxError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+///First model of MagicaVoxel file. Only size, voxels and palette are read.
+#[derive(Clone, Debug)]
+pub struct VoxModel {
+    ///In file axes, where z is up.
+    pub size: UVec3,
+    ///Cell and palette index.
+    pub voxels: Vec<(UVec3, u8)>,
+    ///Indexed by palette index. Index 0 is unused by format.
+    pub palette: [Color; 256],
+}
+
+impl VoxModel {
+    ///Guards against models that would take forever to place.
+    pub const MAX_VOXELS: usize = 32_768;
+
+    pub fn read(path: &Path) -> Result<Self, VoxError> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, VoxError> {
+        let mut reader = Reader { bytes, at: 0 };
+        if reader.take(4)? != b"VOX " {
+            return Err(VoxError::Malformed("missing VOX header".to_owned()));
+        }
+        let _version = reader.u32()?;
+        if reader.take(4)? != b"MAIN" {
+            return Err(VoxError::Malformed("missing MAIN chunk".to_owned()));
+        }
+        let content = reader.u32()? as usize;
+        let _children = reader.u32()?;
+        reader.take(content)?;
+
+        let mut size = None;
+        let mut voxels = None;
+        //File without palette uses white, since default palette isn't embedded.
+        let mut palette = [Color::WHITE; 256];
+        while reader.at < bytes.len() {
+            let id = reader.take(4)?;
+            let content = reader.u32()? as usize;
+            let children = reader.u32()? as usize;
+            let mut chunk = Reader {
+                bytes: reader.take(content)?,
+                at: 0,
+            };
+            reader.take(children)?;
+            match id {
+                //Only first model is read.
+                b"SIZE" if size.is_none() => {
+                    size = Some(UVec3::new(chunk.u32()?, chunk.u32()?, chunk.u32()?));
+                }
+                b"XYZI" if voxels.is_none() => {
+                    let count = chunk.u32()? as usize;
+                    let mut read = Vec::with_capacity(count.min(Self::MAX_VOXELS));
+                    for _ in 0..count {
+                        let v = chunk.take(4)?;
+                        read.push((UVec3::new(v[0] as u32, v[1] as u32, v[2] as u32), v[3]));
+                    }
+                    voxels = Some(read);
+                }
+                b"RGBA" => {
+                    //Color at i is for palette index i + 1.
+                    for i in 0..255 {
+                        let c = chunk.take(4)?;
+                        palette[i + 1] = Color::rgba_u8(c[0], c[1], c[2], c[3]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        match (size, voxels) {
+            (Some(size), Some(voxels)) if !voxels.is_empty() => Ok(Self {
+                size,
+                voxels,
+                palette,
+            }),
+            _ => Err(VoxError::Empty),
+        }
+    }
+
+    ///Drops voxels whose six neighbours are all filled, since they can't be seen.
+    ///Return is how many are dropped.
+    pub fn hollow(&mut self) -> usize {
+        let filled: HashSet<UVec3> = self.voxels.iter().map(|(cell, _)| *cell).collect();
+        let enclosed = |cell: UVec3| {
+            let cell = cell.as_ivec3();
+            [IVec3::X, IVec3::Y, IVec3::Z]
+                .into_iter()
+                .flat_map(|axis| [cell + axis, cell - axis])
+                .all(|n| n.cmpge(IVec3::ZERO).all() && filled.contains(&n.as_uvec3()))
+        };
+        let before = self.voxels.len();
+        self.voxels.retain(|(cell, _)| !enclosed(*cell));
+        before - self.voxels.len()
+    }
+
+    ///Keeps first voxels up to max. Return is how many are dropped.
+    pub fn truncate(&mut self, max: usize) -> usize {
+        let dropped = self.voxels.len().saturating_sub(max);
+        self.voxels.truncate(max);
+        dropped
+    }
+
+    ///Offsets of voxels in game axes, where y is up, centered on footprint and resting on y 0.
+    ///Palette name is from `PALETTE`.
+    pub fn placements(&self) -> Vec<(Vec3, &'static str)> {
+        //Whole cells, so voxels stay on grid.
+        let center = Vec3::new((self.size.x / 2) as f32, 0., (self.size.y / 2) as f32);
+        self.voxels
+            .iter()
+            .map(|(cell, index)| {
+                let offset = Vec3::new(cell.x as f32, cell.z as f32, cell.y as f32) - center;
+                (offset, nearest_palette(self.palette[*index as usize]))
+            })
+            .collect()
+    }
+}
+
+///Name of palette color closest to given color. Tie goes to earlier entry.
+pub fn nearest_palette(color: Color) -> &'static str {
+    let target = Vec3::new(color.r(), color.g(), color.b());
+    let mut best = (f32::INFINITY, PALETTE[0].0);
+    for (name, candidate) in PALETTE {
+        let distance =
+            target.distance_squared(Vec3::new(candidate.r(), candidate.g(), candidate.b()));
+        if distance < best.0 {
+            best = (distance, name);
+        }
+    }
+    best.1
+}
+
+///Cursor over little endian bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], VoxError> {
+        let end = self
+            .at
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| VoxError::Malformed(format!("cut short at byte {}", self.at)))?;
+        let ret = &self.bytes[self.at..end];
+        self.at = end;
+        Ok(ret)
+    }
+
+    fn u32(&mut self) -> Result<u32, VoxError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE: &[u8] = include_bytes!("../tests/fixtures/cube.vox");
+    const NO_PALETTE: &[u8] = include_bytes!("../tests/fixtures/no_palette.vox");
+
+    #[test]
+    fn fixture_cube_maps_to_palette() {
+        let model = VoxModel::parse(CUBE).unwrap();
+        assert_eq!(model.size, UVec3::splat(3));
+        assert_eq!(model.voxels.len(), 27);
+        let placements = model.placements();
+        //Top layer of file is red, and file z is game y.
+        for (offset, name) in placements.iter() {
+            assert!(offset.abs().cmple(Vec3::new(1., 2., 1.)).all(), "{offset}");
+            assert_eq!(*name, if offset.y == 2. { "red" } else { "blue" });
+        }
+        assert_eq!(
+            placements.iter().filter(|(_, name)| *name == "red").count(),
+            9
+        );
+        //Same file always gives same placements.
+        assert_eq!(VoxModel::parse(CUBE).unwrap().placements(), placements);
+    }
+
+    #[test]
+    fn fixture_without_palette_is_white() {
+        let model = VoxModel::parse(NO_PALETTE).unwrap();
+        assert_eq!(
+            model.placements(),
+            [(Vec3::new(-1., 0., 0.), "white"), (Vec3::ZERO, "white")]
+        );
+    }
+
+    #[test]
+    fn hollowing_drops_enclosed_voxels() {
+        let mut model = VoxModel::parse(CUBE).unwrap();
+        assert_eq!(model.hollow(), 1);
+        assert_eq!(model.voxels.len(), 26);
+        assert!(model.voxels.iter().all(|(cell, _)| *cell != UVec3::ONE));
+        //Shell has nothing enclosed left.
+        assert_eq!(model.hollow(), 0);
+        assert_eq!(model.truncate(20), 6);
+        assert_eq!(model.voxels.len(), 20);
+    }
+
+    #[test]
+    fn broken_files_are_malformed() {
+        for bytes in [&CUBE[..CUBE.len() - 1], &CUBE[..10], &b"RIFF0000"[..]] {
+            assert!(matches!(
+                VoxModel::parse(bytes),
+                Err(VoxError::Malformed(_))
+            ));
+        }
+    }
+}